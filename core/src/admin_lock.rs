@@ -0,0 +1,157 @@
+//! Read-only mode for shared machines, like lab computers or kiosks.
+//!
+//! Once an administrator sets a passphrase, settings, trust changes, guest access, notes and
+//! deleting or restoring history need it, so people using the machine to drop files cannot
+//! change its configuration. Receiving and sending files keeps working. Unlocking with the
+//! passphrase allows changes for a few minutes.
+//!
+//! The passphrase is checked by decrypting a marker encrypted with it, as for the
+//! [`crate::vault`] key.
+
+use std::fmt;
+use std::io::{Read, Write};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use age::secrecy::Secret;
+use anyhow::Result;
+use serde::Serialize;
+
+use crate::error::DropError;
+
+/// How long changes are allowed after unlocking.
+const UNLOCK_DURATION: Duration = Duration::from_secs(5 * 60);
+
+/// Plaintext of the lock file, proving the passphrase once decrypted.
+const MARKER: &[u8] = b"iroh-drop admin lock";
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct AdminLockStatus {
+    /// Whether the machine is in read-only mode
+    pub enabled: bool,
+    /// Whether changes are currently allowed
+    pub unlocked: bool,
+}
+
+pub struct AdminLock {
+    /// The marker encrypted with the passphrase, only present while enabled
+    path: PathBuf,
+    /// When the administrator last unlocked
+    unlocked_at: Mutex<Option<Instant>>,
+}
+
+impl fmt::Debug for AdminLock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AdminLock")
+            .field("path", &self.path)
+            .field("status", &self.status())
+            .finish()
+    }
+}
+
+impl AdminLock {
+    /// The lock persisted at `path`, disabled if the file does not exist.
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            unlocked_at: Mutex::new(None),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.path.exists()
+    }
+
+    pub fn status(&self) -> AdminLockStatus {
+        let enabled = self.is_enabled();
+        AdminLockStatus {
+            enabled,
+            unlocked: !enabled || self.is_unlocked(),
+        }
+    }
+
+    fn is_unlocked(&self) -> bool {
+        self.unlocked_at
+            .lock()
+            .unwrap()
+            .is_some_and(|at| at.elapsed() < UNLOCK_DURATION)
+    }
+
+    /// Fails with [`DropError::ReadOnly`] unless changes are allowed.
+    pub fn check(&self) -> Result<(), DropError> {
+        if !self.status().unlocked {
+            return Err(DropError::ReadOnly);
+        }
+        Ok(())
+    }
+
+    /// Turns on read-only mode, protected by `passphrase`.
+    pub fn enable(&self, passphrase: &str) -> Result<()> {
+        if self.is_enabled() {
+            let message = "read-only mode is on already".to_string();
+            return Err(DropError::InvalidArgument(message).into());
+        }
+        if passphrase.is_empty() {
+            let message = "the passphrase must not be empty".to_string();
+            return Err(DropError::InvalidArgument(message).into());
+        }
+        let encryptor = age::Encryptor::with_user_passphrase(Secret::new(passphrase.to_string()));
+        let mut data = Vec::new();
+        let mut writer = encryptor.wrap_output(&mut data)?;
+        writer.write_all(MARKER)?;
+        writer.finish()?;
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&self.path, data)?;
+        *self.unlocked_at.lock().unwrap() = None;
+        tracing::info!("read-only mode turned on");
+        Ok(())
+    }
+
+    /// Turns off read-only mode.
+    pub fn disable(&self, passphrase: &str) -> Result<()> {
+        self.verify(passphrase)?;
+        std::fs::remove_file(&self.path)?;
+        tracing::info!("read-only mode turned off");
+        Ok(())
+    }
+
+    /// Allows changes for a few minutes.
+    pub fn unlock(&self, passphrase: &str) -> Result<()> {
+        self.verify(passphrase)?;
+        *self.unlocked_at.lock().unwrap() = Some(Instant::now());
+        tracing::info!("unlocked read-only mode");
+        Ok(())
+    }
+
+    /// Ends the changes allowed by [`Self::unlock`] before they time out.
+    pub fn lock(&self) {
+        *self.unlocked_at.lock().unwrap() = None;
+    }
+
+    fn verify(&self, passphrase: &str) -> Result<()> {
+        let wrong_passphrase = || DropError::InvalidArgument("wrong passphrase".to_string());
+        let data = match std::fs::read(&self.path) {
+            Ok(data) => data,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                let message = "read-only mode is off".to_string();
+                return Err(DropError::InvalidArgument(message).into());
+            }
+            Err(err) => return Err(err.into()),
+        };
+        let age::Decryptor::Passphrase(decryptor) = age::Decryptor::new(&data[..])? else {
+            anyhow::bail!("the admin lock is not protected by a passphrase");
+        };
+        let mut reader = decryptor
+            .decrypt(&Secret::new(passphrase.to_string()), None)
+            .map_err(|_| wrong_passphrase())?;
+        let mut marker = Vec::new();
+        reader.read_to_end(&mut marker)?;
+        if marker != MARKER {
+            return Err(wrong_passphrase().into());
+        }
+        Ok(())
+    }
+}
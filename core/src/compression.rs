@@ -0,0 +1,77 @@
+//! Transparent compression of text-like files, like logs and CSVs.
+//!
+//! The sender compresses such files with zstd before adding them to the blob store, and
+//! tags the offer with the [`Encoding`]. The blob stays compressed on the receiver, so its
+//! hash matches the sender's, and is only decompressed when it is read, e.g. on export.
+
+use std::io::Read;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::sniff::SNIFF_LEN;
+
+/// zstd level, fast enough to not slow down sending on a local network.
+const LEVEL: i32 = 3;
+
+/// How a blob is encoded, `None` in offers and history entries for plain blobs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Encoding {
+    Zstd,
+}
+
+/// Whether a file with `mime` and `data` is worth compressing.
+///
+/// Without a MIME type, files that look like text are compressed.
+pub fn is_compressible(mime: Option<&str>, data: &[u8]) -> bool {
+    match mime {
+        Some(mime) => {
+            mime.starts_with("text/")
+                || matches!(
+                    mime,
+                    "application/json"
+                        | "application/xml"
+                        | "application/javascript"
+                        | "application/x-ndjson"
+                        | "application/sql"
+                )
+        }
+        None => {
+            let head = &data[..data.len().min(SNIFF_LEN)];
+            !head.contains(&0) && infer::get(head).is_none()
+        }
+    }
+}
+
+/// Compresses `data`, returning `None` if that does not make it smaller.
+pub fn compress(data: &[u8]) -> Result<Option<Vec<u8>>> {
+    let compressed = zstd::bulk::compress(data, LEVEL)?;
+    Ok((compressed.len() < data.len()).then_some(compressed))
+}
+
+/// Decompresses `data`, failing if it would grow beyond `max_size` bytes.
+pub fn decompress(encoding: Encoding, data: &[u8], max_size: u64) -> Result<Vec<u8>> {
+    let Encoding::Zstd = encoding;
+    let mut plain = Vec::new();
+    zstd::stream::read::Decoder::new(data)?
+        .take(max_size.saturating_add(1))
+        .read_to_end(&mut plain)
+        .context("invalid zstd data")?;
+    anyhow::ensure!(
+        plain.len() as u64 <= max_size,
+        "decompressed data is larger than {max_size} bytes"
+    );
+    Ok(plain)
+}
+
+/// The first bytes of the decompressed `data`, for content type detection.
+pub fn decompress_head(encoding: Encoding, data: &[u8]) -> Result<Vec<u8>> {
+    let Encoding::Zstd = encoding;
+    let mut head = Vec::with_capacity(SNIFF_LEN);
+    zstd::stream::read::Decoder::new(data)?
+        .take(SNIFF_LEN as u64)
+        .read_to_end(&mut head)
+        .context("invalid zstd data")?;
+    Ok(head)
+}
@@ -0,0 +1,75 @@
+//! The group of the user's own devices, which share a secret.
+//!
+//! A group ticket is our node ticket with the secret, `mine:<secret>:<node ticket>`. The
+//! device it is pasted on joins the group once we proved the secret to it, a device in
+//! another group has to leave that one first. From then on every intro carries a proof of
+//! the secret: the hash of the secret with the node ids of both sides, so the secret itself
+//! never leaves the group and a proof can not be replayed to another device. Devices that
+//! prove the group are marked as own devices, which files are accepted from without asking
+//! and settings are synced with.
+
+use std::fmt;
+use std::str::FromStr;
+
+use base64::Engine as _;
+use iroh::base::ticket::NodeTicket;
+use iroh::blobs::Hash;
+use iroh::net::NodeId;
+use serde::{Deserialize, Serialize};
+
+use crate::error::DropError;
+
+const PREFIX: &str = "mine:";
+
+/// The secret shared by the group, base64 encoded.
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GroupSecret(String);
+
+impl fmt::Debug for GroupSecret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("GroupSecret(..)")
+    }
+}
+
+impl GroupSecret {
+    pub fn generate() -> Self {
+        Self(base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(rand::random::<[u8; 32]>()))
+    }
+
+    /// What `from` presents to `to` in its intro.
+    pub fn proof(&self, from: NodeId, to: NodeId) -> Hash {
+        let mut data = self.0.as_bytes().to_vec();
+        data.extend_from_slice(from.as_bytes());
+        data.extend_from_slice(to.as_bytes());
+        Hash::new(data)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GroupTicket {
+    pub secret: GroupSecret,
+    pub ticket: NodeTicket,
+}
+
+impl fmt::Display for GroupTicket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{PREFIX}{}:{}", self.secret.0, self.ticket)
+    }
+}
+
+impl FromStr for GroupTicket {
+    type Err = DropError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || DropError::InvalidArgument("invalid ticket of an own device".to_string());
+        let (secret, ticket) = s
+            .strip_prefix(PREFIX)
+            .and_then(|s| s.split_once(':'))
+            .filter(|(secret, _)| !secret.is_empty())
+            .ok_or_else(invalid)?;
+        Ok(Self {
+            secret: GroupSecret(secret.to_string()),
+            ticket: ticket.parse().map_err(|_| invalid())?,
+        })
+    }
+}
@@ -0,0 +1,243 @@
+//! Audit of outgoing connection attempts, so reports about unreachable peers
+//! contain what actually happened.
+//!
+//! The attempts also feed the guided troubleshooting shown while no peer was found, see
+//! [`CheckResult`].
+
+use std::collections::VecDeque;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use iroh::net::{
+    endpoint::{ConnectionType, RemoteInfo},
+    NodeId,
+};
+use serde::Serialize;
+use tokio::sync::RwLock;
+
+use crate::error::DropError;
+use crate::history::{now, ConnectionPath};
+use crate::strategy::HIGH_RTT;
+
+/// Number of attempts kept, older ones are dropped.
+const MAX_ENTRIES: usize = 1000;
+
+/// Round trip time up to which a direct connection is considered good.
+const GOOD_RTT: Duration = Duration::from_millis(100);
+
+/// Attempts older than this are not considered by the troubleshooting, in seconds.
+const RECENT_ATTEMPTS: u64 = 10 * 60;
+
+/// How well a peer is currently reachable, shown next to it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConnectionQuality {
+    Good,
+    Fair,
+    Poor,
+    /// There is no connection to the peer
+    Unknown,
+}
+
+/// The current connection to a peer, for debugging slow transfers.
+#[derive(Debug, Clone, Serialize)]
+pub struct ConnectionInfo {
+    pub path: ConnectionPath,
+    /// Round trip time of the current path
+    pub latency_ms: Option<u64>,
+    /// Home relay of the peer
+    pub relay_url: Option<String>,
+    /// Direct addresses the peer is known under
+    pub addrs: Vec<SocketAddr>,
+    pub quality: ConnectionQuality,
+}
+
+impl From<&RemoteInfo> for ConnectionInfo {
+    fn from(info: &RemoteInfo) -> Self {
+        let direct = matches!(
+            info.conn_type,
+            ConnectionType::Direct(_) | ConnectionType::Mixed(..)
+        );
+        let quality = match (&info.conn_type, info.latency) {
+            (ConnectionType::None, _) => ConnectionQuality::Unknown,
+            (_, Some(rtt)) if direct && rtt > GOOD_RTT => ConnectionQuality::Fair,
+            _ if direct => ConnectionQuality::Good,
+            (_, Some(rtt)) if rtt > HIGH_RTT => ConnectionQuality::Poor,
+            _ => ConnectionQuality::Fair,
+        };
+        Self {
+            path: ConnectionPath::from(&info.conn_type),
+            latency_ms: info.latency.map(|rtt| rtt.as_millis() as u64),
+            relay_url: info
+                .relay_url
+                .as_ref()
+                .map(|relay| relay.relay_url.to_string()),
+            addrs: info.addrs.iter().map(|addr| addr.addr).collect(),
+            quality,
+        }
+    }
+}
+
+/// A step of the troubleshooting, in the order they are shown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Check {
+    /// The device is not invisible
+    Visibility,
+    /// The device is on a local network, where peers are discovered
+    SameNetwork,
+    /// The OS allows discovery on the local network, only known to the app
+    Permission,
+    /// Connections to peers on the local network get through
+    Firewall,
+    /// The device is connected to a relay, for peers on other networks
+    Relay,
+}
+
+impl Check {
+    /// What the user can do if the check found a problem, or could not tell.
+    pub fn advice(&self) -> &'static str {
+        match self {
+            Self::Visibility => "Turn invisible mode off, others can not find this device.",
+            Self::SameNetwork => {
+                "Connect both devices to the same Wi-Fi or LAN. Guest networks often keep \
+                 devices apart."
+            }
+            Self::Permission => "Allow iroh-drop to access the local network.",
+            Self::Firewall => {
+                "Allow iroh-drop in the firewall, on both devices, for incoming UDP traffic."
+            }
+            Self::Relay => {
+                "Devices on other networks are only reached through a relay, check the relay \
+                 settings and the internet connection."
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckState {
+    Ok,
+    Problem,
+    /// The check could not tell
+    Unknown,
+}
+
+/// The outcome of a troubleshooting step.
+#[derive(Debug, Clone, Serialize)]
+pub struct CheckResult {
+    pub check: Check,
+    pub state: CheckState,
+    /// What was found, e.g. the network the device is on
+    pub detail: Option<String>,
+    /// What to do about it, unless the check passed
+    pub advice: Option<&'static str>,
+}
+
+impl CheckResult {
+    pub fn new(check: Check, state: CheckState, detail: Option<String>) -> Self {
+        Self {
+            check,
+            state,
+            detail,
+            advice: (state != CheckState::Ok).then(|| check.advice()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ConnectionAttempt {
+    /// Seconds since the unix epoch
+    pub timestamp: u64,
+    pub node_id: NodeId,
+    /// The protocol that was dialed
+    pub alpn: String,
+    /// Path at the end of the handshake, relay connections might still upgrade later
+    pub path: ConnectionPath,
+    /// Time until the connection was established or failed
+    pub handshake_ms: u64,
+    /// The error code of the failure, `None` if the attempt succeeded
+    pub failure: Option<&'static str>,
+    /// Details of the failure
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Default)]
+pub struct Diagnostics {
+    attempts: RwLock<VecDeque<ConnectionAttempt>>,
+}
+
+impl Diagnostics {
+    pub async fn record(
+        &self,
+        node_id: NodeId,
+        alpn: &[u8],
+        path: ConnectionPath,
+        elapsed: Duration,
+        result: Result<(), &DropError>,
+    ) {
+        let (failure, error) = match result {
+            Ok(()) => (None, None),
+            Err(err) => {
+                tracing::info!("connecting to {node_id} failed after {elapsed:?}: {err}");
+                (Some(err.code()), Some(err.to_string()))
+            }
+        };
+        let mut attempts = self.attempts.write().await;
+        if attempts.len() == MAX_ENTRIES {
+            attempts.pop_front();
+        }
+        attempts.push_back(ConnectionAttempt {
+            timestamp: now(),
+            node_id,
+            alpn: String::from_utf8_lossy(alpn).into_owned(),
+            path,
+            handshake_ms: elapsed.as_millis() as u64,
+            failure,
+            error,
+        });
+    }
+
+    /// All attempts, or only those to `node_id`, newest first.
+    pub async fn list(&self, node_id: Option<NodeId>) -> Vec<ConnectionAttempt> {
+        self.attempts
+            .read()
+            .await
+            .iter()
+            .rev()
+            .filter(|attempt| node_id.is_none_or(|node_id| attempt.node_id == node_id))
+            .cloned()
+            .collect()
+    }
+
+    /// Whether connections on the local network get through, judged by the recent attempts.
+    ///
+    /// Any connection to a peer on the local network rules the firewall out, while only
+    /// failed attempts hint at one.
+    pub async fn check_firewall(&self) -> CheckResult {
+        let since = now().saturating_sub(RECENT_ATTEMPTS);
+        let attempts = self.attempts.read().await;
+        let recent = attempts.iter().filter(|attempt| attempt.timestamp >= since);
+        let (mut local, mut failed) = (0, 0);
+        for attempt in recent {
+            match attempt.failure {
+                None if attempt.path == ConnectionPath::Local => local += 1,
+                Some("timeout" | "connection_failed") => failed += 1,
+                _ => {}
+            }
+        }
+        let (state, detail) = match (local, failed) {
+            (0, 0) => (CheckState::Unknown, None),
+            (0, failed) => (
+                CheckState::Problem,
+                Some(format!("{failed} recent connection attempts failed")),
+            ),
+            _ => (
+                CheckState::Ok,
+                Some("Reached a device on the local network".to_string()),
+            ),
+        };
+        CheckResult::new(Check::Firewall, state, detail)
+    }
+}
@@ -0,0 +1,83 @@
+//! Discovery services of the node, whose announcements can be withdrawn for invisible mode.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use anyhow::Result;
+use futures_lite::stream::Boxed as BoxStream;
+use iroh::net::{
+    discovery::{
+        dns::DnsDiscovery, local_swarm_discovery::LocalSwarmDiscovery, pkarr::PkarrPublisher,
+        ConcurrentDiscovery, Discovery, DiscoveryItem,
+    },
+    key::SecretKey,
+    AddrInfo, Endpoint, NodeId,
+};
+
+/// Wraps the discovery services, so publishing can be paused while the device is invisible.
+///
+/// Cloned handles share their state, one is handed to the node and one to the protocol.
+#[derive(Debug, Clone)]
+pub struct HideableDiscovery(Arc<Inner>);
+
+#[derive(Debug)]
+struct Inner {
+    services: ConcurrentDiscovery,
+    hidden: AtomicBool,
+    /// Addresses last published by the endpoint, announced again once visible.
+    published: Mutex<Option<AddrInfo>>,
+}
+
+impl HideableDiscovery {
+    /// The default services of a node: DNS, pkarr and the local network.
+    pub fn n0(secret_key: &SecretKey) -> Result<Self> {
+        let services = ConcurrentDiscovery::from_services(vec![
+            Box::new(DnsDiscovery::n0_dns()),
+            Box::new(PkarrPublisher::n0_dns(secret_key.clone())),
+            Box::new(LocalSwarmDiscovery::new(secret_key.public())?),
+        ]);
+        Ok(Self(Arc::new(Inner {
+            services,
+            hidden: AtomicBool::new(false),
+            published: Mutex::new(None),
+        })))
+    }
+
+    /// Withdraws our addresses from all services, or announces them again.
+    pub fn set_hidden(&self, hidden: bool) {
+        if self.0.hidden.swap(hidden, Ordering::SeqCst) == hidden {
+            return;
+        }
+        if hidden {
+            // Publishing no addresses stops the local network announcements.
+            self.0.services.publish(&AddrInfo::default());
+        } else if let Some(ref info) = *self.0.published.lock().unwrap() {
+            self.0.services.publish(info);
+        }
+    }
+
+    pub fn is_hidden(&self) -> bool {
+        self.0.hidden.load(Ordering::SeqCst)
+    }
+}
+
+impl Discovery for HideableDiscovery {
+    fn publish(&self, info: &AddrInfo) {
+        *self.0.published.lock().unwrap() = Some(info.clone());
+        if !self.is_hidden() {
+            self.0.services.publish(info);
+        }
+    }
+
+    fn resolve(
+        &self,
+        endpoint: Endpoint,
+        node_id: NodeId,
+    ) -> Option<BoxStream<Result<DiscoveryItem>>> {
+        self.0.services.resolve(endpoint, node_id)
+    }
+
+    fn subscribe(&self) -> Option<BoxStream<DiscoveryItem>> {
+        self.0.services.subscribe()
+    }
+}
@@ -0,0 +1,100 @@
+use serde::{ser::SerializeStruct, Serialize, Serializer};
+
+use crate::security_log::RejectReason;
+
+/// Error returned from all commands, serialized as `{ code, message, reason }` for the
+/// frontend, `reason` is only set for rejected offers.
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum DropError {
+    #[error("invalid argument: {0}")]
+    InvalidArgument(String),
+    #[error("unknown node")]
+    UnknownNode,
+    #[error("connection failed: {0}")]
+    ConnectionFailed(String),
+    #[error("timed out: {0}")]
+    Timeout(String),
+    #[error("{0}")]
+    NeedsConfirmation(String),
+    #[error("rejected by the receiver: {message}")]
+    Rejected {
+        /// `None` for receivers from before version 15, which only explain it
+        reason: Option<RejectReason>,
+        message: String,
+    },
+    #[error("io error: {0}")]
+    Io(String),
+    #[error("the device is in another room")]
+    OtherRoom,
+    #[error("the device runs an incompatible version: {0}")]
+    Incompatible(String),
+    #[error("the encrypted storage is locked")]
+    StorageLocked,
+    #[error("this machine is read-only, changes need the admin passphrase")]
+    ReadOnly,
+    #[error("{0}")]
+    Internal(String),
+}
+
+impl DropError {
+    /// Stable identifier the frontend can match on.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::InvalidArgument(_) => "invalid_argument",
+            Self::UnknownNode => "unknown_node",
+            Self::ConnectionFailed(_) => "connection_failed",
+            Self::Timeout(_) => "timeout",
+            Self::NeedsConfirmation(_) => "needs_confirmation",
+            Self::Rejected { .. } => "rejected",
+            Self::Io(_) => "io",
+            Self::OtherRoom => "other_room",
+            Self::Incompatible(_) => "incompatible",
+            Self::StorageLocked => "storage_locked",
+            Self::ReadOnly => "read_only",
+            Self::Internal(_) => "internal",
+        }
+    }
+}
+
+impl Serialize for DropError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let reason = match self {
+            Self::Rejected { reason, .. } => *reason,
+            _ => None,
+        };
+        let mut s = serializer.serialize_struct("DropError", 3)?;
+        s.serialize_field("code", self.code())?;
+        s.serialize_field("message", &self.to_string())?;
+        s.serialize_field("reason", &reason)?;
+        s.end()
+    }
+}
+
+impl From<RejectReason> for DropError {
+    fn from(reason: RejectReason) -> Self {
+        Self::Rejected {
+            reason: Some(reason),
+            message: reason.to_string(),
+        }
+    }
+}
+
+impl From<anyhow::Error> for DropError {
+    fn from(err: anyhow::Error) -> Self {
+        if let Some(err) = err.downcast_ref::<DropError>() {
+            return err.clone();
+        }
+        if let Some(err) = err.downcast_ref::<std::io::Error>() {
+            return Self::Io(err.to_string());
+        }
+        Self::Internal(format!("{err:#}"))
+    }
+}
+
+impl From<std::io::Error> for DropError {
+    fn from(err: std::io::Error) -> Self {
+        Self::Io(err.to_string())
+    }
+}
+
+pub type DropResult<T> = Result<T, DropError>;
@@ -0,0 +1,63 @@
+//! Sorting exported files into subfolders of the export directory, see
+//! [`crate::settings::ExportSettings`].
+
+use std::path::PathBuf;
+
+use crate::settings::ExportSettings;
+
+/// Folder of the export directory sorted files go to, e.g. `Downloads/iroh-drop`.
+const APP_FOLDER: &str = "iroh-drop";
+
+/// Where a file received from `sender` at `timestamp` goes, relative to the export
+/// directory, `None` if files are not sorted.
+pub fn subfolder(settings: &ExportSettings, sender: &str, timestamp: u64) -> Option<PathBuf> {
+    if !settings.by_sender && !settings.by_date {
+        return None;
+    }
+    let mut path = PathBuf::from(APP_FOLDER);
+    if settings.by_sender {
+        path.push(folder_name(sender));
+    }
+    if settings.by_date {
+        path.push(date(timestamp));
+    }
+    Some(path)
+}
+
+/// `name` as a folder name that is valid on all platforms.
+fn folder_name(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    // Windows drops trailing dots and spaces, and `..` would leave the folder.
+    let name = name.trim().trim_end_matches('.');
+    match name {
+        "" => "unknown".to_string(),
+        name => name.to_string(),
+    }
+}
+
+/// The UTC date of `timestamp` as `YYYY-MM-DD`, so the folders sort by date.
+fn date(timestamp: u64) -> String {
+    // See http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let days = (timestamp / (24 * 60 * 60)) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!("{year:04}-{month:02}-{day:02}")
+}
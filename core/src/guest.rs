@@ -0,0 +1,128 @@
+//! Guest tickets, letting a visitor's device send files to this one for a limited time.
+//!
+//! A guest ticket is our node ticket with a random token, `guest:<token>:<node ticket>`.
+//! The visitor sends the token along with its intro, and may send files until the ticket
+//! expires, whatever the receive mode. After that its connections are refused.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use base64::Engine as _;
+use iroh::base::ticket::NodeTicket;
+use iroh::net::NodeId;
+
+use crate::error::DropError;
+
+/// How long guest tickets are valid if not chosen otherwise.
+pub const DEFAULT_DURATION: Duration = Duration::from_secs(60 * 60);
+
+/// Longest a guest ticket can be valid.
+pub const MAX_DURATION: Duration = Duration::from_secs(24 * 60 * 60);
+
+const PREFIX: &str = "guest:";
+
+#[derive(Debug, Clone)]
+pub struct GuestTicket {
+    pub token: String,
+    pub ticket: NodeTicket,
+}
+
+impl fmt::Display for GuestTicket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{PREFIX}{}:{}", self.token, self.ticket)
+    }
+}
+
+impl FromStr for GuestTicket {
+    type Err = DropError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || DropError::InvalidArgument("invalid guest ticket".to_string());
+        let (token, ticket) = s
+            .strip_prefix(PREFIX)
+            .and_then(|s| s.split_once(':'))
+            .ok_or_else(invalid)?;
+        Ok(Self {
+            token: token.to_string(),
+            ticket: ticket.parse().map_err(|_| invalid())?,
+        })
+    }
+}
+
+/// Issued tokens and the guests that presented them.
+#[derive(Debug, Default)]
+pub struct Guests {
+    /// Tokens of the issued tickets, with when they expire.
+    tokens: Mutex<BTreeMap<String, Instant>>,
+    /// Admitted guests, with when their access expires.
+    guests: Mutex<BTreeMap<NodeId, Instant>>,
+    /// Guests whose access expired, their connections are refused.
+    expired: Mutex<BTreeSet<NodeId>>,
+}
+
+impl Guests {
+    /// Issues a token valid for `duration`, capped at [`MAX_DURATION`].
+    pub fn issue(&self, duration: Duration) -> String {
+        let expires = Instant::now() + duration.min(MAX_DURATION);
+        let token = base64::engine::general_purpose::URL_SAFE_NO_PAD
+            .encode(rand::random::<[u8; 16]>());
+        let mut tokens = self.tokens.lock().unwrap();
+        tokens.retain(|_, expires| *expires > Instant::now());
+        tokens.insert(token.clone(), expires);
+        token
+    }
+
+    /// Admits `node_id` as a guest if `token` is valid, until the token expires.
+    ///
+    /// Tokens can be used by several devices, e.g. everyone in a meeting.
+    pub fn admit(&self, node_id: NodeId, token: &str) -> bool {
+        let Some(expires) = self.tokens.lock().unwrap().get(token).copied() else {
+            return false;
+        };
+        if expires <= Instant::now() {
+            return false;
+        }
+        self.expired.lock().unwrap().remove(&node_id);
+        let mut guests = self.guests.lock().unwrap();
+        let until = guests.entry(node_id).or_insert(expires);
+        *until = (*until).max(expires);
+        true
+    }
+
+    /// Whether `node_id` currently has guest access.
+    pub fn is_guest(&self, node_id: &NodeId) -> bool {
+        self.guests
+            .lock()
+            .unwrap()
+            .get(node_id)
+            .is_some_and(|expires| *expires > Instant::now())
+    }
+
+    /// Whether the guest access of `node_id` expired.
+    pub fn is_expired(&self, node_id: &NodeId) -> bool {
+        self.expired.lock().unwrap().contains(node_id)
+            || self
+                .guests
+                .lock()
+                .unwrap()
+                .get(node_id)
+                .is_some_and(|expires| *expires <= Instant::now())
+    }
+
+    /// Guests whose access expired since the last call.
+    pub fn take_expired(&self) -> Vec<NodeId> {
+        let now = Instant::now();
+        let mut expired = Vec::new();
+        self.guests.lock().unwrap().retain(|node_id, expires| {
+            if *expires <= now {
+                expired.push(*node_id);
+            }
+            *expires > now
+        });
+        self.expired.lock().unwrap().extend(expired.iter().copied());
+        expired
+    }
+}
@@ -0,0 +1,178 @@
+//! Human readable handles like `alice@example.com`, resolved to the node id of a device.
+//!
+//! A handle is published as a TXT record `node=<node id>` at `_iroh-drop.alice.example.com`.
+//! Any DNS server can serve it, including one backed by pkarr. Other name services can be
+//! plugged in with [`NameService`].
+//!
+//! A record alone is not trusted: the device has to claim the handle in its intro as well,
+//! see [`crate::protocol::Protocol::connect_by_handle`].
+
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use futures_util::future::{BoxFuture, FutureExt};
+use iroh::net::{dns::DnsResolver, NodeId};
+
+use crate::error::DropError;
+
+/// How long a resolved handle is used without looking it up again.
+const CACHE_TTL: Duration = Duration::from_secs(10 * 60);
+
+/// Longest handle in bytes, as for domain names.
+const MAX_HANDLE_LEN: usize = 253;
+
+/// Label the TXT record of a handle is published under.
+const RECORD_LABEL: &str = "_iroh-drop";
+
+const RECORD_PREFIX: &str = "node=";
+
+/// A handle as `user@domain`, both lowercase.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Handle {
+    user: String,
+    domain: String,
+}
+
+impl Handle {
+    /// Name of the TXT record the handle is published as.
+    pub fn record_name(&self) -> String {
+        format!("{RECORD_LABEL}.{}.{}", self.user, self.domain)
+    }
+
+    /// Value of the TXT record that publishes `node_id`.
+    pub fn record_value(node_id: NodeId) -> String {
+        format!("{RECORD_PREFIX}{node_id}")
+    }
+}
+
+impl fmt::Display for Handle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}@{}", self.user, self.domain)
+    }
+}
+
+impl FromStr for Handle {
+    type Err = DropError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || DropError::InvalidArgument(format!("invalid handle: {s}"));
+        let s = s.trim().to_lowercase();
+        if s.len() > MAX_HANDLE_LEN {
+            return Err(invalid());
+        }
+        let (user, domain) = s.split_once('@').ok_or_else(invalid)?;
+        // The user becomes a single DNS label.
+        let is_label = |label: &str| {
+            !label.is_empty()
+                && label.len() <= 63
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        };
+        if !is_label(user) || !domain.contains('.') || !domain.split('.').all(is_label) {
+            return Err(invalid());
+        }
+        Ok(Self {
+            user: user.to_string(),
+            domain: domain.to_string(),
+        })
+    }
+}
+
+/// A service handles can be looked up in.
+pub trait NameService: fmt::Debug + Send + Sync {
+    /// The node id published for `handle`, `None` if the service has none.
+    fn resolve<'a>(&'a self, handle: &'a Handle) -> BoxFuture<'a, Result<Option<NodeId>>>;
+}
+
+/// Looks up handles as TXT records, see the module docs.
+#[derive(Debug)]
+pub struct DnsNameService {
+    resolver: DnsResolver,
+}
+
+impl DnsNameService {
+    pub fn new(resolver: DnsResolver) -> Self {
+        Self { resolver }
+    }
+}
+
+impl NameService for DnsNameService {
+    fn resolve<'a>(&'a self, handle: &'a Handle) -> BoxFuture<'a, Result<Option<NodeId>>> {
+        async move {
+            let name = handle.record_name();
+            let lookup = self
+                .resolver
+                .txt_lookup(name.clone())
+                .await
+                .with_context(|| format!("failed to look up {name}"))?;
+            for txt in lookup.iter() {
+                for part in txt.txt_data() {
+                    let Some(node_id) = std::str::from_utf8(part)
+                        .ok()
+                        .and_then(|part| part.strip_prefix(RECORD_PREFIX))
+                    else {
+                        continue;
+                    };
+                    let node_id = node_id
+                        .parse()
+                        .with_context(|| format!("invalid node id in {name}"))?;
+                    return Ok(Some(node_id));
+                }
+            }
+            Ok(None)
+        }
+        .boxed()
+    }
+}
+
+/// Resolves handles with the configured services, asked in order, and caches the results.
+#[derive(Debug)]
+pub struct HandleRegistry {
+    services: Vec<Box<dyn NameService>>,
+    cache: Mutex<HashMap<Handle, (NodeId, Instant)>>,
+}
+
+impl HandleRegistry {
+    pub fn new(services: Vec<Box<dyn NameService>>) -> Self {
+        Self {
+            services,
+            cache: Default::default(),
+        }
+    }
+
+    /// The node id of `handle`, from the cache if it was resolved recently.
+    pub async fn resolve(&self, handle: &Handle) -> Result<NodeId> {
+        if let Some((node_id, resolved)) = self.cache.lock().unwrap().get(handle) {
+            if resolved.elapsed() < CACHE_TTL {
+                return Ok(*node_id);
+            }
+        }
+        for service in &self.services {
+            match service.resolve(handle).await {
+                Ok(Some(node_id)) => {
+                    tracing::info!("resolved {handle} to {node_id}");
+                    self.cache
+                        .lock()
+                        .unwrap()
+                        .insert(handle.clone(), (node_id, Instant::now()));
+                    return Ok(node_id);
+                }
+                Ok(None) => {}
+                Err(err) => tracing::warn!("failed to resolve {handle} with {service:?}: {err:#}"),
+            }
+        }
+        Err(DropError::InvalidArgument(format!("{handle} is not published")).into())
+    }
+
+    /// Drops the cached node id of `handle`, e.g. because the device did not confirm it.
+    pub fn forget(&self, handle: &Handle) {
+        self.cache.lock().unwrap().remove(handle);
+    }
+}
@@ -0,0 +1,421 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use iroh::{
+    blobs::Hash,
+    net::{endpoint::ConnectionType, NodeId},
+};
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+
+use crate::compression::Encoding;
+use crate::metadata::FileMetadata;
+use crate::persistence::{self, Backend};
+
+/// Key the history is persisted under.
+const HISTORY_KEY: &str = "history";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Direction {
+    Sent,
+    Received,
+}
+
+impl Direction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Sent => "sent",
+            Self::Received => "received",
+        }
+    }
+}
+
+/// How the data of a transfer travelled between the nodes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConnectionPath {
+    /// Direct over the local network
+    Local,
+    Direct,
+    Relay,
+    Unknown,
+}
+
+impl From<&ConnectionType> for ConnectionPath {
+    fn from(conn_type: &ConnectionType) -> Self {
+        match conn_type {
+            ConnectionType::Direct(addr) | ConnectionType::Mixed(addr, _)
+                if crate::strategy::is_local_addr(addr) =>
+            {
+                Self::Local
+            }
+            ConnectionType::Direct(_) | ConnectionType::Mixed(..) => Self::Direct,
+            ConnectionType::Relay(_) => Self::Relay,
+            ConnectionType::None => Self::Unknown,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub direction: Direction,
+    /// The other side of the transfer
+    pub node_id: NodeId,
+    pub name: String,
+    pub hash: Hash,
+    pub size: u64,
+    /// Seconds since the unix epoch
+    pub timestamp: u64,
+    /// Set if the received content does not match what was advertised
+    pub content_warning: Option<String>,
+    /// Executables and scripts, which need a second confirmation before export
+    pub quarantined: bool,
+    /// How long the transfer took, if known
+    pub duration_ms: Option<u64>,
+    pub path: ConnectionPath,
+    /// Whether the receiver confirmed the hash and size, `None` if unknown
+    pub verified: Option<bool>,
+    /// When the entry was moved to the trash, seconds since the unix epoch
+    pub deleted_at: Option<u64>,
+    /// As announced by the sender, applied when exporting
+    #[serde(default)]
+    pub metadata: FileMetadata,
+    /// Added by either side after the transfer, e.g. "final version"
+    #[serde(default)]
+    pub note: Option<String>,
+    /// When the note was last changed, seconds since the unix epoch
+    #[serde(default)]
+    pub note_updated_at: u64,
+    /// Whether the other side has the current note
+    #[serde(default)]
+    pub note_synced: bool,
+    /// How the blob is compressed, see [`crate::compression`]
+    #[serde(default)]
+    pub encoding: Option<Encoding>,
+    /// When the received file was last exported, seconds since the unix epoch
+    #[serde(default)]
+    pub exported_at: Option<u64>,
+    /// Whether the blob was removed from the store to free space, see
+    /// [`crate::protocol::Protocol::spawn_gc`]
+    #[serde(default)]
+    pub collected: bool,
+}
+
+impl HistoryEntry {
+    pub fn new(direction: Direction, node_id: NodeId, name: String, hash: Hash, size: u64) -> Self {
+        Self {
+            direction,
+            node_id,
+            name,
+            hash,
+            size,
+            timestamp: now(),
+            content_warning: None,
+            quarantined: false,
+            duration_ms: None,
+            path: ConnectionPath::Unknown,
+            verified: None,
+            deleted_at: None,
+            metadata: Default::default(),
+            note: None,
+            note_updated_at: 0,
+            note_synced: true,
+            encoding: None,
+            exported_at: None,
+            collected: false,
+        }
+    }
+
+    /// Bytes per second, if the duration is known.
+    pub fn throughput(&self) -> Option<u64> {
+        let duration_ms = self.duration_ms.filter(|d| *d > 0)?;
+        Some(self.size * 1000 / duration_ms)
+    }
+}
+
+/// Log of all transfers of this node.
+#[derive(Debug, Default)]
+pub struct History {
+    /// Where the history is persisted, `None` keeps it in memory only.
+    backend: Option<Arc<dyn Backend>>,
+    entries: RwLock<Vec<HistoryEntry>>,
+}
+
+impl History {
+    /// Loads the history from `backend`, starting empty if there is none yet.
+    pub fn load(backend: Arc<dyn Backend>) -> anyhow::Result<Self> {
+        let entries = match backend.load(HISTORY_KEY)? {
+            Some(data) => serde_json::from_slice(&data).unwrap_or_else(|err| {
+                tracing::warn!("invalid history: {err}");
+                Vec::new()
+            }),
+            None => Vec::new(),
+        };
+
+        Ok(Self {
+            backend: Some(backend),
+            entries: RwLock::new(entries),
+        })
+    }
+
+    pub async fn push(&self, entry: HistoryEntry) {
+        let mut entries = self.entries.write().await;
+        entries.push(entry);
+        self.persist(&entries);
+    }
+
+    /// Records the verification result of the most recent transfer of `hash` with `node_id`.
+    pub async fn set_verified(
+        &self,
+        direction: Direction,
+        node_id: NodeId,
+        hash: Hash,
+        verified: Option<bool>,
+    ) {
+        let mut entries = self.entries.write().await;
+        if let Some(entry) = entries
+            .iter_mut()
+            .rev()
+            .find(|e| e.direction == direction && e.node_id == node_id && e.hash == hash)
+        {
+            entry.verified = verified;
+        }
+        self.persist(&entries);
+    }
+
+    /// Sets the note of the most recent transfer of `hash` with `node_id`, changed locally.
+    ///
+    /// Returns when the note was changed, `None` if there is no such transfer.
+    pub async fn set_note(&self, node_id: NodeId, hash: Hash, note: Option<String>) -> Option<u64> {
+        let mut entries = self.entries.write().await;
+        let entry = entries
+            .iter_mut()
+            .rev()
+            .find(|e| e.node_id == node_id && e.hash == hash)?;
+        // Newer than the current note, even if it came from a peer with a clock ahead of ours.
+        let updated_at = now().max(entry.note_updated_at + 1);
+        entry.note = note;
+        entry.note_updated_at = updated_at;
+        entry.note_synced = false;
+        self.persist(&entries);
+        Some(updated_at)
+    }
+
+    /// Applies a note of the transfer of `hash` received from `node_id`, if it is newer.
+    ///
+    /// Notes changed at the same second are ordered by their text, so both sides keep the
+    /// same one. Returns whether the note was applied.
+    pub async fn merge_note(
+        &self,
+        node_id: NodeId,
+        hash: Hash,
+        note: Option<String>,
+        updated_at: u64,
+    ) -> bool {
+        let mut entries = self.entries.write().await;
+        let Some(entry) = entries
+            .iter_mut()
+            .rev()
+            .find(|e| e.node_id == node_id && e.hash == hash)
+        else {
+            return false;
+        };
+        if (updated_at, &note) <= (entry.note_updated_at, &entry.note) {
+            return false;
+        }
+        entry.note = note;
+        entry.note_updated_at = updated_at;
+        entry.note_synced = true;
+        self.persist(&entries);
+        true
+    }
+
+    /// Notes of transfers with `node_id` the other side does not have yet, as
+    /// `(hash, note, updated_at)`.
+    pub async fn unsynced_notes(&self, node_id: NodeId) -> Vec<(Hash, Option<String>, u64)> {
+        self.entries
+            .read()
+            .await
+            .iter()
+            .filter(|e| e.node_id == node_id && !e.note_synced)
+            .map(|e| (e.hash, e.note.clone(), e.note_updated_at))
+            .collect()
+    }
+
+    /// Marks the note of `hash` with `node_id` as synced, unless it changed since `updated_at`.
+    pub async fn set_note_synced(&self, node_id: NodeId, hash: Hash, updated_at: u64) {
+        let mut entries = self.entries.write().await;
+        for entry in entries.iter_mut() {
+            if entry.node_id == node_id && entry.hash == hash && entry.note_updated_at == updated_at
+            {
+                entry.note_synced = true;
+            }
+        }
+        self.persist(&entries);
+    }
+
+    /// Removes the most recent transfer of `hash` with `node_id`, e.g. an offer that was
+    /// replaced by another one.
+    pub async fn remove(&self, direction: Direction, node_id: NodeId, hash: Hash) {
+        let mut entries = self.entries.write().await;
+        if let Some(index) = entries
+            .iter()
+            .rposition(|e| e.direction == direction && e.node_id == node_id && e.hash == hash)
+        {
+            entries.remove(index);
+        }
+        self.persist(&entries);
+    }
+
+    /// Records that the received file `hash` was exported.
+    pub async fn set_exported(&self, hash: &Hash) {
+        let now = now();
+        let mut entries = self.entries.write().await;
+        for entry in entries.iter_mut() {
+            if entry.direction == Direction::Received && &entry.hash == hash {
+                entry.exported_at = Some(now);
+            }
+        }
+        self.persist(&entries);
+    }
+
+    /// Blobs that are no longer needed, with when they were last transferred, oldest first.
+    ///
+    /// A blob is no longer needed once all its sends finished and all its received files
+    /// were exported. Blobs of entries in the trash are left to [`Self::purge`].
+    pub async fn collectable(&self) -> Vec<(Hash, u64)> {
+        let entries = self.entries.read().await;
+        let mut last_used = BTreeMap::new();
+        let mut needed = BTreeSet::new();
+        for entry in entries.iter().filter(|e| !e.collected) {
+            let done = match entry.direction {
+                Direction::Sent => entry.verified.is_some(),
+                Direction::Received => entry.exported_at.is_some(),
+            };
+            if !done || entry.deleted_at.is_some() {
+                needed.insert(entry.hash);
+            }
+            let timestamp = entry.exported_at.unwrap_or(entry.timestamp);
+            let last = last_used.entry(entry.hash).or_insert(timestamp);
+            *last = timestamp.max(*last);
+        }
+        let mut collectable: Vec<_> = last_used
+            .into_iter()
+            .filter(|(hash, _)| !needed.contains(hash))
+            .collect();
+        collectable.sort_by_key(|(_, last_used)| *last_used);
+        collectable
+    }
+
+    /// Marks the entries of `hash` as removed from the store.
+    pub async fn set_collected(&self, hash: &Hash) {
+        let mut entries = self.entries.write().await;
+        for entry in entries.iter_mut() {
+            if &entry.hash == hash {
+                entry.collected = true;
+            }
+        }
+        self.persist(&entries);
+    }
+
+    /// The most recent received entry for `hash`.
+    pub async fn find_received(&self, hash: &Hash) -> Option<HistoryEntry> {
+        self.entries
+            .read()
+            .await
+            .iter()
+            .rev()
+            .find(|e| {
+                e.direction == Direction::Received && &e.hash == hash && e.deleted_at.is_none()
+            })
+            .cloned()
+    }
+
+    /// All entries that are not in the trash, newest first.
+    pub async fn list(&self) -> Vec<HistoryEntry> {
+        self.filtered(|e| e.deleted_at.is_none()).await
+    }
+
+    /// Entries in the trash, newest first.
+    pub async fn trashed(&self) -> Vec<HistoryEntry> {
+        self.filtered(|e| e.deleted_at.is_some()).await
+    }
+
+    /// Moves all entries of `hash` to the trash, returns whether there were any.
+    pub async fn trash(&self, hash: &Hash) -> bool {
+        let now = now();
+        let mut found = false;
+        let mut entries = self.entries.write().await;
+        for entry in entries.iter_mut() {
+            if &entry.hash == hash && entry.deleted_at.is_none() {
+                entry.deleted_at = Some(now);
+                found = true;
+            }
+        }
+        self.persist(&entries);
+        found
+    }
+
+    /// Restores the entries of `hash` from the trash, returns whether there were any.
+    pub async fn restore(&self, hash: &Hash) -> bool {
+        let mut found = false;
+        let mut entries = self.entries.write().await;
+        for entry in entries.iter_mut() {
+            if &entry.hash == hash && entry.deleted_at.take().is_some() {
+                found = true;
+            }
+        }
+        self.persist(&entries);
+        found
+    }
+
+    /// Removes entries that have been in the trash for at least `grace`.
+    ///
+    /// Returns the hashes that are no longer referenced by any entry.
+    pub async fn purge(&self, grace: Duration) -> Vec<Hash> {
+        let cutoff = now().saturating_sub(grace.as_secs());
+        let mut entries = self.entries.write().await;
+        let mut purged = Vec::new();
+        entries.retain(|e| match e.deleted_at {
+            Some(deleted_at) if deleted_at <= cutoff => {
+                purged.push(e.hash);
+                false
+            }
+            _ => true,
+        });
+        purged.sort();
+        purged.dedup();
+        purged.retain(|hash| !entries.iter().any(|e| &e.hash == hash));
+        self.persist(&entries);
+        purged
+    }
+
+    /// Saves `entries`, failures are only logged as the history is not critical.
+    fn persist(&self, entries: &[HistoryEntry]) {
+        if let Some(ref backend) = self.backend {
+            if let Err(err) = persistence::save(backend.as_ref(), HISTORY_KEY, &entries) {
+                tracing::warn!("failed to save the history: {err:#}");
+            }
+        }
+    }
+
+    async fn filtered(&self, f: impl Fn(&HistoryEntry) -> bool) -> Vec<HistoryEntry> {
+        self.entries
+            .read()
+            .await
+            .iter()
+            .rev()
+            .filter(|e| f(e))
+            .cloned()
+            .collect()
+    }
+}
+
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
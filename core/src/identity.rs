@@ -0,0 +1,73 @@
+//! Pinning of the names peers introduce themselves with.
+//!
+//! The node id of a peer is authenticated by the connection, but the name in its intro is
+//! whatever the peer claims. The first name seen for a node id is pinned, and the user is
+//! warned when a known node id shows up with another name, or a pinned name shows up with
+//! another node id. The user settles a warning by comparing the [`verification_code`] both
+//! devices show, see [`crate::protocol::Protocol::verify_identity`].
+
+use std::collections::BTreeMap;
+
+use iroh::blobs::Hash;
+use iroh::net::NodeId;
+use serde::{Deserialize, Serialize};
+
+/// The name first seen for a node id.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PinnedIdentity {
+    pub name: String,
+    /// When the name was pinned, seconds since the unix epoch
+    pub pinned_at: u64,
+    /// Whether the user compared the verification codes
+    pub verified: bool,
+}
+
+/// Why the name a peer introduced itself with is suspicious.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum IdentityWarning {
+    /// The node id is pinned to another name.
+    NameChanged { pinned: String },
+    /// The name is pinned to another node id.
+    NameTaken { node_id: NodeId },
+}
+
+/// Checks the `name` claimed by `node_id` against the `pinned` identities.
+pub fn check(
+    pinned: &BTreeMap<NodeId, PinnedIdentity>,
+    node_id: NodeId,
+    name: &str,
+) -> Option<IdentityWarning> {
+    match pinned.get(&node_id) {
+        Some(identity) if identity.name != name => Some(IdentityWarning::NameChanged {
+            pinned: identity.name.clone(),
+        }),
+        // The user accepted the node, even if others use the same name.
+        Some(identity) if identity.verified => None,
+        _ => pinned
+            .iter()
+            .find(|(id, identity)| **id != node_id && is_same_name(&identity.name, name))
+            .map(|(id, _)| IdentityWarning::NameTaken { node_id: *id }),
+    }
+}
+
+/// Names differing only in case or surrounding whitespace look the same in the device list.
+fn is_same_name(a: &str, b: &str) -> bool {
+    a.trim().to_lowercase() == b.trim().to_lowercase()
+}
+
+/// Code shown on both sides of a connection between `a` and `b`, e.g. `042 917 333 508`.
+///
+/// It only matches if both devices see each other's real node id.
+pub fn verification_code(a: NodeId, b: NodeId) -> String {
+    let (first, second) = if a < b { (a, b) } else { (b, a) };
+    let mut data = first.as_bytes().to_vec();
+    data.extend_from_slice(second.as_bytes());
+    let hash = Hash::new(data);
+    hash.as_bytes()
+        .chunks(2)
+        .take(4)
+        .map(|pair| format!("{:03}", u16::from_be_bytes([pair[0], pair[1]]) % 1000))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
@@ -0,0 +1,45 @@
+//! The iroh-drop protocol and the state around it, independent of the UI.
+//!
+//! The Tauri app in `src-tauri` wraps this crate with commands, notifications and the
+//! tray icon. Keeping it free of Tauri lets it build for targets the app does not
+//! support, e.g. a headless daemon.
+//!
+//! Other apps embed the protocol by starting a node with [`node::spawn`], driving it
+//! through the methods of [`protocol::Protocol`] and reading its
+//! [`protocol::LocalProtocolMessage`]s from [`node::DropNode::events`].
+
+pub mod admin_lock;
+pub mod compression;
+pub mod device_group;
+pub mod diagnostics;
+pub mod discovery;
+pub mod error;
+pub mod export;
+pub mod guest;
+pub mod handles;
+pub mod history;
+pub mod identity;
+pub mod library;
+pub mod metadata;
+pub mod network_trust;
+pub mod node;
+pub mod peer_cache;
+pub mod persistence;
+pub mod preview;
+pub mod profile;
+pub mod protocol;
+pub mod quarantine;
+pub mod ratelimit;
+pub mod retry;
+pub mod security_log;
+pub mod settings;
+pub mod share;
+pub mod sniff;
+pub mod stats;
+pub mod storage;
+pub mod strategy;
+pub mod sync;
+pub mod transfers;
+pub mod transform;
+pub mod vault;
+pub mod workflow;
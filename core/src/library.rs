@@ -0,0 +1,132 @@
+//! Searching the received files, by name, sender, date and type.
+
+use std::collections::BTreeMap;
+
+use iroh::net::NodeId;
+use serde::{Deserialize, Serialize};
+
+use crate::history::{Direction, HistoryEntry};
+use crate::metadata::FileMetadata;
+
+/// What a file is, for filtering the library.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileKind {
+    Image,
+    Video,
+    Audio,
+    Document,
+    Archive,
+    Text,
+    Other,
+}
+
+impl FileKind {
+    /// The kind of the file `name`, by the MIME type the sender told if any, or by its
+    /// extension.
+    pub fn of(name: &str, metadata: &FileMetadata) -> Self {
+        if let Some(kind) = metadata.mime.as_deref().and_then(Self::from_mime) {
+            return kind;
+        }
+        let extension = name
+            .rsplit_once('.')
+            .map(|(_, ext)| ext.to_lowercase())
+            .unwrap_or_default();
+        match extension.as_str() {
+            "jpg" | "jpeg" | "png" | "gif" | "webp" | "bmp" | "heic" | "heif" | "avif" | "svg"
+            | "tiff" => Self::Image,
+            "mp4" | "mov" | "m4v" | "webm" | "mkv" | "avi" => Self::Video,
+            "mp3" | "m4a" | "aac" | "ogg" | "opus" | "flac" | "wav" => Self::Audio,
+            "pdf" | "doc" | "docx" | "odt" | "rtf" | "xls" | "xlsx" | "ods" | "ppt" | "pptx"
+            | "odp" | "epub" => Self::Document,
+            "zip" | "tar" | "gz" | "tgz" | "bz2" | "xz" | "zst" | "7z" | "rar" => Self::Archive,
+            "txt" | "md" | "csv" | "json" | "log" => Self::Text,
+            _ => Self::Other,
+        }
+    }
+
+    fn from_mime(mime: &str) -> Option<Self> {
+        let kind = match mime.split_once('/')? {
+            ("image", _) => Self::Image,
+            ("video", _) => Self::Video,
+            ("audio", _) => Self::Audio,
+            ("text", _) => Self::Text,
+            ("application", "pdf" | "epub+zip" | "msword" | "rtf") => Self::Document,
+            ("application", subtype)
+                if subtype.starts_with("vnd.openxmlformats")
+                    || subtype.starts_with("vnd.oasis.opendocument") =>
+            {
+                Self::Document
+            }
+            ("application", "zip" | "gzip" | "x-tar" | "x-7z-compressed" | "zstd") => Self::Archive,
+            _ => return None,
+        };
+        Some(kind)
+    }
+}
+
+/// Filters of a search, all set ones have to match.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SearchQuery {
+    /// Words that all have to be in the name, the sender or the note, ignoring case
+    pub text: String,
+    /// Only files from this peer
+    pub node_id: Option<NodeId>,
+    /// Received at or after, seconds since the unix epoch
+    pub from: Option<u64>,
+    /// Received before, seconds since the unix epoch
+    pub until: Option<u64>,
+    pub kind: Option<FileKind>,
+}
+
+/// A received file found by a search.
+#[derive(Debug, Clone, Serialize)]
+pub struct ReceivedFile {
+    #[serde(flatten)]
+    pub entry: HistoryEntry,
+    /// Name of the sender, if it is known
+    pub sender: Option<String>,
+    pub kind: FileKind,
+}
+
+/// The received files in `entries` matching `query`, in the order of `entries`.
+///
+/// `names` are the names of the peers, to search for the sender.
+pub fn search(
+    entries: Vec<HistoryEntry>,
+    names: &BTreeMap<NodeId, String>,
+    query: &SearchQuery,
+) -> Vec<ReceivedFile> {
+    let words: Vec<String> = query
+        .text
+        .split_whitespace()
+        .map(str::to_lowercase)
+        .collect();
+    entries
+        .into_iter()
+        .filter(|entry| entry.direction == Direction::Received)
+        .filter(|entry| query.node_id.is_none_or(|node_id| entry.node_id == node_id))
+        .filter(|entry| query.from.is_none_or(|from| entry.timestamp >= from))
+        .filter(|entry| query.until.is_none_or(|until| entry.timestamp < until))
+        .map(|entry| ReceivedFile {
+            sender: names.get(&entry.node_id).cloned(),
+            kind: FileKind::of(&entry.name, &entry.metadata),
+            entry,
+        })
+        .filter(|file| query.kind.is_none_or(|kind| file.kind == kind))
+        .filter(|file| {
+            let haystack = [
+                Some(file.entry.name.as_str()),
+                file.sender.as_deref(),
+                file.entry.note.as_deref(),
+            ]
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>()
+            .join("\n")
+            .to_lowercase();
+            words.iter().all(|word| haystack.contains(word.as_str()))
+        })
+        .collect()
+}
@@ -0,0 +1,57 @@
+//! File metadata sent along with an offer, so received files keep it when exported.
+
+use std::path::Path;
+use std::time::{Duration, UNIX_EPOCH};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileMetadata {
+    /// Content type as reported by the sender
+    pub mime: Option<String>,
+    /// Last modification, seconds since the unix epoch
+    pub modified: Option<u64>,
+    /// Unix permission bits, only sent from unix systems
+    pub permissions: Option<u32>,
+}
+
+impl FileMetadata {
+    /// Metadata of a file on the local file system.
+    pub fn from_fs(metadata: &std::fs::Metadata) -> Self {
+        let modified = metadata
+            .modified()
+            .ok()
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map(|d| d.as_secs());
+        #[cfg(unix)]
+        let permissions = {
+            use std::os::unix::fs::PermissionsExt;
+            Some(metadata.permissions().mode() & 0o777)
+        };
+        #[cfg(not(unix))]
+        let permissions = None;
+        Self {
+            mime: None,
+            modified,
+            permissions,
+        }
+    }
+
+    /// Applies the modification time and permissions to the exported file at `path`.
+    pub fn apply(&self, path: &Path) -> Result<()> {
+        if let Some(modified) = self.modified {
+            let file = std::fs::File::options().write(true).open(path)?;
+            file.set_modified(UNIX_EPOCH + Duration::from_secs(modified))?;
+        }
+        #[cfg(unix)]
+        if let Some(mode) = self.permissions {
+            use std::os::unix::fs::PermissionsExt;
+            // Never take over setuid or sticky bits from a remote, and keep the file
+            // writable for us.
+            let mode = (mode & 0o777) | 0o200;
+            std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,78 @@
+//! Recognizes the network this device is on, so untrusted networks get safer defaults.
+//!
+//! There is no portable way to read the SSID, so a network is identified by the local
+//! subnets and the public address of the device instead. Changing either, e.g. by joining
+//! another Wi-Fi, counts as a new network.
+
+use std::collections::BTreeSet;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+use iroh::blobs::Hash;
+use serde::{Deserialize, Serialize};
+
+use crate::settings::NetworkSettings;
+
+/// A network environment, identified by its addresses.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Network {
+    /// Stable id the user's decision is stored under
+    pub id: String,
+    /// The subnets of the network, shown when asking the user
+    pub label: String,
+}
+
+/// Whether the user trusts a network.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Trust {
+    Trusted,
+    Untrusted,
+    /// The user was not asked yet, handled like an untrusted network.
+    Unknown,
+}
+
+impl NetworkSettings {
+    pub fn trust(&self, id: &str) -> Trust {
+        if self.trusted.contains(id) {
+            Trust::Trusted
+        } else if self.untrusted.contains(id) {
+            Trust::Untrusted
+        } else {
+            Trust::Unknown
+        }
+    }
+}
+
+/// The network of the direct addresses `addrs`, `None` if the device is offline.
+pub fn identify(addrs: impl IntoIterator<Item = SocketAddr>) -> Option<Network> {
+    let subnets: BTreeSet<String> = addrs
+        .into_iter()
+        .map(|addr| addr.ip())
+        .filter(|ip| !ip.is_loopback() && !ip.is_unspecified())
+        .map(subnet)
+        .collect();
+    if subnets.is_empty() {
+        return None;
+    }
+    let label = subnets.into_iter().collect::<Vec<_>>().join(", ");
+    let id = Hash::new(label.as_bytes()).to_hex()[..16].to_string();
+    Some(Network { id, label })
+}
+
+/// The subnet of `ip`, public IPv4 addresses are kept as they are.
+///
+/// IPv6 addresses are reduced to their /64 prefix, as hosts rotate the rest for privacy.
+fn subnet(ip: IpAddr) -> String {
+    match ip {
+        IpAddr::V4(ip) if ip.is_private() || ip.is_link_local() => {
+            let [a, b, c, _] = ip.octets();
+            format!("{}/24", Ipv4Addr::new(a, b, c, 0))
+        }
+        IpAddr::V4(ip) => ip.to_string(),
+        IpAddr::V6(ip) => {
+            let mut segments = ip.segments();
+            segments[4..].fill(0);
+            format!("{}/64", Ipv6Addr::from(segments))
+        }
+    }
+}
@@ -0,0 +1,115 @@
+//! Starts an iroh node serving the drop protocol, for apps embedding this crate.
+
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use iroh::blobs::store::mem;
+use iroh::net::key::SecretKey;
+use iroh::node::{Builder, DiscoveryConfig, DocsStorage, MemNode, StorageConfig};
+use tokio::sync::mpsc;
+
+use crate::discovery::HideableDiscovery;
+use crate::history::History;
+use crate::peer_cache::PeerCache;
+use crate::persistence::{self, Backend};
+use crate::protocol::{self, LocalProtocolMessage, Protocol};
+use crate::settings::{NetworkSettings, RelayMode, SettingsStore};
+
+/// Capacity of the event channel.
+const EVENT_CAPACITY: usize = 64;
+
+const SECRET_KEY_KEY: &str = "secret_key";
+
+/// A running node with the drop protocol registered under [`protocol::ALPN`].
+#[derive(Debug)]
+pub struct DropNode {
+    pub node: MemNode,
+    pub protocol: Arc<Protocol>,
+    /// Events of the protocol, the protocol waits for room once the channel is full.
+    pub events: mpsc::Receiver<LocalProtocolMessage>,
+}
+
+/// The secret key of this device from `backend`, generating it on the first start.
+///
+/// Keeping the key keeps the node id, so paired devices can find the device again.
+pub fn load_secret_key(backend: &dyn Backend) -> Result<SecretKey> {
+    if let Some(data) = backend.load(SECRET_KEY_KEY)? {
+        let key: String = serde_json::from_slice(&data)?;
+        return key.parse().context("invalid secret key");
+    }
+    let key = SecretKey::generate();
+    save_secret_key(backend, &key)?;
+    Ok(key)
+}
+
+/// Replaces the secret key in `backend`, the node uses it from the next start.
+pub fn save_secret_key(backend: &dyn Backend, key: &SecretKey) -> Result<()> {
+    persistence::save(backend, SECRET_KEY_KEY, &key.to_string())
+}
+
+/// The relays of the endpoint, as configured in `settings`.
+fn relay_mode(settings: &NetworkSettings) -> Result<iroh::net::relay::RelayMode> {
+    use iroh::net::relay::{RelayMap, RelayMode as Relays};
+
+    let mode = match settings.relay {
+        RelayMode::Default => Relays::Default,
+        RelayMode::Disabled => Relays::Disabled,
+        RelayMode::Custom => {
+            let url = settings
+                .relay_url
+                .as_deref()
+                .context("a custom relay needs a URL")?;
+            Relays::Custom(RelayMap::from_url(url.trim().parse()?))
+        }
+    };
+    Ok(mode)
+}
+
+/// Starts an in-memory node announcing itself as `name`, discoverable via n0 and the local
+/// network, with the relays configured in `settings`.
+pub async fn spawn(
+    name: String,
+    secret_key: SecretKey,
+    settings: Arc<SettingsStore>,
+    history: History,
+    peer_cache: PeerCache,
+    storage_dir: PathBuf,
+) -> Result<DropNode> {
+    let discovery = HideableDiscovery::n0(&secret_key)?;
+    let (upload_events, uploads) = protocol::UploadEvents::new();
+    let store = mem::Store::default();
+    let builder =
+        Builder::with_db_and_store(store.clone(), DocsStorage::Disabled, StorageConfig::Mem)
+            .secret_key(secret_key)
+            .blobs_events(upload_events)
+            .relay_mode(relay_mode(&settings.get().await.network)?)
+            .node_discovery(DiscoveryConfig::Custom(Box::new(discovery.clone())))
+            .build()
+            .await?;
+
+    let (s, events) = mpsc::channel(EVENT_CAPACITY);
+    let protocol = Protocol::new(
+        name,
+        builder.client().clone(),
+        builder.endpoint().clone(),
+        store,
+        settings.clone(),
+        history,
+        peer_cache,
+        storage_dir,
+        Some(discovery),
+        s,
+    );
+    protocol.apply_settings(&settings.get().await);
+    protocol.spawn_upload_progress(uploads);
+    let node = builder
+        .accept(protocol::ALPN.to_vec(), protocol.clone())
+        .spawn()
+        .await?;
+    Ok(DropNode {
+        node,
+        protocol,
+        events,
+    })
+}
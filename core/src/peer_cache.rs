@@ -0,0 +1,125 @@
+//! Peers remembered across restarts, so files can be sent to them before discovery finds
+//! them again.
+//!
+//! Restored peers are listed as offline, with what they told about themselves the last time.
+//! Their last addresses are handed to the endpoint, and they are introduced to again before
+//! the first transfer, which refreshes their capabilities. Whether a peer is trusted, e.g.
+//! paired or an own device, is kept in the settings.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use iroh::net::relay::RelayUrl;
+use iroh::net::NodeId;
+use serde::{Deserialize, Serialize};
+
+use crate::history::now;
+use crate::persistence::{self, Backend};
+use crate::protocol::{Capabilities, DeviceInfo, ReceiveConstraints};
+
+const PEER_CACHE_KEY: &str = "peers";
+
+/// Peers not seen for this long are forgotten.
+const MAX_AGE: Duration = Duration::from_secs(90 * 24 * 60 * 60);
+
+/// How often a peer that did not change is saved, to keep when it was last seen.
+const SEEN_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// A peer as it was last seen.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CachedPeer {
+    pub name: String,
+    /// Seconds since the unix epoch
+    pub last_seen: u64,
+    pub capabilities: Capabilities,
+    pub device: DeviceInfo,
+    pub constraints: Option<ReceiveConstraints>,
+    /// Handle the peer claimed, verified again once it is back
+    pub handle: Option<String>,
+    /// Relay the peer was last connected to
+    pub relay_url: Option<RelayUrl>,
+    /// Direct addresses the peer was last reached at
+    pub addrs: BTreeSet<SocketAddr>,
+}
+
+/// Known peers of this node, persisted so they survive a restart.
+#[derive(Debug, Default)]
+pub struct PeerCache {
+    /// Where the peers are persisted, `None` keeps them in memory only.
+    backend: Option<Arc<dyn Backend>>,
+    peers: Mutex<BTreeMap<NodeId, CachedPeer>>,
+}
+
+impl PeerCache {
+    /// Loads the peers from `backend`, dropping those not seen for a long time.
+    pub fn load(backend: Arc<dyn Backend>) -> anyhow::Result<Self> {
+        let mut peers: BTreeMap<NodeId, CachedPeer> = match backend.load(PEER_CACHE_KEY)? {
+            Some(data) => serde_json::from_slice(&data).unwrap_or_else(|err| {
+                tracing::warn!("invalid peer cache: {err}");
+                BTreeMap::new()
+            }),
+            None => BTreeMap::new(),
+        };
+        let cutoff = now().saturating_sub(MAX_AGE.as_secs());
+        peers.retain(|_, peer| peer.last_seen >= cutoff);
+
+        Ok(Self {
+            backend: Some(backend),
+            peers: Mutex::new(peers),
+        })
+    }
+
+    pub fn list(&self) -> BTreeMap<NodeId, CachedPeer> {
+        self.peers.lock().unwrap().clone()
+    }
+
+    /// Remembers `peer`, peers are seen all the time so it is only saved if it changed.
+    pub fn update(&self, node_id: NodeId, peer: CachedPeer) {
+        let mut peers = self.peers.lock().unwrap();
+        let unchanged = peers.get(&node_id).is_some_and(|old| {
+            let same = CachedPeer {
+                last_seen: old.last_seen,
+                ..peer.clone()
+            } == *old;
+            same && peer.last_seen < old.last_seen + SEEN_INTERVAL.as_secs()
+        });
+        if unchanged {
+            return;
+        }
+        peers.insert(node_id, peer);
+        self.persist(&peers);
+    }
+
+    /// Adds `imported` peers, keeping the ones seen more recently here.
+    pub fn merge(&self, imported: BTreeMap<NodeId, CachedPeer>) {
+        let mut peers = self.peers.lock().unwrap();
+        for (node_id, peer) in imported {
+            if peers
+                .get(&node_id)
+                .is_none_or(|known| known.last_seen < peer.last_seen)
+            {
+                peers.insert(node_id, peer);
+            }
+        }
+        self.persist(&peers);
+    }
+
+    /// Forgets `node_id`, e.g. after it moved to another room.
+    pub fn remove(&self, node_id: &NodeId) {
+        let mut peers = self.peers.lock().unwrap();
+        if peers.remove(node_id).is_some() {
+            self.persist(&peers);
+        }
+    }
+
+    /// Saves `peers`, failures are only logged as peers are found again by discovery.
+    fn persist(&self, peers: &BTreeMap<NodeId, CachedPeer>) {
+        if let Some(ref backend) = self.backend {
+            if let Err(err) = persistence::save(backend.as_ref(), PEER_CACHE_KEY, peers) {
+                tracing::warn!("failed to save the peer cache: {err:#}");
+            }
+        }
+    }
+}
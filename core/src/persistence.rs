@@ -0,0 +1,117 @@
+//! Backends the history and settings are persisted in.
+//!
+//! Both are stored as JSON documents under a key, so every backend only needs to
+//! load and save opaque values.
+
+use std::fmt::Debug;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use anyhow::Result;
+use serde::Serialize;
+
+/// Key value store for persisted state.
+pub trait Backend: Debug + Send + Sync {
+    /// The value of `key`, `None` if it was never saved.
+    fn load(&self, key: &str) -> Result<Option<Vec<u8>>>;
+
+    fn save(&self, key: &str, value: &[u8]) -> Result<()>;
+}
+
+/// Opens the backend this build uses, with its data in `dir`.
+///
+/// SQLite is used if the `sqlite` feature is enabled, JSON files otherwise.
+pub fn open(dir: PathBuf) -> Result<Arc<dyn Backend>> {
+    #[cfg(feature = "sqlite")]
+    let backend = Sqlite::open(dir)?;
+    #[cfg(not(feature = "sqlite"))]
+    let backend = JsonFiles::new(dir);
+    Ok(Arc::new(backend))
+}
+
+/// Encodes and saves `value` under `key`.
+pub fn save<T: Serialize>(backend: &dyn Backend, key: &str, value: &T) -> Result<()> {
+    backend.save(key, &serde_json::to_vec_pretty(value)?)
+}
+
+/// One `<key>.json` file per key.
+#[derive(Debug)]
+pub struct JsonFiles {
+    dir: PathBuf,
+}
+
+impl JsonFiles {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{key}.json"))
+    }
+}
+
+impl Backend for JsonFiles {
+    fn load(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        match std::fs::read(self.path(key)) {
+            Ok(data) => Ok(Some(data)),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn save(&self, key: &str, value: &[u8]) -> Result<()> {
+        std::fs::create_dir_all(&self.dir)?;
+        // Written to a temporary file first, so a crash never leaves a truncated file.
+        let tmp = self.path(&format!("{key}.tmp"));
+        std::fs::write(&tmp, value)?;
+        std::fs::rename(tmp, self.path(key))?;
+        Ok(())
+    }
+}
+
+/// A single SQLite database with a key value table.
+#[cfg(feature = "sqlite")]
+#[derive(Debug)]
+pub struct Sqlite {
+    conn: std::sync::Mutex<rusqlite::Connection>,
+}
+
+#[cfg(feature = "sqlite")]
+impl Sqlite {
+    const FILE: &'static str = "iroh-drop.sqlite";
+
+    pub fn open(dir: PathBuf) -> Result<Self> {
+        std::fs::create_dir_all(&dir)?;
+        let conn = rusqlite::Connection::open(dir.join(Self::FILE))?;
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS kv (key TEXT PRIMARY KEY, value BLOB NOT NULL)",
+            (),
+        )?;
+        Ok(Self {
+            conn: std::sync::Mutex::new(conn),
+        })
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl Backend for Sqlite {
+    fn load(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        use rusqlite::OptionalExtension;
+
+        let conn = self.conn.lock().unwrap();
+        let value = conn
+            .query_row("SELECT value FROM kv WHERE key = ?1", [key], |row| row.get(0))
+            .optional()?;
+        Ok(value)
+    }
+
+    fn save(&self, key: &str, value: &[u8]) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO kv (key, value) VALUES (?1, ?2) \
+             ON CONFLICT (key) DO UPDATE SET value = excluded.value",
+            rusqlite::params![key, value],
+        )?;
+        Ok(())
+    }
+}
@@ -0,0 +1,64 @@
+//! Thumbnails of received images, shown in the received files list, and previews of
+//! offered text files.
+
+use std::io::Cursor;
+
+use anyhow::Result;
+use base64::Engine;
+use image::{codecs::jpeg::JpegEncoder, ImageReader};
+
+/// Width and height the thumbnails fit into.
+const THUMBNAIL_SIZE: u32 = 256;
+
+/// Images larger than this are not decoded, to bound memory use.
+pub const MAX_IMAGE_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Number of bytes fetched to preview an offered text file.
+pub const TEXT_PREVIEW_LEN: u64 = 16 * 1024;
+
+/// Extensions of files that are previewed as text.
+const TEXT_EXTENSIONS: &[&str] = &[
+    "txt", "log", "md", "csv", "tsv", "json", "jsonl", "xml", "yaml", "yml", "toml", "ini", "conf",
+];
+
+/// Whether `head` is the start of an image we can preview.
+pub fn is_image(head: &[u8]) -> bool {
+    matches!(
+        infer::get(head).map(|kind| kind.mime_type()),
+        Some("image/jpeg" | "image/png" | "image/gif" | "image/webp" | "image/bmp")
+    )
+}
+
+/// Whether an offered file should be previewed before it is downloaded.
+///
+/// Small files are downloaded faster than their preview would be.
+pub fn wants_text_preview(name: &str, size: u64) -> bool {
+    let ext = std::path::Path::new(name)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.to_ascii_lowercase());
+    size > TEXT_PREVIEW_LEN && ext.is_some_and(|ext| TEXT_EXTENSIONS.contains(&ext.as_str()))
+}
+
+/// The start of a text file, `None` if `head` does not look like text.
+pub fn text(head: &[u8]) -> Option<String> {
+    if head.contains(&0) || infer::get(head).is_some() {
+        return None;
+    }
+    // The preview might end in the middle of a character.
+    Some(String::from_utf8_lossy(head).into_owned())
+}
+
+/// Downscales the image in `data` and returns it as a JPEG `data:` URL.
+pub fn thumbnail(data: &[u8]) -> Result<String> {
+    let image = ImageReader::new(Cursor::new(data))
+        .with_guessed_format()?
+        .decode()?
+        .thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE)
+        .into_rgb8();
+
+    let mut jpeg = Vec::new();
+    image.write_with_encoder(JpegEncoder::new_with_quality(&mut jpeg, 80))?;
+    let encoded = base64::engine::general_purpose::STANDARD.encode(jpeg);
+    Ok(format!("data:image/jpeg;base64,{encoded}"))
+}
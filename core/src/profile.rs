@@ -0,0 +1,102 @@
+//! Moving the configuration of this device to a new machine.
+//!
+//! A profile bundles the settings, with the paired devices, the own devices and their group
+//! secret, the peers remembered across restarts and, if the user wants, the secret key of
+//! the node. With the key the new machine keeps the node id, so the other devices know it
+//! without pairing again, and the old machine should not be used any more. The bundle is
+//! encrypted with [age](https://age-encryption.org) to a password chosen on export.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::{Read, Write};
+
+use age::secrecy::Secret;
+use anyhow::{Context, Result};
+use iroh::net::key::SecretKey;
+use iroh::net::NodeId;
+use serde::{Deserialize, Serialize};
+
+use crate::error::DropError;
+use crate::peer_cache::CachedPeer;
+use crate::settings::Settings;
+
+/// Version of the bundle, raised when a profile can not be read by older versions.
+const FORMAT_VERSION: u32 = 1;
+
+/// Everything moved to a new machine.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Profile {
+    pub version: u32,
+    pub settings: Settings,
+    pub peers: BTreeMap<NodeId, CachedPeer>,
+    /// Secret key of the node, only if the user chose to move it
+    secret_key: Option<String>,
+}
+
+impl fmt::Debug for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Profile")
+            .field("version", &self.version)
+            .field("settings", &self.settings)
+            .field("peers", &self.peers.len())
+            .field("secret_key", &self.secret_key.as_ref().map(|_| ".."))
+            .finish()
+    }
+}
+
+impl Profile {
+    pub fn new(
+        settings: Settings,
+        peers: BTreeMap<NodeId, CachedPeer>,
+        secret_key: Option<&SecretKey>,
+    ) -> Self {
+        Self {
+            version: FORMAT_VERSION,
+            settings,
+            peers,
+            secret_key: secret_key.map(|key| key.to_string()),
+        }
+    }
+
+    /// The secret key of the node, if it was exported.
+    pub fn secret_key(&self) -> Result<Option<SecretKey>> {
+        self.secret_key
+            .as_deref()
+            .map(|key| key.parse().context("invalid secret key"))
+            .transpose()
+    }
+
+    /// The profile as a bundle encrypted with `password`.
+    pub fn encrypt(&self, password: &str) -> Result<Vec<u8>> {
+        if password.is_empty() {
+            let message = "the password must not be empty".to_string();
+            return Err(DropError::InvalidArgument(message).into());
+        }
+        let encryptor = age::Encryptor::with_user_passphrase(Secret::new(password.to_string()));
+        let mut bundle = Vec::new();
+        let mut writer = encryptor.wrap_output(&mut bundle)?;
+        writer.write_all(&serde_json::to_vec(self)?)?;
+        writer.finish()?;
+        Ok(bundle)
+    }
+
+    /// Reads a bundle written by [`Self::encrypt`].
+    pub fn decrypt(bundle: &[u8], password: &str) -> Result<Self> {
+        let wrong_password = || DropError::InvalidArgument("wrong password".to_string());
+        let age::Decryptor::Passphrase(decryptor) = age::Decryptor::new(bundle)? else {
+            anyhow::bail!("not an exported profile");
+        };
+        let mut reader = decryptor
+            .decrypt(&Secret::new(password.to_string()), None)
+            .map_err(|_| wrong_password())?;
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data)?;
+        let profile: Self = serde_json::from_slice(&data)?;
+        anyhow::ensure!(
+            profile.version <= FORMAT_VERSION,
+            "the profile was exported by a newer version"
+        );
+        profile.settings.validate()?;
+        Ok(profile)
+    }
+}
@@ -0,0 +1,3323 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use std::{io, marker::PhantomData, pin::Pin};
+
+use anyhow::Result;
+use bao_tree::{io::BaoContentItem, ChunkNum, ChunkRanges};
+use bytes::{BufMut as _, Bytes, BytesMut};
+use futures_lite::stream::{Stream, StreamExt};
+use futures_util::sink::SinkExt;
+use iroh::net::NodeAddr;
+use iroh::{
+    base::ticket::NodeTicket,
+    blobs::{
+        get::{
+            self,
+            fsm::{self, BlobContentNext, ConnectedNext, EndBlobNext},
+        },
+        protocol::{GetRequest, RangeSpecSeq},
+        store::{mem, BaoBatchWriter, ExportFormat, ExportMode, MapEntryMut, MapMut, Store as _},
+        util::SetTagOption,
+        BlobFormat, Hash,
+    },
+    net::{
+        endpoint::{
+            get_remote_node_id, Connection, ConnectionError, ConnectionType, RecvStream, SendStream,
+        },
+        key::SecretKey,
+        NodeId,
+    },
+    node::ProtocolHandler,
+};
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio::sync::{mpsc, watch, RwLock};
+use tokio_serde::{Deserializer, Serializer};
+use tokio_util::sync::CancellationToken;
+use tracing::Instrument;
+
+use crate::admin_lock::AdminLock;
+use crate::compression::{self, Encoding};
+use crate::device_group::{GroupSecret, GroupTicket};
+use crate::diagnostics::{ConnectionInfo, Diagnostics};
+use crate::discovery::HideableDiscovery;
+use crate::error::DropError;
+use crate::export;
+use crate::guest::{GuestTicket, Guests};
+use crate::handles::{DnsNameService, Handle, HandleRegistry};
+use crate::history::{now, ConnectionPath, Direction, History, HistoryEntry};
+use crate::identity::IdentityWarning;
+use crate::library::{self, ReceivedFile, SearchQuery};
+use crate::metadata::FileMetadata;
+use crate::network_trust::Network;
+use crate::peer_cache::PeerCache;
+use crate::preview;
+use crate::profile::Profile;
+use crate::quarantine;
+use crate::ratelimit::{RateLimiter, Throughput};
+use crate::retry;
+use crate::security_log::{RejectReason, SecurityLog};
+use crate::settings::{ReceiveMode, Settings, SettingsStore};
+use crate::share::{self, ShareServer};
+use crate::sniff;
+use crate::storage;
+use crate::strategy::{self, SendSlots, TransferStrategy};
+use crate::sync::{self, SyncedSection};
+use crate::transfers::{Transfer, TransferManager};
+use crate::transform::{self, Downscale, OutgoingFile, Transform};
+use crate::vault::Vault;
+use crate::workflow;
+
+mod annotations;
+mod gc;
+mod identity;
+mod network;
+mod peers;
+mod pool;
+mod swap;
+mod uploads;
+
+pub use self::gc::StorageUsage;
+pub use self::network::RelayStatus;
+pub use self::peers::PeerInfo;
+pub use self::uploads::UploadEvents;
+use self::peers::Introduction;
+use self::peers::{restore_nodes, RemoteNode};
+use self::pool::ConnectionPool;
+use self::swap::SwapSession;
+
+pub const ALPN: &[u8] = b"iroh-drop/0";
+
+/// How long sending waits for a local path, see [`crate::settings::AdvancedSettings::prefer_local_transport`].
+const LOCAL_PATH_WAIT: Duration = Duration::from_secs(3);
+
+/// How long shutting down waits for running transfers.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Images smaller than this are received as they are, see [`Protocol::wanted_variant`].
+const COUNTER_OFFER_MIN_SIZE: u64 = 1024 * 1024;
+
+/// Offers at least this large are checked with a `Preflight` first, see [`Protocol::preflight`].
+const PREFLIGHT_MIN_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Files up to this size are sent in a `SendInline`, skipping the blob download.
+const MAX_INLINE_SIZE: u64 = 256 * 1024;
+
+/// How long a stranger may use a grant from [`Protocol::accept_once`].
+const ACCEPT_ONCE_TTL: Duration = Duration::from_secs(10 * 60);
+
+/// How long deleted transfers can be restored, before their blobs are removed.
+const TRASH_GRACE: Duration = Duration::from_secs(30);
+
+/// A file offered again this soon after it was received from the same peer is not
+/// received again, see [`Protocol::duplicate_offer`].
+const DUPLICATE_OFFER_WINDOW: Duration = Duration::from_secs(10 * 60);
+
+/// Time an expired offer is given to arrive as a `SendReject`, before the sender gives up on it.
+const OFFER_TTL_GRACE: Duration = Duration::from_secs(30);
+
+/// The drop protocol, accepting connections on [`ALPN`].
+///
+/// Offers, downloads and the peer table are handled in the background, what happens is
+/// reported as [`LocalProtocolMessage`]s. Use [`crate::node::spawn`] to start a node with it.
+#[derive(Debug)]
+pub struct Protocol {
+    /// Name we announce, changed through the settings
+    name: std::sync::RwLock<String>,
+    /// Name used if none is set in the settings
+    default_name: String,
+    known_nodes: RwLock<BTreeMap<NodeId, RemoteNode>>,
+    /// Discovered nodes we are currently introducing ourselves to.
+    pending_intros: std::sync::Mutex<BTreeSet<NodeId>>,
+    /// Open connections to recently used peers.
+    pool: ConnectionPool,
+    client: iroh::client::Iroh,
+    endpoint: iroh::net::Endpoint,
+    /// Blob store of the node, downloads are written into it directly.
+    store: mem::Store,
+    send_slots: Arc<SendSlots>,
+    history: History,
+    /// Peers remembered across restarts
+    peer_cache: PeerCache,
+    security_log: SecurityLog,
+    diagnostics: Diagnostics,
+    settings: Arc<SettingsStore>,
+    /// App scoped directory received data is stored in.
+    storage_dir: PathBuf,
+    /// Encrypted copies of received files, once the user set it up.
+    vault: Vault,
+    /// Read-only mode of shared machines.
+    admin_lock: AdminLock,
+    /// Set while the user paused receiving, offers are rejected.
+    receiving_paused: AtomicBool,
+    /// Strangers allowed to send a single file, with when they were allowed.
+    ///
+    /// Kept apart from the own devices, and never persisted.
+    accept_once: std::sync::Mutex<BTreeMap<NodeId, Instant>>,
+    /// Visitors that may send files for a limited time.
+    guests: Guests,
+    /// Resolves handles of other devices, see [`crate::handles`].
+    handles: HandleRegistry,
+    /// Files held for the next swap with each peer.
+    swap_queue: std::sync::Mutex<BTreeMap<NodeId, Vec<OutgoingFile>>>,
+    /// Running swaps, by peer.
+    swaps: std::sync::Mutex<BTreeMap<NodeId, SwapSession>>,
+    /// Discovery services of the node, if they can be hidden.
+    discovery: Option<HideableDiscovery>,
+    /// Set while the device is invisible, see [`Self::set_invisible`].
+    invisible: watch::Sender<bool>,
+    /// The network the device is on, `None` until the addresses are known.
+    network: std::sync::Mutex<Option<Network>>,
+    /// Set if invisible mode was turned on because the network is not trusted.
+    network_invisible: AtomicBool,
+    /// Queue of uploads and downloads.
+    transfers: TransferManager,
+    /// Server of the share links, started with the first link.
+    share: tokio::sync::OnceCell<Arc<ShareServer>>,
+    /// Cancelled when the node shuts down, closing open streams.
+    shutdown: CancellationToken,
+    s: mpsc::Sender<LocalProtocolMessage>,
+}
+
+impl ProtocolHandler for Protocol {
+    fn accept(
+        self: Arc<Self>,
+        connecting: iroh::net::endpoint::Connecting,
+    ) -> futures_lite::future::Boxed<Result<()>> {
+        Box::pin(async move {
+            // Wait for the connection to be fully established.
+            let connection = connecting.await?;
+            // We can get the remote's node id from the connection.
+            let node_id = get_remote_node_id(&connection)?;
+            if self.guests.is_expired(&node_id) {
+                tracing::info!("refusing connection from {node_id}, its guest access expired");
+                connection.close(0u32.into(), b"guest access expired");
+                return Ok(());
+            }
+            if self.is_invisible() {
+                tracing::info!("refusing connection from {node_id} while invisible");
+                connection.close(0u32.into(), b"invisible");
+                return Ok(());
+            }
+            tracing::info!("accepted connection from {node_id}");
+
+            // Every request comes on its own bi-directional stream. Peers from version 18
+            // keep the connection open for further requests, see [`pool`].
+            loop {
+                let streams = tokio::select! {
+                    streams = connection.accept_bi() => streams,
+                    _ = self.shutdown.cancelled() => break,
+                };
+                let Ok((send_stream, recv_stream)) = streams else {
+                    break;
+                };
+                if self.guests.is_expired(&node_id) {
+                    tracing::info!("closing connection from {node_id}, its guest access expired");
+                    connection.close(0u32.into(), b"guest access expired");
+                    break;
+                }
+                if self.is_invisible() {
+                    tracing::info!("closing connection from {node_id} while invisible");
+                    connection.close(0u32.into(), b"invisible");
+                    break;
+                }
+                self.serve_stream(node_id, send_stream, recv_stream).await;
+            }
+
+            Ok(())
+        })
+    }
+
+    fn shutdown(self: Arc<Self>) -> futures_lite::future::Boxed<()> {
+        Box::pin(async move {
+            self.shutdown.cancel();
+            let active = self.transfers.active();
+            if active > 0 {
+                tracing::info!("waiting for {active} transfers before shutting down");
+            }
+            let idle = async {
+                while self.transfers.active() > 0 {
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                }
+            };
+            if tokio::time::timeout(SHUTDOWN_TIMEOUT, idle).await.is_err() {
+                tracing::warn!("shutting down with transfers still running");
+            }
+        })
+    }
+}
+
+/// Events reported to the embedding app, e.g. to notify the user.
+pub enum LocalProtocolMessage {
+    IncomingFile {
+        from: NodeId,
+        /// Name of the sending node
+        sender: String,
+        name: String,
+        hash: Hash,
+        size: u64,
+    },
+    FileDownloaded {
+        from: NodeId,
+        name: String,
+        hash: Hash,
+        size: u64,
+        /// Whether the file was in the store already, so nothing was downloaded
+        already_had: bool,
+    },
+    TransferWarning { node_id: NodeId, message: String },
+    ContentMismatch { name: String, hash: Hash, message: String },
+    PeerOnline { node_id: NodeId, name: String },
+    PeerOffline { node_id: NodeId },
+    /// A node was found that runs an incompatible version, see [`PeerInfo::incompatible`].
+    PeerIncompatible {
+        node_id: NodeId,
+    },
+    /// Connecting to the peer failed even after retrying.
+    PeerUnreachable { node_id: NodeId, error: String },
+    /// The receiver finished downloading a file we sent.
+    FileSent {
+        to: NodeId,
+        name: String,
+        hash: Hash,
+        size: u64,
+        /// Whether the receiver verified the file, `None` for older receivers
+        verified: Option<bool>,
+        /// Whether the receiver had the file already, so nothing was uploaded
+        already_had: bool,
+    },
+    /// The receiver of a file we sent started or finished downloading it.
+    SentFileDelivery {
+        to: NodeId,
+        name: String,
+        hash: Hash,
+        status: DeliveryStatus,
+    },
+    /// An offer was rejected because this device can not store it, or it expired.
+    OfferRejected {
+        sender: String,
+        name: String,
+        size: u64,
+        reason: RejectReason,
+    },
+    /// Settings were changed by one of our other devices.
+    SettingsChanged,
+    /// The number of running uploads and downloads changed.
+    TransfersActive { active: usize },
+    /// A transfer was queued or changed its state.
+    TransferUpdated(Transfer),
+    /// A transfer left the queue, finished or not.
+    TransferFinished { id: u64 },
+    /// A download made progress, `bps` is its current throughput.
+    TransferProgress {
+        id: u64,
+        offset: u64,
+        size: u64,
+        bps: u64,
+        /// The download is paused at `offset`
+        paused: bool,
+    },
+    /// The receiver pulled more of a file we sent, `bps` is the current throughput.
+    UploadProgress {
+        id: u64,
+        offset: u64,
+        size: u64,
+        bps: u64,
+    },
+    /// The start of an offered text file, fetched before the full download.
+    IncomingPreview { hash: Hash, text: String },
+    /// Deleted transfers were removed for good and can no longer be restored.
+    TrashPurged { hashes: Vec<Hash> },
+    /// Invisible mode was turned on or off.
+    VisibilityChanged { invisible: bool },
+    /// The device joined a network the user did not decide on yet.
+    UnknownNetwork { network: Network },
+    /// A swap started or made progress, it is done once all files were sent and received.
+    SwapProgress {
+        node_id: NodeId,
+        sent: u32,
+        received: u32,
+        to_send: u32,
+        to_receive: u32,
+    },
+    /// The history changed without a transfer, e.g. a peer changed the note of one of our
+    /// transfers with it.
+    HistoryChanged,
+    /// A known peer changed its name.
+    PeerRenamed {
+        node_id: NodeId,
+        name: String,
+    },
+    /// A peer introduced itself with a name that does not match the pinned identities, see
+    /// [`crate::identity`].
+    IdentityWarning {
+        node_id: NodeId,
+        name: String,
+        warning: IdentityWarning,
+    },
+    /// A peer asked to verify the connection, the user compares `code` with the one it shows.
+    IdentityVerification {
+        node_id: NodeId,
+        name: String,
+        code: String,
+    },
+    /// A file was sent to all members of a peer group, with the error of each member it
+    /// failed for.
+    GroupSent {
+        group: String,
+        name: String,
+        results: Vec<(NodeId, Option<DropError>)>,
+    },
+}
+
+/// How far the receiver got with a file we sent, see [`LocalProtocolMessage::SentFileDelivery`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeliveryStatus {
+    /// The receiver accepted the file and is downloading it
+    Downloading,
+    /// The receiver has the file and verified it
+    Delivered,
+    /// The download failed or the file could not be verified
+    Failed,
+}
+
+/// A blob in the store, see [`Protocol::list_blobs`].
+#[derive(Debug, Clone, Serialize)]
+pub struct StoredBlob {
+    pub hash: Hash,
+    pub size: u64,
+    /// Name of the latest transfer of the blob, `None` if it is not in the history
+    pub name: Option<String>,
+    /// Whether the blob was received, only those can be exported with
+    /// [`Protocol::export_blob`]
+    pub received: bool,
+}
+
+impl Protocol {
+    /// Creates the protocol, it still has to be registered on the node under [`ALPN`].
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        name: String,
+        client: iroh::client::Iroh,
+        endpoint: iroh::net::Endpoint,
+        store: mem::Store,
+        settings: Arc<SettingsStore>,
+        history: History,
+        peer_cache: PeerCache,
+        storage_dir: PathBuf,
+        discovery: Option<HideableDiscovery>,
+        s: mpsc::Sender<LocalProtocolMessage>,
+    ) -> Arc<Self> {
+        let handles = HandleRegistry::new(vec![Box::new(DnsNameService::new(
+            endpoint.dns_resolver().clone(),
+        ))]);
+        let known_nodes = restore_nodes(&peer_cache, &endpoint);
+        Arc::new(Self {
+            name: std::sync::RwLock::new(name.clone()),
+            default_name: name,
+            client,
+            endpoint,
+            store,
+            known_nodes: RwLock::new(known_nodes),
+            send_slots: Default::default(),
+            pending_intros: Default::default(),
+            pool: Default::default(),
+            history,
+            peer_cache,
+            security_log: Default::default(),
+            diagnostics: Default::default(),
+            settings,
+            vault: Vault::new(storage_dir.join("vault")),
+            admin_lock: AdminLock::new(storage_dir.join("admin-lock.age")),
+            storage_dir,
+            receiving_paused: AtomicBool::new(false),
+            accept_once: Default::default(),
+            guests: Default::default(),
+            handles,
+            swap_queue: Default::default(),
+            swaps: Default::default(),
+            discovery,
+            invisible: watch::Sender::new(false),
+            network: Default::default(),
+            network_invisible: AtomicBool::new(false),
+            transfers: TransferManager::new(s.clone()),
+            share: Default::default(),
+            shutdown: CancellationToken::new(),
+            s,
+        })
+    }
+
+    pub fn security_log(&self) -> &SecurityLog {
+        &self.security_log
+    }
+
+    pub fn diagnostics(&self) -> &Diagnostics {
+        &self.diagnostics
+    }
+
+    pub fn settings(&self) -> &SettingsStore {
+        &self.settings
+    }
+
+    pub fn vault(&self) -> &Vault {
+        &self.vault
+    }
+
+    pub fn admin_lock(&self) -> &AdminLock {
+        &self.admin_lock
+    }
+
+    /// The name this device announces to others.
+    pub fn name(&self) -> String {
+        self.name.read().unwrap().clone()
+    }
+
+    /// Applies changed settings to the running protocol.
+    ///
+    /// Must be called once after construction, to initialize the limits.
+    pub fn apply_settings(&self, settings: &Settings) {
+        self.transfers.set_limits(
+            settings.advanced.max_concurrent_downloads,
+            settings.advanced.max_concurrent_uploads,
+        );
+        let name = settings.name.as_ref().unwrap_or(&self.default_name);
+        *self.name.write().unwrap() = name.clone();
+    }
+
+    /// Writes the settings, the known peers and, with `include_key`, the secret key of this
+    /// node to `path`, encrypted with `password`.
+    pub async fn export_profile(
+        &self,
+        path: &Path,
+        password: &str,
+        include_key: bool,
+    ) -> Result<()> {
+        let settings = self.settings.get().await;
+        let secret_key = include_key.then(|| self.endpoint.secret_key());
+        let profile = Profile::new(settings, self.peer_cache.list(), secret_key);
+        std::fs::write(path, profile.encrypt(password)?)?;
+        tracing::info!("exported the profile to {}", path.display());
+        Ok(())
+    }
+
+    /// Replaces the settings with those of the profile at `path` and adds its peers.
+    ///
+    /// Returns the secret key of the profile if it is not ours, it has to be saved and only
+    /// takes effect once the node is started again.
+    pub async fn import_profile(&self, path: &Path, password: &str) -> Result<Option<SecretKey>> {
+        let profile = Profile::decrypt(&std::fs::read(path)?, password)?;
+        let secret_key = profile.secret_key()?;
+        self.settings.replace(profile.settings.clone()).await?;
+        self.apply_settings(&profile.settings);
+        self.peer_cache.merge(profile.peers);
+        self.s
+            .send(LocalProtocolMessage::SettingsChanged)
+            .await
+            .ok();
+        tracing::info!("imported the profile from {}", path.display());
+        Ok(secret_key.filter(|key| key.public() != self.endpoint.node_id()))
+    }
+
+    pub fn set_receiving_paused(&self, paused: bool) {
+        tracing::info!("receiving {}", if paused { "paused" } else { "resumed" });
+        self.receiving_paused.store(paused, Ordering::SeqCst);
+    }
+
+    pub fn is_receiving_paused(&self) -> bool {
+        self.receiving_paused.load(Ordering::SeqCst)
+    }
+
+    /// Accepts the next offer of `node_id`, even if it is unknown or not trusted.
+    ///
+    /// The grant is used up by a single transfer, or expires after [`ACCEPT_ONCE_TTL`].
+    pub fn accept_once(&self, node_id: NodeId) {
+        tracing::info!("accepting the next offer of {node_id}");
+        self.accept_once
+            .lock()
+            .unwrap()
+            .insert(node_id, Instant::now());
+    }
+
+    /// Whether `node_id` may send a single file, see [`Self::accept_once`].
+    fn has_grant(&self, node_id: &NodeId) -> bool {
+        let mut grants = self.accept_once.lock().unwrap();
+        grants.retain(|_, granted| granted.elapsed() < ACCEPT_ONCE_TTL);
+        grants.contains_key(node_id)
+    }
+
+    /// Hides this device, e.g. on a public network.
+    ///
+    /// While invisible we stop announcing ourselves, refuse new connections, do not contact
+    /// peers and pause our downloads and offers. Blobs already being fetched by a peer are
+    /// still served.
+    pub async fn set_invisible(&self, invisible: bool) {
+        // The user decides from now on, until the next network change.
+        self.network_invisible.store(false, Ordering::SeqCst);
+        if self.invisible.send_replace(invisible) == invisible {
+            return;
+        }
+        tracing::info!("{}", if invisible { "going invisible" } else { "visible again" });
+        if let Some(ref discovery) = self.discovery {
+            discovery.set_hidden(invisible);
+        }
+        if invisible {
+            self.pool.clear();
+        }
+        self.s
+            .send(LocalProtocolMessage::VisibilityChanged { invisible })
+            .await
+            .ok();
+    }
+
+    pub fn is_invisible(&self) -> bool {
+        *self.invisible.borrow()
+    }
+
+    /// Waits until the device is visible again, returns right away if it is.
+    async fn wait_visible(&self) {
+        if self.is_invisible() {
+            let mut invisible = self.invisible.subscribe();
+            invisible.wait_for(|invisible| !invisible).await.ok();
+        }
+    }
+
+    pub fn transfers(&self) -> &TransferManager {
+        &self.transfers
+    }
+
+    /// Number of downloads currently running.
+    pub fn active_downloads(&self) -> usize {
+        self.transfers.active_in(Direction::Received)
+    }
+
+    /// Name of `node_id`, if it introduced itself.
+    async fn peer_name(&self, node_id: &NodeId) -> Option<String> {
+        self.known_nodes
+            .read()
+            .await
+            .get(node_id)
+            .map(|info| info.name.clone())
+    }
+
+    /// Applies the receive policies to an offer, returning the name of the sender if it is accepted.
+    ///
+    /// `auto_accept` is set for offers a workflow accepts, like a grant that is not used up.
+    async fn check_offer(
+        &self,
+        node_id: NodeId,
+        size: u64,
+        auto_accept: bool,
+    ) -> Result<String, RejectReason> {
+        let (sender, uses_grant) = self.evaluate_offer(node_id, size, auto_accept).await?;
+        if uses_grant {
+            tracing::info!("accepting a single offer of {node_id}");
+            self.accept_once.lock().unwrap().remove(&node_id);
+        }
+        Ok(sender)
+    }
+
+    /// Like [`Self::check_offer`], without using up a grant.
+    ///
+    /// Also returns whether the offer is only accepted because of a grant.
+    async fn evaluate_offer(
+        &self,
+        node_id: NodeId,
+        size: u64,
+        auto_accept: bool,
+    ) -> Result<(String, bool), RejectReason> {
+        let settings = self.settings.get().await;
+        // Own devices are accepted without asking, like a grant that is never used up.
+        let trusted = settings.sync.own_devices.contains(&node_id);
+        let granted = !trusted && self.has_grant(&node_id);
+        let allowed = trusted || granted || auto_accept || self.guests.is_guest(&node_id);
+        let sender = match self.peer_name(&node_id).await {
+            Some(sender) => sender,
+            None if allowed => node_id.fmt_short(),
+            None => return Err(RejectReason::UnknownPeer),
+        };
+        if self.shutdown.is_cancelled() {
+            return Err(RejectReason::ShuttingDown);
+        }
+        if self.is_receiving_paused() || self.is_invisible() {
+            return Err(RejectReason::Paused);
+        }
+        match settings.receive_mode {
+            ReceiveMode::Everyone => {}
+            ReceiveMode::TrustedOnly if allowed => {}
+            ReceiveMode::TrustedOnly => return Err(RejectReason::NotTrusted),
+            ReceiveMode::Off => return Err(RejectReason::ReceiveOff),
+        }
+        if !allowed && self.network_restricted().await {
+            return Err(RejectReason::UntrustedNetwork);
+        }
+        // Even own devices need the user to accept larger files once.
+        let max_size = settings.advanced.max_receive_size;
+        let oversized = max_size > 0 && size > max_size;
+        if oversized && !self.has_grant(&node_id) {
+            return Err(RejectReason::ExceedsLimit);
+        }
+        let reserved = self.transfers.remaining_download_bytes();
+        storage::check(&self.storage_dir, size, reserved)?;
+        Ok((sender, (granted && !auto_accept) || oversized))
+    }
+
+    /// Applies the policies on the name of an offered file.
+    async fn check_name(&self, name: &str) -> Result<(), RejectReason> {
+        let settings = self.settings.get().await;
+        if settings.reject_executables && quarantine::has_executable_extension(name) {
+            return Err(RejectReason::Executable);
+        }
+        Ok(())
+    }
+
+    /// What this node currently accepts, advertised in the intro.
+    async fn receive_constraints(&self) -> ReceiveConstraints {
+        let reserved = self.transfers.remaining_download_bytes();
+        let max_file_size = storage::max_file_size(&self.storage_dir, reserved);
+        ReceiveConstraints {
+            max_file_size,
+            accepts_folders: false,
+            accepts_executables: !self.settings.get().await.reject_executables,
+        }
+    }
+
+    /// Answers a `Preflight`, telling the sender whether the offer would be accepted.
+    async fn handle_preflight(
+        &self,
+        node_id: NodeId,
+        name: String,
+        size: u64,
+        metadata: FileMetadata,
+    ) -> ProtocolMessage {
+        let workflows = self.settings.get().await.workflows;
+        let auto_accept = workflow::find(&workflows, &node_id, &name, &metadata)
+            .is_some_and(|workflow| workflow.auto_accept);
+        let checked = match self.check_name(&name).await {
+            Ok(()) => self.evaluate_offer(node_id, size, auto_accept).await.map(|_| ()),
+            Err(reason) => Err(reason),
+        };
+        match checked {
+            Ok(()) => ProtocolMessage::PreflightOk,
+            Err(reason) => {
+                tracing::info!("{name} from {node_id} would be rejected: {reason}");
+                self.reject(node_id, reason).await
+            }
+        }
+    }
+
+    /// The answer rejecting an offer of `node_id`, with a typed reason if it understands it.
+    pub(super) async fn reject(&self, node_id: NodeId, reason: RejectReason) -> ProtocolMessage {
+        let typed = self
+            .known_nodes
+            .read()
+            .await
+            .get(&node_id)
+            .is_some_and(|node| node.capabilities.supports_reject_reasons());
+        if typed {
+            ProtocolMessage::Rejected { reason }
+        } else {
+            ProtocolMessage::SendReject {
+                reason: reason.to_string(),
+            }
+        }
+    }
+
+    /// Downloads an offered file, unless it is rejected by the receive policies.
+    ///
+    /// `max_bps` is the upload limit of the sender, `0` if unlimited.
+    /// Senders that support it are told on `writer` once the download starts.
+    /// Returns whether the file was received and verified.
+    #[tracing::instrument(skip_all, fields(from = %node_id.fmt_short(), file = %offer.name))]
+    async fn handle_send_request(
+        &self,
+        node_id: NodeId,
+        offer: Offer,
+        writer: &mut RpcWrite<SendStream>,
+    ) -> Result<ProtocolMessage, RejectReason> {
+        let Offer {
+            name,
+            hash,
+            size,
+            max_bps,
+            metadata,
+            variant,
+            encoding,
+            inline,
+        } = offer;
+        let workflows = self.settings.get().await.workflows;
+        let auto_accept = workflow::find(&workflows, &node_id, &name, &metadata)
+            .is_some_and(|workflow| workflow.auto_accept);
+        let checked = match self.check_name(&name).await {
+            Ok(()) => self.check_offer(node_id, size, auto_accept).await,
+            Err(reason) => Err(reason),
+        };
+        let sender = match checked {
+            Ok(sender) => sender,
+            Err(reason) => {
+                let sender = self.peer_name(&node_id).await;
+                self.security_log
+                    .record(node_id, sender.clone(), name.clone(), hash, size, reason)
+                    .await;
+                if matches!(
+                    reason,
+                    RejectReason::TooLarge
+                        | RejectReason::ExceedsLimit
+                        | RejectReason::LowStorage
+                        | RejectReason::LowMemory
+                ) {
+                    self.s
+                        .send(LocalProtocolMessage::OfferRejected {
+                            sender: sender.unwrap_or_default(),
+                            name,
+                            size,
+                            reason,
+                        })
+                        .await
+                        .ok();
+                }
+                return Err(reason);
+            }
+        };
+
+        if let Some(response) = self.duplicate_offer(node_id, hash, size).await {
+            tracing::info!("{name} ({hash}) was offered again, ignoring it");
+            return Ok(response);
+        }
+        if self.has_blob(hash, size).await {
+            tracing::info!("already have {name} ({hash}), skipping the download");
+            let mut entry = HistoryEntry::new(Direction::Received, node_id, name, hash, size);
+            entry.duration_ms = Some(0);
+            entry.verified = Some(true);
+            entry.metadata = metadata;
+            entry.encoding = encoding;
+            self.on_downloaded(entry, true).await;
+            return Ok(ProtocolMessage::AlreadyHave { hash });
+        }
+        if let Some(variant) = self.wanted_variant(node_id, size, &metadata, variant).await {
+            tracing::info!("asking for {name} as {variant:?}");
+            return Ok(ProtocolMessage::CounterOffer { hash, variant });
+        }
+
+        // TODO: ask for accepting
+        tracing::info!(%hash, size, "incoming request for {name} from {sender}");
+        self.s
+            .send(LocalProtocolMessage::IncomingFile {
+                from: node_id,
+                sender: sender.clone(),
+                name: name.clone(),
+                hash,
+                size,
+            })
+            .await
+            .ok();
+        // The head of a compressed blob can not be decoded on its own.
+        if encoding.is_none() && preview::wants_text_preview(&name, size) {
+            let head = match inline {
+                Some(ref data) => {
+                    let len = data.len().min(preview::TEXT_PREVIEW_LEN as usize);
+                    Ok(data[..len].to_vec())
+                }
+                None => {
+                    self.fetch_head(node_id, hash, preview::TEXT_PREVIEW_LEN)
+                        .await
+                }
+            };
+            match head {
+                Ok(head) => {
+                    if let Some(text) = preview::text(&head) {
+                        self.s
+                            .send(LocalProtocolMessage::IncomingPreview { hash, text })
+                            .await
+                            .ok();
+                    }
+                }
+                Err(err) => tracing::warn!("failed to preview {name}: {err:#}"),
+            }
+        }
+        // Nobody may get around to an offer stuck in the queue, give up on it after a while.
+        let offer_ttl = self.settings.get().await.advanced.offer_ttl();
+        let started = tokio::time::timeout(
+            offer_ttl,
+            self.transfers
+                .start(Direction::Received, node_id, name.clone(), Some(hash), size),
+        )
+        .await;
+        let transfer = match started {
+            Ok(Some(transfer)) => transfer,
+            Ok(None) => {
+                return Ok(ProtocolMessage::TransferComplete {
+                    hash,
+                    verified: false,
+                });
+            }
+            Err(_) => {
+                tracing::info!("the offer of {name} from {sender} expired in the queue");
+                self.s
+                    .send(LocalProtocolMessage::OfferRejected {
+                        sender,
+                        name,
+                        size,
+                        reason: RejectReason::TimedOut,
+                    })
+                    .await
+                    .ok();
+                return Err(RejectReason::TimedOut);
+            }
+        };
+        let receipts = self
+            .known_nodes
+            .read()
+            .await
+            .get(&node_id)
+            .is_some_and(|node| node.capabilities.supports_delivery_receipts());
+        if receipts {
+            if let Err(err) = writer.send(ProtocolMessage::DownloadStarted { hash }).await {
+                tracing::warn!("failed to send: {err:?}");
+            }
+        }
+        let start = Instant::now();
+        let max_down_bps = self.settings.get().await.advanced.max_down_bps;
+        let limiter = RateLimiter::lowest([max_down_bps, max_bps]);
+        let fetched = match inline {
+            Some(data) => self.store_inline(node_id, data, transfer.id()).await,
+            None => {
+                self.fetch(node_id, hash, size, limiter, transfer.id())
+                    .await
+            }
+        };
+        let verified = match fetched {
+            Ok(()) => {
+                let verified = self.verify(hash, size).await;
+                let mut entry = HistoryEntry::new(Direction::Received, node_id, name, hash, size);
+                entry.duration_ms = Some(start.elapsed().as_millis() as u64);
+                entry.verified = Some(verified);
+                entry.metadata = metadata;
+                entry.encoding = encoding;
+                self.on_downloaded(entry, false).await;
+                verified
+            }
+            Err(err) => {
+                tracing::warn!("failed to download: {err:?}");
+                false
+            }
+        };
+        Ok(ProtocolMessage::TransferComplete { hash, verified })
+    }
+
+    /// The answer to an offer `node_id` made before, e.g. as the user dropped the file twice.
+    ///
+    /// A file that is still downloading is answered with `AlreadyReceiving`, one that was
+    /// received recently with `AlreadyHave`, without notifying the user again.
+    async fn duplicate_offer(
+        &self,
+        node_id: NodeId,
+        hash: Hash,
+        size: u64,
+    ) -> Option<ProtocolMessage> {
+        let receiving = self.transfers.list().into_iter().any(|transfer| {
+            transfer.direction == Direction::Received
+                && transfer.node_id == node_id
+                && transfer.hash == Some(hash)
+        });
+        if receiving {
+            let supported = self
+                .known_nodes
+                .read()
+                .await
+                .get(&node_id)
+                .is_some_and(|node| node.capabilities.supports_duplicate_offers());
+            // Older senders would fail to decode the answer.
+            if !supported {
+                let reason = "the file is already being received".to_string();
+                return Some(ProtocolMessage::SendReject { reason });
+            }
+            return Some(ProtocolMessage::AlreadyReceiving { hash });
+        }
+
+        let since = now().saturating_sub(DUPLICATE_OFFER_WINDOW.as_secs());
+        let received = self.history.list().await.into_iter().any(|entry| {
+            entry.direction == Direction::Received
+                && entry.node_id == node_id
+                && entry.hash == hash
+                && entry.verified == Some(true)
+                && entry.timestamp >= since
+        });
+        (received && self.has_blob(hash, size).await)
+            .then_some(ProtocolMessage::AlreadyHave { hash })
+    }
+
+    /// The reduced variant to ask for instead of the offered file, if any.
+    ///
+    /// Only large images are downscaled, and only once per offer.
+    async fn wanted_variant(
+        &self,
+        node_id: NodeId,
+        size: u64,
+        metadata: &FileMetadata,
+        offered: Option<Variant>,
+    ) -> Option<Variant> {
+        let max_dimension = self.settings.get().await.advanced.request_max_dimension;
+        let is_image = metadata
+            .mime
+            .as_deref()
+            .is_some_and(|mime| mime.starts_with("image/"));
+        if offered.is_some() || max_dimension == 0 || !is_image || size < COUNTER_OFFER_MIN_SIZE {
+            return None;
+        }
+        let supported = self
+            .known_nodes
+            .read()
+            .await
+            .get(&node_id)
+            .is_some_and(|node| node.capabilities.supports_counter_offer());
+        supported.then_some(Variant::Downscaled { max_dimension })
+    }
+
+    /// Adds the bytes of an inline offer to the store, instead of downloading them.
+    async fn store_inline(&self, node_id: NodeId, data: Vec<u8>, transfer_id: u64) -> Result<()> {
+        let size = data.len() as u64;
+        self.client.blobs().add_bytes(data).await?;
+        let path = self.connection_path(node_id);
+        self.transfers.record(transfer_id, size, path);
+        Ok(())
+    }
+
+    /// Whether `hash` is already complete in the local store, e.g. from an earlier transfer.
+    async fn has_blob(&self, hash: Hash, size: u64) -> bool {
+        match self.client.blobs().read(hash).await {
+            Ok(reader) => reader.is_complete() && reader.size() == size,
+            Err(_) => false,
+        }
+    }
+
+    /// Downloads `hash` from `node_id` into the blob store, at most as fast as `limiter` allows.
+    ///
+    /// The blob is fetched directly, instead of through the downloader of the node,
+    /// to be able to pace and pause it, and to report its progress. Chunks are written to
+    /// the store as soon as they are verified, the blob is never held in memory as a whole.
+    /// While paused the connection is closed, after resuming only the chunks that are not
+    /// verified yet are fetched again.
+    async fn fetch(
+        &self,
+        node_id: NodeId,
+        hash: Hash,
+        size: u64,
+        mut limiter: Option<RateLimiter>,
+        transfer_id: u64,
+    ) -> Result<()> {
+        let dial_timeout = self.settings.get().await.advanced.dial_timeout();
+        let entry = self.store.get_or_create(hash, size).await?;
+        let mut throughput = Throughput::default();
+        // Refreshed with each progress report, the path can change during the download.
+        let mut path = self.connection_path(node_id);
+        loop {
+            // Like the writes below, reading the in-memory store completes right away.
+            let valid =
+                futures_lite::future::block_on(get::db::valid_ranges::<mem::Store>(&entry))?;
+            let missing: ChunkRanges = ChunkRanges::all().difference(&valid);
+            if missing.is_empty() {
+                break;
+            }
+            let conn = self
+                .connect(node_id.into(), iroh::blobs::protocol::ALPN, dial_timeout)
+                .await?;
+            let request = GetRequest::new(hash, RangeSpecSeq::from_ranges([missing]));
+            let connected = fsm::start(conn, request).next().await?;
+            let ConnectedNext::StartRoot(start) = connected.next().await? else {
+                anyhow::bail!("unexpected response for {hash}");
+            };
+            let (mut content, blob_size) = start.next().next().await?;
+            anyhow::ensure!(
+                blob_size == size,
+                "{hash} has {blob_size} bytes, but {size} were announced"
+            );
+
+            let mut writer = entry.batch_writer().await?;
+            // Set to the offset reached when the user paused the download.
+            let mut paused_at = 0;
+            let end = loop {
+                match content.next().await {
+                    BlobContentNext::More((next, item)) => {
+                        let item = item?;
+                        let mut offset = None;
+                        if let BaoContentItem::Leaf(leaf) = &item {
+                            self.wait_visible().await;
+                            if let Some(ref mut limiter) = limiter {
+                                limiter.consume(leaf.data.len()).await;
+                            }
+                            throughput.add(leaf.data.len());
+                            self.transfers
+                                .record(transfer_id, leaf.data.len() as u64, path);
+                            offset = Some(leaf.offset + leaf.data.len() as u64);
+                        }
+                        // The writes of the in-memory store complete right away, its futures
+                        // are just not `Send`.
+                        futures_lite::future::block_on(writer.write_batch(size, vec![item]))?;
+                        let Some(offset) = offset else {
+                            content = next;
+                            continue;
+                        };
+                        if self.transfers.is_paused(transfer_id) {
+                            paused_at = offset;
+                            break None;
+                        }
+                        if let Some(bps) = throughput.report() {
+                            path = self.connection_path(node_id);
+                            self.s
+                                .try_send(LocalProtocolMessage::TransferProgress {
+                                    id: transfer_id,
+                                    offset,
+                                    size,
+                                    bps,
+                                    paused: false,
+                                })
+                                .ok();
+                        }
+                        content = next;
+                    }
+                    BlobContentNext::Done(end) => break Some(end),
+                }
+            };
+            drop(writer);
+            if let Some(end) = end {
+                if let EndBlobNext::Closing(closing) = end.next() {
+                    closing.next().await?;
+                }
+                break;
+            }
+
+            // Dropping the response closes the connection, the sender does not have to
+            // keep it open while the user decides.
+            self.s
+                .try_send(LocalProtocolMessage::TransferProgress {
+                    id: transfer_id,
+                    offset: paused_at,
+                    size,
+                    bps: 0,
+                    paused: true,
+                })
+                .ok();
+            self.transfers.wait_resumed(transfer_id).await;
+            throughput = Throughput::default();
+            tracing::info!("continuing {hash} at {paused_at} after a pause");
+        }
+
+        // Every chunk was verified against `hash` while streaming.
+        self.store.insert_complete(entry).await?;
+        Ok(())
+    }
+
+    /// Fetches up to the first `len` bytes of `hash` from `node_id`, without storing them.
+    async fn fetch_head(&self, node_id: NodeId, hash: Hash, len: u64) -> Result<Vec<u8>> {
+        let dial_timeout = self.settings.get().await.advanced.dial_timeout();
+        let conn = self
+            .connect(node_id.into(), iroh::blobs::protocol::ALPN, dial_timeout)
+            .await?;
+        let chunks = ChunkRanges::from(..ChunkNum::full_chunks(len));
+        let request = GetRequest::new(hash, RangeSpecSeq::from_ranges([chunks]));
+        let connected = fsm::start(conn, request).next().await?;
+        let ConnectedNext::StartRoot(start) = connected.next().await? else {
+            anyhow::bail!("unexpected response for {hash}");
+        };
+        let (mut content, _size) = start.next().next().await?;
+
+        let mut data = Vec::with_capacity(len as usize);
+        let end = loop {
+            match content.next().await {
+                BlobContentNext::More((next, item)) => {
+                    if let BaoContentItem::Leaf(leaf) = item? {
+                        data.extend_from_slice(&leaf.data);
+                    }
+                    content = next;
+                }
+                BlobContentNext::Done(end) => break end,
+            }
+        };
+        if let EndBlobNext::Closing(closing) = end.next() {
+            closing.next().await?;
+        }
+        data.truncate(len as usize);
+        Ok(data)
+    }
+
+    /// Checks that the blob of `hash` is complete and has the announced `size`.
+    ///
+    /// The content itself is verified against the hash while downloading.
+    async fn verify(&self, hash: Hash, size: u64) -> bool {
+        match self.client.blobs().read(hash).await {
+            Ok(reader) if reader.is_complete() && reader.size() == size => true,
+            Ok(reader) => {
+                tracing::warn!(
+                    "{hash} does not match the offer: {} of {size} bytes, complete: {}",
+                    reader.size(),
+                    reader.is_complete()
+                );
+                false
+            }
+            Err(err) => {
+                tracing::warn!("failed to verify {hash}: {err:?}");
+                false
+            }
+        }
+    }
+
+    /// Applies settings synced from `node_id`, if it is one of our devices.
+    async fn handle_settings_sync(&self, node_id: NodeId, sections: Vec<SyncedSection>) {
+        if !self.settings.get().await.sync.own_devices.contains(&node_id) {
+            tracing::warn!("ignoring settings from {node_id}, which is not one of our devices");
+            return;
+        }
+        if self.admin_lock.check().is_err() {
+            tracing::warn!("ignoring settings from {node_id}, this machine is read-only");
+            return;
+        }
+        match self.settings.merge_synced(sections).await {
+            Ok(Some(settings)) => {
+                tracing::info!("applied settings synced from {node_id}");
+                self.apply_settings(&settings);
+                self.s.send(LocalProtocolMessage::SettingsChanged).await.ok();
+            }
+            Ok(None) => {}
+            Err(err) => {
+                tracing::warn!("invalid settings from {node_id}: {err:?}");
+            }
+        }
+    }
+
+    /// Sends our synced settings to all of our devices that are online.
+    pub async fn push_settings(self: &Arc<Self>) {
+        let own_devices = self.settings.get().await.sync.own_devices;
+        for node_id in own_devices {
+            let this = self.clone();
+            tokio::spawn(async move {
+                this.sync_settings_with(node_id).await;
+            });
+        }
+    }
+
+    /// Sends our synced settings to `node_id`, if it is online and one of our devices.
+    pub async fn sync_settings_with(&self, node_id: NodeId) {
+        let supported = self
+            .known_nodes
+            .read()
+            .await
+            .get(&node_id)
+            .is_some_and(|node| node.online && node.capabilities.supports_settings_sync());
+        if !supported {
+            return;
+        }
+        if let Err(err) = self.send_settings(node_id).await {
+            tracing::warn!("failed to sync settings with {node_id}: {err:?}");
+        }
+    }
+
+    async fn send_settings(&self, node_id: NodeId) -> Result<()> {
+        let settings = self.settings.get().await;
+        if !settings.sync.own_devices.contains(&node_id) || settings.sync.sections.is_empty() {
+            return Ok(());
+        }
+        let sections = sync::export(&settings)?;
+
+        let advanced = settings.advanced;
+        let conn = self.dial(node_id.into(), advanced.dial_timeout()).await?;
+        let (send, recv) = conn.open_bi().await?;
+
+        let (_reader, mut writer) = wrap_streams(send, recv, advanced.max_frame_size);
+        writer.send(ProtocolMessage::SettingsSync { sections }).await?;
+
+        writer.send(ProtocolMessage::Finish).await?;
+        let mut writer = writer.into_inner().into_inner();
+        writer.finish()?;
+        writer.stopped().await?;
+
+        Ok(())
+    }
+
+    pub fn history(&self) -> &History {
+        &self.history
+    }
+
+    /// The received files matching `query`, newest first.
+    pub async fn search_received(&self, query: &SearchQuery) -> Vec<ReceivedFile> {
+        // Senders that are not around any more are found by the name they were last seen or
+        // paired with.
+        let settings = self.settings.get().await;
+        let mut names: BTreeMap<NodeId, String> = settings
+            .identities
+            .into_iter()
+            .map(|(node_id, identity)| (node_id, identity.name))
+            .collect();
+        names.extend(settings.paired);
+        names.extend(
+            self.peer_cache
+                .list()
+                .into_iter()
+                .map(|(node_id, peer)| (node_id, peer.name)),
+        );
+        names.extend(
+            self.known_nodes
+                .read()
+                .await
+                .iter()
+                .map(|(node_id, node)| (*node_id, node.name.clone())),
+        );
+        library::search(self.history.list().await, &names, query)
+    }
+
+    /// Moves the transfers of `hash` to the trash, see [`Self::undo_delete`].
+    ///
+    /// The blob is removed once [`TRASH_GRACE`] passed, unless the deletion was undone.
+    pub async fn delete_transfer(self: &Arc<Self>, hash: Hash) -> Result<()> {
+        if !self.history.trash(&hash).await {
+            return Err(DropError::InvalidArgument(format!("no transfer for {hash}")).into());
+        }
+
+        let this = self.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(TRASH_GRACE).await;
+            this.purge_trash().await;
+        });
+        Ok(())
+    }
+
+    /// Restores the transfers of `hash` from the trash.
+    pub async fn undo_delete(&self, hash: Hash) -> Result<()> {
+        if !self.history.restore(&hash).await {
+            return Err(DropError::InvalidArgument(format!("{hash} is not in the trash")).into());
+        }
+        Ok(())
+    }
+
+    async fn purge_trash(&self) {
+        let hashes = self.history.purge(TRASH_GRACE).await;
+        if hashes.is_empty() {
+            return;
+        }
+        for hash in &hashes {
+            if let Err(err) = self.client.blobs().delete_blob(*hash).await {
+                tracing::warn!("failed to delete {hash}: {err:#}");
+            }
+            if let Err(err) = self.vault.remove(hash) {
+                tracing::warn!("failed to delete the encrypted copy of {hash}: {err:#}");
+            }
+        }
+        self.s
+            .send(LocalProtocolMessage::TrashPurged { hashes })
+            .await
+            .ok();
+    }
+
+    /// Records a finished download and checks its content against the advertised type.
+    ///
+    /// `already_had` is set if the blob was in the store already, and nothing was downloaded.
+    async fn on_downloaded(&self, mut entry: HistoryEntry, already_had: bool) {
+        let (node_id, name, hash, size) =
+            (entry.node_id, entry.name.clone(), entry.hash, entry.size);
+        entry.path = self.connection_path(node_id);
+        match self.read_head_as(hash, entry.encoding).await {
+            Ok(head) => {
+                entry.quarantined = quarantine::is_executable(&name, &head);
+                if let Some(message) = sniff::check(&name, &head) {
+                    tracing::warn!("content mismatch for {name} ({hash}): {message}");
+                    entry.content_warning = Some(message.clone());
+                    self.s
+                        .send(LocalProtocolMessage::ContentMismatch {
+                            name: name.clone(),
+                            hash,
+                            message,
+                        })
+                        .await
+                        .ok();
+                }
+            }
+            Err(err) => {
+                tracing::warn!("failed to read {hash}: {err:?}");
+                // The content could not be checked, so exporting it needs a confirmation.
+                entry.quarantined = true;
+            }
+        }
+        self.history.push(entry).await;
+        if self.vault.is_set_up() {
+            let stored = match self.client.blobs().read_to_bytes(hash).await {
+                Ok(data) => self.vault.store(hash, data.to_vec()).await,
+                Err(err) => Err(err),
+            };
+            if let Err(err) = stored {
+                tracing::warn!("failed to encrypt {hash} into the vault: {err:#}");
+            }
+        }
+
+        self.s
+            .send(LocalProtocolMessage::FileDownloaded {
+                from: node_id,
+                name,
+                hash,
+                size,
+                already_had,
+            })
+            .await
+            .ok();
+        self.swap_progress(node_id, Direction::Received).await;
+    }
+
+    /// Export a received file into `dir`.
+    ///
+    /// Quarantined files (executables and scripts) are only exported if `confirmed` is set,
+    /// and are marked as untrusted for the OS.
+    /// Returns the exported path, and the history entry of the file.
+    pub async fn export_received(
+        &self,
+        hash: Hash,
+        dir: &Path,
+        confirmed: bool,
+    ) -> Result<(PathBuf, HistoryEntry)> {
+        let entry = self
+            .history
+            .find_received(&hash)
+            .await
+            .ok_or_else(|| DropError::InvalidArgument("unknown file".to_string()))?;
+        let dest = unique_path(dir, &entry.name)?;
+        self.export_entry(&entry, &dest, confirmed).await?;
+        Ok((dest, entry))
+    }
+
+    /// The folder of `dir` the received file `hash` is exported to, sorted by sender and
+    /// date as configured in [`crate::settings::ExportSettings`].
+    ///
+    /// The folder is created if needed.
+    pub async fn sorted_export_dir(&self, hash: Hash, dir: &Path) -> Result<PathBuf> {
+        let settings = self.settings.get().await;
+        let entry = self
+            .history
+            .find_received(&hash)
+            .await
+            .ok_or_else(|| DropError::InvalidArgument("unknown file".to_string()))?;
+        // Offline peers are still known by the name they were pinned with.
+        let sender = match self.peer_name(&entry.node_id).await {
+            Some(name) => name,
+            None => settings
+                .identities
+                .get(&entry.node_id)
+                .map(|identity| identity.name.clone())
+                .unwrap_or_else(|| entry.node_id.fmt_short()),
+        };
+        let Some(subfolder) = export::subfolder(&settings.export, &sender, entry.timestamp) else {
+            return Ok(dir.to_path_buf());
+        };
+        let dir = dir.join(subfolder);
+        tokio::fs::create_dir_all(&dir).await?;
+        Ok(dir)
+    }
+
+    /// Exports the received blob of `hash` again, to the file `dest` the user picked, e.g.
+    /// after the exported copy was deleted.
+    ///
+    /// Unlike [`Self::export_received`], an existing file at `dest` is replaced.
+    pub async fn export_blob(
+        &self,
+        hash: Hash,
+        dest: &Path,
+        confirmed: bool,
+    ) -> Result<HistoryEntry> {
+        let entry = self
+            .history
+            .find_received(&hash)
+            .await
+            .ok_or_else(|| DropError::InvalidArgument(format!("{hash} was not received")))?;
+        self.export_entry(&entry, dest, confirmed).await?;
+        Ok(entry)
+    }
+
+    /// The blobs in the store, with the name of their latest transfer.
+    pub async fn list_blobs(&self) -> Result<Vec<StoredBlob>> {
+        let entries = self.history.list().await;
+        let mut blobs = self.client.blobs().list().await?;
+        let mut stored = Vec::new();
+        while let Some(blob) = blobs.next().await {
+            let blob = blob?;
+            // The latest received entry, or the latest sent one.
+            let entry = entries
+                .iter()
+                .rev()
+                .filter(|entry| entry.hash == blob.hash)
+                .max_by_key(|entry| entry.direction == Direction::Received);
+            stored.push(StoredBlob {
+                hash: blob.hash,
+                size: blob.size,
+                name: entry.map(|entry| entry.name.clone()),
+                received: entry.is_some_and(|entry| entry.direction == Direction::Received),
+            });
+        }
+        Ok(stored)
+    }
+
+    /// Writes the file of the received `entry` to `dest`.
+    async fn export_entry(&self, entry: &HistoryEntry, dest: &Path, confirmed: bool) -> Result<()> {
+        let hash = entry.hash;
+        if entry.quarantined && !confirmed {
+            return Err(DropError::NeedsConfirmation(format!(
+                "\"{}\" is an executable, export needs confirmation",
+                entry.name
+            ))
+            .into());
+        }
+        if entry.collected && !self.vault.contains(&hash) {
+            let message = format!("{} was removed to free space", entry.name);
+            return Err(DropError::InvalidArgument(message).into());
+        }
+        self.vault.ensure_unlocked()?;
+        self.restore_from_vault(hash).await?;
+
+        match entry.encoding {
+            Some(_) => {
+                let data = self.read_plain(hash, entry.encoding).await?;
+                tokio::fs::write(dest, data).await?;
+            }
+            None => {
+                self.client
+                    .blobs()
+                    .export(
+                        hash,
+                        dest.to_path_buf(),
+                        ExportFormat::Blob,
+                        ExportMode::Copy,
+                    )
+                    .await?
+                    .finish()
+                    .await?;
+            }
+        }
+
+        if let Err(err) = entry.metadata.apply(dest) {
+            tracing::warn!("failed to apply the metadata of {}: {err:#}", entry.name);
+        }
+        if entry.quarantined {
+            quarantine::mark(dest)?;
+        }
+        self.history.set_exported(&hash).await;
+        tracing::info!("exported {} to {}", entry.name, dest.display());
+
+        Ok(())
+    }
+
+    /// A link to download the file at `path` once, from a browser on the local network.
+    ///
+    /// See [`crate::share`], the link expires after `ttl`.
+    pub async fn create_share_link(&self, path: &Path, ttl: Duration) -> Result<String> {
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .ok_or_else(|| DropError::InvalidArgument("not a file".to_string()))?;
+        let data = tokio::fs::read(path).await?;
+        let size = data.len() as u64;
+        let tag = self.store.import_bytes(data.into(), BlobFormat::Raw).await?;
+        self.share_blob(*tag.hash(), name, size, ttl).await
+    }
+
+    /// Like [`Self::create_share_link`], for a file of the history.
+    ///
+    /// Like exporting, quarantined files are only shared if `confirmed` is set.
+    pub async fn share_transfer(
+        &self,
+        hash: Hash,
+        ttl: Duration,
+        confirmed: bool,
+    ) -> Result<String> {
+        let entry = self
+            .history
+            .list()
+            .await
+            .into_iter()
+            .find(|entry| entry.hash == hash)
+            .ok_or_else(|| DropError::InvalidArgument(format!("{hash} is not in the history")))?;
+        if entry.quarantined && !confirmed {
+            return Err(DropError::NeedsConfirmation(format!(
+                "\"{}\" is an executable, sharing it needs confirmation",
+                entry.name
+            ))
+            .into());
+        }
+        self.vault.ensure_unlocked()?;
+        self.restore_from_vault(hash).await?;
+        if !self.verify(hash, entry.size).await {
+            let err = DropError::InvalidArgument(format!("{} is not fully stored", entry.name));
+            return Err(err.into());
+        }
+        if entry.encoding.is_some() {
+            // Browsers get the plain file.
+            let data = self.read_plain(hash, entry.encoding).await?;
+            let size = data.len() as u64;
+            let tag = self.store.import_bytes(data, BlobFormat::Raw).await?;
+            return self.share_blob(*tag.hash(), entry.name, size, ttl).await;
+        }
+        self.share_blob(hash, entry.name, entry.size, ttl).await
+    }
+
+    async fn share_blob(
+        &self,
+        hash: Hash,
+        name: String,
+        size: u64,
+        ttl: Duration,
+    ) -> Result<String> {
+        if ttl.is_zero() {
+            let err = DropError::InvalidArgument("the link needs a time to live".to_string());
+            return Err(err.into());
+        }
+        let addrs = tokio::time::timeout(
+            Duration::from_secs(1),
+            self.endpoint.direct_addresses().next(),
+        )
+        .await
+        .ok()
+        .flatten()
+        .unwrap_or_default();
+        let ip = share::pick_ip(addrs.iter().map(|addr| addr.addr.ip()))
+            .ok_or_else(|| DropError::ConnectionFailed("not on a local network".to_string()))?;
+        let server = self
+            .share
+            .get_or_try_init(|| ShareServer::start(self.client.clone()))
+            .await?;
+        Ok(server.add(ip, hash, name, size, ttl))
+    }
+
+    /// Puts `hash` back into the blob store from the vault, e.g. after a restart.
+    async fn restore_from_vault(&self, hash: Hash) -> Result<()> {
+        if !self.vault.contains(&hash) {
+            return Ok(());
+        }
+        if let Ok(reader) = self.client.blobs().read(hash).await {
+            if reader.is_complete() {
+                return Ok(());
+            }
+        }
+        let data = self.vault.load(hash).await?;
+        self.client.blobs().add_bytes(data).await?;
+        tracing::info!("restored {hash} from the encrypted storage");
+        Ok(())
+    }
+
+    /// Reads the first bytes of a blob, used for content type detection.
+    ///
+    /// Received blobs that are compressed are decompressed first.
+    pub async fn read_head(&self, hash: Hash) -> Result<Vec<u8>> {
+        let encoding = self
+            .history
+            .find_received(&hash)
+            .await
+            .and_then(|entry| entry.encoding);
+        self.read_head_as(hash, encoding).await
+    }
+
+    /// Like [`Self::read_head`], for a blob stored with `encoding`.
+    async fn read_head_as(&self, hash: Hash, encoding: Option<Encoding>) -> Result<Vec<u8>> {
+        if let Some(encoding) = encoding {
+            let data = self.client.blobs().read_to_bytes(hash).await?;
+            return compression::decompress_head(encoding, &data);
+        }
+        let reader = self.client.blobs().read(hash).await?;
+        let mut head = Vec::with_capacity(sniff::SNIFF_LEN);
+        AsyncReadExt::take(reader, sniff::SNIFF_LEN as u64)
+            .read_to_end(&mut head)
+            .await?;
+        Ok(head)
+    }
+
+    /// A preview of the received image `hash`, as a `data:` URL.
+    pub async fn preview(&self, hash: Hash) -> Result<String> {
+        let entry = self
+            .history
+            .find_received(&hash)
+            .await
+            .ok_or_else(|| DropError::InvalidArgument(format!("{hash} was not received")))?;
+        if entry.size > preview::MAX_IMAGE_SIZE {
+            return Err(DropError::InvalidArgument(format!("{} is too large", entry.name)).into());
+        }
+        self.vault.ensure_unlocked()?;
+        self.restore_from_vault(hash).await?;
+        if !preview::is_image(&self.read_head(hash).await?) {
+            let err = DropError::InvalidArgument(format!("{} is not an image", entry.name));
+            return Err(err.into());
+        }
+
+        let data = self.read_plain(hash, entry.encoding).await?;
+        tokio::task::spawn_blocking(move || preview::thumbnail(&data)).await?
+    }
+
+    /// The content of the blob `hash`, decompressed if it is stored with `encoding`.
+    async fn read_plain(&self, hash: Hash, encoding: Option<Encoding>) -> Result<Bytes> {
+        let data = self.client.blobs().read_to_bytes(hash).await?;
+        let Some(encoding) = encoding else {
+            return Ok(data);
+        };
+        let max_size = storage::MAX_FILE_SIZE.unwrap_or(u64::MAX);
+        let plain =
+            tokio::task::spawn_blocking(move || compression::decompress(encoding, &data, max_size))
+                .await??;
+        Ok(plain.into())
+    }
+
+    /// A ticket other nodes can use to dial us, including our relay and direct addresses.
+    pub async fn ticket(&self) -> Result<NodeTicket> {
+        let addr = self.endpoint.node_addr().await?;
+        NodeTicket::new(addr)
+    }
+
+    /// Dial a node given its ticket or node id, and introduce ourselves.
+    ///
+    /// The node is remembered as paired, see [`Self::spawn_paired_reconnect`], unless the
+    /// ticket is a guest ticket.
+    /// Returns the node id and name of the remote.
+    pub async fn connect_by_ticket(&self, ticket: &str) -> Result<(NodeId, String)> {
+        let ticket = ticket.trim();
+        // Neither tickets nor node ids contain an `@`.
+        if ticket.contains('@') {
+            return self.connect_by_handle(&ticket.parse()?).await;
+        }
+        // Guests are not paired, their access ends with the ticket.
+        if let Ok(guest) = ticket.parse::<GuestTicket>() {
+            let node_addr = guest.ticket.node_addr().clone();
+            let node_id = node_addr.node_id;
+            let (name, _) = self.introduce(node_addr, Some(guest.token)).await?;
+            return Ok((node_id, name));
+        }
+        // The group is only joined once the device proved it in its answer to our intro.
+        if let Ok(group) = ticket.parse::<GroupTicket>() {
+            let node_addr = group.ticket.node_addr().clone();
+            let node_id = node_addr.node_id;
+            let (name, proof) = self.introduce(node_addr.clone(), None).await?;
+            let expected = group.secret.proof(node_id, self.endpoint.node_id());
+            let Some(proof) = proof.filter(|proof| *proof == expected) else {
+                let message = format!("{node_id} is not in the group of the ticket");
+                return Err(DropError::InvalidArgument(message).into());
+            };
+            self.settings.set_group(group.secret).await?;
+            self.group_member_seen(node_id, &name, proof).await;
+            // Proves the group to the device in turn, which marks us as its own device.
+            self.send_intro(node_addr).await?;
+            return Ok((node_id, name));
+        }
+        let node_addr = match ticket.parse::<NodeTicket>() {
+            Ok(ticket) => ticket.node_addr().clone(),
+            Err(_) => NodeAddr::new(
+                ticket
+                    .parse::<NodeId>()
+                    .map_err(|_| DropError::InvalidArgument("invalid ticket".to_string()))?,
+            ),
+        };
+        let node_id = node_addr.node_id;
+        let name = self.send_intro(node_addr).await?;
+        self.settings.set_paired(node_id, Some(name.clone())).await?;
+        Ok((node_id, name))
+    }
+
+    /// Connects to the device published for `handle` and pairs with it, see
+    /// [`crate::handles`].
+    ///
+    /// The device has to claim the same handle in its intro, so a stale or forged record
+    /// does not pair us with someone else.
+    pub async fn connect_by_handle(&self, handle: &Handle) -> Result<(NodeId, String)> {
+        let node_id = self.handles.resolve(handle).await?;
+        let name = self.send_intro(node_id.into()).await?;
+        if !self.verify_handle(node_id, handle).await {
+            self.handles.forget(handle);
+            return Err(
+                DropError::InvalidArgument(format!("{node_id} does not claim {handle}")).into(),
+            );
+        }
+        self.settings
+            .set_paired(node_id, Some(name.clone()))
+            .await?;
+        Ok((node_id, name))
+    }
+
+    /// Connects to `node_addr`, giving up after `timeout`.
+    ///
+    /// The connection is kept open for the next requests if the peer supports it, see [`pool`].
+    async fn dial(&self, node_addr: NodeAddr, timeout: Duration) -> Result<Connection> {
+        let node_id = node_addr.node_id;
+        if let Some(conn) = self.pool.get(&node_id) {
+            return Ok(conn);
+        }
+        let conn = self.connect(node_addr, ALPN, timeout).await?;
+        let reusable = self
+            .known_nodes
+            .read()
+            .await
+            .get(&node_id)
+            .is_some_and(|node| node.capabilities.supports_connection_reuse());
+        let idle_timeout = self.settings.get().await.advanced.idle_connection_timeout_secs;
+        if reusable && idle_timeout > 0 {
+            self.pool.insert(node_id, conn.clone());
+        }
+        Ok(conn)
+    }
+
+    /// Connects to `node_addr` using `alpn`, giving up after `timeout`.
+    async fn connect(
+        &self,
+        node_addr: NodeAddr,
+        alpn: &[u8],
+        timeout: Duration,
+    ) -> Result<Connection> {
+        let node_id = node_addr.node_id;
+        let start = Instant::now();
+        let res = tokio::time::timeout(timeout, self.endpoint.connect(node_addr, alpn))
+            .await
+            .map_err(|_| DropError::Timeout(format!("connecting to {node_id}")))
+            .and_then(|res| {
+                res.map_err(|err| {
+                    if is_alpn_rejected(&err) {
+                        let alpn = String::from_utf8_lossy(alpn);
+                        DropError::Incompatible(format!("it does not speak {alpn}"))
+                    } else {
+                        DropError::ConnectionFailed(format!("{err:#}"))
+                    }
+                })
+            });
+        self.diagnostics
+            .record(
+                node_id,
+                alpn,
+                self.connection_path(node_id),
+                start.elapsed(),
+                res.as_ref().map(|_| ()),
+            )
+            .await;
+        Ok(res?)
+    }
+
+    /// Answers the requests `node_id` sends on one stream, until it finishes.
+    async fn serve_stream(
+        self: &Arc<Self>,
+        node_id: NodeId,
+        send_stream: SendStream,
+        recv_stream: RecvStream,
+    ) {
+        let max_frame_size = self.settings.get().await.advanced.max_frame_size;
+        let (mut reader, mut writer) = wrap_streams(send_stream, recv_stream, max_frame_size);
+
+        let this = self.clone();
+        let span = tracing::info_span!("connection", remote = %node_id.fmt_short());
+        tokio::spawn(async move {
+            loop {
+                let message = tokio::select! {
+                    message = reader.next() => message,
+                    _ = this.shutdown.cancelled() => {
+                        // Tell the remote we are going away, instead of dropping the stream.
+                        writer.send(ProtocolMessage::Finish).await.ok();
+                        break;
+                    }
+                };
+                let Some(message) = message else {
+                    break;
+                };
+                match message {
+                    Ok(message) => {
+                        match message {
+                            ProtocolMessage::IntroRequest {
+                                name,
+                                capabilities,
+                                device,
+                                room,
+                                constraints,
+                                guest_token,
+                                handle,
+                                group_proof,
+                            } => {
+                                if let Some(token) = guest_token {
+                                    if this.guests.admit(node_id, &token) {
+                                        tracing::info!("{node_id} joined as a guest");
+                                    } else {
+                                        tracing::info!("invalid guest ticket of {node_id}");
+                                    }
+                                }
+                                let own_room = this.room_id().await;
+                                if room == own_room {
+                                    let peer = Introduction {
+                                        name,
+                                        capabilities,
+                                        device,
+                                        constraints,
+                                        handle,
+                                        group_proof,
+                                    };
+                                    this.peer_seen(node_id, peer).await;
+                                } else {
+                                    this.peer_left(node_id).await;
+                                }
+
+                                // Answered either way, so the remote can hide us as well.
+                                if let Err(err) = writer
+                                    .send(ProtocolMessage::IntroResponse {
+                                        name: this.name(),
+                                        capabilities: Capabilities::local(),
+                                        device: DeviceInfo::local(),
+                                        room: own_room,
+                                        constraints: Some(
+                                            this.receive_constraints().await,
+                                        ),
+                                        handle: this.own_handle().await,
+                                        group_proof: this.group_proof(node_id).await,
+                                    })
+                                    .await
+                                {
+                                    tracing::warn!("failed to send: {err:?}");
+                                }
+                            }
+                            ProtocolMessage::IntroResponse {
+                                name,
+                                capabilities,
+                                device,
+                                room,
+                                constraints,
+                                handle,
+                                group_proof,
+                            } => {
+                                if room == this.room_id().await {
+                                    let peer = Introduction {
+                                        name,
+                                        capabilities,
+                                        device,
+                                        constraints,
+                                        handle,
+                                        group_proof,
+                                    };
+                                    this.peer_seen(node_id, peer).await;
+                                }
+                            }
+                            ProtocolMessage::SendRequest {
+                                name,
+                                hash,
+                                size,
+                                max_bps,
+                                metadata,
+                                variant,
+                                encoding,
+                            } => {
+                                let offer = Offer {
+                                    name,
+                                    hash,
+                                    size,
+                                    max_bps,
+                                    metadata,
+                                    variant,
+                                    encoding,
+                                    inline: None,
+                                };
+                                let response = match this
+                                    .handle_send_request(node_id, offer, &mut writer)
+                                    .await
+                                {
+                                    Ok(response) => response,
+                                    // Let the sender know right away, instead of timing out.
+                                    Err(reason) => this.reject(node_id, reason).await,
+                                };
+                                if let Err(err) = writer.send(response).await {
+                                    tracing::warn!("failed to send: {err:?}");
+                                }
+                            }
+                            ProtocolMessage::SendInline {
+                                name,
+                                data,
+                                metadata,
+                                variant,
+                                encoding,
+                            } => {
+                                let offer = Offer {
+                                    name,
+                                    hash: Hash::new(&data),
+                                    size: data.len() as u64,
+                                    max_bps: 0,
+                                    metadata,
+                                    variant,
+                                    encoding,
+                                    inline: Some(data),
+                                };
+                                let response = match this
+                                    .handle_send_request(node_id, offer, &mut writer)
+                                    .await
+                                {
+                                    Ok(response) => response,
+                                    Err(reason) => this.reject(node_id, reason).await,
+                                };
+                                if let Err(err) = writer.send(response).await {
+                                    tracing::warn!("failed to send: {err:?}");
+                                }
+                            }
+                            ProtocolMessage::Preflight {
+                                name,
+                                size,
+                                metadata,
+                            } => {
+                                let response =
+                                    this.handle_preflight(node_id, name, size, metadata).await;
+                                if let Err(err) = writer.send(response).await {
+                                    tracing::warn!("failed to send: {err:?}");
+                                }
+                            }
+                            ProtocolMessage::SwapRequest { files, size } => {
+                                let response =
+                                    this.handle_swap_request(node_id, files, size).await;
+                                if let Err(err) = writer.send(response).await {
+                                    tracing::warn!("failed to send: {err:?}");
+                                }
+                            }
+                            ProtocolMessage::SendReject { .. }
+                            | ProtocolMessage::Rejected { .. }
+                            | ProtocolMessage::TransferComplete { .. }
+                            | ProtocolMessage::AlreadyHave { .. }
+                            | ProtocolMessage::CounterOffer { .. }
+                            | ProtocolMessage::SwapAccept { .. }
+                            | ProtocolMessage::PreflightOk
+                            | ProtocolMessage::DownloadStarted { .. }
+                            | ProtocolMessage::IdentityShown
+                            | ProtocolMessage::AlreadyReceiving { .. } => {
+                                tracing::warn!("unexpected response from {node_id}: {message:?}");
+                            }
+                            ProtocolMessage::Ping => {
+                                this.peer_alive(node_id).await;
+                                if let Err(err) = writer.send(ProtocolMessage::Pong).await {
+                                    tracing::warn!("failed to send: {err:?}");
+                                }
+                            }
+                            ProtocolMessage::Pong => {
+                                this.peer_alive(node_id).await;
+                            }
+                            ProtocolMessage::SettingsSync { sections } => {
+                                this.handle_settings_sync(node_id, sections).await;
+                            }
+                            ProtocolMessage::NameChanged { name } => {
+                                this.peer_renamed(node_id, name).await;
+                            }
+                            ProtocolMessage::VerifyIdentity => {
+                                let response = this.handle_verify_identity(node_id).await;
+                                if let Err(err) = writer.send(response).await {
+                                    tracing::warn!("failed to send: {err:?}");
+                                }
+                            }
+                            ProtocolMessage::HistoryAnnotation {
+                                hash,
+                                note,
+                                updated_at,
+                            } => {
+                                this.handle_annotation(node_id, hash, note, updated_at).await;
+                            }
+                            ProtocolMessage::Finish => {
+                                break;
+                            }
+                        }
+                    }
+                    Err(err) => {
+                        tracing::warn!("failed to read a message: {err:?}");
+                    }
+                }
+            }
+
+            let mut writer = writer.into_inner().into_inner();
+            writer.finish().ok();
+            writer.stopped().await.ok();
+        }
+        .instrument(span));
+    }
+
+    /// Issues a guest ticket for this device, valid for `duration`, see [`crate::guest`].
+    pub async fn guest_ticket(&self, duration: Duration) -> Result<GuestTicket> {
+        let ticket = self.ticket().await?;
+        let token = self.guests.issue(duration);
+        tracing::info!("issued a guest ticket valid for {duration:?}");
+        Ok(GuestTicket { token, ticket })
+    }
+
+    /// Issues a ticket adding another device to the group of own devices, see
+    /// [`crate::device_group`].
+    ///
+    /// The group is created with the first ticket.
+    pub async fn own_device_ticket(&self) -> Result<GroupTicket> {
+        let secret = match self.settings.get().await.sync.group {
+            Some(secret) => secret,
+            None => {
+                let secret = GroupSecret::generate();
+                self.settings.set_group(secret.clone()).await?;
+                tracing::info!("created a group of own devices");
+                secret
+            }
+        };
+        let ticket = self.ticket().await?;
+        Ok(GroupTicket { secret, ticket })
+    }
+
+    /// Leaves the group of own devices, so tickets of another group can be used.
+    pub async fn leave_group(&self) -> Result<()> {
+        self.settings.leave_group().await?;
+        tracing::info!("left the group of own devices");
+        self.s
+            .send(LocalProtocolMessage::SettingsChanged)
+            .await
+            .ok();
+        Ok(())
+    }
+
+    /// The TXT record to publish for the handle in the settings, as name and value.
+    pub async fn handle_record(&self) -> Result<(String, String)> {
+        let handle: Handle = self
+            .own_handle()
+            .await
+            .ok_or_else(|| DropError::InvalidArgument("no handle is set".to_string()))?
+            .parse()?;
+        let node_id = self.endpoint.node_id();
+        Ok((handle.record_name(), Handle::record_value(node_id)))
+    }
+
+    pub async fn send_intro(&self, node_addr: NodeAddr) -> Result<String> {
+        let (name, _) = self.introduce(node_addr, None).await?;
+        Ok(name)
+    }
+
+    /// Introduces us to `node_addr`, presenting `guest_token` if we are a guest there.
+    ///
+    /// Returns the name of the remote and its proof of the group of own devices.
+    async fn introduce(
+        &self,
+        node_addr: NodeAddr,
+        guest_token: Option<String>,
+    ) -> Result<(String, Option<Hash>)> {
+        let advanced = self.settings.get().await.advanced;
+        let conn = self.dial(node_addr.clone(), advanced.dial_timeout()).await?;
+        let (send, recv) = conn.open_bi().await?;
+
+        let (mut reader, mut writer) = wrap_streams(send, recv, advanced.max_frame_size);
+
+        let own_room = self.room_id().await;
+        writer
+            .send(ProtocolMessage::IntroRequest {
+                name: self.name(),
+                capabilities: Capabilities::local(),
+                device: DeviceInfo::local(),
+                room: own_room,
+                constraints: Some(self.receive_constraints().await),
+                guest_token,
+                handle: self.own_handle().await,
+                group_proof: self.group_proof(node_addr.node_id).await,
+            })
+            .await?;
+
+        let (name, group_proof) = match reader.next().await {
+            Some(Ok(ProtocolMessage::IntroResponse {
+                name,
+                capabilities,
+                device,
+                room,
+                constraints,
+                handle,
+                group_proof,
+            })) => {
+                if room != own_room {
+                    self.peer_left(node_addr.node_id).await;
+                    return Err(DropError::OtherRoom.into());
+                }
+                let peer = Introduction {
+                    name: name.clone(),
+                    capabilities,
+                    device,
+                    constraints,
+                    handle,
+                    group_proof,
+                };
+                self.peer_seen(node_addr.node_id, peer).await;
+                (name, group_proof)
+            }
+            Some(Ok(msg)) => {
+                anyhow::bail!("unexpected response: {:?}", msg);
+            }
+            // The peer speaks a wire format we do not understand.
+            Some(Err(err)) if err.kind() == io::ErrorKind::InvalidData => {
+                return Err(DropError::Incompatible(format!("unreadable intro: {err}")).into());
+            }
+            Some(Err(err)) => return Err(err.into()),
+            None => anyhow::bail!("remote aborted"),
+        };
+
+        writer.send(ProtocolMessage::Finish).await?;
+        let mut writer = writer.into_inner().into_inner();
+        writer.finish()?;
+        writer.stopped().await?;
+
+        Ok((name, group_proof))
+    }
+
+    /// The current connection to `node_id`, `None` if it was never reachable.
+    pub fn connection_info(&self, node_id: NodeId) -> Option<ConnectionInfo> {
+        self.endpoint
+            .remote_info(node_id)
+            .map(|info| ConnectionInfo::from(&info))
+    }
+
+    /// The current path of the connection to `node_id`.
+    fn connection_path(&self, node_id: NodeId) -> ConnectionPath {
+        self.endpoint
+            .remote_info(node_id)
+            .map(|info| ConnectionPath::from(&info.conn_type))
+            .unwrap_or(ConnectionPath::Unknown)
+    }
+
+    /// Checks that `node_id` is still reachable.
+    pub async fn ping(&self, node_id: NodeId) -> Result<()> {
+        let advanced = self.settings.get().await.advanced;
+        let conn = self.dial(node_id.into(), advanced.dial_timeout()).await?;
+        self.ping_over(node_id, &conn, advanced.max_frame_size)
+            .await
+    }
+
+    /// Pings `node_id` over the open `conn`.
+    async fn ping_over(
+        &self,
+        node_id: NodeId,
+        conn: &Connection,
+        max_frame_size: usize,
+    ) -> Result<()> {
+        let (send, recv) = conn.open_bi().await?;
+
+        let (mut reader, mut writer) = wrap_streams(send, recv, max_frame_size);
+        writer.send(ProtocolMessage::Ping).await?;
+        match reader.next().await {
+            Some(Ok(ProtocolMessage::Pong)) => {}
+            Some(Ok(msg)) => anyhow::bail!("unexpected response: {:?}", msg),
+            Some(Err(err)) => return Err(err.into()),
+            None => anyhow::bail!("remote aborted"),
+        }
+        self.peer_alive(node_id).await;
+
+        writer.send(ProtocolMessage::Finish).await?;
+        let mut writer = writer.into_inner().into_inner();
+        writer.finish()?;
+        writer.stopped().await?;
+
+        Ok(())
+    }
+
+    /// Waits a moment for a direct path to `node_id` over the local network, if it has a
+    /// local address.
+    ///
+    /// The endpoint always picks the fastest path it found, but upgrading from a relay can
+    /// take a few seconds, which would otherwise be spent on the slower path.
+    async fn await_local_path(&self, node_id: NodeId) {
+        let is_local = |conn_type: &ConnectionType| {
+            matches!(conn_type, ConnectionType::Direct(addr) if strategy::is_local_addr(addr))
+        };
+        let Some(info) = self.endpoint.remote_info(node_id) else {
+            return;
+        };
+        if is_local(&info.conn_type)
+            || !info.addrs.iter().any(|a| strategy::is_local_addr(&a.addr))
+        {
+            return;
+        }
+        let Ok(mut stream) = self.endpoint.conn_type_stream(node_id) else {
+            return;
+        };
+        let found = tokio::time::timeout(LOCAL_PATH_WAIT, async {
+            while let Some(conn_type) = stream.next().await {
+                if is_local(&conn_type) {
+                    return true;
+                }
+            }
+            false
+        })
+        .await
+        .unwrap_or(false);
+        tracing::info!(
+            "local path to {node_id} {}",
+            if found { "established" } else { "not available" }
+        );
+    }
+
+    /// Pick the transfer strategy for `node_id`, based on the current connection.
+    pub fn select_strategy(&self, node_id: NodeId) -> TransferStrategy {
+        let info = self.endpoint.remote_info(node_id);
+        TransferStrategy::select(node_id, info.as_ref())
+    }
+
+    pub async fn send_file(
+        &self,
+        node_id: NodeId,
+        file_name: String,
+        file_data: Vec<u8>,
+        mut metadata: FileMetadata,
+    ) -> Result<()> {
+        if metadata.mime.is_none() {
+            metadata.mime = infer::get(&file_data).map(|kind| kind.mime_type().to_string());
+        }
+        let mut file = OutgoingFile {
+            name: file_name,
+            data: file_data,
+            metadata,
+        };
+        let transforms = self
+            .settings
+            .get()
+            .await
+            .send_transforms
+            .get(&node_id)
+            .cloned()
+            .unwrap_or_default();
+        if !transforms.is_empty() {
+            file = tokio::task::spawn_blocking(move || transform::run(&transforms, file)).await?;
+        }
+        let content = Outgoing::Data(file.data);
+        self.send(node_id, file.name, content, file.metadata, None).await
+    }
+
+    /// Like [`Self::send_file`], streaming the file from `reader` into the store instead of
+    /// holding it in memory, e.g. a video picked on a phone.
+    ///
+    /// Peers with send transforms get it through [`Self::send_file`], the transforms need all
+    /// of the file.
+    pub async fn send_reader(
+        &self,
+        node_id: NodeId,
+        file_name: String,
+        mut reader: impl AsyncRead + Unpin + Send + 'static,
+        metadata: FileMetadata,
+    ) -> Result<()> {
+        let transformed = self
+            .settings
+            .get()
+            .await
+            .send_transforms
+            .get(&node_id)
+            .is_some_and(|transforms| !transforms.is_empty());
+        if transformed {
+            let mut data = Vec::new();
+            reader.read_to_end(&mut data).await?;
+            return self.send_file(node_id, file_name, data, metadata).await;
+        }
+        let added = self
+            .client
+            .blobs()
+            .add_reader(reader, SetTagOption::Auto)
+            .await?
+            .finish()
+            .await?;
+        let content = Outgoing::Stored {
+            hash: added.hash,
+            size: added.size,
+            encoding: None,
+        };
+        self.send(node_id, file_name, content, metadata, None).await
+    }
+
+    /// Sends one file to several peers at the same time, returning the result for each.
+    ///
+    /// The file is added to the store once and offered from there to all peers, except to
+    /// those with send transforms, which get their own converted copy.
+    pub async fn send_file_to_many(
+        &self,
+        node_ids: Vec<NodeId>,
+        file_name: String,
+        file_data: Vec<u8>,
+        mut metadata: FileMetadata,
+    ) -> Vec<(NodeId, Result<()>)> {
+        if metadata.mime.is_none() {
+            metadata.mime = infer::get(&file_data).map(|kind| kind.mime_type().to_string());
+        }
+        let send_transforms = self.settings.get().await.send_transforms;
+        let needs_store = node_ids
+            .iter()
+            .any(|node_id| !send_transforms.contains_key(node_id));
+        // Shared by all recipients, so a failure is kept as its message.
+        let stored = if needs_store {
+            let res = self.client.blobs().add_bytes(file_data.clone()).await;
+            Some(
+                res.map(|res| (res.hash, res.size))
+                    .map_err(|err| format!("{err:#}")),
+            )
+        } else {
+            None
+        };
+
+        let sends = node_ids.into_iter().map(|node_id| {
+            let (file_name, metadata) = (file_name.clone(), metadata.clone());
+            let (file_data, stored, send_transforms) = (&file_data, &stored, &send_transforms);
+            async move {
+                let plain = !send_transforms.contains_key(&node_id);
+                let result = match stored {
+                    Some(Ok((hash, size))) if plain => {
+                        let content = Outgoing::Stored {
+                            hash: *hash,
+                            size: *size,
+                            encoding: None,
+                        };
+                        self.send(node_id, file_name, content, metadata, None).await
+                    }
+                    Some(Err(err)) if plain => {
+                        Err(anyhow::anyhow!("failed to add {file_name}: {err}"))
+                    }
+                    _ => {
+                        self.send_file(node_id, file_name, file_data.clone(), metadata)
+                            .await
+                    }
+                };
+                (node_id, result)
+            }
+        });
+        futures_util::future::join_all(sends).await
+    }
+
+    /// Sends one file to all members of the peer group `group`, see
+    /// [`Protocol::send_file_to_many`].
+    ///
+    /// Returns each member with its error, `None` if the file was delivered. The results are
+    /// also reported as [`LocalProtocolMessage::GroupSent`].
+    pub async fn send_to_group(
+        &self,
+        group: &str,
+        file_name: String,
+        file_data: Vec<u8>,
+        metadata: FileMetadata,
+    ) -> Result<Vec<(NodeId, Option<DropError>)>> {
+        let members = self
+            .settings
+            .get()
+            .await
+            .peer_groups
+            .remove(group)
+            .ok_or_else(|| DropError::InvalidArgument(format!("unknown group: {group}")))?;
+        tracing::info!(
+            "sending {file_name} to {} members of {group}",
+            members.len()
+        );
+        let members = members.into_iter().collect();
+        let results: Vec<_> = self
+            .send_file_to_many(members, file_name.clone(), file_data, metadata)
+            .await
+            .into_iter()
+            .map(|(node_id, result)| (node_id, result.err().map(DropError::from)))
+            .collect();
+        self.s
+            .send(LocalProtocolMessage::GroupSent {
+                group: group.to_string(),
+                name: file_name,
+                results: results.clone(),
+            })
+            .await
+            .ok();
+        Ok(results)
+    }
+
+    /// Asks `node_id` whether it would accept the file `name`, before anything is transferred.
+    ///
+    /// Peers from before version 5 can not tell, their answer is assumed to be yes.
+    pub async fn preflight(
+        &self,
+        node_id: NodeId,
+        name: &str,
+        size: u64,
+        metadata: &FileMetadata,
+    ) -> Result<()> {
+        let capabilities = self
+            .known_nodes
+            .read()
+            .await
+            .get(&node_id)
+            .map(|node| node.capabilities.clone())
+            .ok_or(DropError::UnknownNode)?;
+        if !capabilities.supports_preflight() {
+            return Ok(());
+        }
+
+        let advanced = self.settings.get().await.advanced;
+        let conn = self.dial(node_id.into(), advanced.dial_timeout()).await?;
+        let (send, recv) = conn.open_bi().await?;
+        let (mut reader, mut writer) = wrap_streams(send, recv, advanced.max_frame_size);
+        writer
+            .send(ProtocolMessage::Preflight {
+                name: name.to_string(),
+                size,
+                metadata: metadata.clone(),
+            })
+            .await?;
+        writer.send(ProtocolMessage::Finish).await?;
+        let mut writer = writer.into_inner().into_inner();
+        writer.finish()?;
+
+        let response = tokio::time::timeout(advanced.offer_timeout(), reader.next())
+            .await
+            .map_err(|_| DropError::Timeout("waiting for the preflight".to_string()))?;
+        match response {
+            Some(Ok(ProtocolMessage::PreflightOk)) => Ok(()),
+            Some(Ok(ProtocolMessage::Rejected { reason })) => Err(DropError::from(reason).into()),
+            Some(Ok(ProtocolMessage::SendReject { reason })) => {
+                Err(legacy_rejection(reason).into())
+            }
+            Some(Ok(msg)) => anyhow::bail!("unexpected response: {:?}", msg),
+            Some(Err(err)) => Err(err.into()),
+            None => anyhow::bail!("remote aborted"),
+        }
+    }
+
+    /// Sets the transforms applied to files sent to `node_id`.
+    pub async fn set_send_transforms(&self, node_id: NodeId, names: Vec<String>) -> Result<()> {
+        let mut settings = self.settings.get().await;
+        if names.is_empty() {
+            settings.send_transforms.remove(&node_id);
+        } else {
+            settings.send_transforms.insert(node_id, names);
+        }
+        self.settings.set(settings).await
+    }
+
+    /// Forwards the received file `hash` to `node_id`, straight from the store.
+    ///
+    /// Like exporting, quarantined files are only forwarded if `confirmed` is set.
+    pub async fn reshare(&self, hash: Hash, node_id: NodeId, confirmed: bool) -> Result<()> {
+        let entry = self
+            .history
+            .find_received(&hash)
+            .await
+            .ok_or_else(|| DropError::InvalidArgument(format!("{hash} was not received")))?;
+        if entry.quarantined && !confirmed {
+            return Err(DropError::NeedsConfirmation(format!(
+                "\"{}\" is an executable, forwarding it needs confirmation",
+                entry.name
+            ))
+            .into());
+        }
+        self.vault.ensure_unlocked()?;
+        self.restore_from_vault(hash).await?;
+        if !self.verify(hash, entry.size).await {
+            let err = DropError::InvalidArgument(format!("{} is not fully stored", entry.name));
+            return Err(err.into());
+        }
+        self.send(
+            node_id,
+            entry.name,
+            Outgoing::Stored {
+                hash,
+                size: entry.size,
+                encoding: entry.encoding,
+            },
+            entry.metadata,
+            None,
+        )
+        .await
+    }
+
+    /// Offers the sent blob `hash` again as `variant`, which the receiver asked for.
+    async fn send_variant(
+        &self,
+        node_id: NodeId,
+        file_name: String,
+        hash: Hash,
+        metadata: FileMetadata,
+        variant: Variant,
+    ) -> Result<()> {
+        let data = self.client.blobs().read_to_bytes(hash).await?.to_vec();
+        let file = OutgoingFile {
+            name: file_name,
+            data,
+            metadata,
+        };
+        let file = tokio::task::spawn_blocking(move || {
+            let mut file = file;
+            // The receiver still gets the original if it can not be reduced.
+            if let Err(err) = variant.transform().apply(&mut file) {
+                tracing::warn!("failed to create {variant:?} of {}: {err:#}", file.name);
+            }
+            file
+        })
+        .await?;
+        let content = Outgoing::Data(file.data);
+        Box::pin(self.send(node_id, file.name, content, file.metadata, Some(variant))).await
+    }
+
+    /// Sends `request` to `node_id` on a new stream, returning the stream for the answer.
+    async fn deliver(
+        &self,
+        node_id: NodeId,
+        request: ProtocolMessage,
+    ) -> Result<(RpcRead<RecvStream>, SendStream)> {
+        let advanced = self.settings.get().await.advanced;
+        let conn = self.dial(node_id.into(), advanced.dial_timeout()).await?;
+        let (send, recv) = conn.open_bi().await?;
+        let (reader, mut writer) = wrap_streams(send, recv, advanced.max_frame_size);
+
+        writer.send(request).await?;
+        writer.send(ProtocolMessage::Finish).await?;
+        let mut writer = writer.into_inner().into_inner();
+        writer.finish()?;
+        Ok((reader, writer))
+    }
+
+    #[tracing::instrument(skip_all, fields(to = %node_id.fmt_short(), file = %file_name))]
+    async fn send(
+        &self,
+        node_id: NodeId,
+        file_name: String,
+        content: Outgoing,
+        metadata: FileMetadata,
+        variant: Option<Variant>,
+    ) -> Result<()> {
+        self.revalidate(node_id).await?;
+        let capabilities = self
+            .known_nodes
+            .read()
+            .await
+            .get(&node_id)
+            .map(|node| node.capabilities.clone())
+            .ok_or(DropError::UnknownNode)?;
+        if let Some(max_file_size) = capabilities.max_file_size {
+            anyhow::ensure!(
+                content.size() <= max_file_size,
+                "file is too large for this peer (max {max_file_size} bytes)"
+            );
+        }
+        if content.size() >= PREFLIGHT_MIN_SIZE {
+            self.preflight(node_id, &file_name, content.size(), &metadata)
+                .await?;
+        }
+
+        let transfer = self
+            .transfers
+            .start(Direction::Sent, node_id, file_name.clone(), None, content.size())
+            .await
+            .ok_or_else(|| anyhow::anyhow!("transfer queue closed"))?;
+        self.wait_visible().await;
+
+        let advanced = self.settings.get().await.advanced;
+        if advanced.prefer_local_transport {
+            self.await_local_path(node_id).await;
+        }
+        let strategy = self.select_strategy(node_id);
+        tracing::info!("sending {file_name} to {node_id} using {strategy:?}");
+        if let Some(message) = strategy.warning {
+            self.s
+                .send(LocalProtocolMessage::TransferWarning { node_id, message })
+                .await
+                .ok();
+        }
+        let _slot = self
+            .send_slots
+            .acquire(node_id, strategy.max_concurrent_files)
+            .await;
+
+        let (hash, size, encoding, inline) = match content {
+            Outgoing::Data(data) => {
+                // Slow connections are worth compressing small files too.
+                let compress = advanced.compress
+                    && capabilities.supports_compression()
+                    && (strategy.compress || data.len() as u64 >= advanced.compress_min_size)
+                    && compression::is_compressible(metadata.mime.as_deref(), &data);
+                let (data, encoding) = if compress {
+                    tokio::task::spawn_blocking(move || match compression::compress(&data) {
+                        Ok(Some(compressed)) => (compressed, Some(Encoding::Zstd)),
+                        Ok(None) => (data, None),
+                        Err(err) => {
+                            tracing::warn!("failed to compress: {err:#}");
+                            (data, None)
+                        }
+                    })
+                    .await?
+                } else {
+                    (data, None)
+                };
+                // Tiny files go along with the offer, leaving room for the rest of the frame.
+                let inline = capabilities.supports_inline()
+                    && data.len() as u64 <= MAX_INLINE_SIZE.min(advanced.max_frame_size as u64 / 2);
+                if inline {
+                    (Hash::new(&data), data.len() as u64, encoding, Some(data))
+                } else {
+                    let size = data.len() as u64;
+                    let tag = self.store.import_bytes(data.into(), BlobFormat::Raw).await?;
+                    (*tag.hash(), size, encoding, None)
+                }
+            }
+            // Peers from before compression get the plain file.
+            Outgoing::Stored {
+                hash,
+                encoding: encoding @ Some(_),
+                ..
+            } if !capabilities.supports_compression() => {
+                let data = self.read_plain(hash, encoding).await?;
+                let size = data.len() as u64;
+                let tag = self.store.import_bytes(data, BlobFormat::Raw).await?;
+                (*tag.hash(), size, None, None)
+            }
+            Outgoing::Stored {
+                hash,
+                size,
+                encoding,
+            } => (hash, size, encoding, None),
+        };
+        if let Some(encoding) = encoding {
+            tracing::info!("sending {file_name} as {encoding:?}, {size} bytes");
+        }
+        transfer.set_hash(hash);
+        let mut entry = HistoryEntry::new(Direction::Sent, node_id, file_name.clone(), hash, size);
+        entry.metadata = metadata.clone();
+        entry.encoding = encoding;
+        entry.path = self.connection_path(node_id);
+        self.history.push(entry).await;
+
+        let request = match inline {
+            Some(data) => ProtocolMessage::SendInline {
+                name: file_name.clone(),
+                data,
+                metadata: metadata.clone(),
+                variant,
+                encoding,
+            },
+            None => ProtocolMessage::SendRequest {
+                name: file_name.clone(),
+                hash,
+                size,
+                max_bps: advanced.max_up_bps,
+                metadata: metadata.clone(),
+                variant,
+                encoding,
+            },
+        };
+        let delivered = retry::SEND
+            .retry("sending the offer", || self.deliver(node_id, request.clone()))
+            .await;
+        let (mut reader, mut writer) = match delivered {
+            Ok(streams) => streams,
+            Err(err) => {
+                self.peer_unreachable(node_id, &err).await;
+                return Err(err);
+            }
+        };
+        // The receiver answers with `Rejected`, or closes the stream once it is done with the offer.
+        // Only the answer is waited for with a timeout, the download takes as long as it needs.
+        let mut response = if capabilities.supports_delivery_receipts() {
+            // Receivers announce the start of the download, until then the offer may expire.
+            tokio::time::timeout(advanced.offer_ttl() + OFFER_TTL_GRACE, reader.next())
+                .await
+                .map_err(|_| DropError::from(RejectReason::TimedOut))?
+        } else {
+            // Older receivers only answer once they are done.
+            tokio::time::timeout(advanced.offer_timeout(), reader.next())
+                .await
+                .map_err(|_| DropError::Timeout("waiting for the receiver".to_string()))?
+        };
+        if let Some(Ok(ProtocolMessage::DownloadStarted { hash: started })) = response {
+            if started == hash {
+                self.delivery_changed(node_id, &file_name, hash, DeliveryStatus::Downloading)
+                    .await;
+            }
+            response = reader.next().await;
+        }
+        writer.stopped().await?;
+        // Receivers from before version 2 close the stream without answering.
+        let (verified, already_had) = match response {
+            Some(Ok(ProtocolMessage::Rejected { reason })) => {
+                return Err(DropError::from(reason).into());
+            }
+            Some(Ok(ProtocolMessage::SendReject { reason })) => {
+                return Err(legacy_rejection(reason).into());
+            }
+            Some(Ok(ProtocolMessage::TransferComplete {
+                hash: received,
+                verified,
+            })) => (Some(verified && received == hash), false),
+            Some(Ok(ProtocolMessage::AlreadyHave { hash: received })) => {
+                (Some(received == hash), true)
+            }
+            Some(Ok(ProtocolMessage::AlreadyReceiving { hash: received })) if received == hash => {
+                tracing::info!("{node_id} is already receiving {file_name}");
+                // The earlier send of the file reports its delivery.
+                self.history.remove(Direction::Sent, node_id, hash).await;
+                let message = format!("{file_name} is already being sent to this device");
+                self.s
+                    .send(LocalProtocolMessage::TransferWarning { node_id, message })
+                    .await
+                    .ok();
+                return Ok(());
+            }
+            Some(Ok(ProtocolMessage::CounterOffer {
+                hash: requested,
+                variant,
+            })) if requested == hash => {
+                tracing::info!("{node_id} asked for {file_name} as {variant:?}");
+                self.history.remove(Direction::Sent, node_id, hash).await;
+                // Frees the upload slot for the new offer.
+                drop(transfer);
+                return self
+                    .send_variant(node_id, file_name, hash, metadata, variant)
+                    .await;
+            }
+            _ => (None, false),
+        };
+        if verified == Some(true) && !already_had {
+            transfer.record_total(size, self.connection_path(node_id));
+        }
+        self.history
+            .set_verified(Direction::Sent, node_id, hash, verified)
+            .await;
+
+        let status = match verified {
+            Some(true) => Some(DeliveryStatus::Delivered),
+            Some(false) => Some(DeliveryStatus::Failed),
+            None => None,
+        };
+        if let Some(status) = status {
+            self.delivery_changed(node_id, &file_name, hash, status).await;
+        }
+        self.s
+            .send(LocalProtocolMessage::FileSent {
+                to: node_id,
+                name: file_name,
+                hash,
+                size,
+                verified,
+                already_had,
+            })
+            .await
+            .ok();
+        self.swap_progress(node_id, Direction::Sent).await;
+        anyhow::ensure!(
+            verified != Some(false),
+            "the receiver could not verify the file"
+        );
+
+        Ok(())
+    }
+
+    async fn delivery_changed(
+        &self,
+        node_id: NodeId,
+        name: &str,
+        hash: Hash,
+        status: DeliveryStatus,
+    ) {
+        self.s
+            .send(LocalProtocolMessage::SentFileDelivery {
+                to: node_id,
+                name: name.to_string(),
+                hash,
+                status,
+            })
+            .await
+            .ok();
+    }
+}
+
+/// Content of an outgoing transfer.
+enum Outgoing {
+    /// Data that still needs to be imported
+    Data(Vec<u8>),
+    /// A blob that is already in the store, e.g. a received file
+    Stored {
+        hash: Hash,
+        size: u64,
+        encoding: Option<Encoding>,
+    },
+}
+
+impl Outgoing {
+    fn size(&self) -> u64 {
+        match self {
+            Self::Data(data) => data.len() as u64,
+            Self::Stored { size, .. } => *size,
+        }
+    }
+}
+
+/// The error for a `SendReject` of a receiver from before version 15, without a reason.
+fn legacy_rejection(message: String) -> DropError {
+    DropError::Rejected {
+        reason: None,
+        message,
+    }
+}
+
+/// Picks a path in `dir` for `name` that does not exist yet.
+///
+/// Only the file name component of `name` is used, so remote peers can not write outside of `dir`.
+fn unique_path(dir: &Path, name: &str) -> Result<PathBuf> {
+    let name = Path::new(name)
+        .file_name()
+        .ok_or_else(|| anyhow::anyhow!("invalid file name: {name}"))?;
+    let path = dir.join(name);
+    if !path.exists() {
+        return Ok(path);
+    }
+
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let ext = path
+        .extension()
+        .map(|e| format!(".{}", e.to_string_lossy()))
+        .unwrap_or_default();
+    (1..)
+        .map(|i| dir.join(format!("{stem} ({i}){ext}")))
+        .find(|p| !p.exists())
+        .ok_or_else(|| anyhow::anyhow!("no free file name"))
+}
+
+/// Version of the messages this node speaks.
+pub const PROTOCOL_VERSION: u32 = 18;
+
+/// Protocol version and limits of a node, exchanged during the intro.
+///
+/// Features are told apart by the version, see the `supports_*` methods.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Capabilities {
+    pub version: u32,
+    /// Largest file the node accepts, if limited
+    pub max_file_size: Option<u64>,
+}
+
+impl Default for Capabilities {
+    /// Nodes from before capabilities were exchanged.
+    fn default() -> Self {
+        Self {
+            version: 0,
+            max_file_size: None,
+        }
+    }
+}
+
+impl Capabilities {
+    /// What this node supports.
+    pub fn local() -> Self {
+        Self {
+            version: PROTOCOL_VERSION,
+            max_file_size: storage::MAX_FILE_SIZE,
+        }
+    }
+
+    /// Whether the node understands `Ping`/`Pong`.
+    pub fn supports_ping(&self) -> bool {
+        self.version >= 1
+    }
+
+    /// Whether the node understands `SettingsSync`.
+    pub fn supports_settings_sync(&self) -> bool {
+        self.version >= 2
+    }
+
+    /// Whether the node can answer a `CounterOffer`.
+    pub fn supports_counter_offer(&self) -> bool {
+        self.version >= 3
+    }
+
+    /// Whether the node can answer a `SwapRequest`.
+    pub fn supports_swap(&self) -> bool {
+        self.version >= 4
+    }
+
+    /// Whether the node can answer a `Preflight`.
+    pub fn supports_preflight(&self) -> bool {
+        self.version >= 5
+    }
+
+    /// Whether the node understands `HistoryAnnotation`.
+    pub fn supports_annotations(&self) -> bool {
+        self.version >= 8
+    }
+
+    /// Whether the node understands `NameChanged`.
+    pub fn supports_name_changes(&self) -> bool {
+        self.version >= 9
+    }
+
+    /// Whether the node accepts compressed offers, see [`crate::compression`].
+    pub fn supports_compression(&self) -> bool {
+        self.version >= 12
+    }
+
+    /// Whether the node understands `DownloadStarted` in answer to a `SendRequest`.
+    pub fn supports_delivery_receipts(&self) -> bool {
+        self.version >= 13
+    }
+
+    /// Whether the node can answer a `VerifyIdentity`.
+    pub fn supports_identity_verification(&self) -> bool {
+        self.version >= 14
+    }
+
+    /// Whether the node understands `Rejected`, instead of `SendReject`.
+    pub fn supports_reject_reasons(&self) -> bool {
+        self.version >= 15
+    }
+
+    /// Whether the node understands `AlreadyReceiving` in answer to a `SendRequest`.
+    pub fn supports_duplicate_offers(&self) -> bool {
+        self.version >= 16
+    }
+
+    /// Whether the node accepts tiny files in a `SendInline`.
+    pub fn supports_inline(&self) -> bool {
+        self.version >= 17
+    }
+
+    /// Whether the node serves further requests on a connection, after the first stream.
+    pub fn supports_connection_reuse(&self) -> bool {
+        self.version >= 18
+    }
+}
+
+/// Operating system of a node, new platforms are appended at the end.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Platform {
+    #[default]
+    Unknown,
+    #[serde(rename = "macos")]
+    MacOs,
+    Windows,
+    Linux,
+    Android,
+    #[serde(rename = "ios")]
+    IOs,
+}
+
+/// Form factor of a node, new types are appended at the end.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeviceType {
+    #[default]
+    Unknown,
+    /// A laptop or desktop computer
+    Laptop,
+    Phone,
+}
+
+/// Coarse limits of what a node accepts, so senders can warn before an offer is rejected.
+///
+/// Only a hint, offers are still checked by the receiver, see `Preflight`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReceiveConstraints {
+    /// Largest file that fits, `None` if unknown
+    pub max_file_size: Option<u64>,
+    /// Whether folders are accepted, no current version does
+    pub accepts_folders: bool,
+    pub accepts_executables: bool,
+}
+
+/// What kind of device a node runs on, so the UI can show a matching icon.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeviceInfo {
+    pub platform: Platform,
+    pub device_type: DeviceType,
+}
+
+impl DeviceInfo {
+    /// The device this node runs on.
+    pub fn local() -> Self {
+        let platform = if cfg!(target_os = "macos") {
+            Platform::MacOs
+        } else if cfg!(target_os = "windows") {
+            Platform::Windows
+        } else if cfg!(target_os = "linux") {
+            Platform::Linux
+        } else if cfg!(target_os = "android") {
+            Platform::Android
+        } else if cfg!(target_os = "ios") {
+            Platform::IOs
+        } else {
+            Platform::Unknown
+        };
+        let device_type = match platform {
+            Platform::Android | Platform::IOs => DeviceType::Phone,
+            Platform::MacOs | Platform::Windows | Platform::Linux => DeviceType::Laptop,
+            Platform::Unknown => DeviceType::Unknown,
+        };
+        Self {
+            platform,
+            device_type,
+        }
+    }
+}
+
+/// Deserializes a field appended to a message in a later protocol version.
+///
+/// Postcard ignores trailing bytes, so older nodes can read messages with the new field.
+/// When reading a message from an older node the field is missing, which postcard reports
+/// as an error, in which case the default is used.
+fn deserialize_trailing<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Deserialize<'de> + Default,
+{
+    Ok(T::deserialize(deserializer).unwrap_or_default())
+}
+
+/// Messages on a stream of the protocol.
+///
+/// Variants are encoded by their position, new ones are only ever appended.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum ProtocolMessage {
+    IntroRequest {
+        /// The name of the node sending the request
+        name: String,
+        /// Added in version 1
+        #[serde(deserialize_with = "deserialize_trailing")]
+        capabilities: Capabilities,
+        /// Added in version 2
+        #[serde(deserialize_with = "deserialize_trailing")]
+        device: DeviceInfo,
+        /// Hash of the room of the sender, `None` outside of rooms
+        /// Added in version 2
+        #[serde(deserialize_with = "deserialize_trailing")]
+        room: Option<Hash>,
+        /// Added in version 6
+        #[serde(deserialize_with = "deserialize_trailing")]
+        constraints: Option<ReceiveConstraints>,
+        /// Token of a guest ticket of the receiver, see [`crate::guest`]
+        /// Added in version 7
+        #[serde(deserialize_with = "deserialize_trailing")]
+        guest_token: Option<String>,
+        /// Handle the sender claims, see [`crate::handles`]
+        /// Added in version 10
+        #[serde(deserialize_with = "deserialize_trailing")]
+        handle: Option<String>,
+        /// Proof of the group of own devices, see [`crate::device_group`]
+        /// Added in version 11
+        #[serde(deserialize_with = "deserialize_trailing")]
+        group_proof: Option<Hash>,
+    },
+    IntroResponse {
+        /// The name of the node answering
+        name: String,
+        /// Added in version 1
+        #[serde(deserialize_with = "deserialize_trailing")]
+        capabilities: Capabilities,
+        /// Added in version 2
+        #[serde(deserialize_with = "deserialize_trailing")]
+        device: DeviceInfo,
+        /// Hash of the room of the sender, `None` outside of rooms
+        /// Added in version 2
+        #[serde(deserialize_with = "deserialize_trailing")]
+        room: Option<Hash>,
+        /// Added in version 6
+        #[serde(deserialize_with = "deserialize_trailing")]
+        constraints: Option<ReceiveConstraints>,
+        /// Handle the sender claims, see [`crate::handles`]
+        /// Added in version 10
+        #[serde(deserialize_with = "deserialize_trailing")]
+        handle: Option<String>,
+        /// Proof of the group of own devices, see [`crate::device_group`]
+        /// Added in version 11
+        #[serde(deserialize_with = "deserialize_trailing")]
+        group_proof: Option<Hash>,
+    },
+    SendRequest {
+        name: String,
+        hash: Hash,
+        size: u64,
+        /// Upload limit of the sender in bytes per second, `0` if unlimited
+        /// Added in version 2
+        #[serde(deserialize_with = "deserialize_trailing")]
+        max_bps: u64,
+        /// Added in version 2
+        #[serde(deserialize_with = "deserialize_trailing")]
+        metadata: FileMetadata,
+        /// The reduced variant this offer is, if the receiver asked for one
+        /// Added in version 3
+        #[serde(deserialize_with = "deserialize_trailing")]
+        variant: Option<Variant>,
+        /// How the blob is compressed, `None` for the plain file
+        /// Added in version 12
+        #[serde(deserialize_with = "deserialize_trailing")]
+        encoding: Option<Encoding>,
+    },
+    Finish,
+    /// Liveness check, answered with `Pong`, added in version 1
+    Ping,
+    Pong,
+    /// Settings sections shared with our own devices, added in version 2
+    SettingsSync { sections: Vec<SyncedSection> },
+    /// Answer to a `SendRequest` the receiver does not accept, added in version 2
+    ///
+    /// Superseded by `Rejected`, still sent to nodes from before version 15.
+    SendReject { reason: String },
+    /// Answer to an accepted `SendRequest` once the download finished, added in version 2
+    TransferComplete { hash: Hash, verified: bool },
+    /// Answer to a `SendRequest` for a blob the receiver has already, nothing is downloaded,
+    /// added in version 2
+    AlreadyHave { hash: Hash },
+    /// Answer to a `SendRequest`, asking for a reduced variant of the file instead,
+    /// added in version 3
+    CounterOffer { hash: Hash, variant: Variant },
+    /// Starts a swap, announcing the files the sender will offer, added in version 4
+    SwapRequest { files: u32, size: u64 },
+    /// Answer to a `SwapRequest` with the files offered in return, added in version 4
+    SwapAccept { files: u32, size: u64 },
+    /// Asks whether a `SendRequest` would be accepted, before the sender prepares it.
+    /// Answered with `PreflightOk` or `SendReject`, added in version 5
+    Preflight {
+        name: String,
+        size: u64,
+        metadata: FileMetadata,
+    },
+    /// Answer to a `Preflight` the receiver would accept, added in version 5
+    PreflightOk,
+    /// The note of a finished transfer between both sides changed, `updated_at` in seconds
+    /// since the unix epoch, added in version 8
+    HistoryAnnotation {
+        hash: Hash,
+        note: Option<String>,
+        updated_at: u64,
+    },
+    /// The sender changed its name, added in version 9
+    NameChanged { name: String },
+    /// The receiver of a `SendRequest` started downloading, sent before `TransferComplete`
+    /// to senders from version 13
+    DownloadStarted { hash: Hash },
+    /// Asks the receiver to show the verification code of the connection, see
+    /// [`crate::identity`]. Answered with `IdentityShown`, added in version 14
+    VerifyIdentity,
+    /// Answer to a `VerifyIdentity`, the code is shown to the user, added in version 14
+    IdentityShown,
+    /// Like `SendReject`, with the reason for the sender to tell apart, added in version 15
+    Rejected { reason: RejectReason },
+    /// Answer to a `SendRequest` for a file the receiver is downloading from the sender
+    /// already, e.g. as it was dropped twice, added in version 16
+    AlreadyReceiving {
+        hash: Hash,
+    },
+    /// A `SendRequest` carrying the file itself, for files of up to 256 KiB.
+    /// Answered like a `SendRequest`, without a download, added in version 17
+    SendInline {
+        name: String,
+        data: Vec<u8>,
+        metadata: FileMetadata,
+        variant: Option<Variant>,
+        encoding: Option<Encoding>,
+    },
+}
+
+/// A reduced variant of an offered file, see [`ProtocolMessage::CounterOffer`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Variant {
+    /// The image scaled down to fit into `max_dimension` pixels
+    Downscaled { max_dimension: u32 },
+}
+
+impl Variant {
+    fn transform(&self) -> Box<dyn Transform> {
+        match *self {
+            Self::Downscaled { max_dimension } => Box::new(Downscale { max_dimension }),
+        }
+    }
+}
+
+/// An offer received in a `SendRequest` or `SendInline`.
+struct Offer {
+    name: String,
+    hash: Hash,
+    size: u64,
+    max_bps: u64,
+    metadata: FileMetadata,
+    variant: Option<Variant>,
+    encoding: Option<Encoding>,
+    /// The file itself, if it came with the offer
+    inline: Option<Vec<u8>>,
+}
+
+type RpcRead<R> = tokio_serde::SymmetricallyFramed<
+    tokio_util::codec::FramedRead<R, tokio_util::codec::LengthDelimitedCodec>,
+    ProtocolMessage,
+    SymmetricalPostcard<ProtocolMessage>,
+>;
+type RpcWrite<W> = tokio_serde::SymmetricallyFramed<
+    tokio_util::codec::FramedWrite<W, tokio_util::codec::LengthDelimitedCodec>,
+    ProtocolMessage,
+    SymmetricalPostcard<ProtocolMessage>,
+>;
+
+static_assertions::assert_impl_all!(RpcRead<RecvStream>: Stream<Item = std::io::Result<ProtocolMessage>>);
+
+/// Whether the peer refused a connection as it does not speak the ALPN, e.g. as it runs an
+/// incompatible version.
+fn is_alpn_rejected(err: &anyhow::Error) -> bool {
+    // TLS alert 120 (no_application_protocol), in the range of QUIC crypto errors.
+    const NO_APPLICATION_PROTOCOL: u64 = 0x100 + 120;
+    matches!(
+        err.downcast_ref::<ConnectionError>(),
+        Some(ConnectionError::ConnectionClosed(close))
+            if u64::from(close.error_code) == NO_APPLICATION_PROTOCOL
+    )
+}
+
+fn wrap_streams<R, W>(
+    send_stream: W,
+    recv_stream: R,
+    max_frame_size: usize,
+) -> (RpcRead<R>, RpcWrite<W>)
+where
+    W: tokio::io::AsyncWrite,
+    R: tokio::io::AsyncRead,
+{
+    let codec = || {
+        tokio_util::codec::LengthDelimitedCodec::builder()
+            .max_frame_length(max_frame_size)
+            .new_codec()
+    };
+    let transport = tokio_util::codec::FramedRead::new(recv_stream, codec());
+    let framed_read = tokio_serde::SymmetricallyFramed::<_, ProtocolMessage, _>::new(
+        transport,
+        SymmetricalPostcard::<ProtocolMessage>::default(),
+    );
+
+    let transport = tokio_util::codec::FramedWrite::new(send_stream, codec());
+    let framed_write = tokio_serde::SymmetricallyFramed::<_, ProtocolMessage, _>::new(
+        transport,
+        SymmetricalPostcard::<ProtocolMessage>::default(),
+    );
+
+    (framed_read, framed_write)
+}
+
+pub struct Postcard<Item, SinkItem> {
+    _marker: PhantomData<(Item, SinkItem)>,
+}
+
+impl<Item, SinkItem> Default for Postcard<Item, SinkItem> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<Item, SinkItem> Postcard<Item, SinkItem> {
+    pub fn new() -> Self {
+        Self {
+            _marker: PhantomData,
+        }
+    }
+}
+
+pub type SymmetricalPostcard<T> = Postcard<T, T>;
+
+impl<Item, SinkItem> Deserializer<Item> for Postcard<Item, SinkItem>
+where
+    for<'a> Item: Deserialize<'a>,
+{
+    type Error = io::Error;
+
+    fn deserialize(self: Pin<&mut Self>, src: &BytesMut) -> Result<Item, Self::Error> {
+        postcard::from_bytes(src).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+}
+
+impl<Item, SinkItem> Serializer<SinkItem> for Postcard<Item, SinkItem>
+where
+    SinkItem: Serialize,
+{
+    type Error = io::Error;
+
+    fn serialize(self: Pin<&mut Self>, data: &SinkItem) -> Result<Bytes, Self::Error> {
+        postcard::experimental::serialized_size(data)
+            .and_then(|size| postcard::to_io(data, BytesMut::with_capacity(size).writer()))
+            .map(|writer| writer.into_inner().freeze())
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+}
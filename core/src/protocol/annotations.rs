@@ -0,0 +1,113 @@
+//! Notes added to finished transfers, e.g. "final version", kept the same on both sides.
+//!
+//! A changed note is sent to the peer with `HistoryAnnotation` right away. If the peer is
+//! offline or too old, the note stays unsynced and is sent once the peer comes back, see
+//! [`Protocol::sync_annotations_with`].
+
+use anyhow::Result;
+use futures_util::sink::SinkExt;
+use iroh::blobs::Hash;
+use iroh::net::NodeId;
+
+use super::{wrap_streams, LocalProtocolMessage, Protocol, ProtocolMessage};
+use crate::error::DropError;
+
+/// Longest note in bytes, longer ones from peers are ignored.
+const MAX_NOTE_LEN: usize = 500;
+
+impl Protocol {
+    /// Sets the note of the transfer of `hash` with `node_id`, `None` removes it.
+    pub async fn annotate(&self, node_id: NodeId, hash: Hash, note: Option<String>) -> Result<()> {
+        let note = note
+            .map(|note| note.trim().to_string())
+            .filter(|note| !note.is_empty());
+        if note.as_ref().is_some_and(|note| note.len() > MAX_NOTE_LEN) {
+            let message = format!("notes are limited to {MAX_NOTE_LEN} bytes");
+            return Err(DropError::InvalidArgument(message).into());
+        }
+        if self.history.set_note(node_id, hash, note).await.is_none() {
+            return Err(DropError::InvalidArgument(format!("no transfer for {hash}")).into());
+        }
+        self.sync_annotations_with(node_id).await;
+        Ok(())
+    }
+
+    /// Sends the notes `node_id` does not have yet, if it is online.
+    pub async fn sync_annotations_with(&self, node_id: NodeId) {
+        let supported = self
+            .known_nodes
+            .read()
+            .await
+            .get(&node_id)
+            .is_some_and(|node| node.online && node.capabilities.supports_annotations());
+        if !supported {
+            return;
+        }
+        let notes = self.history.unsynced_notes(node_id).await;
+        if notes.is_empty() {
+            return;
+        }
+        match self.send_annotations(node_id, &notes).await {
+            Ok(()) => {
+                for (hash, _, updated_at) in notes {
+                    self.history
+                        .set_note_synced(node_id, hash, updated_at)
+                        .await;
+                }
+            }
+            Err(err) => {
+                tracing::warn!("failed to sync notes with {node_id}: {err:?}");
+            }
+        }
+    }
+
+    async fn send_annotations(
+        &self,
+        node_id: NodeId,
+        notes: &[(Hash, Option<String>, u64)],
+    ) -> Result<()> {
+        let advanced = self.settings.get().await.advanced;
+        let conn = self.dial(node_id.into(), advanced.dial_timeout()).await?;
+        let (send, recv) = conn.open_bi().await?;
+
+        let (_reader, mut writer) = wrap_streams(send, recv, advanced.max_frame_size);
+        for (hash, note, updated_at) in notes {
+            writer
+                .send(ProtocolMessage::HistoryAnnotation {
+                    hash: *hash,
+                    note: note.clone(),
+                    updated_at: *updated_at,
+                })
+                .await?;
+        }
+
+        writer.send(ProtocolMessage::Finish).await?;
+        let mut writer = writer.into_inner().into_inner();
+        writer.finish()?;
+        writer.stopped().await?;
+
+        Ok(())
+    }
+
+    /// Applies a note `node_id` added to one of our transfers with it.
+    pub(super) async fn handle_annotation(
+        &self,
+        node_id: NodeId,
+        hash: Hash,
+        note: Option<String>,
+        updated_at: u64,
+    ) {
+        if note.as_ref().is_some_and(|note| note.len() > MAX_NOTE_LEN) {
+            tracing::warn!("ignoring a note from {node_id} that is too long");
+            return;
+        }
+        if self
+            .history
+            .merge_note(node_id, hash, note, updated_at)
+            .await
+        {
+            tracing::info!("applied a note from {node_id} to {hash}");
+            self.s.send(LocalProtocolMessage::HistoryChanged).await.ok();
+        }
+    }
+}
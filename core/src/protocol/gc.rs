@@ -0,0 +1,120 @@
+//! Garbage collection of the blob store, which otherwise keeps every sent and received file.
+//!
+//! Blobs are removed once they are no longer needed and either past the retention period,
+//! or the store grew larger than allowed. The history entries stay, marked as collected.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Result;
+use futures_lite::stream::StreamExt;
+use iroh::blobs::Hash;
+use serde::Serialize;
+
+use super::{LocalProtocolMessage, Protocol};
+use crate::history;
+
+/// How often the store is cleaned up.
+const GC_TICK: Duration = Duration::from_secs(60 * 60);
+
+/// Disk usage of the blob store.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct StorageUsage {
+    /// Size of all blobs, in bytes
+    pub size: u64,
+    pub blobs: usize,
+    /// Size of the blobs of finished transfers, which are removed as configured, in bytes
+    pub collectable: u64,
+}
+
+impl Protocol {
+    /// Starts the background task, removing blobs that are no longer needed as configured in
+    /// [`crate::settings::StorageSettings`].
+    ///
+    /// Only blobs whose sends finished and whose received files were exported are removed,
+    /// see [`crate::history::History::collectable`].
+    pub fn spawn_gc(self: &Arc<Self>) {
+        let this = self.clone();
+        tokio::spawn(async move {
+            let mut tick = tokio::time::interval(GC_TICK);
+            loop {
+                tick.tick().await;
+                if let Err(err) = this.collect_garbage().await {
+                    tracing::warn!("failed to clean up the store: {err:#}");
+                }
+            }
+        });
+    }
+
+    pub async fn storage_usage(&self) -> Result<StorageUsage> {
+        let sizes = self.blob_sizes().await?;
+        let collectable = self
+            .history
+            .collectable()
+            .await
+            .iter()
+            .filter_map(|(hash, _)| sizes.get(hash))
+            .sum();
+        Ok(StorageUsage {
+            size: sizes.values().sum(),
+            blobs: sizes.len(),
+            collectable,
+        })
+    }
+
+    /// Removes the blobs that are past the retention period, and the least recently used
+    /// ones while the store is larger than allowed.
+    ///
+    /// Returns the removed blobs.
+    pub async fn collect_garbage(&self) -> Result<Vec<Hash>> {
+        let storage = self.settings.get().await.storage;
+        let cutoff = storage
+            .retention()
+            .map(|retention| history::now().saturating_sub(retention.as_secs()));
+        let sizes = self.blob_sizes().await?;
+        let mut size: u64 = sizes.values().sum();
+        // Running transfers may still read their blob.
+        let active: BTreeSet<_> = self
+            .transfers
+            .list()
+            .into_iter()
+            .filter_map(|transfer| transfer.hash)
+            .collect();
+
+        let mut collected = Vec::new();
+        for (hash, last_used) in self.history.collectable().await {
+            let expired = cutoff.is_some_and(|cutoff| last_used <= cutoff);
+            let too_large = storage.max_store_size > 0 && size > storage.max_store_size;
+            // Oldest first, so none of the remaining blobs is due either.
+            if !expired && !too_large {
+                break;
+            }
+            if active.contains(&hash) {
+                continue;
+            }
+            if let Err(err) = self.client.blobs().delete_blob(hash).await {
+                tracing::warn!("failed to delete {hash}: {err:#}");
+                continue;
+            }
+            self.history.set_collected(&hash).await;
+            size = size.saturating_sub(sizes.get(&hash).copied().unwrap_or_default());
+            collected.push(hash);
+        }
+        if !collected.is_empty() {
+            tracing::info!("removed {} blobs from the store", collected.len());
+            self.s.send(LocalProtocolMessage::HistoryChanged).await.ok();
+        }
+        Ok(collected)
+    }
+
+    async fn blob_sizes(&self) -> Result<BTreeMap<Hash, u64>> {
+        let mut blobs = self.client.blobs().list().await?;
+        let mut sizes = BTreeMap::new();
+        while let Some(blob) = blobs.next().await {
+            let blob = blob?;
+            sizes.insert(blob.hash, blob.size);
+        }
+        Ok(sizes)
+    }
+}
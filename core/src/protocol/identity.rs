@@ -0,0 +1,152 @@
+//! Pinning and verification of the names of peers, see [`crate::identity`].
+//!
+//! Verifying asks the peer with `VerifyIdentity` to show the code of the connection, while
+//! we show the same code. Once the user confirmed that both match, the current name of the
+//! peer is pinned as verified.
+
+use anyhow::Result;
+use futures_lite::stream::StreamExt;
+use futures_util::sink::SinkExt;
+use iroh::net::NodeId;
+
+use super::{legacy_rejection, wrap_streams, LocalProtocolMessage, Protocol, ProtocolMessage};
+use crate::error::DropError;
+use crate::history::now;
+use crate::identity::{self, PinnedIdentity};
+use crate::security_log::RejectReason;
+
+impl Protocol {
+    /// Checks the `name` `node_id` introduced itself with, pinning it on first sight, and
+    /// warns the user once about a suspicious name.
+    pub(super) async fn check_identity(&self, node_id: NodeId, name: &str) {
+        let settings = self.settings.get().await;
+        // Own devices proved the group secret, whatever they are called.
+        let own_device = settings.sync.own_devices.contains(&node_id);
+        let pinned = settings.identities.get(&node_id);
+        let mut identities = settings.identities.clone();
+        if pinned.is_none() || (own_device && pinned.is_some_and(|p| p.name != name)) {
+            let identity = PinnedIdentity {
+                name: name.to_string(),
+                pinned_at: now(),
+                verified: own_device,
+            };
+            identities.insert(node_id, identity.clone());
+            if let Err(err) = self.settings.set_identity(node_id, identity).await {
+                tracing::warn!("failed to pin the name of {node_id}: {err:?}");
+            }
+        }
+        let warning = (!own_device)
+            .then(|| identity::check(&identities, node_id, name))
+            .flatten();
+
+        let changed = match self.known_nodes.write().await.get_mut(&node_id) {
+            Some(node) if node.identity_warning != warning => {
+                node.identity_warning = warning.clone();
+                true
+            }
+            _ => false,
+        };
+        if let (true, Some(warning)) = (changed, warning) {
+            tracing::warn!("{node_id} introduced itself as {name:?}: {warning:?}");
+            self.s
+                .send(LocalProtocolMessage::IdentityWarning {
+                    node_id,
+                    name: name.to_string(),
+                    warning,
+                })
+                .await
+                .ok();
+        }
+    }
+
+    /// Asks `node_id` to show the verification code of our connection, returning the code
+    /// the user compares it with.
+    pub async fn verify_identity(&self, node_id: NodeId) -> Result<String> {
+        let capabilities = self
+            .known_nodes
+            .read()
+            .await
+            .get(&node_id)
+            .map(|node| node.capabilities.clone())
+            .ok_or(DropError::UnknownNode)?;
+        if !capabilities.supports_identity_verification() {
+            let message = "the device does not support verification".to_string();
+            return Err(DropError::InvalidArgument(message).into());
+        }
+
+        let advanced = self.settings.get().await.advanced;
+        let conn = self.dial(node_id.into(), advanced.dial_timeout()).await?;
+        let (send, recv) = conn.open_bi().await?;
+
+        let (mut reader, mut writer) = wrap_streams(send, recv, advanced.max_frame_size);
+        writer.send(ProtocolMessage::VerifyIdentity).await?;
+        match reader.next().await {
+            Some(Ok(ProtocolMessage::IdentityShown)) => {}
+            Some(Ok(ProtocolMessage::Rejected { reason })) => {
+                return Err(DropError::from(reason).into());
+            }
+            Some(Ok(ProtocolMessage::SendReject { reason })) => {
+                return Err(legacy_rejection(reason).into());
+            }
+            Some(Ok(msg)) => anyhow::bail!("unexpected response: {:?}", msg),
+            Some(Err(err)) => return Err(err.into()),
+            None => anyhow::bail!("remote aborted"),
+        }
+
+        writer.send(ProtocolMessage::Finish).await?;
+        let mut writer = writer.into_inner().into_inner();
+        writer.finish()?;
+        writer.stopped().await?;
+
+        Ok(identity::verification_code(
+            self.endpoint.node_id(),
+            node_id,
+        ))
+    }
+
+    /// Shows the verification code to the user, as `node_id` asked for it.
+    pub(super) async fn handle_verify_identity(&self, node_id: NodeId) -> ProtocolMessage {
+        let name = self
+            .known_nodes
+            .read()
+            .await
+            .get(&node_id)
+            .map(|node| node.name.clone());
+        let Some(name) = name else {
+            return self.reject(node_id, RejectReason::UnknownPeer).await;
+        };
+        let code = identity::verification_code(self.endpoint.node_id(), node_id);
+        self.s
+            .send(LocalProtocolMessage::IdentityVerification {
+                node_id,
+                name,
+                code,
+            })
+            .await
+            .ok();
+        ProtocolMessage::IdentityShown
+    }
+
+    /// Pins the current name of `node_id` as verified, after the user saw the same code on
+    /// both devices.
+    pub async fn confirm_identity(&self, node_id: NodeId) -> Result<()> {
+        let name = self
+            .known_nodes
+            .read()
+            .await
+            .get(&node_id)
+            .map(|node| node.name.clone())
+            .ok_or(DropError::UnknownNode)?;
+        let identity = PinnedIdentity {
+            name,
+            pinned_at: now(),
+            verified: true,
+        };
+        self.settings.set_identity(node_id, identity).await?;
+        if let Some(node) = self.known_nodes.write().await.get_mut(&node_id) {
+            node.identity_warning = None;
+        }
+        tracing::info!("verified the identity of {node_id}");
+        Ok(())
+    }
+}
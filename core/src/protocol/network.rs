@@ -0,0 +1,176 @@
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Result;
+use futures_lite::stream::StreamExt;
+use serde::Serialize;
+
+use super::{LocalProtocolMessage, Protocol};
+use crate::diagnostics::{Check, CheckResult, CheckState};
+use crate::network_trust::{self, Network, Trust};
+use crate::settings::{RelayMode, UntrustedPolicy};
+use crate::strategy;
+
+/// The configured relays, and the one the node is connected to.
+#[derive(Debug, Clone, Serialize)]
+pub struct RelayStatus {
+    /// As configured, changes are applied on the next start
+    pub mode: RelayMode,
+    pub url: Option<String>,
+    /// The relay the node is reachable through, `None` while there is none
+    pub home_relay: Option<String>,
+}
+
+impl Protocol {
+    /// Starts the background task, applying the trust of the network whenever the local
+    /// addresses change.
+    pub fn spawn_network_watch(self: &Arc<Self>) {
+        let mut addrs = self.endpoint.direct_addresses();
+        let this = self.clone();
+        tokio::spawn(async move {
+            while let Some(addrs) = addrs.next().await {
+                let network = network_trust::identify(addrs.iter().map(|addr| addr.addr));
+                if let Some(network) = network {
+                    this.network_changed(network).await;
+                }
+            }
+        });
+    }
+
+    pub async fn relay_status(&self) -> RelayStatus {
+        let network = self.settings.get().await.network;
+        RelayStatus {
+            mode: network.relay,
+            url: network.relay_url,
+            home_relay: self.endpoint.home_relay().map(|url| url.to_string()),
+        }
+    }
+
+    /// Checks why no peers may be found, for the guided troubleshooting.
+    ///
+    /// The OS permission for the local network is only known to the app, which adds the
+    /// [`Check::Permission`] step.
+    pub async fn troubleshoot(&self) -> Vec<CheckResult> {
+        let state = if self.is_invisible() {
+            CheckState::Problem
+        } else {
+            CheckState::Ok
+        };
+        let visibility = CheckResult::new(Check::Visibility, state, None);
+
+        let addrs = tokio::time::timeout(
+            Duration::from_secs(1),
+            self.endpoint.direct_addresses().next(),
+        )
+        .await
+        .ok()
+        .flatten()
+        .unwrap_or_default();
+        let local = addrs.iter().any(|addr| strategy::is_local_addr(&addr.addr));
+        let same_network = match self.current_network().await {
+            Some((network, _)) if local => CheckResult::new(
+                Check::SameNetwork,
+                CheckState::Ok,
+                Some(format!("On {}", network.label)),
+            ),
+            _ => CheckResult::new(
+                Check::SameNetwork,
+                CheckState::Problem,
+                Some("Not on a local network".to_string()),
+            ),
+        };
+
+        let firewall = self.diagnostics.check_firewall().await;
+
+        let status = self.relay_status().await;
+        let relay = match (status.mode, status.home_relay) {
+            (RelayMode::Disabled, _) => CheckResult::new(
+                Check::Relay,
+                CheckState::Problem,
+                Some("Relays are turned off".to_string()),
+            ),
+            (_, Some(url)) => CheckResult::new(
+                Check::Relay,
+                CheckState::Ok,
+                Some(format!("Connected to {url}")),
+            ),
+            (_, None) => CheckResult::new(
+                Check::Relay,
+                CheckState::Problem,
+                Some("No relay is reachable".to_string()),
+            ),
+        };
+
+        vec![visibility, same_network, firewall, relay]
+    }
+
+    /// The network the device is on, and whether it is trusted.
+    pub async fn current_network(&self) -> Option<(Network, Trust)> {
+        let network = self.network.lock().unwrap().clone()?;
+        let trust = self.settings.get().await.network.trust(&network.id);
+        Some((network, trust))
+    }
+
+    /// Remembers whether the network `id` is trusted, applying it if we are on it.
+    pub async fn set_network_trusted(&self, id: String, trusted: bool) -> Result<()> {
+        let mut settings = self.settings.get().await;
+        settings.network.trusted.remove(&id);
+        settings.network.untrusted.remove(&id);
+        if trusted {
+            settings.network.trusted.insert(id.clone());
+        } else {
+            settings.network.untrusted.insert(id.clone());
+        }
+        self.settings.set(settings).await?;
+
+        if let Some((network, trust)) = self.current_network().await {
+            if network.id == id {
+                self.apply_network_trust(trust).await;
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether offers are restricted to the user's own devices on the current network.
+    pub(super) async fn network_restricted(&self) -> bool {
+        let policy = self.settings.get().await.network.untrusted_policy;
+        match self.current_network().await {
+            Some((_, trust)) => policy == UntrustedPolicy::TrustedOnly && trust != Trust::Trusted,
+            None => false,
+        }
+    }
+
+    async fn network_changed(&self, network: Network) {
+        {
+            let mut current = self.network.lock().unwrap();
+            if current.as_ref() == Some(&network) {
+                return;
+            }
+            *current = Some(network.clone());
+        }
+        let trust = self.settings.get().await.network.trust(&network.id);
+        tracing::info!("network changed to {} ({trust:?})", network.label);
+        self.apply_network_trust(trust).await;
+        if trust == Trust::Unknown {
+            self.s
+                .send(LocalProtocolMessage::UnknownNetwork { network })
+                .await
+                .ok();
+        }
+    }
+
+    /// Goes invisible on untrusted networks if the policy asks for it, and visible again
+    /// once on a trusted one, unless the user turned invisible mode on.
+    async fn apply_network_trust(&self, trust: Trust) {
+        let policy = self.settings.get().await.network.untrusted_policy;
+        if trust != Trust::Trusted && policy == UntrustedPolicy::Invisible {
+            if !self.is_invisible() {
+                self.set_invisible(true).await;
+                self.network_invisible.store(true, Ordering::SeqCst);
+            }
+        } else if self.network_invisible.swap(false, Ordering::SeqCst) {
+            self.set_invisible(false).await;
+        }
+    }
+}
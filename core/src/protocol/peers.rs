@@ -0,0 +1,789 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use futures_lite::stream::StreamExt;
+use futures_util::sink::SinkExt;
+use iroh::blobs::Hash;
+use iroh::net::{NodeAddr, NodeId};
+use serde::Serialize;
+
+use super::{
+    wrap_streams, Capabilities, DeviceInfo, LocalProtocolMessage, Protocol, ProtocolMessage,
+    ReceiveConstraints,
+};
+use crate::error::DropError;
+use crate::handles::Handle;
+use crate::identity::IdentityWarning;
+use crate::peer_cache::{CachedPeer, PeerCache};
+use crate::retry;
+use crate::settings::{Settings, MAX_NAME_LEN};
+
+/// How often online peers are checked for liveness.
+const LIVENESS_TICK: Duration = Duration::from_secs(10);
+
+/// How often paired devices that are offline are looked up.
+const PAIRED_TICK: Duration = Duration::from_secs(60);
+
+/// Source of peers that were reached because they are paired, not discovered.
+const PAIRED_SOURCE: &str = "paired";
+
+/// Source of favorites that were reached because the user pinned them, not discovered.
+const FAVORITE_SOURCE: &str = "favorite";
+
+#[derive(Debug, Clone)]
+pub(super) struct RemoteNode {
+    /// Name of the remote node
+    pub(super) name: String,
+    pub(super) protocol_supported: bool,
+    pub(super) online: bool,
+    /// Last time we successfully talked to the node
+    pub(super) last_seen: SystemTime,
+    pub(super) capabilities: Capabilities,
+    pub(super) device: DeviceInfo,
+    /// `None` for peers older than version 6
+    pub(super) constraints: Option<ReceiveConstraints>,
+    /// Discovery services that reported the node
+    pub(super) sources: BTreeSet<&'static str>,
+    /// Handle the node claims in its intro, see [`crate::handles`]
+    pub(super) handle: Option<String>,
+    /// Whether the claimed handle was also published for the node
+    pub(super) handle_verified: bool,
+    /// Set if the name does not match the pinned identities, see [`crate::identity`]
+    pub(super) identity_warning: Option<IdentityWarning>,
+    /// Loaded from the [`PeerCache`] and not heard from since, so its capabilities may be
+    /// stale
+    pub(super) restored: bool,
+    /// Whether the node runs a version we can not talk to, see [`PeerInfo::incompatible`]
+    pub(super) incompatible: bool,
+}
+
+/// What a peer tells about itself in the intro.
+#[derive(Debug, Clone)]
+pub(super) struct Introduction {
+    pub(super) name: String,
+    pub(super) capabilities: Capabilities,
+    pub(super) device: DeviceInfo,
+    pub(super) constraints: Option<ReceiveConstraints>,
+    pub(super) handle: Option<String>,
+    pub(super) group_proof: Option<Hash>,
+}
+
+/// A peer as shown in the device list.
+#[derive(Debug, Clone, Serialize)]
+pub struct PeerInfo {
+    pub node_id: NodeId,
+    pub name: String,
+    pub online: bool,
+    /// Seconds since the unix epoch
+    pub last_seen: u64,
+    pub capabilities: Capabilities,
+    pub device: DeviceInfo,
+    /// What the peer accepts, `None` for peers older than version 6
+    pub constraints: Option<ReceiveConstraints>,
+    /// Discovery services that reported the node
+    pub sources: Vec<&'static str>,
+    /// Whether this is one of the user's own devices
+    pub own_device: bool,
+    /// Whether the peer was paired by ticket or QR code
+    pub paired: bool,
+    /// Paired, but never found on the local network
+    pub remote: bool,
+    /// Whether the peer joined with a guest ticket, see [`crate::guest`]
+    pub guest: bool,
+    /// Transforms applied to files sent to the peer, see [`crate::transform`]
+    pub send_transforms: Vec<String>,
+    /// Handle of the peer, only once it was resolved to the peer, see [`crate::handles`]
+    pub handle: Option<String>,
+    /// Set if the name does not match the pinned identities, see [`crate::identity`]
+    pub identity_warning: Option<IdentityWarning>,
+    /// Whether the user compared the verification codes of the peer
+    pub identity_verified: bool,
+    /// Whether the user pinned the peer to the top of the device list
+    pub favorite: bool,
+    /// Whether the peer runs an incompatible version, so files can not be sent to it.
+    ///
+    /// The version it reported the last time it was compatible is in `capabilities`.
+    pub incompatible: bool,
+}
+
+impl Protocol {
+    /// All peers speaking our protocol, online or not.
+    pub async fn list_peers(&self) -> Vec<PeerInfo> {
+        let settings = self.settings.get().await;
+        self.known_nodes
+            .read()
+            .await
+            .iter()
+            .filter(|(_, info)| info.protocol_supported || info.incompatible)
+            .map(|(id, info)| PeerInfo {
+                node_id: *id,
+                name: info.name.clone(),
+                online: info.online,
+                last_seen: info
+                    .last_seen
+                    .duration_since(UNIX_EPOCH)
+                    .map(|d| d.as_secs())
+                    .unwrap_or_default(),
+                capabilities: info.capabilities.clone(),
+                device: info.device,
+                constraints: info.constraints.clone(),
+                sources: info.sources.iter().copied().collect(),
+                own_device: settings.sync.own_devices.contains(id),
+                paired: settings.paired.contains_key(id),
+                remote: settings.paired.contains_key(id)
+                    && info.sources.iter().all(|source| *source == PAIRED_SOURCE),
+                guest: self.guests.is_guest(id),
+                send_transforms: settings
+                    .send_transforms
+                    .get(id)
+                    .cloned()
+                    .unwrap_or_default(),
+                handle: info.handle.clone().filter(|_| info.handle_verified),
+                identity_warning: info.identity_warning.clone(),
+                identity_verified: settings
+                    .identities
+                    .get(id)
+                    .is_some_and(|identity| identity.verified && identity.name == info.name),
+                favorite: settings.favorites.contains(id),
+                incompatible: info.incompatible,
+            })
+            .collect()
+    }
+
+    pub async fn is_known_node(&self, node_id: &NodeId) -> bool {
+        self.known_nodes.read().await.contains_key(node_id)
+    }
+
+    /// Marks `node_id` as not speaking our protocol, after the intro failed with `err`.
+    ///
+    /// Nodes running an incompatible version are still listed, so the user knows why files
+    /// can not be sent to them.
+    pub async fn mark_protocol_missmatch(&self, node_id: &NodeId, err: &anyhow::Error) {
+        let incompatible = matches!(
+            err.downcast_ref::<DropError>(),
+            Some(DropError::Incompatible(_))
+        );
+        let mut known_nodes = self.known_nodes.write().await;
+        let entry = known_nodes.entry(*node_id).or_insert_with(|| RemoteNode {
+            name: String::new(),
+            protocol_supported: false,
+            online: false,
+            last_seen: SystemTime::now(),
+            capabilities: Default::default(),
+            device: Default::default(),
+            constraints: None,
+            sources: Default::default(),
+            handle: None,
+            handle_verified: false,
+            identity_warning: None,
+            restored: false,
+            incompatible: false,
+        });
+        entry.protocol_supported = false;
+        entry.online = false;
+        let newly_incompatible = incompatible && !entry.incompatible;
+        entry.incompatible = incompatible;
+        drop(known_nodes);
+
+        if newly_incompatible {
+            tracing::info!("{node_id} runs an incompatible version: {err:#}");
+            self.s
+                .send(LocalProtocolMessage::PeerIncompatible { node_id: *node_id })
+                .await
+                .ok();
+        }
+    }
+
+    /// Hides `node_id` from the device list, as it is in another room.
+    pub(super) async fn peer_left(&self, node_id: NodeId) {
+        self.peer_cache.remove(&node_id);
+        let was_online = match self.known_nodes.write().await.get_mut(&node_id) {
+            Some(node) if node.protocol_supported => {
+                node.protocol_supported = false;
+                std::mem::replace(&mut node.online, false)
+            }
+            _ => false,
+        };
+        if was_online {
+            tracing::info!("peer {node_id} is in another room");
+            self.s
+                .send(LocalProtocolMessage::PeerOffline { node_id })
+                .await
+                .ok();
+        }
+    }
+
+    /// Hash of our room as sent in the intro, so the name itself is not shared.
+    pub(super) async fn room_id(&self) -> Option<Hash> {
+        self.settings
+            .get()
+            .await
+            .room
+            .map(|room| Hash::new(room.as_bytes()))
+    }
+
+    /// Handle we claim in the intro, see [`crate::handles`].
+    pub(super) async fn own_handle(&self) -> Option<String> {
+        self.settings.get().await.handle
+    }
+
+    /// Marks `handle` as verified for `node_id`, if the node claims it.
+    pub(super) async fn verify_handle(&self, node_id: NodeId, handle: &Handle) -> bool {
+        let mut known_nodes = self.known_nodes.write().await;
+        let Some(node) = known_nodes.get_mut(&node_id) else {
+            return false;
+        };
+        let claimed: Option<Handle> = node
+            .handle
+            .as_deref()
+            .and_then(|claimed| claimed.parse().ok());
+        node.handle_verified = claimed.as_ref() == Some(handle);
+        node.handle_verified
+    }
+
+    /// Joins `room`, or leaves the current one if `None`.
+    ///
+    /// All known peers are introduced to again, those in other rooms are hidden on both sides.
+    pub async fn set_room(self: &Arc<Self>, room: Option<String>) -> Result<()> {
+        let room = room
+            .map(|room| room.trim().to_string())
+            .filter(|room| !room.is_empty());
+        let mut settings = self.settings.get().await;
+        settings.room = room;
+        self.settings.set(settings).await?;
+        self.s.send(LocalProtocolMessage::SettingsChanged).await.ok();
+
+        let node_ids: Vec<NodeId> = self.known_nodes.read().await.keys().copied().collect();
+        for node_id in node_ids {
+            let this = self.clone();
+            tokio::spawn(async move {
+                if let Err(err) = this.send_intro(node_id.into()).await {
+                    tracing::debug!("failed to introduce us to {node_id}: {err:#}");
+                }
+            });
+        }
+        Ok(())
+    }
+
+    /// Records a successful exchange with `node_id`, announcing it if it just came online.
+    pub(super) async fn peer_seen(&self, node_id: NodeId, peer: Introduction) {
+        let Introduction {
+            name,
+            capabilities,
+            device,
+            constraints,
+            handle,
+            group_proof,
+        } = peer;
+        let mut known_nodes = self.known_nodes.write().await;
+        let now = SystemTime::now();
+        let changed = match known_nodes.get_mut(&node_id) {
+            Some(node) => {
+                let changed = !node.online || !node.protocol_supported || node.name != name;
+                node.name = name.clone();
+                node.protocol_supported = true;
+                node.online = true;
+                node.last_seen = now;
+                node.capabilities = capabilities;
+                node.device = device;
+                node.constraints = constraints;
+                node.restored = false;
+                node.incompatible = false;
+                // A verification only holds for the handle that was resolved.
+                if node.handle != handle {
+                    node.handle = handle;
+                    node.handle_verified = false;
+                }
+                changed
+            }
+            None => {
+                known_nodes.insert(
+                    node_id,
+                    RemoteNode {
+                        name: name.clone(),
+                        protocol_supported: true,
+                        online: true,
+                        last_seen: now,
+                        capabilities,
+                        device,
+                        constraints,
+                        sources: Default::default(),
+                        handle,
+                        handle_verified: false,
+                        identity_warning: None,
+                        restored: false,
+                        incompatible: false,
+                    },
+                );
+                true
+            }
+        };
+        drop(known_nodes);
+        self.remember_peer(node_id).await;
+
+        if let Some(proof) = group_proof {
+            self.group_member_seen(node_id, &name, proof).await;
+        }
+        self.check_identity(node_id, &name).await;
+        if changed {
+            self.s
+                .send(LocalProtocolMessage::PeerOnline { node_id, name })
+                .await
+                .ok();
+        }
+    }
+
+    /// Our proof of the group of own devices for `node_id`, `None` outside of a group.
+    pub(super) async fn group_proof(&self, node_id: NodeId) -> Option<Hash> {
+        let secret = self.settings.get().await.sync.group?;
+        Some(secret.proof(self.endpoint.node_id(), node_id))
+    }
+
+    /// Marks `node_id` as an own device and pairs it, if `proof` shows it is in our group.
+    pub(super) async fn group_member_seen(&self, node_id: NodeId, name: &str, proof: Hash) {
+        let settings = self.settings.get().await;
+        let Some(secret) = settings.sync.group else {
+            return;
+        };
+        if secret.proof(node_id, self.endpoint.node_id()) != proof {
+            tracing::debug!("{node_id} is in another group of devices");
+            return;
+        }
+        if settings.sync.own_devices.contains(&node_id) {
+            return;
+        }
+        tracing::info!("{node_id} joined our group of devices");
+        let res = async {
+            self.settings.set_own_device(node_id, true).await?;
+            self.settings
+                .set_paired(node_id, Some(name.to_string()))
+                .await
+        };
+        if let Err(err) = res.await {
+            tracing::warn!("failed to add {node_id} to our devices: {err:?}");
+            return;
+        }
+        self.s
+            .send(LocalProtocolMessage::SettingsChanged)
+            .await
+            .ok();
+    }
+
+    /// Tells all online peers our name, after it was changed in the settings.
+    pub async fn announce_name(self: &Arc<Self>) {
+        let name = self.name();
+        let peers: Vec<NodeId> = self
+            .known_nodes
+            .read()
+            .await
+            .iter()
+            .filter(|(_, node)| node.online && node.capabilities.supports_name_changes())
+            .map(|(node_id, _)| *node_id)
+            .collect();
+        tracing::info!("announcing the name {name:?} to {} peers", peers.len());
+        for node_id in peers {
+            let this = self.clone();
+            let name = name.clone();
+            tokio::spawn(async move {
+                if let Err(err) = this.send_name(node_id, name).await {
+                    tracing::warn!("failed to send our name to {node_id}: {err:?}");
+                }
+            });
+        }
+    }
+
+    async fn send_name(&self, node_id: NodeId, name: String) -> Result<()> {
+        let advanced = self.settings.get().await.advanced;
+        let conn = self.dial(node_id.into(), advanced.dial_timeout()).await?;
+        let (send, recv) = conn.open_bi().await?;
+
+        let (_reader, mut writer) = wrap_streams(send, recv, advanced.max_frame_size);
+        writer.send(ProtocolMessage::NameChanged { name }).await?;
+
+        writer.send(ProtocolMessage::Finish).await?;
+        let mut writer = writer.into_inner().into_inner();
+        writer.finish()?;
+        writer.stopped().await?;
+
+        Ok(())
+    }
+
+    /// Applies the new name of `node_id`, if it is a known peer.
+    pub(super) async fn peer_renamed(&self, node_id: NodeId, name: String) {
+        if name.is_empty() || name.len() > MAX_NAME_LEN {
+            tracing::warn!("ignoring an invalid name from {node_id}");
+            return;
+        }
+        let mut known_nodes = self.known_nodes.write().await;
+        let Some(node) = known_nodes.get_mut(&node_id) else {
+            return;
+        };
+        if node.name == name {
+            return;
+        }
+        tracing::info!("{node_id} renamed itself from {:?} to {name:?}", node.name);
+        node.name = name.clone();
+        drop(known_nodes);
+        self.remember_peer(node_id).await;
+        self.check_identity(node_id, &name).await;
+
+        if self.settings.get().await.paired.contains_key(&node_id) {
+            if let Err(err) = self.settings.set_paired(node_id, Some(name.clone())).await {
+                tracing::warn!("failed to rename the paired device {node_id}: {err:?}");
+            }
+        }
+        self.s
+            .send(LocalProtocolMessage::PeerRenamed { node_id, name })
+            .await
+            .ok();
+    }
+
+    /// Saves `node_id` to the [`PeerCache`], with the addresses it is reached at.
+    ///
+    /// Guests are not remembered, their access ends with the app anyway.
+    async fn remember_peer(&self, node_id: NodeId) {
+        if self.guests.is_guest(&node_id) {
+            return;
+        }
+        let peer = match self.known_nodes.read().await.get(&node_id) {
+            Some(node) if node.protocol_supported => CachedPeer {
+                name: node.name.clone(),
+                last_seen: node
+                    .last_seen
+                    .duration_since(UNIX_EPOCH)
+                    .map(|d| d.as_secs())
+                    .unwrap_or_default(),
+                capabilities: node.capabilities.clone(),
+                device: node.device,
+                constraints: node.constraints.clone(),
+                handle: node.handle.clone(),
+                relay_url: None,
+                addrs: BTreeSet::new(),
+            },
+            _ => return,
+        };
+        let peer = match self.endpoint.remote_info(node_id) {
+            Some(info) => CachedPeer {
+                relay_url: info.relay_url.map(|relay| relay.relay_url),
+                addrs: info.addrs.iter().map(|addr| addr.addr).collect(),
+                ..peer
+            },
+            None => peer,
+        };
+        self.peer_cache.update(node_id, peer);
+    }
+
+    /// Introduces us to `node_id` again if it was only restored from the [`PeerCache`], so
+    /// its capabilities are current before the first transfer.
+    pub(super) async fn revalidate(&self, node_id: NodeId) -> Result<()> {
+        let restored = self
+            .known_nodes
+            .read()
+            .await
+            .get(&node_id)
+            .is_some_and(|node| node.restored);
+        if restored {
+            tracing::debug!("revalidating the restored peer {node_id}");
+            self.send_intro(node_id.into()).await?;
+        }
+        Ok(())
+    }
+
+    /// Reports that `node_id` could not be reached, after all retries failed with `err`.
+    ///
+    /// Does nothing for permanent failures, like a rejected offer.
+    pub(super) async fn peer_unreachable(&self, node_id: NodeId, err: &anyhow::Error) {
+        if !retry::is_transient(err) {
+            return;
+        }
+        self.s
+            .send(LocalProtocolMessage::PeerUnreachable {
+                node_id,
+                error: format!("{err:#}"),
+            })
+            .await
+            .ok();
+    }
+
+    /// Records that `node_id` is still alive, without changing its identity.
+    pub(super) async fn peer_alive(&self, node_id: NodeId) {
+        let came_online = match self.known_nodes.write().await.get_mut(&node_id) {
+            Some(node) if node.protocol_supported => {
+                node.last_seen = SystemTime::now();
+                let came_online = !node.online;
+                node.online = true;
+                came_online.then(|| node.name.clone())
+            }
+            _ => None,
+        };
+
+        if let Some(name) = came_online {
+            self.s
+                .send(LocalProtocolMessage::PeerOnline { node_id, name })
+                .await
+                .ok();
+        }
+    }
+
+    /// Adds `source` to the discovery sources of `node_id`.
+    ///
+    /// Returns `true` if the node is already known and online, so it does not need another intro.
+    async fn merge_discovery_source(&self, node_id: NodeId, source: &'static str) -> bool {
+        match self.known_nodes.write().await.get_mut(&node_id) {
+            Some(node) => {
+                node.sources.insert(source);
+                node.online && node.protocol_supported
+            }
+            None => false,
+        }
+    }
+
+    /// Starts the background task, introducing us to newly discovered nodes and
+    /// keeping track of which peers are online.
+    ///
+    /// The same node is often reported by several discovery services within seconds,
+    /// these are merged into a single peer keyed by its node id.
+    pub fn spawn_discovery(self: &Arc<Self>) -> Result<()> {
+        let mut stream = self
+            .endpoint
+            .discovery()
+            .and_then(|discovery| discovery.subscribe())
+            .context("discovery is not enabled")?;
+
+        let this = self.clone();
+        tokio::spawn(async move {
+            tracing::info!("spawning discovery stream");
+            let mut liveness = tokio::time::interval(LIVENESS_TICK);
+            loop {
+                tokio::select! {
+                    item = stream.next() => {
+                        let Some(item) = item else {
+                            break;
+                        };
+                        let node_id = item.node_id;
+                        if this.merge_discovery_source(node_id, item.provenance).await {
+                            continue;
+                        }
+                        // Introducing ourselves would reveal the device.
+                        if this.is_invisible() {
+                            continue;
+                        }
+                        // Another source already reported the node, and the intro is running.
+                        if !this.pending_intros.lock().unwrap().insert(node_id) {
+                            continue;
+                        }
+                        let mut node_addr = NodeAddr::new(node_id);
+                        node_addr.info = item.addr_info;
+                        let this = this.clone();
+                        tokio::spawn(async move {
+                            let res = retry::INTRO
+                                .retry("introducing us", || this.send_intro(node_addr.clone()))
+                                .await;
+                            if let Err(err) = res {
+                                tracing::warn!("failed to discover {node_id}: {err:?}");
+                                if retry::is_transient(&err) {
+                                    // Not marked, so the next discovery report tries again.
+                                    this.peer_unreachable(node_id, &err).await;
+                                } else {
+                                    this.mark_protocol_missmatch(&node_id, &err).await;
+                                }
+                            }
+                            this.merge_discovery_source(node_id, item.provenance).await;
+                            this.pending_intros.lock().unwrap().remove(&node_id);
+                        });
+                    }
+                    _ = liveness.tick() => {
+                        this.check_liveness().await;
+                    }
+                }
+            }
+        });
+
+        Ok(())
+    }
+
+    /// Starts the background task, connecting to paired devices and favorites that are
+    /// offline.
+    ///
+    /// They are listed right away, as offline until they answer. Dialing them by their node
+    /// id resolves their addresses through the discovery services, which finds them via DNS
+    /// when they are not on the local network.
+    pub fn spawn_paired_reconnect(self: &Arc<Self>) {
+        let this = self.clone();
+        tokio::spawn(async move {
+            let remembered = remembered_peers(&this.settings.get().await);
+            {
+                let mut known_nodes = this.known_nodes.write().await;
+                for (node_id, name, source) in remembered {
+                    known_nodes.entry(node_id).or_insert_with(|| RemoteNode {
+                        name,
+                        protocol_supported: true,
+                        online: false,
+                        last_seen: UNIX_EPOCH,
+                        capabilities: Default::default(),
+                        device: Default::default(),
+                        constraints: None,
+                        sources: [source].into(),
+                        handle: None,
+                        handle_verified: false,
+                        identity_warning: None,
+                        restored: false,
+                        incompatible: false,
+                    });
+                }
+            }
+
+            let mut tick = tokio::time::interval(PAIRED_TICK);
+            loop {
+                tick.tick().await;
+                this.reconnect_paired().await;
+            }
+        });
+    }
+
+    async fn reconnect_paired(self: &Arc<Self>) {
+        // Introducing ourselves would reveal the device.
+        if self.is_invisible() {
+            return;
+        }
+        let remembered = remembered_peers(&self.settings.get().await);
+        for (node_id, _, source) in remembered {
+            let online = self
+                .known_nodes
+                .read()
+                .await
+                .get(&node_id)
+                .is_some_and(|node| node.online);
+            if online || !self.pending_intros.lock().unwrap().insert(node_id) {
+                continue;
+            }
+            let this = self.clone();
+            tokio::spawn(async move {
+                match this.send_intro(node_id.into()).await {
+                    Ok(_) => {
+                        this.merge_discovery_source(node_id, source).await;
+                    }
+                    Err(err) => tracing::debug!("{source} device {node_id} not reachable: {err:#}"),
+                }
+                this.pending_intros.lock().unwrap().remove(&node_id);
+            });
+        }
+    }
+
+    /// Marks peers that timed out as offline, and pings those not seen recently.
+    ///
+    /// Guests whose access expired are removed.
+    async fn check_liveness(self: &Arc<Self>) {
+        for node_id in self.guests.take_expired() {
+            tracing::info!("guest access of {node_id} expired");
+            if self.known_nodes.write().await.remove(&node_id).is_some() {
+                self.s
+                    .send(LocalProtocolMessage::PeerOffline { node_id })
+                    .await
+                    .ok();
+            }
+        }
+
+        let timeout = self.settings.get().await.advanced.peer_timeout();
+        let mut lost = Vec::new();
+        let mut stale = Vec::new();
+        for (node_id, node) in self.known_nodes.write().await.iter_mut() {
+            if !node.online || !node.protocol_supported {
+                continue;
+            }
+            let since = node.last_seen.elapsed().unwrap_or_default();
+            if since > timeout {
+                node.online = false;
+                lost.push(*node_id);
+            } else if since > timeout / 3 {
+                stale.push((*node_id, node.capabilities.supports_ping()));
+            }
+        }
+
+        for node_id in lost {
+            tracing::info!("peer {node_id} went offline");
+            self.s
+                .send(LocalProtocolMessage::PeerOffline { node_id })
+                .await
+                .ok();
+        }
+        if self.is_invisible() {
+            return;
+        }
+        for (node_id, supports_ping) in stale {
+            let this = self.clone();
+            tokio::spawn(async move {
+                // Older nodes don't know `Ping`, fall back to a full intro.
+                let res = if supports_ping {
+                    this.ping(node_id).await
+                } else {
+                    this.send_intro(node_id.into()).await.map(|_| ())
+                };
+                if let Err(err) = res {
+                    tracing::debug!("liveness check for {node_id} failed: {err:?}");
+                }
+            });
+        }
+    }
+}
+
+/// The paired devices and favorites, which are reconnected to while offline, with their last
+/// known names and the source they are listed under.
+fn remembered_peers(settings: &Settings) -> Vec<(NodeId, String, &'static str)> {
+    let paired = settings
+        .paired
+        .iter()
+        .map(|(node_id, name)| (*node_id, name.clone(), PAIRED_SOURCE));
+    let favorites = settings
+        .favorites
+        .iter()
+        .filter(|node_id| !settings.paired.contains_key(node_id))
+        .map(|node_id| {
+            let name = settings
+                .identities
+                .get(node_id)
+                .map(|identity| identity.name.clone())
+                .unwrap_or_else(|| node_id.fmt_short());
+            (*node_id, name, FAVORITE_SOURCE)
+        });
+    paired.chain(favorites).collect()
+}
+
+/// The peers of `cache` as offline nodes, handing their last addresses to `endpoint` so
+/// they can be dialed before discovery finds them.
+pub(super) fn restore_nodes(
+    cache: &PeerCache,
+    endpoint: &iroh::net::Endpoint,
+) -> BTreeMap<NodeId, RemoteNode> {
+    cache
+        .list()
+        .into_iter()
+        .map(|(node_id, peer)| {
+            if peer.relay_url.is_some() || !peer.addrs.is_empty() {
+                let addr =
+                    NodeAddr::from_parts(node_id, peer.relay_url, peer.addrs.into_iter().collect());
+                if let Err(err) = endpoint.add_node_addr(addr) {
+                    tracing::debug!("failed to restore the addresses of {node_id}: {err:#}");
+                }
+            }
+            let node = RemoteNode {
+                name: peer.name,
+                protocol_supported: true,
+                online: false,
+                last_seen: UNIX_EPOCH + Duration::from_secs(peer.last_seen),
+                capabilities: peer.capabilities,
+                device: peer.device,
+                constraints: peer.constraints,
+                sources: Default::default(),
+                handle: peer.handle,
+                handle_verified: false,
+                identity_warning: None,
+                restored: true,
+                incompatible: false,
+            };
+            (node_id, node)
+        })
+        .collect()
+}
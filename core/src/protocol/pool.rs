@@ -0,0 +1,107 @@
+//! Connections kept open to recently used peers.
+//!
+//! Connecting takes a handshake, and hole punching when the peer is behind a NAT. Peers from
+//! version 18 serve any number of requests on a connection, each on its own stream, so the
+//! connection to them is kept for the next request. Kept connections are pinged every
+//! [`KEEP_ALIVE_TICK`], which keeps the paths through NATs open, until they were not used for
+//! [`crate::settings::AdvancedSettings::idle_connection_timeout_secs`].
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use iroh::net::endpoint::Connection;
+use iroh::net::NodeId;
+
+use super::Protocol;
+
+/// How often kept connections are pinged.
+const KEEP_ALIVE_TICK: Duration = Duration::from_secs(15);
+
+/// Open connections by peer.
+#[derive(Debug, Default)]
+pub(super) struct ConnectionPool {
+    connections: Mutex<BTreeMap<NodeId, PooledConnection>>,
+}
+
+#[derive(Debug)]
+struct PooledConnection {
+    conn: Connection,
+    /// When a request was last sent on it, pings do not count.
+    last_used: Instant,
+}
+
+impl ConnectionPool {
+    /// The open connection to `node_id`, if any.
+    pub(super) fn get(&self, node_id: &NodeId) -> Option<Connection> {
+        let mut connections = self.connections.lock().unwrap();
+        let pooled = connections.get_mut(node_id)?;
+        if pooled.conn.close_reason().is_some() {
+            connections.remove(node_id);
+            return None;
+        }
+        pooled.last_used = Instant::now();
+        Some(pooled.conn.clone())
+    }
+
+    pub(super) fn insert(&self, node_id: NodeId, conn: Connection) {
+        let pooled = PooledConnection {
+            conn,
+            last_used: Instant::now(),
+        };
+        self.connections.lock().unwrap().insert(node_id, pooled);
+    }
+
+    /// Closes all connections, e.g. as the device goes invisible.
+    pub(super) fn clear(&self) {
+        let connections = std::mem::take(&mut *self.connections.lock().unwrap());
+        for pooled in connections.into_values() {
+            pooled.conn.close(0u32.into(), b"closing");
+        }
+    }
+
+    /// Closes the connections not used for `timeout`, returning the others.
+    fn expire(&self, timeout: Duration) -> Vec<(NodeId, Connection)> {
+        let mut connections = self.connections.lock().unwrap();
+        connections.retain(|node_id, pooled| {
+            if pooled.conn.close_reason().is_some() {
+                return false;
+            }
+            if pooled.last_used.elapsed() >= timeout {
+                tracing::debug!("closing idle connection to {node_id}");
+                pooled.conn.close(0u32.into(), b"idle");
+                return false;
+            }
+            true
+        });
+        connections
+            .iter()
+            .map(|(node_id, pooled)| (*node_id, pooled.conn.clone()))
+            .collect()
+    }
+}
+
+impl Protocol {
+    /// Starts the background task, pinging kept connections and closing idle ones.
+    pub fn spawn_keep_alive(self: &Arc<Self>) {
+        let this = self.clone();
+        tokio::spawn(async move {
+            let mut tick = tokio::time::interval(KEEP_ALIVE_TICK);
+            loop {
+                tick.tick().await;
+                let advanced = this.settings.get().await.advanced;
+                for (node_id, conn) in this.pool.expire(advanced.idle_connection_timeout()) {
+                    let this = this.clone();
+                    tokio::spawn(async move {
+                        let res = this
+                            .ping_over(node_id, &conn, advanced.max_frame_size)
+                            .await;
+                        if let Err(err) = res {
+                            tracing::debug!("keep-alive for {node_id} failed: {err:#}");
+                        }
+                    });
+                }
+            }
+        });
+    }
+}
@@ -0,0 +1,242 @@
+//! Swaps, where both sides queue files for each other and exchange them in one session.
+//!
+//! The initiator announces its queue with `SwapRequest`, the remote answers with its own in
+//! `SwapAccept`. Both sides then send their queues at the same time as regular offers, each
+//! counting the files of the session in either direction for a combined progress.
+
+use std::sync::Arc;
+
+use anyhow::Result;
+use futures_lite::stream::StreamExt;
+use futures_util::sink::SinkExt;
+use iroh::net::NodeId;
+
+use super::{legacy_rejection, wrap_streams, LocalProtocolMessage, Protocol, ProtocolMessage};
+use crate::error::DropError;
+use crate::history::Direction;
+use crate::metadata::FileMetadata;
+use crate::transform::OutgoingFile;
+
+/// Progress of a swap with one peer, in files.
+#[derive(Debug, Clone, Copy, Default)]
+pub(super) struct SwapSession {
+    to_send: u32,
+    to_receive: u32,
+    sent: u32,
+    received: u32,
+}
+
+impl SwapSession {
+    fn is_done(&self) -> bool {
+        self.sent >= self.to_send && self.received >= self.to_receive
+    }
+}
+
+impl Protocol {
+    /// Holds a file for the next swap with `node_id`, returning the number of queued files.
+    pub fn queue_swap(
+        &self,
+        node_id: NodeId,
+        name: String,
+        data: Vec<u8>,
+        metadata: FileMetadata,
+    ) -> usize {
+        let mut queues = self.swap_queue.lock().unwrap();
+        let queue = queues.entry(node_id).or_default();
+        queue.push(OutgoingFile {
+            name,
+            data,
+            metadata,
+        });
+        queue.len()
+    }
+
+    /// Names of the files held for the next swap with `node_id`.
+    pub fn swap_queue(&self, node_id: NodeId) -> Vec<String> {
+        self.swap_queue
+            .lock()
+            .unwrap()
+            .get(&node_id)
+            .map(|queue| queue.iter().map(|file| file.name.clone()).collect())
+            .unwrap_or_default()
+    }
+
+    pub fn clear_swap_queue(&self, node_id: NodeId) {
+        self.swap_queue.lock().unwrap().remove(&node_id);
+    }
+
+    /// Asks `node_id` for a swap, sending our queue while receiving theirs.
+    pub async fn start_swap(self: &Arc<Self>, node_id: NodeId) -> Result<()> {
+        let capabilities = self
+            .known_nodes
+            .read()
+            .await
+            .get(&node_id)
+            .map(|node| node.capabilities.clone())
+            .ok_or(DropError::UnknownNode)?;
+        if !capabilities.supports_swap() {
+            let message = "the device does not support swaps".to_string();
+            return Err(DropError::InvalidArgument(message).into());
+        }
+
+        let queue = self.take_swap_queue(node_id);
+        let files = queue.len() as u32;
+        let size = queue.iter().map(|file| file.data.len() as u64).sum();
+        let to_receive = match self.request_swap(node_id, files, size).await {
+            Ok(to_receive) => to_receive,
+            Err(err) => {
+                // Kept for the next attempt.
+                self.swap_queue
+                    .lock()
+                    .unwrap()
+                    .entry(node_id)
+                    .or_default()
+                    .splice(0..0, queue);
+                return Err(err);
+            }
+        };
+        tracing::info!("swapping {files} files for {to_receive} with {node_id}");
+        self.start_session(node_id, files, to_receive).await;
+        self.send_swap_files(node_id, queue).await
+    }
+
+    /// Announces our queue to `node_id`, returning the number of files it will send back.
+    async fn request_swap(&self, node_id: NodeId, files: u32, size: u64) -> Result<u32> {
+        let advanced = self.settings.get().await.advanced;
+        let conn = self.dial(node_id.into(), advanced.dial_timeout()).await?;
+        let (send, recv) = conn.open_bi().await?;
+        let (mut reader, mut writer) = wrap_streams(send, recv, advanced.max_frame_size);
+
+        writer
+            .send(ProtocolMessage::SwapRequest { files, size })
+            .await?;
+        writer.send(ProtocolMessage::Finish).await?;
+        let mut writer = writer.into_inner().into_inner();
+        writer.finish()?;
+
+        let response = tokio::time::timeout(advanced.offer_timeout(), reader.next())
+            .await
+            .map_err(|_| DropError::Timeout("waiting for the swap".to_string()))?;
+        match response {
+            Some(Ok(ProtocolMessage::SwapAccept { files, .. })) => Ok(files),
+            Some(Ok(ProtocolMessage::Rejected { reason })) => Err(DropError::from(reason).into()),
+            Some(Ok(ProtocolMessage::SendReject { reason })) => {
+                Err(legacy_rejection(reason).into())
+            }
+            Some(Ok(msg)) => anyhow::bail!("unexpected response: {:?}", msg),
+            Some(Err(err)) => Err(err.into()),
+            None => anyhow::bail!("remote aborted"),
+        }
+    }
+
+    /// Answers a `SwapRequest` of `node_id` and starts sending our queue to it.
+    pub(super) async fn handle_swap_request(
+        self: &Arc<Self>,
+        node_id: NodeId,
+        files: u32,
+        size: u64,
+    ) -> ProtocolMessage {
+        if let Err(reason) = self.check_offer(node_id, size, false).await {
+            tracing::info!("rejecting swap with {node_id}: {reason}");
+            return self.reject(node_id, reason).await;
+        }
+        let queue = self.take_swap_queue(node_id);
+        let (to_send, size) = (
+            queue.len() as u32,
+            queue.iter().map(|file| file.data.len() as u64).sum(),
+        );
+        tracing::info!("swapping {to_send} files for {files} with {node_id}");
+        self.start_session(node_id, to_send, files).await;
+
+        let this = self.clone();
+        tokio::spawn(async move {
+            if let Err(err) = this.send_swap_files(node_id, queue).await {
+                tracing::warn!("failed to swap with {node_id}: {err:#}");
+            }
+        });
+        ProtocolMessage::SwapAccept {
+            files: to_send,
+            size,
+        }
+    }
+
+    /// Counts a transfer with `node_id` towards its swap, if there is one.
+    pub(super) async fn swap_progress(&self, node_id: NodeId, direction: Direction) {
+        let session = {
+            let mut swaps = self.swaps.lock().unwrap();
+            let Some(session) = swaps.get_mut(&node_id) else {
+                return;
+            };
+            match direction {
+                Direction::Sent => session.sent += 1,
+                Direction::Received => session.received += 1,
+            }
+            let session = *session;
+            if session.is_done() {
+                swaps.remove(&node_id);
+            }
+            session
+        };
+        self.report_swap(node_id, session).await;
+    }
+
+    fn take_swap_queue(&self, node_id: NodeId) -> Vec<OutgoingFile> {
+        self.swap_queue
+            .lock()
+            .unwrap()
+            .remove(&node_id)
+            .unwrap_or_default()
+    }
+
+    async fn start_session(&self, node_id: NodeId, to_send: u32, to_receive: u32) {
+        let session = SwapSession {
+            to_send,
+            to_receive,
+            ..Default::default()
+        };
+        if !session.is_done() {
+            self.swaps.lock().unwrap().insert(node_id, session);
+        }
+        self.report_swap(node_id, session).await;
+    }
+
+    /// Sends the files of a swap at the same time, in the limits of the upload queue.
+    async fn send_swap_files(&self, node_id: NodeId, queue: Vec<OutgoingFile>) -> Result<()> {
+        let results = futures_util::future::join_all(queue.into_iter().map(|file| {
+            self.send_file(node_id, file.name, file.data, file.metadata)
+        }))
+        .await;
+        let failed = results.iter().filter(|res| res.is_err()).count() as u32;
+        if failed > 0 {
+            // Failed files will not be sent, so the session can still finish.
+            let session = {
+                let mut swaps = self.swaps.lock().unwrap();
+                let session = swaps.get_mut(&node_id).map(|session| {
+                    session.to_send = session.to_send.saturating_sub(failed);
+                    *session
+                });
+                if session.is_some_and(|session| session.is_done()) {
+                    swaps.remove(&node_id);
+                }
+                session
+            };
+            if let Some(session) = session {
+                self.report_swap(node_id, session).await;
+            }
+        }
+        results.into_iter().collect()
+    }
+
+    async fn report_swap(&self, node_id: NodeId, session: SwapSession) {
+        self.s
+            .send(LocalProtocolMessage::SwapProgress {
+                node_id,
+                sent: session.sent,
+                received: session.received,
+                to_send: session.to_send,
+                to_receive: session.to_receive,
+            })
+            .await
+            .ok();
+    }
+}
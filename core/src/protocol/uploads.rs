@@ -0,0 +1,140 @@
+//! Progress of uploads, which the receiver pulls from the blob provider of the node.
+//!
+//! The provider reports the requests it serves through [`UploadEvents`]. A request for the
+//! blob of a running send is attributed to that send, and its progress is reported as
+//! [`LocalProtocolMessage::UploadProgress`].
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use futures_lite::future::Boxed;
+use iroh::blobs::provider::{CustomEventSender, Event};
+use iroh::blobs::Hash;
+use iroh::net::NodeId;
+use tokio::sync::mpsc;
+
+use super::{LocalProtocolMessage, Protocol};
+use crate::history::Direction;
+use crate::ratelimit::Throughput;
+
+/// Capacity of the channel of provider events, later events are dropped while it is full.
+const EVENT_CAPACITY: usize = 256;
+
+/// Forwards the events of the blob provider to [`Protocol::spawn_upload_progress`].
+#[derive(Debug, Clone)]
+pub struct UploadEvents(mpsc::Sender<Event>);
+
+impl UploadEvents {
+    pub fn new() -> (Self, mpsc::Receiver<Event>) {
+        let (s, r) = mpsc::channel(EVENT_CAPACITY);
+        (Self(s), r)
+    }
+}
+
+impl CustomEventSender for UploadEvents {
+    fn send(&self, event: Event) -> Boxed<()> {
+        // Serving the blob never waits for the progress.
+        self.try_send(event);
+        Box::pin(async {})
+    }
+
+    fn try_send(&self, event: Event) {
+        self.0.try_send(event).ok();
+    }
+}
+
+/// A request of the provider, served for a running send.
+struct Upload {
+    transfer_id: u64,
+    node_id: NodeId,
+    size: u64,
+    offset: u64,
+    throughput: Throughput,
+}
+
+impl Protocol {
+    /// Starts the background task reporting the progress of uploads from the `events` of
+    /// the blob provider.
+    pub fn spawn_upload_progress(self: &Arc<Self>, mut events: mpsc::Receiver<Event>) {
+        let this = self.clone();
+        tokio::spawn(async move {
+            // By connection and request id
+            let mut uploads = BTreeMap::<(u64, u64), Upload>::new();
+            while let Some(event) = events.recv().await {
+                match event {
+                    Event::GetRequestReceived {
+                        connection_id,
+                        request_id,
+                        hash,
+                    } => {
+                        if let Some(upload) = this.find_upload(hash, &uploads) {
+                            uploads.insert((connection_id, request_id), upload);
+                        }
+                    }
+                    Event::TransferProgress {
+                        connection_id,
+                        request_id,
+                        end_offset,
+                        ..
+                    } => {
+                        if let Some(upload) = uploads.get_mut(&(connection_id, request_id)) {
+                            this.upload_progress(upload, end_offset);
+                        }
+                    }
+                    Event::TransferCompleted {
+                        connection_id,
+                        request_id,
+                        ..
+                    }
+                    | Event::TransferAborted {
+                        connection_id,
+                        request_id,
+                        ..
+                    } => {
+                        uploads.remove(&(connection_id, request_id));
+                    }
+                    _ => {}
+                }
+            }
+        });
+    }
+
+    /// The running send of `hash` that no other request is served for yet.
+    ///
+    /// Requests only tell the hash, so sends of the same file to several peers are told
+    /// apart by the order of their requests.
+    fn find_upload(&self, hash: Hash, uploads: &BTreeMap<(u64, u64), Upload>) -> Option<Upload> {
+        self.transfers
+            .list()
+            .into_iter()
+            .filter(|transfer| transfer.direction == Direction::Sent)
+            .filter(|transfer| transfer.hash == Some(hash))
+            .find(|transfer| !uploads.values().any(|u| u.transfer_id == transfer.id))
+            .map(|transfer| Upload {
+                transfer_id: transfer.id,
+                node_id: transfer.node_id,
+                size: transfer.size,
+                offset: 0,
+                throughput: Throughput::default(),
+            })
+    }
+
+    fn upload_progress(&self, upload: &mut Upload, end_offset: u64) {
+        let bytes = end_offset.saturating_sub(upload.offset);
+        upload.offset = upload.offset.max(end_offset);
+        upload.throughput.add(bytes as usize);
+        let path = self.connection_path(upload.node_id);
+        self.transfers.record(upload.transfer_id, bytes, path);
+        if let Some(bps) = upload.throughput.report() {
+            self.s
+                .try_send(LocalProtocolMessage::UploadProgress {
+                    id: upload.transfer_id,
+                    // Compressed files are sent smaller than announced.
+                    offset: upload.offset.min(upload.size),
+                    size: upload.size,
+                    bps,
+                })
+                .ok();
+        }
+    }
+}
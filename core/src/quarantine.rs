@@ -0,0 +1,61 @@
+use std::path::Path;
+
+use anyhow::Result;
+
+/// Extensions of files that can run code when opened.
+const EXECUTABLE_EXTENSIONS: &[&str] = &[
+    "exe", "msi", "bat", "cmd", "com", "scr", "ps1", "vbs", "js", "jar", "app", "dmg", "pkg",
+    "sh", "bash", "zsh", "command", "py", "pl", "rb", "apk", "deb", "rpm", "appimage", "run",
+    "bin", "elf", "dylib", "so", "dll",
+];
+
+/// Whether a received file needs a second confirmation before it is exported.
+pub fn is_executable(name: &str, head: &[u8]) -> bool {
+    has_executable_extension(name) || head.starts_with(b"#!") || infer::is_app(head)
+}
+
+/// Whether `name` looks like an executable or script, before its content is known.
+pub fn has_executable_extension(name: &str) -> bool {
+    Path::new(name)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| EXECUTABLE_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()))
+}
+
+/// Mark an exported file as coming from an untrusted source, so the OS asks before running it.
+#[cfg(target_os = "macos")]
+pub fn mark(path: &Path) -> Result<()> {
+    let value = format!("0081;{:x};iroh-drop;", crate::history::now());
+    let status = std::process::Command::new("xattr")
+        .arg("-w")
+        .arg("com.apple.quarantine")
+        .arg(value)
+        .arg(path)
+        .status()?;
+    anyhow::ensure!(status.success(), "xattr failed: {status}");
+    Ok(())
+}
+
+/// Mark an exported file as coming from an untrusted source, so the OS asks before running it.
+#[cfg(windows)]
+pub fn mark(path: &Path) -> Result<()> {
+    let mut stream = path.as_os_str().to_owned();
+    stream.push(":Zone.Identifier");
+    std::fs::write(stream, "[ZoneTransfer]\r\nZoneId=3\r\n")?;
+    Ok(())
+}
+
+/// Mark an exported file as coming from an untrusted source, so the OS asks before running it.
+#[cfg(not(any(target_os = "macos", windows)))]
+pub fn mark(path: &Path) -> Result<()> {
+    // No standard quarantine mechanism, make sure at least the executable bit is not set.
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        let mut permissions = std::fs::metadata(path)?.permissions();
+        permissions.set_mode(permissions.mode() & !0o111);
+        std::fs::set_permissions(path, permissions)?;
+    }
+    Ok(())
+}
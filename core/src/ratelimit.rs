@@ -0,0 +1,121 @@
+//! Bandwidth limits and throughput measurement for transfers.
+
+use std::time::{Duration, Instant};
+
+/// How often progress with the current throughput is reported.
+pub const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Token bucket limiting a transfer to a number of bytes per second.
+///
+/// Allows bursts of up to one second worth of data.
+#[derive(Debug)]
+pub struct RateLimiter {
+    bps: u64,
+    /// Bytes that can be consumed without waiting, negative if in debt.
+    budget: f64,
+    last: Instant,
+}
+
+impl RateLimiter {
+    pub fn new(bps: u64) -> Self {
+        Self {
+            bps,
+            budget: bps as f64,
+            last: Instant::now(),
+        }
+    }
+
+    /// Limiter for the lower of the given rates, `0` meaning unlimited.
+    pub fn lowest(rates: impl IntoIterator<Item = u64>) -> Option<Self> {
+        rates.into_iter().filter(|bps| *bps > 0).min().map(Self::new)
+    }
+
+    /// Takes `bytes` from the budget, waiting until they are available.
+    pub async fn consume(&mut self, bytes: usize) {
+        let now = Instant::now();
+        let refill = now.duration_since(self.last).as_secs_f64() * self.bps as f64;
+        self.budget = (self.budget + refill).min(self.bps as f64);
+        self.last = now;
+
+        self.budget -= bytes as f64;
+        if self.budget < 0. {
+            let wait = Duration::from_secs_f64(-self.budget / self.bps as f64);
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+/// Measures the throughput of a transfer, in bytes per second.
+#[derive(Debug)]
+pub struct Throughput {
+    since: Instant,
+    bytes: u64,
+}
+
+impl Default for Throughput {
+    fn default() -> Self {
+        Self {
+            since: Instant::now(),
+            bytes: 0,
+        }
+    }
+}
+
+impl Throughput {
+    pub fn add(&mut self, bytes: usize) {
+        self.bytes += bytes as u64;
+    }
+
+    /// The throughput since the last report, once [`PROGRESS_INTERVAL`] passed.
+    pub fn report(&mut self) -> Option<u64> {
+        let elapsed = self.since.elapsed();
+        if elapsed < PROGRESS_INTERVAL {
+            return None;
+        }
+        let bps = (self.bytes as f64 / elapsed.as_secs_f64()) as u64;
+        *self = Self::default();
+        Some(bps)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lowest_ignores_unlimited() {
+        assert_eq!(RateLimiter::lowest([0, 2000, 1000]).map(|l| l.bps), Some(1000));
+        assert!(RateLimiter::lowest([0, 0]).is_none());
+        assert!(RateLimiter::lowest([]).is_none());
+    }
+
+    #[tokio::test]
+    async fn consume_within_burst() {
+        let mut limiter = RateLimiter::new(1000);
+        let start = Instant::now();
+        limiter.consume(1000).await;
+        assert!(start.elapsed() < Duration::from_millis(50));
+    }
+
+    #[tokio::test]
+    async fn consume_waits_for_budget() {
+        let mut limiter = RateLimiter::new(1000);
+        limiter.consume(1000).await;
+        let start = Instant::now();
+        limiter.consume(200).await;
+        assert!(start.elapsed() >= Duration::from_millis(150));
+    }
+
+    #[test]
+    fn throughput_reports_after_interval() {
+        let mut throughput = Throughput::default();
+        throughput.add(1000);
+        assert_eq!(throughput.report(), None);
+
+        std::thread::sleep(PROGRESS_INTERVAL);
+        let bps = throughput.report().unwrap();
+        assert!(bps > 0 && bps <= 4000);
+        // Reporting starts a new measurement.
+        assert_eq!(throughput.bytes, 0);
+    }
+}
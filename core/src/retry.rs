@@ -0,0 +1,155 @@
+//! Retrying network operations with exponential backoff.
+//!
+//! Peers reported by discovery are often not reachable yet, e.g. while their relay
+//! connection is still being set up. Only failures that may go away on their own are
+//! retried, a rejection is final.
+
+use std::future::Future;
+use std::time::Duration;
+
+use anyhow::Result;
+
+use crate::error::DropError;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Backoff {
+    /// Number of attempts, including the first one
+    pub attempts: u32,
+    /// Delay before the second attempt, doubled for each following one
+    pub initial_delay: Duration,
+    pub max_delay: Duration,
+}
+
+/// For intros to newly discovered peers, giving up after about half a minute.
+pub const INTRO: Backoff = Backoff {
+    attempts: 5,
+    initial_delay: Duration::from_secs(2),
+    max_delay: Duration::from_secs(16),
+};
+
+/// For delivering a `SendRequest`, which the user is waiting for.
+pub const SEND: Backoff = Backoff {
+    attempts: 3,
+    initial_delay: Duration::from_secs(1),
+    max_delay: Duration::from_secs(4),
+};
+
+impl Backoff {
+    /// Delay before attempt `attempt`, counting from 0 for the first retry.
+    fn delay(&self, attempt: u32) -> Duration {
+        self.initial_delay
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.max_delay)
+    }
+
+    /// Runs `f` until it succeeds, fails permanently or runs out of attempts.
+    ///
+    /// Returns the error of the last attempt.
+    pub async fn retry<T, F, Fut>(&self, what: &str, mut f: F) -> Result<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let mut attempt = 0;
+        loop {
+            match f().await {
+                Ok(value) => return Ok(value),
+                Err(err) if attempt + 1 < self.attempts && is_transient(&err) => {
+                    let delay = self.delay(attempt);
+                    tracing::debug!("{what} failed, retrying in {delay:?}: {err:#}");
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+}
+
+/// Whether `err` may not happen again when retrying, like a timeout or a lost connection.
+pub fn is_transient(err: &anyhow::Error) -> bool {
+    if let Some(err) = err.downcast_ref::<DropError>() {
+        return matches!(err, DropError::ConnectionFailed(_) | DropError::Timeout(_));
+    }
+    err.is::<iroh::net::endpoint::ConnectionError>()
+        || err.is::<iroh::net::endpoint::WriteError>()
+        || err.is::<std::io::Error>()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::*;
+    use crate::security_log::RejectReason;
+
+    const FAST: Backoff = Backoff {
+        attempts: 3,
+        initial_delay: Duration::from_millis(1),
+        max_delay: Duration::from_millis(2),
+    };
+
+    #[test]
+    fn delay_doubles_up_to_max() {
+        let delays: Vec<_> = (0..5).map(|attempt| INTRO.delay(attempt).as_secs()).collect();
+        assert_eq!(delays, [2, 4, 8, 16, 16]);
+        assert_eq!(INTRO.delay(u32::MAX), INTRO.max_delay);
+    }
+
+    #[test]
+    fn transient_errors() {
+        assert!(is_transient(&DropError::Timeout("intro".to_string()).into()));
+        assert!(is_transient(&DropError::ConnectionFailed("dial".to_string()).into()));
+        assert!(is_transient(&std::io::Error::other("reset").into()));
+        assert!(!is_transient(&DropError::from(RejectReason::Paused).into()));
+        assert!(!is_transient(&anyhow::anyhow!("invalid ticket")));
+    }
+
+    #[tokio::test]
+    async fn retries_transient_errors() {
+        let calls = Cell::new(0);
+        let res = FAST
+            .retry("test", || async {
+                calls.set(calls.get() + 1);
+                if calls.get() < 3 {
+                    Err(DropError::Timeout("test".to_string()).into())
+                } else {
+                    Ok(calls.get())
+                }
+            })
+            .await;
+        assert_eq!(res.unwrap(), 3);
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_attempts() {
+        let calls = Cell::new(0);
+        let res: Result<()> = FAST
+            .retry("test", || async {
+                calls.set(calls.get() + 1);
+                Err(DropError::Timeout("test".to_string()).into())
+            })
+            .await;
+        assert!(res.is_err());
+        assert_eq!(calls.get(), FAST.attempts);
+    }
+
+    #[tokio::test]
+    async fn rejection_is_final() {
+        let calls = Cell::new(0);
+        let res: Result<()> = FAST
+            .retry("test", || async {
+                calls.set(calls.get() + 1);
+                Err(DropError::from(RejectReason::Paused).into())
+            })
+            .await;
+        assert!(matches!(
+            res.unwrap_err().downcast_ref(),
+            Some(DropError::Rejected {
+                reason: Some(RejectReason::Paused),
+                ..
+            })
+        ));
+        assert_eq!(calls.get(), 1);
+    }
+}
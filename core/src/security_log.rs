@@ -0,0 +1,117 @@
+use std::collections::VecDeque;
+
+use iroh::{blobs::Hash, net::NodeId};
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+
+use crate::history::now;
+
+/// Number of entries kept, older ones are dropped.
+const MAX_ENTRIES: usize = 500;
+
+/// Why an offer was rejected without asking the user.
+///
+/// Sent to the rejected sender, so new reasons are appended at the end.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RejectReason {
+    /// The sender never introduced itself.
+    UnknownPeer,
+    /// The file is larger than this platform accepts.
+    TooLarge,
+    /// Not enough free space to store the file.
+    LowStorage,
+    /// Receiving was paused by the user.
+    Paused,
+    /// Only trusted devices may send files.
+    NotTrusted,
+    /// Receiving is turned off.
+    ReceiveOff,
+    /// The device is on an untrusted network, only trusted devices may send files.
+    UntrustedNetwork,
+    /// The app is quitting.
+    ShuttingDown,
+    /// Executables and scripts are not accepted, see
+    /// [`crate::settings::Settings::reject_executables`].
+    Executable,
+    /// The file is larger than the user accepts without asking, see
+    /// [`crate::settings::AdvancedSettings::max_receive_size`].
+    ExceedsLimit,
+    /// Not enough free memory to download the file, see [`crate::storage::check`].
+    LowMemory,
+    /// The offer waited in the queue for longer than
+    /// [`crate::settings::AdvancedSettings::offer_ttl_secs`].
+    TimedOut,
+}
+
+impl std::fmt::Display for RejectReason {
+    /// Explanation sent to the rejected sender.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let reason = match self {
+            Self::UnknownPeer => "unknown device",
+            // The sender can not tell the limits apart anyway.
+            Self::TooLarge | Self::ExceedsLimit => "file is too large",
+            Self::LowStorage => "not enough free space",
+            Self::Paused => "receiving is paused",
+            Self::NotTrusted => "only accepting files from trusted devices",
+            Self::ReceiveOff => "not accepting files",
+            Self::UntrustedNetwork => "only accepting files from trusted devices on this network",
+            Self::ShuttingDown => "the app is quitting",
+            Self::Executable => "not accepting executables",
+            Self::LowMemory => "not enough free memory",
+            Self::TimedOut => "the offer expired",
+        };
+        f.write_str(reason)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RejectedOffer {
+    /// Seconds since the unix epoch
+    pub timestamp: u64,
+    pub node_id: NodeId,
+    /// Name of the sender, if known
+    pub sender: Option<String>,
+    pub name: String,
+    pub hash: Hash,
+    pub size: u64,
+    pub reason: RejectReason,
+}
+
+/// Log of offers that were silently rejected by the receive policies, so users can audit them.
+#[derive(Debug, Default)]
+pub struct SecurityLog {
+    entries: RwLock<VecDeque<RejectedOffer>>,
+}
+
+impl SecurityLog {
+    pub async fn record(
+        &self,
+        node_id: NodeId,
+        sender: Option<String>,
+        name: String,
+        hash: Hash,
+        size: u64,
+        reason: RejectReason,
+    ) {
+        tracing::warn!("rejected {name} ({size} bytes) from {node_id}: {reason:?}");
+        let mut entries = self.entries.write().await;
+        if entries.len() == MAX_ENTRIES {
+            entries.pop_front();
+        }
+        entries.push_back(RejectedOffer {
+            timestamp: now(),
+            node_id,
+            sender,
+            name,
+            hash,
+            size,
+            reason,
+        });
+    }
+
+    /// All entries, newest first.
+    pub async fn list(&self) -> Vec<RejectedOffer> {
+        self.entries.read().await.iter().rev().cloned().collect()
+    }
+}
@@ -0,0 +1,647 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Result;
+use iroh::net::{relay::RelayUrl, NodeId};
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+
+use crate::device_group::GroupSecret;
+use crate::error::DropError;
+use crate::identity::PinnedIdentity;
+use crate::persistence::{self, Backend};
+use crate::workflow::Workflow;
+
+/// Key the settings are persisted under.
+const SETTINGS_KEY: &str = "settings";
+
+/// Longest device name in bytes.
+pub const MAX_NAME_LEN: usize = 64;
+
+/// User configurable settings, persisted as JSON in the app config dir.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// Name shown to other devices, `None` for the default one
+    pub name: Option<String>,
+    /// Hide the window instead of quitting when it is closed, the tray icon brings it back.
+    pub close_to_tray: bool,
+    /// When started at login, stay in the tray instead of opening the window.
+    pub start_minimized: bool,
+    /// On mobile, keep accepting offers while the app is in the background.
+    pub receive_in_background: bool,
+    pub receive_mode: ReceiveMode,
+    /// Reject offers of executables and scripts, instead of quarantining them.
+    pub reject_executables: bool,
+    /// Fetch the title and image of links received as text, which tells the linked site the
+    /// address of this device.
+    pub link_previews: bool,
+    /// Only devices in the same room see each other, `None` to see everyone outside of rooms.
+    pub room: Option<String>,
+    /// Handle like `alice@example.com` this device claims to other devices, see
+    /// [`crate::handles`].
+    pub handle: Option<String>,
+    pub advanced: AdvancedSettings,
+    pub automation: AutomationSettings,
+    pub control: ControlSettings,
+    pub sync: SyncSettings,
+    pub network: NetworkSettings,
+    pub storage: StorageSettings,
+    pub export: ExportSettings,
+    pub appearance: AppearanceSettings,
+    /// Names of the transforms applied to files sent to each peer, see [`crate::transform`].
+    pub send_transforms: BTreeMap<NodeId, Vec<String>>,
+    /// Workflows for received files, see [`crate::workflow`].
+    pub workflows: Vec<Workflow>,
+    /// Devices paired by ticket or QR code, with their names when they were paired.
+    ///
+    /// They are looked up through DNS when they are not on the local network.
+    pub paired: BTreeMap<NodeId, String>,
+    /// Names first seen for each peer, see [`crate::identity`].
+    pub identities: BTreeMap<NodeId, PinnedIdentity>,
+    /// Devices pinned to the top of the device list.
+    pub favorites: BTreeSet<NodeId>,
+    /// Named groups of peers, e.g. "Team", a file can be sent to all members at once.
+    pub peer_groups: BTreeMap<String, BTreeSet<NodeId>>,
+}
+
+impl Settings {
+    pub fn validate(&self) -> Result<()> {
+        if let Some(ref name) = self.name {
+            anyhow::ensure!(
+                (1..=MAX_NAME_LEN).contains(&name.len()),
+                "device names must have between 1 and {MAX_NAME_LEN} bytes"
+            );
+        }
+        if let Some(ref room) = self.room {
+            anyhow::ensure!(
+                (1..=64).contains(&room.len()),
+                "room names must have between 1 and 64 bytes"
+            );
+        }
+        if let Some(ref handle) = self.handle {
+            handle.parse::<crate::handles::Handle>()?;
+        }
+        for name in self.send_transforms.values().flatten() {
+            anyhow::ensure!(
+                crate::transform::AVAILABLE.contains(&name.as_str()),
+                "unknown transform: {name}"
+            );
+        }
+        let mut names = BTreeSet::new();
+        for workflow in &self.workflows {
+            workflow.validate()?;
+            anyhow::ensure!(
+                names.insert(workflow.name.trim()),
+                "there are two workflows called {}",
+                workflow.name
+            );
+        }
+        self.advanced.validate()?;
+        self.network.validate()?;
+        self.storage.validate()?;
+        self.control.validate()?;
+        self.appearance.validate()?;
+        self.sync.validate()
+    }
+}
+
+/// Who may send files to this device.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ReceiveMode {
+    #[default]
+    Everyone,
+    /// Only the user's own devices, see [`SyncSettings::own_devices`].
+    TrustedOnly,
+    /// Do not disturb, all offers are rejected.
+    Off,
+}
+
+/// Timeouts and limits of the protocol, for power users.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AdvancedSettings {
+    /// How long a sent offer waits for receivers from before delivery receipts, which only
+    /// answer once they are done, in seconds.
+    pub offer_timeout_secs: u64,
+    /// Offers that did not start downloading within this are given up on, by the sender
+    /// and the receiver, in seconds.
+    pub offer_ttl_secs: u64,
+    /// How long to wait for a connection to be established, in seconds.
+    pub dial_timeout_secs: u64,
+    /// Maximum size of a single protocol message, in bytes.
+    pub max_frame_size: usize,
+    /// Maximum number of downloads running at the same time, others wait in a queue.
+    pub max_concurrent_downloads: usize,
+    /// Maximum number of uploads running at the same time, others wait in a queue.
+    pub max_concurrent_uploads: usize,
+    /// Peers that did not answer for this long are shown as offline, in seconds.
+    pub peer_timeout_secs: u64,
+    /// Before sending, wait briefly for a direct path over the local network if the peer
+    /// has one, instead of starting over a relay or a slower route.
+    pub prefer_local_transport: bool,
+    /// Upload limit in bytes per second, `0` for unlimited.
+    pub max_up_bps: u64,
+    /// Download limit in bytes per second, `0` for unlimited.
+    pub max_down_bps: u64,
+    /// Ask senders of large images to downscale them to fit into this many pixels,
+    /// `0` to always receive the original.
+    pub request_max_dimension: u32,
+    /// Compress text-like files before sending them, see [`crate::compression`].
+    pub compress: bool,
+    /// Smaller files are sent as they are, except over slow connections, in bytes.
+    pub compress_min_size: u64,
+    /// Offers of larger files are rejected unless the user accepts the sender once, in
+    /// bytes, `0` for unlimited.
+    pub max_receive_size: u64,
+    /// Connections to peers are kept open for this long after the last request, in seconds,
+    /// `0` to close them right away.
+    pub idle_connection_timeout_secs: u64,
+}
+
+impl Default for AdvancedSettings {
+    fn default() -> Self {
+        Self {
+            offer_timeout_secs: 10 * 60,
+            offer_ttl_secs: 5 * 60,
+            dial_timeout_secs: 30,
+            max_frame_size: 8 * 1024 * 1024,
+            max_concurrent_downloads: 4,
+            max_concurrent_uploads: 4,
+            peer_timeout_secs: 90,
+            prefer_local_transport: false,
+            max_up_bps: 0,
+            max_down_bps: 0,
+            request_max_dimension: 0,
+            compress: true,
+            compress_min_size: 64 * 1024,
+            max_receive_size: 0,
+            idle_connection_timeout_secs: 2 * 60,
+        }
+    }
+}
+
+impl AdvancedSettings {
+    pub fn validate(&self) -> Result<()> {
+        anyhow::ensure!(
+            (1..=24 * 60 * 60).contains(&self.offer_timeout_secs),
+            "offer timeout must be between 1 second and 24 hours"
+        );
+        anyhow::ensure!(
+            (10..=24 * 60 * 60).contains(&self.offer_ttl_secs),
+            "offers must expire after between 10 seconds and 24 hours"
+        );
+        anyhow::ensure!(
+            (1..=10 * 60).contains(&self.dial_timeout_secs),
+            "dial timeout must be between 1 second and 10 minutes"
+        );
+        anyhow::ensure!(
+            (1024..=64 * 1024 * 1024).contains(&self.max_frame_size),
+            "max frame size must be between 1KiB and 64MiB"
+        );
+        anyhow::ensure!(
+            (1..=64).contains(&self.max_concurrent_downloads),
+            "max concurrent downloads must be between 1 and 64"
+        );
+        anyhow::ensure!(
+            (1..=64).contains(&self.max_concurrent_uploads),
+            "max concurrent uploads must be between 1 and 64"
+        );
+        anyhow::ensure!(
+            (30..=24 * 60 * 60).contains(&self.peer_timeout_secs),
+            "peer timeout must be between 30 seconds and 24 hours"
+        );
+        anyhow::ensure!(
+            self.request_max_dimension == 0 || self.request_max_dimension >= 256,
+            "images can not be downscaled below 256 pixels"
+        );
+        anyhow::ensure!(
+            self.idle_connection_timeout_secs <= 60 * 60,
+            "idle connections can be kept for at most an hour"
+        );
+        Ok(())
+    }
+
+    pub fn offer_timeout(&self) -> Duration {
+        Duration::from_secs(self.offer_timeout_secs)
+    }
+
+    pub fn offer_ttl(&self) -> Duration {
+        Duration::from_secs(self.offer_ttl_secs)
+    }
+
+    pub fn dial_timeout(&self) -> Duration {
+        Duration::from_secs(self.dial_timeout_secs)
+    }
+
+    pub fn peer_timeout(&self) -> Duration {
+        Duration::from_secs(self.peer_timeout_secs)
+    }
+
+    pub fn idle_connection_timeout(&self) -> Duration {
+        Duration::from_secs(self.idle_connection_timeout_secs)
+    }
+}
+
+/// How exported files are sorted into subfolders of the export directory, see
+/// [`crate::export`].
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ExportSettings {
+    /// Into a folder per sender, named after the device, e.g. `iroh-drop/Laptop`.
+    pub by_sender: bool,
+    /// Into a folder per day the file was received, e.g. `iroh-drop/2024-10-17`.
+    pub by_date: bool,
+}
+
+/// How the windows of the app look.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AppearanceSettings {
+    pub theme: Theme,
+    /// Color of highlights like progress bars as `#rrggbb`, `None` for the default one
+    pub accent_color: Option<String>,
+    /// Less spacing and smaller text, to fit more into the window.
+    pub compact: bool,
+}
+
+impl AppearanceSettings {
+    pub fn validate(&self) -> Result<()> {
+        if let Some(ref color) = self.accent_color {
+            let hex = color.strip_prefix('#').unwrap_or_default();
+            anyhow::ensure!(
+                hex.len() == 6 && hex.chars().all(|c| c.is_ascii_hexdigit()),
+                "accent colors must look like #396cd8"
+            );
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Theme {
+    /// Light or dark, following the operating system.
+    #[default]
+    System,
+    Light,
+    Dark,
+}
+
+/// Hooks the app runs when transfers complete.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AutomationSettings {
+    pub enabled: bool,
+    /// Shell command to run for every event.
+    pub command: Option<String>,
+    /// Name of the macOS Shortcut to run for every event.
+    pub shortcut: Option<String>,
+}
+
+/// Local API scripts control the app through, a WebSocket on localhost.
+///
+/// It is never synced, every device has its own token.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ControlSettings {
+    pub enabled: bool,
+    /// Port on localhost the API listens on.
+    pub port: u16,
+    /// Clients authenticate with it, see [`ControlSettings::generate_token`].
+    pub token: String,
+}
+
+impl Default for ControlSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: 47_811,
+            token: String::new(),
+        }
+    }
+}
+
+impl ControlSettings {
+    /// Shortest token accepted, in bytes.
+    pub const MIN_TOKEN_LEN: usize = 16;
+
+    pub fn validate(&self) -> Result<()> {
+        if self.enabled {
+            anyhow::ensure!(self.port != 0, "the control API needs a port");
+            anyhow::ensure!(
+                self.token.len() >= Self::MIN_TOKEN_LEN,
+                "the control token must have at least {} bytes",
+                Self::MIN_TOKEN_LEN
+            );
+        }
+        Ok(())
+    }
+
+    /// A random token of 32 characters.
+    pub fn generate_token() -> String {
+        use base64::Engine as _;
+        base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(rand::random::<[u8; 24]>())
+    }
+}
+
+/// Syncing of settings between the user's own devices, see [`crate::sync`].
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SyncSettings {
+    /// Devices of this user, settings are only exchanged with these.
+    pub own_devices: BTreeSet<NodeId>,
+    /// Secret of the group of own devices, see [`crate::device_group`].
+    pub group: Option<GroupSecret>,
+    /// Names of the sections that are synced, e.g. `"advanced"`.
+    pub sections: BTreeSet<String>,
+    /// When each section was last changed, in milliseconds since the unix epoch.
+    pub updated_at: BTreeMap<String, u64>,
+}
+
+impl SyncSettings {
+    pub fn validate(&self) -> Result<()> {
+        for section in &self.sections {
+            anyhow::ensure!(
+                crate::sync::SYNCABLE_SECTIONS.contains(&section.as_str()),
+                "unknown settings section: {section}"
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Networks the user decided on, see [`crate::network_trust`].
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NetworkSettings {
+    /// Ids of trusted networks
+    pub trusted: BTreeSet<String>,
+    /// Ids of untrusted networks
+    pub untrusted: BTreeSet<String>,
+    /// What applies on untrusted and new networks.
+    pub untrusted_policy: UntrustedPolicy,
+    /// Which relays connect peers that can not reach each other directly, applied on the
+    /// next start.
+    pub relay: RelayMode,
+    /// URL of the own relay, for [`RelayMode::Custom`].
+    pub relay_url: Option<String>,
+}
+
+impl NetworkSettings {
+    pub fn validate(&self) -> Result<()> {
+        if self.relay == RelayMode::Custom {
+            let url = self
+                .relay_url
+                .as_deref()
+                .filter(|url| !url.trim().is_empty())
+                .ok_or_else(|| anyhow::anyhow!("a custom relay needs a URL"))?;
+            url.trim()
+                .parse::<RelayUrl>()
+                .map_err(|err| anyhow::anyhow!("invalid relay URL {url}: {err}"))?;
+        }
+        Ok(())
+    }
+}
+
+/// When blobs of finished transfers are removed from the store, see
+/// [`crate::protocol::Protocol::spawn_gc`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StorageSettings {
+    /// Blobs are kept for this many days after they were last used, `0` to keep them.
+    pub retention_days: u32,
+    /// The least recently used blobs are removed once the store is larger, in bytes, `0`
+    /// for no limit.
+    pub max_store_size: u64,
+}
+
+impl Default for StorageSettings {
+    fn default() -> Self {
+        Self {
+            retention_days: 30,
+            max_store_size: 0,
+        }
+    }
+}
+
+impl StorageSettings {
+    pub fn validate(&self) -> Result<()> {
+        anyhow::ensure!(
+            self.retention_days <= 10 * 365,
+            "blobs can not be kept for more than 10 years"
+        );
+        Ok(())
+    }
+
+    /// `None` if blobs are kept.
+    pub fn retention(&self) -> Option<Duration> {
+        (self.retention_days > 0)
+            .then(|| Duration::from_secs(u64::from(self.retention_days) * 24 * 60 * 60))
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RelayMode {
+    /// The public relays of n0.
+    #[default]
+    Default,
+    /// A single relay, see [`NetworkSettings::relay_url`].
+    Custom,
+    /// No relays, only peers on the same network are reachable.
+    Disabled,
+}
+
+/// Defaults on networks that are not trusted.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum UntrustedPolicy {
+    /// Go invisible, see [`crate::protocol::Protocol::set_invisible`].
+    #[default]
+    Invisible,
+    /// Stay visible, but only accept files from the user's own devices.
+    TrustedOnly,
+}
+
+#[derive(Debug, Default)]
+pub struct SettingsStore {
+    /// Where settings are persisted, `None` keeps them in memory only.
+    backend: Option<Arc<dyn Backend>>,
+    settings: RwLock<Settings>,
+}
+
+impl SettingsStore {
+    /// Loads the settings from `backend`, falling back to the defaults if there are none yet.
+    pub fn load(backend: Arc<dyn Backend>) -> Result<Self> {
+        let settings = match backend.load(SETTINGS_KEY)? {
+            Some(data) => match serde_json::from_slice(&data) {
+                Ok(settings) => settings,
+                Err(err) => {
+                    tracing::warn!("invalid settings: {err}");
+                    Settings::default()
+                }
+            },
+            None => Settings::default(),
+        };
+
+        Ok(Self {
+            backend: Some(backend),
+            settings: RwLock::new(settings),
+        })
+    }
+
+    pub async fn get(&self) -> Settings {
+        self.settings.read().await.clone()
+    }
+
+    /// Validates and persists `settings`.
+    ///
+    /// The own devices and their group, paired devices, pinned identities, favorites, peer
+    /// groups and sync timestamps are managed by the store, and ignored here.
+    pub async fn set(&self, mut settings: Settings) -> Result<()> {
+        settings.validate()?;
+        let mut current = self.settings.write().await;
+        settings.sync.own_devices = current.sync.own_devices.clone();
+        settings.sync.group = current.sync.group.clone();
+        settings.paired = current.paired.clone();
+        settings.identities = current.identities.clone();
+        settings.favorites = current.favorites.clone();
+        settings.peer_groups = current.peer_groups.clone();
+        settings.sync.updated_at = current.sync.updated_at.clone();
+        crate::sync::touch_changed(&current, &mut settings)?;
+        self.persist(&settings)?;
+        *current = settings;
+        Ok(())
+    }
+
+    /// Adds or removes `node_id` from the devices settings are synced with.
+    pub async fn set_own_device(&self, node_id: NodeId, own: bool) -> Result<()> {
+        let mut current = self.settings.write().await;
+        let mut settings = current.clone();
+        if own {
+            settings.sync.own_devices.insert(node_id);
+        } else {
+            settings.sync.own_devices.remove(&node_id);
+        }
+        self.persist(&settings)?;
+        *current = settings;
+        Ok(())
+    }
+
+    /// Joins the group of own devices with `secret`.
+    ///
+    /// Fails if this device is in another group, it has to [`Self::leave_group`] first.
+    pub async fn set_group(&self, secret: GroupSecret) -> Result<()> {
+        let mut current = self.settings.write().await;
+        if current.sync.group.as_ref().is_some_and(|group| *group != secret) {
+            let message = "this device is in another group of own devices".to_string();
+            return Err(DropError::InvalidArgument(message).into());
+        }
+        let mut settings = current.clone();
+        settings.sync.group = Some(secret);
+        self.persist(&settings)?;
+        *current = settings;
+        Ok(())
+    }
+
+    /// Leaves the group of own devices, devices added through it stay own devices.
+    pub async fn leave_group(&self) -> Result<()> {
+        let mut current = self.settings.write().await;
+        let mut settings = current.clone();
+        settings.sync.group = None;
+        self.persist(&settings)?;
+        *current = settings;
+        Ok(())
+    }
+
+    /// Remembers `node_id` as paired under `name`, or forgets it if `None`.
+    pub async fn set_paired(&self, node_id: NodeId, name: Option<String>) -> Result<()> {
+        let mut current = self.settings.write().await;
+        let mut settings = current.clone();
+        match name {
+            Some(name) => settings.paired.insert(node_id, name),
+            None => settings.paired.remove(&node_id),
+        };
+        self.persist(&settings)?;
+        *current = settings;
+        Ok(())
+    }
+
+    /// Pins `node_id` to the top of the device list, or unpins it.
+    pub async fn set_favorite(&self, node_id: NodeId, favorite: bool) -> Result<()> {
+        let mut current = self.settings.write().await;
+        let mut settings = current.clone();
+        if favorite {
+            settings.favorites.insert(node_id);
+        } else {
+            settings.favorites.remove(&node_id);
+        }
+        self.persist(&settings)?;
+        *current = settings;
+        Ok(())
+    }
+
+    /// Replaces the members of the peer group `name`, no members remove the group.
+    pub async fn set_peer_group(&self, name: &str, members: BTreeSet<NodeId>) -> Result<()> {
+        let name = name.trim();
+        anyhow::ensure!(
+            (1..=64).contains(&name.len()),
+            "group names must have between 1 and 64 bytes"
+        );
+        let mut current = self.settings.write().await;
+        let mut settings = current.clone();
+        if members.is_empty() {
+            settings.peer_groups.remove(name);
+        } else {
+            settings.peer_groups.insert(name.to_string(), members);
+        }
+        self.persist(&settings)?;
+        *current = settings;
+        Ok(())
+    }
+
+    /// Pins `identity` for `node_id`, replacing the one pinned before.
+    pub async fn set_identity(&self, node_id: NodeId, identity: PinnedIdentity) -> Result<()> {
+        let mut current = self.settings.write().await;
+        let mut settings = current.clone();
+        settings.identities.insert(node_id, identity);
+        self.persist(&settings)?;
+        *current = settings;
+        Ok(())
+    }
+
+    /// Replaces all settings, including those managed by the store, e.g. with an imported
+    /// [`crate::profile::Profile`].
+    pub async fn replace(&self, settings: Settings) -> Result<()> {
+        settings.validate()?;
+        let mut current = self.settings.write().await;
+        self.persist(&settings)?;
+        *current = settings;
+        Ok(())
+    }
+
+    /// Merges sections synced from another device, returning the new settings if any changed.
+    pub async fn merge_synced(
+        &self,
+        sections: Vec<crate::sync::SyncedSection>,
+    ) -> Result<Option<Settings>> {
+        let mut current = self.settings.write().await;
+        let mut settings = current.clone();
+        if !crate::sync::merge(&mut settings, sections)? {
+            return Ok(None);
+        }
+        self.persist(&settings)?;
+        *current = settings.clone();
+        Ok(Some(settings))
+    }
+
+    fn persist(&self, settings: &Settings) -> Result<()> {
+        if let Some(ref backend) = self.backend {
+            persistence::save(backend.as_ref(), SETTINGS_KEY, settings)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,182 @@
+//! Share links, handing a file to a device without iroh-drop through its browser.
+//!
+//! A small HTTP server on the local network serves blobs of the store under random
+//! tokens. Each link works for a single download and expires after its time to live.
+//! The server only starts once the first link is created.
+
+use std::collections::BTreeMap;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use base64::Engine as _;
+use iroh::blobs::Hash;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+
+/// Longest a share link can be valid.
+pub const MAX_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Longest request head that is read, the server only needs the request line.
+const MAX_REQUEST_LEN: usize = 8 * 1024;
+
+#[derive(Debug, Clone)]
+struct ShareLink {
+    hash: Hash,
+    name: String,
+    size: u64,
+    expires: Instant,
+}
+
+/// The HTTP server and the links it serves.
+#[derive(Debug)]
+pub struct ShareServer {
+    addr: SocketAddr,
+    links: Mutex<BTreeMap<String, ShareLink>>,
+}
+
+impl ShareServer {
+    /// Binds to a random port on all interfaces and starts serving.
+    pub async fn start(client: iroh::client::Iroh) -> Result<Arc<Self>> {
+        let listener = TcpListener::bind((Ipv4Addr::UNSPECIFIED, 0)).await?;
+        let server = Arc::new(Self {
+            addr: listener.local_addr()?,
+            links: Default::default(),
+        });
+        tracing::info!("serving share links on port {}", server.addr.port());
+
+        let this = server.clone();
+        tokio::spawn(async move {
+            loop {
+                let (stream, remote) = match listener.accept().await {
+                    Ok(accepted) => accepted,
+                    Err(err) => {
+                        tracing::warn!("failed to accept a share link request: {err}");
+                        continue;
+                    }
+                };
+                let this = this.clone();
+                let client = client.clone();
+                tokio::spawn(async move {
+                    if let Err(err) = this.handle(stream, &client).await {
+                        tracing::debug!("failed to serve {remote}: {err:#}");
+                    }
+                });
+            }
+        });
+        Ok(server)
+    }
+
+    /// Adds a link to the blob `hash`, returning its URL for the device address `ip`.
+    pub fn add(&self, ip: IpAddr, hash: Hash, name: String, size: u64, ttl: Duration) -> String {
+        let token = base64::engine::general_purpose::URL_SAFE_NO_PAD
+            .encode(rand::random::<[u8; 16]>());
+        let link = ShareLink {
+            hash,
+            name,
+            size,
+            expires: Instant::now() + ttl.min(MAX_TTL),
+        };
+        let mut links = self.links.lock().unwrap();
+        links.retain(|_, link| link.expires > Instant::now());
+        links.insert(token.clone(), link);
+        format!("http://{}/s/{token}", SocketAddr::new(ip, self.addr.port()))
+    }
+
+    /// Takes the link `token`, so it can not be used again.
+    fn take(&self, token: &str) -> Option<ShareLink> {
+        self.links
+            .lock()
+            .unwrap()
+            .remove(token)
+            .filter(|link| link.expires > Instant::now())
+    }
+
+    async fn handle(&self, stream: TcpStream, client: &iroh::client::Iroh) -> Result<()> {
+        let mut stream = BufReader::new(stream);
+        let mut request_line = String::new();
+        (&mut stream)
+            .take(MAX_REQUEST_LEN as u64)
+            .read_line(&mut request_line)
+            .await?;
+        // `GET /s/<token> HTTP/1.1`
+        let mut parts = request_line.split_whitespace();
+        let (method, path) = (parts.next(), parts.next());
+        let link = match (method, path.and_then(|path| path.strip_prefix("/s/"))) {
+            (Some("GET"), Some(token)) => self.take(token),
+            _ => None,
+        };
+        let mut stream = stream.into_inner();
+        let Some(link) = link else {
+            let body = "This link expired or was already used.";
+            let response = format!(
+                "HTTP/1.1 410 Gone\r\nContent-Type: text/plain\r\nContent-Length: {}\r\n\
+                 Connection: close\r\n\r\n{body}",
+                body.len()
+            );
+            stream.write_all(response.as_bytes()).await?;
+            return Ok(());
+        };
+
+        tracing::info!("serving {} through a share link", link.name);
+        let name = link.name.replace(['"', '\\', '\r', '\n'], "_");
+        let head = format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: application/octet-stream\r\nContent-Length: {}\r\n\
+             Content-Disposition: attachment; filename=\"{name}\"\r\nConnection: close\r\n\r\n",
+            link.size
+        );
+        stream.write_all(head.as_bytes()).await?;
+        let mut reader = client.blobs().read(link.hash).await?;
+        tokio::io::copy(&mut reader, &mut stream).await?;
+        stream.shutdown().await?;
+        Ok(())
+    }
+}
+
+/// The address other devices on the local network reach this one at, out of `ips`.
+///
+/// Private IPv4 addresses are preferred, browsers handle them best.
+pub fn pick_ip(ips: impl IntoIterator<Item = IpAddr>) -> Option<IpAddr> {
+    ips.into_iter()
+        .filter_map(|ip| match ip {
+            IpAddr::V4(ip) if !ip.is_loopback() && !ip.is_unspecified() => Some(ip),
+            _ => None,
+        })
+        .min_by_key(|ip| !ip.is_private())
+        .map(IpAddr::V4)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv6Addr;
+
+    use super::*;
+
+    #[test]
+    fn pick_ip_prefers_private_ipv4() {
+        let ips = [
+            IpAddr::V4(Ipv4Addr::LOCALHOST),
+            IpAddr::V6(Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 1)),
+            IpAddr::V4(Ipv4Addr::new(203, 0, 113, 7)),
+            IpAddr::V4(Ipv4Addr::new(192, 168, 1, 20)),
+        ];
+        assert_eq!(pick_ip(ips), Some(IpAddr::V4(Ipv4Addr::new(192, 168, 1, 20))));
+    }
+
+    #[test]
+    fn pick_ip_falls_back_to_public_ipv4() {
+        let ips = [
+            IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            IpAddr::V4(Ipv4Addr::new(203, 0, 113, 7)),
+        ];
+        assert_eq!(pick_ip(ips), Some(IpAddr::V4(Ipv4Addr::new(203, 0, 113, 7))));
+    }
+
+    #[test]
+    fn pick_ip_without_usable_address() {
+        let ips = [IpAddr::V4(Ipv4Addr::LOCALHOST), IpAddr::V6(Ipv6Addr::LOCALHOST)];
+        assert_eq!(pick_ip(ips), None);
+        assert_eq!(pick_ip([]), None);
+    }
+}
@@ -0,0 +1,62 @@
+use std::path::Path;
+
+/// Number of bytes read from the start of a blob to detect its type.
+pub const SNIFF_LEN: usize = 8192;
+
+/// Extensions that are stored as zip archives.
+const ZIP_CONTAINERS: &[&str] = &[
+    "zip", "docx", "xlsx", "pptx", "odt", "ods", "odp", "epub", "jar", "apk", "ipa",
+];
+
+/// Compare the type advertised by the file name with the type detected from its content.
+///
+/// Returns a warning if they disagree. Content that can not be detected (e.g. plain text)
+/// is never flagged.
+pub fn check(name: &str, head: &[u8]) -> Option<String> {
+    let sniffed = infer::get(head)?;
+    let advertised = extension(name);
+
+    if infer::is_app(head) && !is_app_extension(advertised.as_deref()) {
+        return Some(format!(
+            "\"{name}\" is an executable ({}), do not open it unless you trust the sender",
+            sniffed.mime_type()
+        ));
+    }
+
+    let advertised = advertised?;
+    if same_type(&advertised, sniffed.extension()) {
+        return None;
+    }
+
+    Some(format!(
+        "\"{name}\" claims to be .{advertised} but looks like {}",
+        sniffed.mime_type()
+    ))
+}
+
+fn extension(name: &str) -> Option<String> {
+    Path::new(name)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.to_ascii_lowercase())
+}
+
+fn is_app_extension(ext: Option<&str>) -> bool {
+    matches!(
+        ext,
+        Some("exe" | "dll" | "msi" | "elf" | "so" | "dylib" | "app" | "bin" | "class" | "dex" | "wasm")
+    )
+}
+
+fn same_type(advertised: &str, sniffed: &str) -> bool {
+    if advertised == sniffed {
+        return true;
+    }
+    if sniffed == "zip" && ZIP_CONTAINERS.contains(&advertised) {
+        return true;
+    }
+    matches!(
+        (advertised, sniffed),
+        ("jpeg" | "jpe", "jpg") | ("tiff", "tif") | ("tif", "tiff") | ("mpeg", "mpg") | ("htm", "html")
+    )
+}
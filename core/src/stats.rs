@@ -0,0 +1,101 @@
+use std::collections::BTreeMap;
+
+use iroh::net::NodeId;
+use serde::Serialize;
+
+use crate::history::{ConnectionPath, Direction, HistoryEntry};
+use crate::protocol::PeerInfo;
+
+const SECS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Aggregated statistics over the transfer history.
+#[derive(Debug, Default, Serialize)]
+pub struct DropStats {
+    pub files_sent: u64,
+    pub files_received: u64,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    /// Best observed download speed, in bytes per second
+    pub best_throughput: Option<u64>,
+    /// Number of transfers over a direct connection
+    pub direct: u64,
+    /// Number of direct transfers over the local network, included in `direct`
+    pub local: u64,
+    /// Number of transfers over a relay
+    pub relay: u64,
+    pub per_peer: Vec<PeerStats>,
+    pub per_day: Vec<DayStats>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PeerStats {
+    pub node_id: NodeId,
+    pub name: Option<String>,
+    pub files: u64,
+    pub bytes: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DayStats {
+    /// Days since the unix epoch
+    pub day: u64,
+    pub files: u64,
+    pub bytes: u64,
+}
+
+pub fn compute(entries: &[HistoryEntry], peers: &[PeerInfo]) -> DropStats {
+    let mut stats = DropStats::default();
+    let mut per_peer = BTreeMap::<NodeId, (u64, u64)>::new();
+    let mut per_day = BTreeMap::<u64, (u64, u64)>::new();
+
+    for entry in entries {
+        match entry.direction {
+            Direction::Sent => {
+                stats.files_sent += 1;
+                stats.bytes_sent += entry.size;
+            }
+            Direction::Received => {
+                stats.files_received += 1;
+                stats.bytes_received += entry.size;
+            }
+        }
+        match entry.path {
+            ConnectionPath::Local => {
+                stats.direct += 1;
+                stats.local += 1;
+            }
+            ConnectionPath::Direct => stats.direct += 1,
+            ConnectionPath::Relay => stats.relay += 1,
+            ConnectionPath::Unknown => {}
+        }
+        if let Some(throughput) = entry.throughput() {
+            stats.best_throughput = stats.best_throughput.max(Some(throughput));
+        }
+
+        let peer = per_peer.entry(entry.node_id).or_default();
+        peer.0 += 1;
+        peer.1 += entry.size;
+        let day = per_day.entry(entry.timestamp / SECS_PER_DAY).or_default();
+        day.0 += 1;
+        day.1 += entry.size;
+    }
+
+    stats.per_peer = per_peer
+        .into_iter()
+        .map(|(node_id, (files, bytes))| PeerStats {
+            node_id,
+            name: peers
+                .iter()
+                .find(|p| p.node_id == node_id)
+                .map(|p| p.name.clone()),
+            files,
+            bytes,
+        })
+        .collect();
+    stats.per_day = per_day
+        .into_iter()
+        .map(|(day, (files, bytes))| DayStats { day, files, bytes })
+        .collect();
+
+    stats
+}
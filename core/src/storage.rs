@@ -0,0 +1,80 @@
+//! Checks run before accepting an offer.
+//!
+//! Downloads are streamed into the in-memory blob store, so a file needs free memory for
+//! one copy. The app scoped directory holds the encrypted copy, if the encrypted storage
+//! is set up, and usually shares its disk with the export directory.
+
+use std::path::Path;
+
+use crate::security_log::RejectReason;
+
+/// Space that is left free for the rest of the system.
+pub const MIN_FREE_SPACE: u64 = 256 * 1024 * 1024;
+
+/// Memory that is left free for the rest of the system.
+pub const MIN_FREE_MEMORY: u64 = 256 * 1024 * 1024;
+
+/// Largest file accepted, mobile platforms keep received data in a constrained sandbox.
+#[cfg(any(target_os = "android", target_os = "ios"))]
+pub const MAX_FILE_SIZE: Option<u64> = Some(2 * 1024 * 1024 * 1024);
+#[cfg(not(any(target_os = "android", target_os = "ios")))]
+pub const MAX_FILE_SIZE: Option<u64> = None;
+
+/// Checks that a file of `size` bytes can be received into memory and `dir`, next to the
+/// `reserved` bytes that running downloads still need.
+pub fn check(dir: &Path, size: u64, reserved: u64) -> Result<(), RejectReason> {
+    if MAX_FILE_SIZE.is_some_and(|max| size > max) {
+        return Err(RejectReason::TooLarge);
+    }
+    let needed = size.saturating_add(reserved);
+    if let Some(available) = available_memory() {
+        if available < needed.saturating_add(MIN_FREE_MEMORY) {
+            tracing::info!(
+                "{size} bytes do not fit into {available} bytes of memory, {reserved} reserved"
+            );
+            return Err(RejectReason::LowMemory);
+        }
+    }
+    match available_space(dir) {
+        Some(available) if available < needed.saturating_add(MIN_FREE_SPACE) => {
+            tracing::info!("{size} bytes do not fit into {available} bytes, {reserved} reserved");
+            Err(RejectReason::LowStorage)
+        }
+        _ => Ok(()),
+    }
+}
+
+/// The largest file that can be received next to the `reserved` bytes of running
+/// downloads, `None` if there is no limit or it is unknown.
+pub fn max_file_size(dir: &Path, reserved: u64) -> Option<u64> {
+    let memory = available_memory()
+        .map(|available| available.saturating_sub(MIN_FREE_MEMORY.saturating_add(reserved)));
+    let space = available_space(dir)
+        .map(|available| available.saturating_sub(MIN_FREE_SPACE.saturating_add(reserved)));
+    [MAX_FILE_SIZE, memory, space].into_iter().flatten().min()
+}
+
+/// Free memory, `None` on platforms where we can't tell.
+fn available_memory() -> Option<u64> {
+    if !sysinfo::IS_SUPPORTED_SYSTEM {
+        return None;
+    }
+    let mut system = sysinfo::System::new();
+    system.refresh_memory();
+    match system.available_memory() {
+        0 => None,
+        available => Some(available),
+    }
+}
+
+/// Free space of the disk of `dir`, `None` if it can't be read.
+fn available_space(dir: &Path) -> Option<u64> {
+    match fs4::available_space(dir) {
+        Ok(available) => Some(available),
+        Err(err) => {
+            // Don't block transfers on platforms where we can't tell.
+            tracing::warn!("failed to read free space of {}: {err}", dir.display());
+            None
+        }
+    }
+}
@@ -0,0 +1,135 @@
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use iroh::net::{
+    endpoint::{ConnectionType, RemoteInfo},
+    NodeId,
+};
+use tokio::sync::Notify;
+
+/// Round trip time above which a relayed connection is considered slow.
+pub(crate) const HIGH_RTT: Duration = Duration::from_millis(150);
+
+/// Parameters used for a single transfer, picked based on the connection to the peer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransferStrategy {
+    /// Files sent to the peer at the same time.
+    pub max_concurrent_files: usize,
+    /// Compress small files too, if compression is enabled.
+    pub compress: bool,
+    /// Warning to show to the user, if the connection is poor.
+    pub warning: Option<String>,
+}
+
+impl TransferStrategy {
+    /// Direct connection, no reason to hold back.
+    pub fn direct() -> Self {
+        Self {
+            max_concurrent_files: 16,
+            compress: false,
+            warning: None,
+        }
+    }
+
+    /// Relayed connection with acceptable latency.
+    pub fn relay() -> Self {
+        Self {
+            max_concurrent_files: 8,
+            compress: false,
+            warning: None,
+        }
+    }
+
+    /// Relayed connection with high latency.
+    pub fn slow_relay(rtt: Duration) -> Self {
+        Self {
+            max_concurrent_files: 2,
+            compress: true,
+            warning: Some(format!(
+                "Peer is only reachable via relay ({}ms), transfer might be slow",
+                rtt.as_millis()
+            )),
+        }
+    }
+
+    /// Select a strategy based on the current state of the connection to `node_id`.
+    pub fn select(node_id: NodeId, info: Option<&RemoteInfo>) -> Self {
+        let Some(info) = info else {
+            tracing::info!("no connection info for {node_id}, assuming relay");
+            return Self::relay();
+        };
+
+        match info.conn_type {
+            ConnectionType::Direct(_) | ConnectionType::Mixed(..) => Self::direct(),
+            ConnectionType::Relay(_) | ConnectionType::None => match info.latency {
+                Some(rtt) if rtt > HIGH_RTT => Self::slow_relay(rtt),
+                _ => Self::relay(),
+            },
+        }
+    }
+}
+
+/// Files being sent to each peer, so a poor connection is not flooded with requests.
+#[derive(Debug, Default)]
+pub struct SendSlots {
+    sending: Mutex<HashMap<NodeId, usize>>,
+    freed: Notify,
+}
+
+impl SendSlots {
+    /// Waits until less than `limit` files are sent to `node_id`, and takes a slot.
+    pub async fn acquire(self: &Arc<Self>, node_id: NodeId, limit: usize) -> SendSlot {
+        loop {
+            let freed = self.freed.notified();
+            tokio::pin!(freed);
+            freed.as_mut().enable();
+            {
+                let mut sending = self.sending.lock().unwrap();
+                let count = sending.entry(node_id).or_default();
+                if *count < limit.max(1) {
+                    *count += 1;
+                    return SendSlot {
+                        slots: self.clone(),
+                        node_id,
+                    };
+                }
+            }
+            freed.await;
+        }
+    }
+}
+
+/// A file being sent, frees its slot when dropped.
+#[derive(Debug)]
+pub struct SendSlot {
+    slots: Arc<SendSlots>,
+    node_id: NodeId,
+}
+
+impl Drop for SendSlot {
+    fn drop(&mut self) {
+        let mut sending = self.slots.sending.lock().unwrap();
+        if let Some(count) = sending.get_mut(&self.node_id) {
+            *count -= 1;
+            if *count == 0 {
+                sending.remove(&self.node_id);
+            }
+        }
+        self.slots.freed.notify_waiters();
+    }
+}
+
+/// Whether `addr` is on the local network, e.g. a LAN or link-local IPv6 address as used by
+/// Wi-Fi Direct and AWDL.
+pub fn is_local_addr(addr: &SocketAddr) -> bool {
+    match addr.ip() {
+        IpAddr::V4(ip) => ip.is_private() || ip.is_link_local(),
+        IpAddr::V6(ip) => {
+            let first = ip.segments()[0];
+            // fe80::/10 link-local, fc00::/7 unique local
+            first & 0xffc0 == 0xfe80 || first & 0xfe00 == 0xfc00
+        }
+    }
+}
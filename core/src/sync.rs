@@ -0,0 +1,180 @@
+//! Syncing of selected settings sections between the user's own devices.
+//!
+//! Every section carries the time it was last changed locally, and the most
+//! recent change wins when devices exchange their sections.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::settings::Settings;
+
+/// Sections of [`Settings`] that can be synced, by their serialized name.
+///
+/// The automation section stays on each device, its command is run for every received file.
+pub const SYNCABLE_SECTIONS: &[&str] = &["advanced", "appearance"];
+
+/// A settings section as sent to other devices.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyncedSection {
+    pub key: String,
+    /// Milliseconds since the unix epoch
+    pub updated_at: u64,
+    /// The section, encoded as JSON
+    pub value: String,
+}
+
+/// Updates the timestamps of all sections that differ between `old` and `new`.
+pub fn touch_changed(old: &Settings, new: &mut Settings) -> Result<()> {
+    let old_value = serde_json::to_value(old)?;
+    let new_value = serde_json::to_value(&*new)?;
+    let now = now_ms();
+    for key in SYNCABLE_SECTIONS {
+        if old_value.get(key) != new_value.get(key) {
+            new.sync.updated_at.insert(key.to_string(), now);
+        }
+    }
+    Ok(())
+}
+
+/// The sections of `settings` that are enabled for syncing.
+pub fn export(settings: &Settings) -> Result<Vec<SyncedSection>> {
+    let value = serde_json::to_value(settings)?;
+    settings
+        .sync
+        .sections
+        .iter()
+        .filter(|key| SYNCABLE_SECTIONS.contains(&key.as_str()))
+        .filter_map(|key| Some((key, value.get(key)?)))
+        .map(|(key, section)| {
+            Ok(SyncedSection {
+                key: key.clone(),
+                updated_at: settings.sync.updated_at.get(key).copied().unwrap_or_default(),
+                value: serde_json::to_string(section)?,
+            })
+        })
+        .collect()
+}
+
+/// Applies the `sections` that are synced and newer than ours to `settings`.
+///
+/// Returns whether anything changed.
+pub fn merge(settings: &mut Settings, sections: Vec<SyncedSection>) -> Result<bool> {
+    let mut value = serde_json::to_value(&*settings)?;
+    let mut updated_at = settings.sync.updated_at.clone();
+    let mut changed = false;
+    // A change can not be from the future, or it would win over every later one.
+    let now = now_ms();
+    for section in sections {
+        // The key comes from the remote, only ever touch sections we allow.
+        if !SYNCABLE_SECTIONS.contains(&section.key.as_str())
+            || !settings.sync.sections.contains(&section.key)
+        {
+            continue;
+        }
+        let local = updated_at.get(&section.key).copied().unwrap_or_default();
+        let remote = section.updated_at.min(now);
+        if remote <= local {
+            continue;
+        }
+        value[&section.key] = serde_json::from_str(&section.value)?;
+        updated_at.insert(section.key, remote);
+        changed = true;
+    }
+    if !changed {
+        return Ok(false);
+    }
+
+    let mut merged: Settings = serde_json::from_value(value)?;
+    merged.sync.updated_at = updated_at;
+    merged.validate()?;
+    *settings = merged;
+    Ok(true)
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::AdvancedSettings;
+
+    fn synced(settings: &Settings, key: &str, updated_at: u64) -> SyncedSection {
+        let value = serde_json::to_value(settings).unwrap();
+        SyncedSection {
+            key: key.to_string(),
+            updated_at,
+            value: value[key].to_string(),
+        }
+    }
+
+    fn syncing_advanced() -> Settings {
+        let mut settings = Settings::default();
+        settings.sync.sections.insert("advanced".to_string());
+        settings
+    }
+
+    #[test]
+    fn merge_newer_section() {
+        let mut local = syncing_advanced();
+        local.sync.updated_at.insert("advanced".to_string(), 10);
+        let mut remote = local.clone();
+        remote.advanced.dial_timeout_secs = 5;
+
+        let changed = merge(&mut local, vec![synced(&remote, "advanced", 20)]).unwrap();
+        assert!(changed);
+        assert_eq!(local.advanced.dial_timeout_secs, 5);
+        assert_eq!(local.sync.updated_at["advanced"], 20);
+    }
+
+    #[test]
+    fn merge_keeps_newer_local_section() {
+        let mut local = syncing_advanced();
+        local.sync.updated_at.insert("advanced".to_string(), 20);
+        let mut remote = local.clone();
+        remote.advanced.dial_timeout_secs = 5;
+
+        let changed = merge(&mut local, vec![synced(&remote, "advanced", 20)]).unwrap();
+        assert!(!changed);
+        assert_eq!(local.advanced, AdvancedSettings::default());
+    }
+
+    #[test]
+    fn merge_ignores_sections_not_synced() {
+        let mut local = Settings::default();
+        let mut remote = local.clone();
+        remote.advanced.dial_timeout_secs = 5;
+        remote.automation.command = Some("rm -rf ~".to_string());
+
+        let sections = vec![synced(&remote, "advanced", 20), synced(&remote, "automation", 20)];
+        let changed = merge(&mut local, sections).unwrap();
+        assert!(!changed);
+        assert_eq!(local, Settings::default());
+    }
+
+    #[test]
+    fn merge_clamps_future_timestamps() {
+        let mut local = syncing_advanced();
+        let mut remote = local.clone();
+        remote.advanced.dial_timeout_secs = 5;
+
+        assert!(merge(&mut local, vec![synced(&remote, "advanced", u64::MAX)]).unwrap());
+        assert!(local.sync.updated_at["advanced"] <= now_ms());
+    }
+
+    #[test]
+    fn merge_rejects_invalid_section() {
+        let mut local = syncing_advanced();
+        let mut remote = local.clone();
+        remote.advanced.dial_timeout_secs = 0;
+
+        assert!(merge(&mut local, vec![synced(&remote, "advanced", 20)]).is_err());
+        assert_eq!(local.advanced, AdvancedSettings::default());
+    }
+}
@@ -0,0 +1,427 @@
+//! Queue of uploads and downloads, limiting how many of them run at the same time.
+//!
+//! The manager also keeps metrics of running and recently finished transfers, see
+//! [`TransferManager::stats`].
+
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use anyhow::Result;
+use iroh::{blobs::Hash, net::NodeId};
+use serde::Serialize;
+use tokio::sync::{mpsc, watch, OwnedSemaphorePermit, Semaphore};
+
+use crate::error::DropError;
+use crate::history::{now, ConnectionPath, Direction};
+use crate::protocol::LocalProtocolMessage;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TransferState {
+    /// Waiting for other transfers to finish
+    Queued,
+    Active,
+    /// Paused by the user, keeping what was downloaded so far
+    Paused,
+}
+
+/// A transfer that is waiting or running.
+#[derive(Debug, Clone, Serialize)]
+pub struct Transfer {
+    pub id: u64,
+    pub direction: Direction,
+    /// The other side of the transfer
+    pub node_id: NodeId,
+    pub name: String,
+    /// Known once the file was imported, for uploads
+    pub hash: Option<Hash>,
+    pub size: u64,
+    pub state: TransferState,
+    /// Seconds since the unix epoch
+    pub queued_at: u64,
+}
+
+/// Number of finished transfers whose metrics are kept.
+const MAX_FINISHED_STATS: usize = 50;
+
+/// Number of per-second samples kept per transfer, later bytes count to the last one.
+const MAX_SAMPLES: usize = 60 * 60;
+
+/// Metrics of a running or finished transfer.
+#[derive(Debug, Clone, Serialize)]
+pub struct TransferStats {
+    pub id: u64,
+    pub direction: Direction,
+    pub node_id: NodeId,
+    pub name: String,
+    pub size: u64,
+    /// Seconds since the unix epoch the transfer started, after waiting in the queue
+    pub started_at: u64,
+    /// Time spent transferring so far, or in total once finished
+    pub duration_ms: u64,
+    /// Bytes transferred in each second since the start.
+    ///
+    /// Empty for uploads the blob provider reported no progress for.
+    pub samples: Vec<u64>,
+    /// Bytes over a direct connection, including the local network
+    pub direct_bytes: u64,
+    pub relay_bytes: u64,
+    pub finished: bool,
+}
+
+/// Metrics collected while a transfer is active.
+#[derive(Debug)]
+struct Metrics {
+    started: Instant,
+    stats: TransferStats,
+}
+
+impl Metrics {
+    fn new(transfer: &Transfer) -> Self {
+        Self {
+            started: Instant::now(),
+            stats: TransferStats {
+                id: transfer.id,
+                direction: transfer.direction,
+                node_id: transfer.node_id,
+                name: transfer.name.clone(),
+                size: transfer.size,
+                started_at: now(),
+                duration_ms: 0,
+                samples: Vec::new(),
+                direct_bytes: 0,
+                relay_bytes: 0,
+                finished: false,
+            },
+        }
+    }
+
+    fn add(&mut self, bytes: u64, path: ConnectionPath, sampled: bool) {
+        match path {
+            ConnectionPath::Local | ConnectionPath::Direct => self.stats.direct_bytes += bytes,
+            ConnectionPath::Relay => self.stats.relay_bytes += bytes,
+            ConnectionPath::Unknown => {}
+        }
+        if sampled {
+            let second = (self.started.elapsed().as_secs() as usize).min(MAX_SAMPLES - 1);
+            if self.stats.samples.len() <= second {
+                self.stats.samples.resize(second + 1, 0);
+            }
+            self.stats.samples[second] += bytes;
+        }
+    }
+
+    fn snapshot(&self) -> TransferStats {
+        TransferStats {
+            duration_ms: self.started.elapsed().as_millis() as u64,
+            ..self.stats.clone()
+        }
+    }
+}
+
+/// Limits the transfers in one direction, waiting transfers are started in FIFO order.
+#[derive(Debug)]
+struct Limit {
+    semaphore: Arc<Semaphore>,
+    /// Current number of permits in `semaphore`.
+    limit: AtomicUsize,
+}
+
+impl Limit {
+    fn new() -> Self {
+        Self {
+            semaphore: Arc::new(Semaphore::new(0)),
+            limit: AtomicUsize::new(0),
+        }
+    }
+
+    fn resize(&self, limit: usize) {
+        let previous = self.limit.swap(limit, Ordering::SeqCst);
+        if limit > previous {
+            self.semaphore.add_permits(limit - previous);
+        } else if limit < previous {
+            let excess = previous - limit;
+            let forgotten = self.semaphore.forget_permits(excess);
+            if forgotten < excess {
+                // The rest is held by running transfers, take them once they are released.
+                let semaphore = self.semaphore.clone();
+                tokio::spawn(async move {
+                    if let Ok(permits) = semaphore.acquire_many_owned((excess - forgotten) as u32).await {
+                        permits.forget();
+                    }
+                });
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct TransferManager {
+    next_id: AtomicU64,
+    transfers: Mutex<BTreeMap<u64, Transfer>>,
+    /// Metrics of the active transfers
+    metrics: Mutex<BTreeMap<u64, Metrics>>,
+    /// Metrics of the last finished transfers, the oldest first
+    finished: Mutex<VecDeque<TransferStats>>,
+    /// Ids of the paused downloads
+    paused: watch::Sender<BTreeSet<u64>>,
+    downloads: Limit,
+    uploads: Limit,
+    s: mpsc::Sender<LocalProtocolMessage>,
+}
+
+impl TransferManager {
+    /// Creates a manager without any permits, see [`Self::set_limits`].
+    pub fn new(s: mpsc::Sender<LocalProtocolMessage>) -> Self {
+        Self {
+            next_id: AtomicU64::new(0),
+            transfers: Default::default(),
+            metrics: Default::default(),
+            finished: Default::default(),
+            paused: watch::channel(BTreeSet::new()).0,
+            downloads: Limit::new(),
+            uploads: Limit::new(),
+            s,
+        }
+    }
+
+    /// Sets how many downloads and uploads may run at the same time.
+    pub fn set_limits(&self, downloads: usize, uploads: usize) {
+        self.downloads.resize(downloads);
+        self.uploads.resize(uploads);
+    }
+
+    /// Queues a transfer and waits until it may start.
+    ///
+    /// The transfer is active until the returned permit is dropped.
+    pub async fn start(
+        &self,
+        direction: Direction,
+        node_id: NodeId,
+        name: String,
+        hash: Option<Hash>,
+        size: u64,
+    ) -> Option<TransferPermit<'_>> {
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        let transfer = Transfer {
+            id,
+            direction,
+            node_id,
+            name,
+            hash,
+            size,
+            state: TransferState::Queued,
+            queued_at: now(),
+        };
+        self.transfers.lock().unwrap().insert(id, transfer.clone());
+        self.notify(LocalProtocolMessage::TransferUpdated(transfer));
+
+        // Created before waiting, so the transfer is removed if the caller gives up.
+        let mut permit = TransferPermit {
+            manager: self,
+            id,
+            _permit: None,
+        };
+        let limit = match direction {
+            Direction::Sent => &self.uploads,
+            Direction::Received => &self.downloads,
+        };
+        permit._permit = Some(limit.semaphore.clone().acquire_owned().await.ok()?);
+
+        self.update(id, |transfer| transfer.state = TransferState::Active);
+        if let Some(transfer) = self.transfers.lock().unwrap().get(&id) {
+            let metrics = Metrics::new(transfer);
+            self.metrics.lock().unwrap().insert(id, metrics);
+        }
+        self.notify(LocalProtocolMessage::TransfersActive {
+            active: self.active(),
+        });
+        Some(permit)
+    }
+
+    /// All waiting and running transfers, in the order they were queued.
+    pub fn list(&self) -> Vec<Transfer> {
+        self.transfers.lock().unwrap().values().cloned().collect()
+    }
+
+    /// Metrics of the running transfers and the last finished ones, the oldest first.
+    pub fn stats(&self) -> Vec<TransferStats> {
+        let mut stats: Vec<_> = self.finished.lock().unwrap().iter().cloned().collect();
+        stats.extend(self.metrics.lock().unwrap().values().map(Metrics::snapshot));
+        stats
+    }
+
+    /// Records `bytes` of transfer `id` that went over `path`, as part of its speed over time.
+    pub fn record(&self, id: u64, bytes: u64, path: ConnectionPath) {
+        if let Some(metrics) = self.metrics.lock().unwrap().get_mut(&id) {
+            metrics.add(bytes, path, true);
+        }
+    }
+
+    /// Pauses the running download `id`, until [`Self::resume`].
+    ///
+    /// Uploads can not be paused, their blobs are served to the receiver by the node.
+    pub fn pause(&self, id: u64) -> Result<()> {
+        let transfer = self.transfers.lock().unwrap().get(&id).cloned();
+        match transfer {
+            Some(transfer) if transfer.direction == Direction::Sent => {
+                let message = "only downloads can be paused".to_string();
+                return Err(DropError::InvalidArgument(message).into());
+            }
+            Some(transfer) if transfer.state == TransferState::Active => {}
+            Some(_) => {
+                let message = format!("transfer {id} is not running");
+                return Err(DropError::InvalidArgument(message).into());
+            }
+            None => {
+                return Err(DropError::InvalidArgument(format!("no transfer {id}")).into());
+            }
+        }
+        self.paused.send_modify(|paused| {
+            paused.insert(id);
+        });
+        self.update(id, |transfer| transfer.state = TransferState::Paused);
+        self.notify(LocalProtocolMessage::TransfersActive {
+            active: self.active(),
+        });
+        Ok(())
+    }
+
+    /// Continues the paused download `id`.
+    pub fn resume(&self, id: u64) -> Result<()> {
+        let mut resumed = false;
+        self.paused.send_if_modified(|paused| {
+            resumed = paused.remove(&id);
+            resumed
+        });
+        if !resumed {
+            let message = format!("transfer {id} is not paused");
+            return Err(DropError::InvalidArgument(message).into());
+        }
+        self.update(id, |transfer| transfer.state = TransferState::Active);
+        self.notify(LocalProtocolMessage::TransfersActive {
+            active: self.active(),
+        });
+        Ok(())
+    }
+
+    pub fn is_paused(&self, id: u64) -> bool {
+        self.paused.borrow().contains(&id)
+    }
+
+    /// Waits until `id` is no longer paused, returns right away if it is not.
+    pub async fn wait_resumed(&self, id: u64) {
+        let mut paused = self.paused.subscribe();
+        paused.wait_for(|paused| !paused.contains(&id)).await.ok();
+    }
+
+    /// Bytes the waiting and running downloads still have to store.
+    pub fn remaining_download_bytes(&self) -> u64 {
+        let downloads: Vec<(u64, u64)> = self
+            .transfers
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|t| t.direction == Direction::Received)
+            .map(|t| (t.id, t.size))
+            .collect();
+        let metrics = self.metrics.lock().unwrap();
+        downloads
+            .into_iter()
+            .map(|(id, size)| {
+                let stored = metrics
+                    .get(&id)
+                    .map(|m| m.stats.direct_bytes + m.stats.relay_bytes)
+                    .unwrap_or_default();
+                size.saturating_sub(stored)
+            })
+            .sum()
+    }
+
+    /// Number of running transfers.
+    pub fn active(&self) -> usize {
+        self.count(|t| t.state == TransferState::Active)
+    }
+
+    /// Number of running transfers in `direction`.
+    pub fn active_in(&self, direction: Direction) -> usize {
+        self.count(|t| t.state == TransferState::Active && t.direction == direction)
+    }
+
+    fn count(&self, f: impl Fn(&Transfer) -> bool) -> usize {
+        self.transfers.lock().unwrap().values().filter(|t| f(t)).count()
+    }
+
+    fn update(&self, id: u64, f: impl FnOnce(&mut Transfer)) {
+        let transfer = self.transfers.lock().unwrap().get_mut(&id).map(|transfer| {
+            f(transfer);
+            transfer.clone()
+        });
+        if let Some(transfer) = transfer {
+            self.notify(LocalProtocolMessage::TransferUpdated(transfer));
+        }
+    }
+
+    /// Moves the metrics of `id` to the finished ones.
+    fn finish(&self, id: u64) {
+        let Some(metrics) = self.metrics.lock().unwrap().remove(&id) else {
+            return;
+        };
+        let mut stats = metrics.snapshot();
+        stats.finished = true;
+        let mut finished = self.finished.lock().unwrap();
+        if finished.len() == MAX_FINISHED_STATS {
+            finished.pop_front();
+        }
+        finished.push_back(stats);
+    }
+
+    fn notify(&self, message: LocalProtocolMessage) {
+        // Only fails if the app is shutting down.
+        self.s.try_send(message).ok();
+    }
+}
+
+/// A queued or running transfer, removed from the queue when dropped.
+pub struct TransferPermit<'a> {
+    manager: &'a TransferManager,
+    id: u64,
+    _permit: Option<OwnedSemaphorePermit>,
+}
+
+impl TransferPermit<'_> {
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn set_hash(&self, hash: Hash) {
+        self.manager
+            .update(self.id, |transfer| transfer.hash = Some(hash));
+    }
+
+    /// Records that `bytes` went over `path` in total, the part that was not recorded with
+    /// [`TransferManager::record`] without a speed over time.
+    pub fn record_total(&self, bytes: u64, path: ConnectionPath) {
+        if let Some(metrics) = self.manager.metrics.lock().unwrap().get_mut(&self.id) {
+            let recorded = metrics.stats.direct_bytes + metrics.stats.relay_bytes;
+            metrics.add(bytes.saturating_sub(recorded), path, false);
+        }
+    }
+}
+
+impl Drop for TransferPermit<'_> {
+    fn drop(&mut self) {
+        self.manager.transfers.lock().unwrap().remove(&self.id);
+        self.manager
+            .paused
+            .send_if_modified(|paused| paused.remove(&self.id));
+        self.manager.finish(self.id);
+        self.manager
+            .notify(LocalProtocolMessage::TransferFinished { id: self.id });
+        self.manager.notify(LocalProtocolMessage::TransfersActive {
+            active: self.manager.active(),
+        });
+    }
+}
@@ -0,0 +1,161 @@
+//! Conversions applied to files before they are sent, for receivers that can not open the
+//! original format or should not get the full size.
+//!
+//! Transforms are enabled per recipient, see [`crate::settings::Settings::send_transforms`],
+//! and run in order before the data is added to the store. HEIC and live photos are not
+//! supported yet, as `image` can not decode them.
+
+use std::io::Cursor;
+use std::path::Path;
+
+use anyhow::Result;
+use image::{codecs::jpeg::JpegEncoder, DynamicImage, ImageFormat, ImageReader};
+
+use crate::metadata::FileMetadata;
+use crate::preview;
+
+/// Images larger than this in either dimension are downscaled by the `downscale` transform.
+const MAX_DIMENSION: u32 = 3840;
+
+/// Quality of JPEGs written by the transforms.
+const JPEG_QUALITY: u8 = 85;
+
+/// A file on its way to a peer.
+#[derive(Debug, Clone)]
+pub struct OutgoingFile {
+    pub name: String,
+    pub data: Vec<u8>,
+    pub metadata: FileMetadata,
+}
+
+/// A stage of the send pipeline.
+pub trait Transform: std::fmt::Debug + Send + Sync {
+    /// Stable name, stored in the settings.
+    fn name(&self) -> &'static str;
+
+    /// Converts `file` in place, returning whether it applied.
+    fn apply(&self, file: &mut OutgoingFile) -> Result<bool>;
+}
+
+/// Names of all transforms, in the order they run.
+pub const AVAILABLE: &[&str] = &[Downscale::NAME, PngToJpeg::NAME];
+
+/// The transform called `name`.
+pub fn by_name(name: &str) -> Option<Box<dyn Transform>> {
+    match name {
+        Downscale::NAME => Some(Box::new(Downscale {
+            max_dimension: MAX_DIMENSION,
+        })),
+        PngToJpeg::NAME => Some(Box::new(PngToJpeg)),
+        _ => None,
+    }
+}
+
+/// Runs the transforms called `names` on `file`, in the order of [`AVAILABLE`].
+///
+/// A failing transform is skipped, sending the file unconverted is better than not at all.
+pub fn run(names: &[String], mut file: OutgoingFile) -> OutgoingFile {
+    for name in AVAILABLE.iter().filter(|name| names.iter().any(|n| n == *name)) {
+        let Some(transform) = by_name(name) else {
+            continue;
+        };
+        let name = transform.name();
+        match transform.apply(&mut file) {
+            Ok(true) => tracing::info!("applied {name} to {}", file.name),
+            Ok(false) => {}
+            Err(err) => tracing::warn!("failed to apply {name} to {}: {err:#}", file.name),
+        }
+    }
+    file
+}
+
+/// Shrinks images to fit into `max_dimension` pixels, keeping their format.
+#[derive(Debug)]
+pub struct Downscale {
+    pub max_dimension: u32,
+}
+
+impl Downscale {
+    const NAME: &'static str = "downscale";
+}
+
+impl Transform for Downscale {
+    fn name(&self) -> &'static str {
+        Self::NAME
+    }
+
+    fn apply(&self, file: &mut OutgoingFile) -> Result<bool> {
+        let Some((image, format)) = decode(&file.data)? else {
+            return Ok(false);
+        };
+        // Re-encoding would drop all but the first frame.
+        if format == ImageFormat::Gif {
+            return Ok(false);
+        }
+        if image.width() <= self.max_dimension && image.height() <= self.max_dimension {
+            return Ok(false);
+        }
+        let image = image.resize(
+            self.max_dimension,
+            self.max_dimension,
+            image::imageops::FilterType::Lanczos3,
+        );
+        file.data = encode(&image, format)?;
+        Ok(true)
+    }
+}
+
+/// Re-encodes PNGs without transparency as JPEG, e.g. large screenshots.
+#[derive(Debug)]
+pub struct PngToJpeg;
+
+impl PngToJpeg {
+    const NAME: &'static str = "png-to-jpeg";
+}
+
+impl Transform for PngToJpeg {
+    fn name(&self) -> &'static str {
+        Self::NAME
+    }
+
+    fn apply(&self, file: &mut OutgoingFile) -> Result<bool> {
+        let Some((image, ImageFormat::Png)) = decode(&file.data)? else {
+            return Ok(false);
+        };
+        if image.color().has_alpha() {
+            return Ok(false);
+        }
+        file.data = encode(&image, ImageFormat::Jpeg)?;
+        file.name = Path::new(&file.name)
+            .with_extension("jpg")
+            .to_string_lossy()
+            .into_owned();
+        file.metadata.mime = Some("image/jpeg".to_string());
+        Ok(true)
+    }
+}
+
+/// Decodes `data` if it is an image we can write again.
+fn decode(data: &[u8]) -> Result<Option<(DynamicImage, ImageFormat)>> {
+    if !preview::is_image(data) || data.len() as u64 > preview::MAX_IMAGE_SIZE {
+        return Ok(None);
+    }
+    let reader = ImageReader::new(Cursor::new(data)).with_guessed_format()?;
+    let Some(format) = reader.format() else {
+        return Ok(None);
+    };
+    Ok(Some((reader.decode()?, format)))
+}
+
+fn encode(image: &DynamicImage, format: ImageFormat) -> Result<Vec<u8>> {
+    let mut data = Vec::new();
+    match format {
+        ImageFormat::Jpeg => {
+            image
+                .to_rgb8()
+                .write_with_encoder(JpegEncoder::new_with_quality(&mut data, JPEG_QUALITY))?;
+        }
+        format => image.write_to(&mut Cursor::new(&mut data), format)?,
+    }
+    Ok(data)
+}
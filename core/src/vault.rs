@@ -0,0 +1,204 @@
+//! Encrypted copies of received files, and a password gate in front of them.
+//!
+//! Once set up, every received file is also written to the vault, encrypted with
+//! [age](https://age-encryption.org) to a key of its own. That key is stored encrypted
+//! with the user's password, so files can be received while the vault is locked.
+//!
+//! The vault does not replace the blob store: received files stay readable in the
+//! in-memory store until the app exits, only the copies on disk are encrypted. What the
+//! vault adds is a gate, saving, previewing and forwarding files needs it unlocked, see
+//! [`Vault::ensure_unlocked`]. After a restart files are restored from the vault once it is
+//! unlocked again.
+
+use std::fmt;
+use std::io::{Read, Write};
+use std::iter;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use age::secrecy::{ExposeSecret, Secret};
+use anyhow::{Context, Result};
+use iroh::blobs::Hash;
+use serde::Serialize;
+
+use crate::error::DropError;
+
+/// The vault key, encrypted with the password.
+const KEY_FILE: &str = "key.age";
+
+/// The public half of the vault key, files are encrypted to it without the password.
+const RECIPIENT_FILE: &str = "recipient.txt";
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct VaultStatus {
+    /// Whether the user opted into encrypted storage
+    pub set_up: bool,
+    pub unlocked: bool,
+}
+
+pub struct Vault {
+    dir: PathBuf,
+    /// The vault key while unlocked
+    identity: Mutex<Option<age::x25519::Identity>>,
+}
+
+impl fmt::Debug for Vault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Vault")
+            .field("dir", &self.dir)
+            .field("unlocked", &self.is_unlocked())
+            .finish()
+    }
+}
+
+impl Vault {
+    /// The vault in `dir`, which may not be set up yet.
+    pub fn new(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            identity: Mutex::new(None),
+        }
+    }
+
+    /// Whether the user opted into encrypted storage.
+    pub fn is_set_up(&self) -> bool {
+        self.dir.join(KEY_FILE).exists()
+    }
+
+    pub fn is_unlocked(&self) -> bool {
+        self.identity.lock().unwrap().is_some()
+    }
+
+    pub fn status(&self) -> VaultStatus {
+        VaultStatus {
+            set_up: self.is_set_up(),
+            unlocked: self.is_unlocked(),
+        }
+    }
+
+    /// Creates the vault key protected by `password`, leaving the vault unlocked.
+    pub fn set_up(&self, password: &str) -> Result<()> {
+        if self.is_set_up() {
+            let message = "encrypted storage is set up already".to_string();
+            return Err(DropError::InvalidArgument(message).into());
+        }
+        if password.is_empty() {
+            let message = "the password must not be empty".to_string();
+            return Err(DropError::InvalidArgument(message).into());
+        }
+        std::fs::create_dir_all(&self.dir)?;
+
+        let identity = age::x25519::Identity::generate();
+        let encryptor = age::Encryptor::with_user_passphrase(Secret::new(password.to_string()));
+        let mut key = Vec::new();
+        let mut writer = encryptor.wrap_output(&mut key)?;
+        writer.write_all(identity.to_string().expose_secret().as_bytes())?;
+        writer.finish()?;
+        std::fs::write(
+            self.dir.join(RECIPIENT_FILE),
+            identity.to_public().to_string(),
+        )?;
+        std::fs::write(self.dir.join(KEY_FILE), key)?;
+
+        *self.identity.lock().unwrap() = Some(identity);
+        tracing::info!("set up encrypted storage in {}", self.dir.display());
+        Ok(())
+    }
+
+    /// Decrypts the vault key with `password`.
+    pub fn unlock(&self, password: &str) -> Result<()> {
+        let wrong_password = || DropError::InvalidArgument("wrong password".to_string());
+        let key =
+            std::fs::read(self.dir.join(KEY_FILE)).context("encrypted storage is not set up")?;
+        let age::Decryptor::Passphrase(decryptor) = age::Decryptor::new(&key[..])? else {
+            anyhow::bail!("the vault key is not protected by a password");
+        };
+        let mut reader = decryptor
+            .decrypt(&Secret::new(password.to_string()), None)
+            .map_err(|_| wrong_password())?;
+        let mut identity = String::new();
+        reader.read_to_string(&mut identity)?;
+        let identity = identity
+            .parse::<age::x25519::Identity>()
+            .map_err(|err| anyhow::anyhow!("invalid vault key: {err}"))?;
+
+        *self.identity.lock().unwrap() = Some(identity);
+        tracing::info!("unlocked the encrypted storage");
+        Ok(())
+    }
+
+    /// Forgets the vault key, until the next [`Self::unlock`].
+    pub fn lock(&self) {
+        self.identity.lock().unwrap().take();
+        tracing::info!("locked the encrypted storage");
+    }
+
+    /// Fails with [`DropError::StorageLocked`] if the vault is set up but locked.
+    pub fn ensure_unlocked(&self) -> Result<()> {
+        if self.is_set_up() && !self.is_unlocked() {
+            return Err(DropError::StorageLocked.into());
+        }
+        Ok(())
+    }
+
+    /// Whether a copy of `hash` is in the vault.
+    pub fn contains(&self, hash: &Hash) -> bool {
+        self.path(hash).exists()
+    }
+
+    /// Encrypts `data` into the vault, works while locked.
+    pub async fn store(&self, hash: Hash, data: Vec<u8>) -> Result<()> {
+        let recipient = std::fs::read_to_string(self.dir.join(RECIPIENT_FILE))?
+            .trim()
+            .parse::<age::x25519::Recipient>()
+            .map_err(|err| anyhow::anyhow!("invalid vault recipient: {err}"))?;
+        let path = self.path(&hash);
+        tokio::task::spawn_blocking(move || {
+            let recipients: Vec<Box<dyn age::Recipient + Send>> = vec![Box::new(recipient)];
+            let encryptor =
+                age::Encryptor::with_recipients(recipients).context("no vault recipient")?;
+            let mut encrypted = Vec::new();
+            let mut writer = encryptor.wrap_output(&mut encrypted)?;
+            writer.write_all(&data)?;
+            writer.finish()?;
+            std::fs::write(path, encrypted)?;
+            anyhow::Ok(())
+        })
+        .await??;
+        Ok(())
+    }
+
+    /// Decrypts the copy of `hash`, needs the vault to be unlocked.
+    pub async fn load(&self, hash: Hash) -> Result<Vec<u8>> {
+        let identity = self
+            .identity
+            .lock()
+            .unwrap()
+            .clone()
+            .ok_or(DropError::StorageLocked)?;
+        let path = self.path(&hash);
+        tokio::task::spawn_blocking(move || {
+            let encrypted = std::fs::read(path)?;
+            let age::Decryptor::Recipients(decryptor) = age::Decryptor::new(&encrypted[..])? else {
+                anyhow::bail!("{hash} is not encrypted to the vault key");
+            };
+            let mut reader = decryptor.decrypt(iter::once(&identity as &dyn age::Identity))?;
+            let mut data = Vec::new();
+            reader.read_to_end(&mut data)?;
+            Ok(data)
+        })
+        .await?
+    }
+
+    /// Removes the copy of `hash`, if there is one.
+    pub fn remove(&self, hash: &Hash) -> Result<()> {
+        match std::fs::remove_file(self.path(hash)) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err.into()),
+            _ => Ok(()),
+        }
+    }
+
+    fn path(&self, hash: &Hash) -> PathBuf {
+        self.dir.join(format!("{hash}.age"))
+    }
+}
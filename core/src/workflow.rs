@@ -0,0 +1,165 @@
+//! Named workflows, combining which received files they apply to with what happens to them.
+//!
+//! E.g. "Receipts": accept PDFs from the phone, save them to `~/Documents/receipts` and run
+//! an OCR script on them. The first enabled workflow matching an offer is used.
+
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use iroh::net::NodeId;
+use serde::{Deserialize, Serialize};
+
+use crate::metadata::FileMetadata;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Workflow {
+    pub name: String,
+    pub enabled: bool,
+    /// Only files from these devices, from everyone if empty.
+    pub from: BTreeSet<NodeId>,
+    /// Only files with these extensions (`pdf`) or MIME types (`image/*`), all if empty.
+    pub types: Vec<String>,
+    /// Accept matching offers even if the receive mode or network would reject the sender.
+    pub auto_accept: bool,
+    /// Directory matching files are saved to once received.
+    pub destination: Option<PathBuf>,
+    /// Shell command run for saved files, with the same environment as automation hooks.
+    pub command: Option<String>,
+}
+
+impl Workflow {
+    pub fn validate(&self) -> Result<()> {
+        anyhow::ensure!(!self.name.trim().is_empty(), "workflows need a name");
+        anyhow::ensure!(
+            !self.auto_accept || !self.from.is_empty(),
+            "workflow {}: only files from selected devices can be accepted automatically",
+            self.name
+        );
+        if let Some(ref destination) = self.destination {
+            anyhow::ensure!(
+                destination.is_absolute(),
+                "workflow {}: the destination must be an absolute path",
+                self.name
+            );
+        }
+        Ok(())
+    }
+
+    /// Whether the file `name` sent by `from` is handled by this workflow.
+    pub fn matches(&self, from: &NodeId, name: &str, metadata: &FileMetadata) -> bool {
+        if !self.enabled || (!self.from.is_empty() && !self.from.contains(from)) {
+            return false;
+        }
+        if self.types.is_empty() {
+            return true;
+        }
+        let extension = Path::new(name)
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_lowercase());
+        let mime = metadata.mime.as_deref().unwrap_or_default();
+        self.types.iter().any(|ty| {
+            let ty = ty.trim().to_lowercase();
+            match ty.split_once('/') {
+                Some((group, "*")) => mime.split_once('/').map(|(g, _)| g) == Some(group),
+                Some(_) => mime == ty,
+                None => extension.as_deref() == Some(ty.trim_start_matches('.')),
+            }
+        })
+    }
+}
+
+/// The first workflow in `workflows` handling the file `name` sent by `from`.
+pub fn find<'a>(
+    workflows: &'a [Workflow],
+    from: &NodeId,
+    name: &str,
+    metadata: &FileMetadata,
+) -> Option<&'a Workflow> {
+    workflows
+        .iter()
+        .find(|workflow| workflow.matches(from, name, metadata))
+}
+
+#[cfg(test)]
+mod tests {
+    use iroh::net::key::SecretKey;
+
+    use super::*;
+
+    fn node_id() -> NodeId {
+        SecretKey::generate().public()
+    }
+
+    fn with_mime(mime: &str) -> FileMetadata {
+        FileMetadata {
+            mime: Some(mime.to_string()),
+            ..Default::default()
+        }
+    }
+
+    fn workflow(types: &[&str]) -> Workflow {
+        Workflow {
+            name: "receipts".to_string(),
+            enabled: true,
+            types: types.iter().map(|ty| ty.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn matches_extension_and_mime() {
+        let from = node_id();
+        let workflow = workflow(&["PDF", "image/*", "text/plain"]);
+        let none = FileMetadata::default();
+        assert!(workflow.matches(&from, "receipt.pdf", &none));
+        assert!(workflow.matches(&from, "scan.bin", &with_mime("image/png")));
+        assert!(workflow.matches(&from, "notes", &with_mime("text/plain")));
+        assert!(!workflow.matches(&from, "notes", &with_mime("text/html")));
+        assert!(!workflow.matches(&from, "receipt.pdf.exe", &none));
+    }
+
+    #[test]
+    fn matches_only_selected_senders() {
+        let phone = node_id();
+        let mut workflow = workflow(&[]);
+        workflow.from.insert(phone);
+        let none = FileMetadata::default();
+        assert!(workflow.matches(&phone, "any.txt", &none));
+        assert!(!workflow.matches(&node_id(), "any.txt", &none));
+
+        workflow.enabled = false;
+        assert!(!workflow.matches(&phone, "any.txt", &none));
+    }
+
+    #[test]
+    fn find_first_match() {
+        let from = node_id();
+        let mut images = workflow(&["image/*"]);
+        images.name = "images".to_string();
+        let workflows = [workflow(&["pdf"]), images, workflow(&[])];
+        let found = |name| find(&workflows, &from, name, &FileMetadata::default());
+        assert_eq!(found("a.pdf"), Some(&workflows[0]));
+        assert_eq!(found("a.txt"), Some(&workflows[2]));
+    }
+
+    #[test]
+    fn validate() {
+        assert!(workflow(&[]).validate().is_ok());
+
+        let mut unnamed = workflow(&[]);
+        unnamed.name = " ".to_string();
+        assert!(unnamed.validate().is_err());
+
+        let mut from_everyone = workflow(&[]);
+        from_everyone.auto_accept = true;
+        assert!(from_everyone.validate().is_err());
+        from_everyone.from.insert(node_id());
+        assert!(from_everyone.validate().is_ok());
+
+        let mut relative = workflow(&[]);
+        relative.destination = Some(PathBuf::from("receipts"));
+        assert!(relative.validate().is_err());
+    }
+}
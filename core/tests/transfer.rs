@@ -0,0 +1,736 @@
+//! Runs two nodes in the same process and transfers files between them over localhost.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Result;
+use iroh::blobs::{store::mem, Hash};
+use iroh::net::{relay::RelayMode, NodeId};
+use iroh::node::{Builder, DocsStorage, MemNode, StorageConfig};
+use tempfile::TempDir;
+use tokio::sync::mpsc;
+
+use iroh_drop_core::compression::Encoding;
+use iroh_drop_core::device_group::GroupSecret;
+use iroh_drop_core::error::DropError;
+use iroh_drop_core::history::{Direction, History};
+use iroh_drop_core::identity::IdentityWarning;
+use iroh_drop_core::library::{FileKind, SearchQuery};
+use iroh_drop_core::metadata::FileMetadata;
+use iroh_drop_core::peer_cache::PeerCache;
+use iroh_drop_core::protocol::{self, DeliveryStatus, LocalProtocolMessage, Protocol};
+use iroh_drop_core::security_log::RejectReason;
+use iroh_drop_core::settings::{ReceiveMode, SettingsStore};
+use iroh_drop_core::sniff::SNIFF_LEN;
+
+/// How long a test waits for an event, before failing.
+const EVENT_TIMEOUT: Duration = Duration::from_secs(10);
+
+struct TestNode {
+    node: MemNode,
+    proto: Arc<Protocol>,
+    events: mpsc::Receiver<LocalProtocolMessage>,
+    /// Storage dir of the node, removed when the test ends.
+    _dir: TempDir,
+}
+
+impl TestNode {
+    /// Starts a node without relays or discovery, peers are dialed by their tickets.
+    async fn spawn(name: &str) -> Result<Self> {
+        let store = mem::Store::default();
+        let builder =
+            Builder::with_db_and_store(store.clone(), DocsStorage::Disabled, StorageConfig::Mem)
+                .relay_mode(RelayMode::Disabled)
+                .bind_random_port()
+                .build()
+                .await?;
+        let dir = tempfile::tempdir()?;
+        let (s, events) = mpsc::channel(64);
+        let proto = Protocol::new(
+            name.to_string(),
+            builder.client().clone(),
+            builder.endpoint().clone(),
+            store,
+            Arc::new(SettingsStore::default()),
+            History::default(),
+            PeerCache::default(),
+            dir.path().to_path_buf(),
+            None,
+            s,
+        );
+        proto.apply_settings(&proto.settings().get().await);
+        let node = builder
+            .accept(protocol::ALPN.to_vec(), proto.clone())
+            .spawn()
+            .await?;
+        Ok(Self {
+            node,
+            proto,
+            events,
+            _dir: dir,
+        })
+    }
+
+    fn node_id(&self) -> NodeId {
+        self.node.node_id()
+    }
+
+    /// Waits for the first event `f` returns a value for, skipping all others.
+    async fn expect<T>(&mut self, mut f: impl FnMut(LocalProtocolMessage) -> Option<T>) -> T {
+        tokio::time::timeout(EVENT_TIMEOUT, async {
+            loop {
+                let event = self.events.recv().await.expect("events closed");
+                if let Some(value) = f(event) {
+                    return value;
+                }
+            }
+        })
+        .await
+        .expect("timed out waiting for an event")
+    }
+
+    async fn set_receive_mode(&self, mode: ReceiveMode) -> Result<()> {
+        let mut settings = self.proto.settings().get().await;
+        settings.receive_mode = mode;
+        self.proto.settings().set(settings).await
+    }
+
+    /// Sends `data` as `name` to `receiver` and waits until it is downloaded there.
+    async fn send_to(&self, receiver: &mut TestNode, name: &str, data: &[u8]) -> Result<Hash> {
+        self.proto
+            .send_file(
+                receiver.node_id(),
+                name.to_string(),
+                data.to_vec(),
+                FileMetadata::default(),
+            )
+            .await?;
+        let hash = receiver
+            .expect(|event| match event {
+                LocalProtocolMessage::FileDownloaded { hash, .. } => Some(hash),
+                _ => None,
+            })
+            .await;
+        Ok(hash)
+    }
+}
+
+/// Starts two nodes which introduced themselves to each other.
+async fn pair() -> Result<(TestNode, TestNode)> {
+    let mut a = TestNode::spawn("a").await?;
+    let mut b = TestNode::spawn("b").await?;
+
+    let ticket = b.proto.ticket().await?.to_string();
+    let (node_id, name) = a.proto.connect_by_ticket(&ticket).await?;
+    assert_eq!(node_id, b.node_id());
+    assert_eq!(name, "b");
+    let ticket = a.proto.ticket().await?.to_string();
+    b.proto.connect_by_ticket(&ticket).await?;
+
+    let a_id = a.node_id();
+    let name = b
+        .expect(|event| match event {
+            LocalProtocolMessage::PeerOnline { node_id, name } if node_id == a_id => Some(name),
+            _ => None,
+        })
+        .await;
+    assert_eq!(name, "a");
+    let b_id = b.node_id();
+    a.expect(|event| match event {
+        LocalProtocolMessage::PeerOnline { node_id, .. } if node_id == b_id => Some(()),
+        _ => None,
+    })
+    .await;
+
+    Ok((a, b))
+}
+
+#[tokio::test]
+async fn intro() -> Result<()> {
+    let (a, b) = pair().await?;
+
+    let peers = a.proto.list_peers().await;
+    let peer = peers
+        .iter()
+        .find(|peer| peer.node_id == b.node_id())
+        .expect("b is known");
+    assert_eq!(peer.name, "b");
+    assert!(peer.online);
+
+    a.node.shutdown().await?;
+    b.node.shutdown().await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn own_devices_by_group_ticket() -> Result<()> {
+    let a = TestNode::spawn("a").await?;
+    let b = TestNode::spawn("b").await?;
+
+    let ticket = a.proto.own_device_ticket().await?.to_string();
+    let (node_id, name) = b.proto.connect_by_ticket(&ticket).await?;
+    assert_eq!(node_id, a.node_id());
+    assert_eq!(name, "a");
+
+    let a_settings = a.proto.settings().get().await;
+    assert!(a_settings.sync.own_devices.contains(&b.node_id()));
+    assert!(a_settings.paired.contains_key(&b.node_id()));
+    let b_settings = b.proto.settings().get().await;
+    assert!(b_settings.sync.own_devices.contains(&a.node_id()));
+    assert_eq!(a_settings.sync.group, b_settings.sync.group);
+
+    a.node.shutdown().await?;
+    b.node.shutdown().await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn group_ticket_needs_proof_and_leaving() -> Result<()> {
+    let a = TestNode::spawn("a").await?;
+    let b = TestNode::spawn("b").await?;
+
+    // A ticket with a secret `a` does not know joins nothing.
+    let mut forged = a.proto.own_device_ticket().await?;
+    forged.secret = GroupSecret::generate();
+    let err = b.proto.connect_by_ticket(&forged.to_string()).await.unwrap_err();
+    assert!(matches!(err.downcast_ref(), Some(DropError::InvalidArgument(_))));
+    assert!(b.proto.settings().get().await.sync.group.is_none());
+    assert!(!b.proto.settings().get().await.sync.own_devices.contains(&a.node_id()));
+
+    // Joining another group needs leaving the current one first.
+    b.proto.own_device_ticket().await?;
+    let ticket = a.proto.own_device_ticket().await?.to_string();
+    let err = b.proto.connect_by_ticket(&ticket).await.unwrap_err();
+    assert!(matches!(err.downcast_ref(), Some(DropError::InvalidArgument(_))));
+    assert!(!b.proto.settings().get().await.sync.own_devices.contains(&a.node_id()));
+    b.proto.leave_group().await?;
+    b.proto.connect_by_ticket(&ticket).await?;
+    assert!(b.proto.settings().get().await.sync.own_devices.contains(&a.node_id()));
+
+    a.node.shutdown().await?;
+    b.node.shutdown().await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn profile_moves_to_new_machine() -> Result<()> {
+    let (a, b) = pair().await?;
+    a.proto
+        .settings()
+        .set_paired(b.node_id(), Some("b".to_string()))
+        .await?;
+    let new = TestNode::spawn("new").await?;
+
+    let path = std::env::temp_dir().join(format!("iroh-drop-test-{}.age", a.node_id()));
+    a.proto.export_profile(&path, "secret", true).await?;
+    let err = new
+        .proto
+        .import_profile(&path, "wrong")
+        .await
+        .expect_err("the password is wrong");
+    assert!(matches!(
+        err.downcast_ref(),
+        Some(DropError::InvalidArgument(_))
+    ));
+    let secret_key = new.proto.import_profile(&path, "secret").await?;
+    std::fs::remove_file(&path)?;
+    assert_eq!(secret_key.map(|key| key.public()), Some(a.node_id()));
+    let settings = new.proto.settings().get().await;
+    assert_eq!(settings.paired.get(&b.node_id()), Some(&"b".to_string()));
+
+    a.node.shutdown().await?;
+    b.node.shutdown().await?;
+    new.node.shutdown().await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn renamed_peer_is_verified() -> Result<()> {
+    let (mut a, mut b) = pair().await?;
+    let b_id = b.node_id();
+
+    let mut settings = b.proto.settings().get().await;
+    settings.name = Some("mallory".to_string());
+    b.proto.apply_settings(&settings);
+    b.proto.announce_name().await;
+    let warning = a
+        .expect(|event| match event {
+            LocalProtocolMessage::IdentityWarning {
+                node_id, warning, ..
+            } if node_id == b_id => Some(warning),
+            _ => None,
+        })
+        .await;
+    assert_eq!(
+        warning,
+        IdentityWarning::NameChanged {
+            pinned: "b".to_string()
+        }
+    );
+
+    let code = a.proto.verify_identity(b_id).await?;
+    let shown = b
+        .expect(|event| match event {
+            LocalProtocolMessage::IdentityVerification { code, .. } => Some(code),
+            _ => None,
+        })
+        .await;
+    assert_eq!(code, shown);
+
+    a.proto.confirm_identity(b_id).await?;
+    let peers = a.proto.list_peers().await;
+    let peer = peers.iter().find(|peer| peer.node_id == b_id).unwrap();
+    assert_eq!(peer.name, "mallory");
+    assert!(peer.identity_warning.is_none());
+    assert!(peer.identity_verified);
+
+    a.node.shutdown().await?;
+    b.node.shutdown().await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn single_file() -> Result<()> {
+    let (mut a, mut b) = pair().await?;
+    let data = b"hello from a".to_vec();
+
+    a.proto
+        .send_file(b.node_id(), "hello.txt".to_string(), data.clone(), FileMetadata::default())
+        .await?;
+
+    let a_id = a.node_id();
+    let (name, size) = b
+        .expect(|event| match event {
+            LocalProtocolMessage::IncomingFile {
+                from, name, size, ..
+            } if from == a_id => Some((name, size)),
+            _ => None,
+        })
+        .await;
+    assert_eq!(name, "hello.txt");
+    assert_eq!(size, data.len() as u64);
+    let (hash, already_had) = b
+        .expect(|event| match event {
+            LocalProtocolMessage::FileDownloaded {
+                hash, already_had, ..
+            } => Some((hash, already_had)),
+            _ => None,
+        })
+        .await;
+    assert!(!already_had);
+    assert_eq!(b.proto.read_head(hash).await?, data);
+
+    let verified = a
+        .expect(|event| match event {
+            LocalProtocolMessage::FileSent { verified, .. } => Some(verified),
+            _ => None,
+        })
+        .await;
+    assert_eq!(verified, Some(true));
+
+    let received = b.proto.history().find_received(&hash).await.expect("in history");
+    assert_eq!(received.direction, Direction::Received);
+    assert_eq!(received.node_id, a_id);
+
+    a.node.shutdown().await?;
+    b.node.shutdown().await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn delivery_receipts() -> Result<()> {
+    let (mut a, b) = pair().await?;
+
+    a.proto
+        .send_file(
+            b.node_id(),
+            "receipt.txt".to_string(),
+            b"receipt".to_vec(),
+            FileMetadata::default(),
+        )
+        .await?;
+
+    for expected in [DeliveryStatus::Downloading, DeliveryStatus::Delivered] {
+        let status = a
+            .expect(|event| match event {
+                LocalProtocolMessage::SentFileDelivery { status, .. } => Some(status),
+                _ => None,
+            })
+            .await;
+        assert_eq!(status, expected);
+    }
+
+    a.node.shutdown().await?;
+    b.node.shutdown().await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn transfer_stats() -> Result<()> {
+    let (a, mut b) = pair().await?;
+    // Zeros look binary, so the file is not compressed and sent byte for byte.
+    let data = vec![0u8; 64 * 1024];
+
+    a.send_to(&mut b, "stats.bin", &data).await?;
+
+    // Without relays everything goes over the direct connection.
+    let sent = a.proto.transfers().stats();
+    let [sent] = &sent[..] else {
+        panic!("expected one upload: {sent:?}");
+    };
+    assert!(sent.finished);
+    assert_eq!(sent.direction, Direction::Sent);
+    assert_eq!(sent.direct_bytes, data.len() as u64);
+    assert_eq!(sent.relay_bytes, 0);
+
+    let received = b.proto.transfers().stats();
+    let [received] = &received[..] else {
+        panic!("expected one download: {received:?}");
+    };
+    assert_eq!(received.direction, Direction::Received);
+    assert_eq!(received.samples.iter().sum::<u64>(), data.len() as u64);
+    assert_eq!(received.direct_bytes, data.len() as u64);
+
+    a.node.shutdown().await?;
+    b.node.shutdown().await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn pause_and_resume() -> Result<()> {
+    let (a, mut b) = pair().await?;
+    let data: Vec<u8> = (0..1024 * 1024).map(|i| (i % 251) as u8).collect();
+    let mut settings = b.proto.settings().get().await;
+    settings.advanced.max_down_bps = 256 * 1024;
+    b.proto.settings().set(settings).await?;
+
+    // Sending returns once the transfer is complete.
+    let sending = tokio::spawn({
+        let (proto, node_id, data) = (a.proto.clone(), b.node_id(), data.clone());
+        async move {
+            proto
+                .send_file(node_id, "paused.bin".to_string(), data, FileMetadata::default())
+                .await
+        }
+    });
+    let id = b
+        .expect(|event| match event {
+            LocalProtocolMessage::TransferProgress { id, paused, .. } if !paused => Some(id),
+            _ => None,
+        })
+        .await;
+    b.proto.transfers().pause(id)?;
+    let offset = b
+        .expect(|event| match event {
+            LocalProtocolMessage::TransferProgress { offset, paused, .. } if paused => {
+                Some(offset)
+            }
+            _ => None,
+        })
+        .await;
+    assert!(offset > 0 && offset < data.len() as u64);
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    b.proto.transfers().resume(id)?;
+
+    let hash = b
+        .expect(|event| match event {
+            LocalProtocolMessage::FileDownloaded { hash, .. } => Some(hash),
+            _ => None,
+        })
+        .await;
+    sending.await??;
+    assert_eq!(b.node.blobs().read_to_bytes(hash).await?, data);
+    // Only what was missing at the pause was fetched again.
+    let received = b.proto.transfers().stats();
+    let [received] = &received[..] else {
+        panic!("expected one download: {received:?}");
+    };
+    assert_eq!(received.samples.iter().sum::<u64>(), data.len() as u64);
+
+    a.node.shutdown().await?;
+    b.node.shutdown().await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn compressed_text_file() -> Result<()> {
+    let (a, mut b) = pair().await?;
+    let data: Vec<u8> = (0..10_000)
+        .flat_map(|i| format!("{i},event,ok\n").into_bytes())
+        .collect();
+
+    let hash = a.send_to(&mut b, "log.csv", &data).await?;
+
+    let received = b
+        .proto
+        .history()
+        .find_received(&hash)
+        .await
+        .expect("in history");
+    assert_eq!(received.encoding, Some(Encoding::Zstd));
+    assert!(received.size < data.len() as u64);
+    assert_eq!(b.proto.read_head(hash).await?, data[..SNIFF_LEN]);
+
+    let dir = std::env::temp_dir().join(format!("iroh-drop-test-{hash}"));
+    std::fs::create_dir_all(&dir)?;
+    let (path, _) = b.proto.export_received(hash, &dir, false).await?;
+    assert_eq!(std::fs::read(&path)?, data);
+    std::fs::remove_dir_all(&dir)?;
+
+    a.node.shutdown().await?;
+    b.node.shutdown().await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn collects_exported_blobs() -> Result<()> {
+    let (a, mut b) = pair().await?;
+    let data = b"saved and cleaned up".to_vec();
+
+    let hash = a.send_to(&mut b, "gc.txt", &data).await?;
+
+    let mut settings = b.proto.settings().get().await;
+    settings.storage.max_store_size = 1;
+    b.proto.settings().set(settings).await?;
+    // Not exported yet, so the file is still needed.
+    assert!(b.proto.collect_garbage().await?.is_empty());
+
+    let dir = std::env::temp_dir().join(format!("iroh-drop-test-{hash}"));
+    std::fs::create_dir_all(&dir)?;
+    b.proto.export_received(hash, &dir, false).await?;
+    std::fs::remove_dir_all(&dir)?;
+    assert_eq!(b.proto.collect_garbage().await?, [hash]);
+    assert_eq!(b.proto.storage_usage().await?.blobs, 0);
+    let received = b
+        .proto
+        .history()
+        .find_received(&hash)
+        .await
+        .expect("in history");
+    assert!(received.collected);
+
+    a.node.shutdown().await?;
+    b.node.shutdown().await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn exports_stored_blob_again() -> Result<()> {
+    let (a, mut b) = pair().await?;
+    let data = b"saved twice".to_vec();
+
+    let hash = a.send_to(&mut b, "twice.txt", &data).await?;
+
+    let blobs = b.proto.list_blobs().await?;
+    let blob = blobs.iter().find(|blob| blob.hash == hash).expect("stored");
+    assert_eq!(blob.name.as_deref(), Some("twice.txt"));
+    assert!(blob.received);
+
+    let dest = std::env::temp_dir().join(format!("iroh-drop-test-{hash}.txt"));
+    b.proto.export_blob(hash, &dest, false).await?;
+    assert_eq!(std::fs::read(&dest)?, data);
+    std::fs::remove_file(&dest)?;
+
+    a.node.shutdown().await?;
+    b.node.shutdown().await?;
+    Ok(())
+}
+
+/// Sends `data` from `sender` to `receiver`, returning whether each side had it already.
+async fn send_back(
+    sender: &mut TestNode,
+    receiver: &mut TestNode,
+    data: &[u8],
+) -> Result<(bool, bool)> {
+    sender
+        .proto
+        .send_file(receiver.node_id(), "back.txt".to_string(), data.to_vec(), FileMetadata::default())
+        .await?;
+    let received = receiver
+        .expect(|event| match event {
+            LocalProtocolMessage::FileDownloaded { already_had, .. } => Some(already_had),
+            _ => None,
+        })
+        .await;
+    let sent = sender
+        .expect(|event| match event {
+            LocalProtocolMessage::FileSent { already_had, .. } => Some(already_had),
+            _ => None,
+        })
+        .await;
+    Ok((received, sent))
+}
+
+#[tokio::test]
+async fn already_had() -> Result<()> {
+    let (mut a, mut b) = pair().await?;
+    // Larger than an inline offer and not compressed, so it goes through the store.
+    let data = vec![0u8; 512 * 1024];
+
+    assert_eq!(send_back(&mut a, &mut b, &data).await?, (false, false));
+    // The sender keeps the file in its store, so it does not need it back.
+    assert_eq!(send_back(&mut b, &mut a, &data).await?, (true, true));
+
+    a.node.shutdown().await?;
+    b.node.shutdown().await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn tiny_file_is_sent_inline() -> Result<()> {
+    let (mut a, mut b) = pair().await?;
+    let data = b"small enough for the offer".to_vec();
+
+    let hash = a.send_to(&mut b, "inline.txt", &data).await?;
+    assert_eq!(b.proto.read_head(hash).await?, data);
+    let verified = a
+        .expect(|event| match event {
+            LocalProtocolMessage::FileSent { verified, .. } => Some(verified),
+            _ => None,
+        })
+        .await;
+    assert_eq!(verified, Some(true));
+    // Nothing was downloaded, so the sender did not need to store it.
+    let sent = a.proto.list_blobs().await?;
+    assert!(sent.iter().all(|blob| blob.hash != hash));
+
+    a.node.shutdown().await?;
+    b.node.shutdown().await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn search_received_files() -> Result<()> {
+    let (a, mut b) = pair().await?;
+    for name in ["report.pdf", "notes.txt"] {
+        let data = format!("contents of {name}").into_bytes();
+        a.send_to(&mut b, name, &data).await?;
+    }
+
+    let query = SearchQuery {
+        kind: Some(FileKind::Document),
+        ..Default::default()
+    };
+    let found = b.proto.search_received(&query).await;
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].entry.name, "report.pdf");
+    assert_eq!(found[0].sender.as_deref(), Some("a"));
+    let query = SearchQuery {
+        text: "NOTES".to_string(),
+        node_id: Some(a.node_id()),
+        ..Default::default()
+    };
+    let found = b.proto.search_received(&query).await;
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].entry.name, "notes.txt");
+    let query = SearchQuery {
+        node_id: Some(b.node_id()),
+        ..Default::default()
+    };
+    assert!(b.proto.search_received(&query).await.is_empty());
+
+    a.node.shutdown().await?;
+    b.node.shutdown().await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn duplicate_offer_is_ignored() -> Result<()> {
+    let (mut a, b) = pair().await?;
+    let data = b"dropped twice".to_vec();
+
+    for expected in [false, true] {
+        a.proto
+            .send_file(b.node_id(), "twice.txt".to_string(), data.clone(), FileMetadata::default())
+            .await?;
+        let already_had = a
+            .expect(|event| match event {
+                LocalProtocolMessage::FileSent { already_had, .. } => Some(already_had),
+                _ => None,
+            })
+            .await;
+        assert_eq!(already_had, expected);
+    }
+    let received = b
+        .proto
+        .history()
+        .list()
+        .await
+        .into_iter()
+        .filter(|entry| entry.direction == Direction::Received)
+        .count();
+    assert_eq!(received, 1);
+
+    a.node.shutdown().await?;
+    b.node.shutdown().await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn rejected_and_accepted_once() -> Result<()> {
+    let (a, b) = pair().await?;
+    b.set_receive_mode(ReceiveMode::TrustedOnly).await?;
+
+    let send = || {
+        a.proto.send_file(
+            b.node_id(),
+            "stranger.txt".to_string(),
+            b"not trusted".to_vec(),
+            FileMetadata::default(),
+        )
+    };
+    let err = send().await.expect_err("a is not trusted");
+    assert!(matches!(
+        err.downcast_ref(),
+        Some(DropError::Rejected {
+            reason: Some(RejectReason::NotTrusted),
+            ..
+        })
+    ));
+    let log = b.proto.security_log().list().await;
+    assert_eq!(log.len(), 1);
+    assert_eq!(log[0].reason, RejectReason::NotTrusted);
+
+    // The grant is used up by the next offer.
+    b.proto.accept_once(a.node_id());
+    send().await?;
+    send().await.expect_err("the grant was used");
+
+    a.node.shutdown().await?;
+    b.node.shutdown().await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn large_file_needs_confirmation() -> Result<()> {
+    let (a, b) = pair().await?;
+    let mut settings = b.proto.settings().get().await;
+    settings.advanced.max_receive_size = 8;
+    b.proto.settings().set(settings).await?;
+
+    let send = || {
+        a.proto.send_file(
+            b.node_id(),
+            "large.txt".to_string(),
+            b"larger than the limit".to_vec(),
+            FileMetadata::default(),
+        )
+    };
+    let err = send().await.expect_err("the file is too large");
+    assert!(matches!(
+        err.downcast_ref(),
+        Some(DropError::Rejected {
+            reason: Some(RejectReason::ExceedsLimit),
+            ..
+        })
+    ));
+    let log = b.proto.security_log().list().await;
+    assert_eq!(log[0].reason, RejectReason::ExceedsLimit);
+
+    b.proto.accept_once(a.node_id());
+    send().await?;
+
+    a.node.shutdown().await?;
+    b.node.shutdown().await?;
+    Ok(())
+}
@@ -0,0 +1,219 @@
+//! Hooks for OS level automation, so users can react to transfers outside of the app.
+//!
+//! - Linux: a `org.irohdrop.Drop.TransferEvent` signal on the session bus.
+//! - macOS: runs the configured Shortcut, with the event as JSON input.
+//! - Windows: shows a toast, with the event as JSON in its activation arguments. Activating it
+//!   runs the configured command again, with `IROH_DROP_ACTIVATED=1`.
+//! - All platforms: runs the configured command, with the event in `IROH_DROP_*` env vars.
+//!
+//! Workflows run their own command the same way, see [`run_workflow`].
+
+use std::path::PathBuf;
+
+use anyhow::Result;
+use iroh::blobs::Hash;
+use iroh_drop_core::protocol::Protocol;
+use iroh_drop_core::settings::AutomationSettings;
+use iroh_drop_core::workflow;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AutomationEventKind {
+    /// A file was downloaded into the blob store.
+    Received,
+    /// A received file was exported to disk.
+    Exported,
+}
+
+impl AutomationEventKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Received => "received",
+            Self::Exported => "exported",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AutomationEvent {
+    pub kind: AutomationEventKind,
+    pub name: String,
+    pub hash: String,
+    pub size: u64,
+    /// Node id of the sender
+    pub from: String,
+    /// Location on disk, only set for exported files
+    pub path: Option<PathBuf>,
+}
+
+/// Notifies all configured automation hooks about `event`.
+pub async fn dispatch(settings: &AutomationSettings, event: AutomationEvent) {
+    if !settings.enabled {
+        return;
+    }
+
+    if let Err(err) = emit_platform(settings, &event).await {
+        tracing::warn!("failed to emit automation event: {err:?}");
+    }
+    if let Some(ref command) = settings.command {
+        if let Err(err) = run_command(command, &event) {
+            tracing::warn!("failed to run automation command: {err:?}");
+        }
+    }
+}
+
+/// Runs the workflow handling the received file `hash`, if there is one.
+///
+/// The file is saved to the destination of the workflow, before its command runs. Quarantined
+/// files are never saved automatically.
+pub async fn run_workflow(proto: &Protocol, hash: Hash) -> Result<()> {
+    let Some(entry) = proto.history().find_received(&hash).await else {
+        return Ok(());
+    };
+    let workflows = proto.settings().get().await.workflows;
+    let Some(workflow) = workflow::find(&workflows, &entry.node_id, &entry.name, &entry.metadata)
+    else {
+        return Ok(());
+    };
+    tracing::info!("running workflow {} for {}", workflow.name, entry.name);
+
+    let path = match workflow.destination {
+        Some(ref dir) => {
+            std::fs::create_dir_all(dir)?;
+            Some(proto.export_received(hash, dir, false).await?.0)
+        }
+        None => None,
+    };
+    if let Some(ref command) = workflow.command {
+        let kind = match path {
+            Some(_) => AutomationEventKind::Exported,
+            None => AutomationEventKind::Received,
+        };
+        let event = AutomationEvent {
+            kind,
+            name: entry.name,
+            hash: hash.to_string(),
+            size: entry.size,
+            from: entry.node_id.to_string(),
+            path,
+        };
+        run_command(command, &event)?;
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+async fn emit_platform(_settings: &AutomationSettings, event: &AutomationEvent) -> Result<()> {
+    let path = event
+        .path
+        .as_ref()
+        .map(|p| p.display().to_string())
+        .unwrap_or_default();
+    let connection = zbus::Connection::session().await?;
+    connection
+        .emit_signal(
+            None::<&str>,
+            "/org/irohdrop/Drop",
+            "org.irohdrop.Drop",
+            "TransferEvent",
+            &(
+                event.kind.as_str(),
+                event.name.as_str(),
+                event.hash.as_str(),
+                event.size,
+                event.from.as_str(),
+                path.as_str(),
+            ),
+        )
+        .await?;
+    Ok(())
+}
+
+#[cfg(target_os = "macos")]
+async fn emit_platform(settings: &AutomationSettings, event: &AutomationEvent) -> Result<()> {
+    let Some(ref shortcut) = settings.shortcut else {
+        return Ok(());
+    };
+    let input = std::env::temp_dir().join(format!("iroh-drop-{}.json", event.hash));
+    std::fs::write(&input, serde_json::to_vec(event)?)?;
+    tokio::process::Command::new("shortcuts")
+        .arg("run")
+        .arg(shortcut)
+        .arg("--input-path")
+        .arg(input)
+        .spawn()?;
+    Ok(())
+}
+
+/// Windows attributes toasts to the app by its AppUserModelID, the bundle identifier.
+#[cfg(windows)]
+const APP_USER_MODEL_ID: &str = "com.irohdrop.app";
+
+#[cfg(windows)]
+async fn emit_platform(settings: &AutomationSettings, event: &AutomationEvent) -> Result<()> {
+    use tauri_winrt_notification::Toast;
+
+    let title = match event.kind {
+        AutomationEventKind::Received => "File received",
+        AutomationEventKind::Exported => "File saved",
+    };
+    let command = settings.command.clone();
+    // Activation runs outside of the runtime, the command is spawned on it.
+    let runtime = tokio::runtime::Handle::current();
+    Toast::new(APP_USER_MODEL_ID)
+        .title(title)
+        .text1(&event.name)
+        .add_button("Run automation", &serde_json::to_string(event)?)
+        .on_activated(move |arguments| {
+            let (Some(command), Some(arguments)) = (&command, arguments) else {
+                return Ok(());
+            };
+            let _guard = runtime.enter();
+            let res = serde_json::from_str(&arguments)
+                .map_err(anyhow::Error::from)
+                .and_then(|event| spawn_command(command, &event, true));
+            if let Err(err) = res {
+                log::warn!("failed to run activated automation command: {err:?}");
+            }
+            Ok(())
+        })
+        .show()?;
+    Ok(())
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+async fn emit_platform(_settings: &AutomationSettings, _event: &AutomationEvent) -> Result<()> {
+    Ok(())
+}
+
+fn run_command(command: &str, event: &AutomationEvent) -> Result<()> {
+    spawn_command(command, event, false)
+}
+
+/// Runs `command` with `event` in its environment, `activated` if the user asked for it.
+fn spawn_command(command: &str, event: &AutomationEvent, activated: bool) -> Result<()> {
+    let mut cmd = if cfg!(windows) {
+        let mut cmd = tokio::process::Command::new("cmd");
+        cmd.arg("/C");
+        cmd
+    } else {
+        let mut cmd = tokio::process::Command::new("sh");
+        cmd.arg("-c");
+        cmd
+    };
+    cmd.arg(command)
+        .env("IROH_DROP_EVENT", event.kind.as_str())
+        .env("IROH_DROP_NAME", &event.name)
+        .env("IROH_DROP_HASH", &event.hash)
+        .env("IROH_DROP_SIZE", event.size.to_string())
+        .env("IROH_DROP_FROM", &event.from);
+    if let Some(ref path) = event.path {
+        cmd.env("IROH_DROP_PATH", path);
+    }
+    if activated {
+        cmd.env("IROH_DROP_ACTIVATED", "1");
+    }
+    cmd.spawn()?;
+    Ok(())
+}
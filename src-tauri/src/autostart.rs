@@ -0,0 +1,61 @@
+//! Starting the app when the user logs in, so the node is ready to receive files after boot.
+//!
+//! The login item launches the app with [`LOGIN_ARG`], it then stays in the tray if
+//! [`iroh_drop_core::settings::Settings::start_minimized`] is set.
+
+use iroh_drop_core::error::{DropError, DropResult};
+use tauri::{AppHandle, Runtime};
+
+/// Argument the login item passes to the app.
+pub const LOGIN_ARG: &str = "--at-login";
+
+#[cfg(desktop)]
+pub fn plugin<R: Runtime>() -> tauri::plugin::TauriPlugin<R> {
+    tauri_plugin_autostart::init(
+        tauri_plugin_autostart::MacosLauncher::LaunchAgent,
+        Some(vec![LOGIN_ARG]),
+    )
+}
+
+/// Whether this process was started by the login item.
+pub fn launched_at_login() -> bool {
+    std::env::args().any(|arg| arg == LOGIN_ARG)
+}
+
+pub fn is_enabled<R: Runtime>(app: &AppHandle<R>) -> DropResult<bool> {
+    #[cfg(desktop)]
+    {
+        use tauri_plugin_autostart::ManagerExt;
+        app.autolaunch()
+            .is_enabled()
+            .map_err(|err| DropError::Internal(err.to_string()))
+    }
+    #[cfg(mobile)]
+    {
+        let _ = app;
+        Ok(false)
+    }
+}
+
+pub fn set_enabled<R: Runtime>(app: &AppHandle<R>, enabled: bool) -> DropResult<()> {
+    #[cfg(desktop)]
+    {
+        use tauri_plugin_autostart::ManagerExt;
+        let autolaunch = app.autolaunch();
+        let result = if enabled {
+            autolaunch.enable()
+        } else {
+            autolaunch.disable()
+        };
+        result.map_err(|err| DropError::Internal(err.to_string()))?;
+        tracing::info!("starting at login: {enabled}");
+        Ok(())
+    }
+    #[cfg(mobile)]
+    {
+        let _ = (app, enabled);
+        Err(DropError::InvalidArgument(
+            "starting at login is not supported on this device".to_string(),
+        ))
+    }
+}
@@ -0,0 +1,165 @@
+//! Keeps transfers running while the app is in the background on mobile.
+//!
+//! The protocol reports the number of running transfers, see
+//! [`iroh_drop_core::protocol::LocalProtocolMessage::TransfersActive`]. While any are running
+//! an ongoing notification is shown, and on iOS a background task asks the system
+//! for extra time to finish them.
+//!
+//! With [`iroh_drop_core::settings::Settings::receive_in_background`] set, the node also
+//! keeps accepting offers while the app is in the background. Files received meanwhile are
+//! collected, counted in an ongoing notification and reported to the UI on resume.
+//!
+//! Android only keeps the process alive with a foreground service, which needs to be
+//! declared in the generated Android project.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use iroh_drop_core::protocol::Protocol;
+use tauri::{AppHandle, Emitter, Manager, Runtime};
+#[cfg(all(mobile, feature = "notifications"))]
+use tauri_plugin_notification::NotificationExt;
+
+/// Id of the progress notification, so it can be updated and removed.
+#[cfg(all(mobile, feature = "notifications"))]
+const NOTIFICATION_ID: i32 = 1;
+
+/// Id of the notification shown while receiving in the background.
+#[cfg(all(mobile, feature = "notifications"))]
+const RECEIVING_NOTIFICATION_ID: i32 = 2;
+
+/// State of the background handling, managed by the app.
+#[derive(Debug)]
+pub struct Background {
+    #[cfg(target_os = "ios")]
+    task: Mutex<Option<ios::BackgroundTask>>,
+    /// Number of running transfers
+    active: Mutex<usize>,
+    /// Whether the window has the focus
+    foreground: AtomicBool,
+    /// Names of the files received while in the background
+    away: Mutex<Vec<String>>,
+}
+
+impl Default for Background {
+    fn default() -> Self {
+        Self {
+            #[cfg(target_os = "ios")]
+            task: Default::default(),
+            active: Default::default(),
+            foreground: AtomicBool::new(true),
+            away: Default::default(),
+        }
+    }
+}
+
+/// Called when the app moves to the background or back.
+pub fn set_foreground<R: Runtime>(app: &AppHandle<R>, foreground: bool) {
+    let state = app.state::<Background>();
+    if state.foreground.swap(foreground, Ordering::SeqCst) == foreground {
+        return;
+    }
+    if foreground {
+        let away = std::mem::take(&mut *state.away.lock().unwrap());
+        if !away.is_empty() {
+            app.emit("received-while-away", away).ok();
+        }
+        #[cfg(all(mobile, feature = "notifications"))]
+        app.notification()
+            .remove_active(vec![RECEIVING_NOTIFICATION_ID])
+            .ok();
+        return;
+    }
+
+    let proto = app.state::<Arc<Protocol>>();
+    let settings = tauri::async_runtime::block_on(proto.settings().get());
+    if cfg!(mobile) && settings.receive_in_background {
+        show_receiving(app, 0);
+    }
+}
+
+/// Called for every received file, collected while in the background.
+pub fn received<R: Runtime>(app: &AppHandle<R>, name: String) {
+    let state = app.state::<Background>();
+    // Desktop notifications are shown right away, and stay until dismissed.
+    if cfg!(desktop) || state.foreground.load(Ordering::SeqCst) {
+        return;
+    }
+    let count = {
+        let mut away = state.away.lock().unwrap();
+        away.push(name);
+        away.len()
+    };
+    show_receiving(app, count);
+}
+
+/// Shows or updates the ongoing notification while receiving in the background.
+#[cfg(all(mobile, feature = "notifications"))]
+fn show_receiving<R: Runtime>(app: &AppHandle<R>, received: usize) {
+    let body = match received {
+        0 => "Ready to receive files".to_string(),
+        1 => "1 file received".to_string(),
+        n => format!("{n} files received"),
+    };
+    let res = app
+        .notification()
+        .builder()
+        .id(RECEIVING_NOTIFICATION_ID)
+        .title("iroh-drop")
+        .body(body)
+        .ongoing()
+        .silent()
+        .show();
+    if let Err(err) = res {
+        tracing::warn!("failed to update the receiving notification: {err}");
+    }
+}
+
+/// Only logged if the app is built without the `notifications` feature.
+#[cfg(not(all(mobile, feature = "notifications")))]
+fn show_receiving<R: Runtime>(_app: &AppHandle<R>, received: usize) {
+    tracing::info!("receiving in the background, {received} files so far");
+}
+
+#[cfg(target_os = "ios")]
+mod ios {
+    use objc2::runtime::AnyObject;
+    use objc2::{class, msg_send};
+
+    /// `UIBackgroundTaskInvalid`
+    const INVALID: usize = 0;
+
+    /// A `UIApplication` background task, ended when dropped.
+    #[derive(Debug)]
+    pub struct BackgroundTask(usize);
+
+    impl BackgroundTask {
+        pub fn begin() -> Option<Self> {
+            // SAFETY: `beginBackgroundTaskWithName:expirationHandler:` is thread safe,
+            // both arguments are nullable.
+            let id: usize = unsafe {
+                let app: *mut AnyObject = msg_send![class!(UIApplication), sharedApplication];
+                msg_send![
+                    app,
+                    beginBackgroundTaskWithName: std::ptr::null::<AnyObject>(),
+                    expirationHandler: std::ptr::null::<AnyObject>()
+                ]
+            };
+            if id == INVALID {
+                tracing::warn!("no background time available");
+                return None;
+            }
+            Some(Self(id))
+        }
+    }
+
+    impl Drop for BackgroundTask {
+        fn drop(&mut self) {
+            // SAFETY: `self.0` is a task returned by `beginBackgroundTask`, ended only once.
+            unsafe {
+                let app: *mut AnyObject = msg_send![class!(UIApplication), sharedApplication];
+                let _: () = msg_send![app, endBackgroundTask: self.0];
+            }
+        }
+    }
+}
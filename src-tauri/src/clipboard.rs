@@ -0,0 +1,27 @@
+//! Sending the content of the clipboard, pasted onto a device card.
+
+use std::io::Cursor;
+
+use iroh_drop_core::error::DropError;
+use iroh_drop_core::history::now;
+use tauri::{AppHandle, Runtime};
+use tauri_plugin_clipboard_manager::ClipboardExt;
+
+/// The clipboard as a file to send: an image as PNG, or text as a text file.
+pub fn read<R: Runtime>(app: &AppHandle<R>) -> Result<(String, Vec<u8>), DropError> {
+    let clipboard = app.clipboard();
+    if let Ok(image) = clipboard.read_image() {
+        let rgba = image.rgba().to_vec();
+        let image = image::RgbaImage::from_raw(image.width(), image.height(), rgba)
+            .ok_or_else(|| DropError::Internal("invalid clipboard image".to_string()))?;
+        let mut png = Vec::new();
+        image
+            .write_to(&mut Cursor::new(&mut png), image::ImageFormat::Png)
+            .map_err(|e| DropError::Internal(e.to_string()))?;
+        return Ok((format!("clipboard-{}.png", now()), png));
+    }
+    match clipboard.read_text() {
+        Ok(text) if !text.is_empty() => Ok((format!("clipboard-{}.txt", now()), text.into_bytes())),
+        _ => Err(DropError::InvalidArgument("the clipboard is empty".to_string())),
+    }
+}
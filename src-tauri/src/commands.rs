@@ -0,0 +1,1188 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use iroh::{blobs::Hash, net::NodeId};
+use iroh_drop_core::error::{DropError, DropResult};
+use iroh_drop_core::{
+    admin_lock, diagnostics, history, library, metadata, network_trust, node, persistence,
+    protocol, security_log, settings, stats, transfers, vault,
+};
+use tauri::Emitter;
+use tauri_plugin_dialog::DialogExt;
+use tauri_plugin_shell::ShellExt;
+
+use crate::{
+    automation, autostart, clipboard, control, kiosk, link, logs, pairing, permissions,
+    share_target, storage,
+};
+
+#[tauri::command]
+pub async fn node_id(iroh: tauri::State<'_, iroh::node::MemNode>) -> DropResult<String> {
+    let id = iroh.node_id().to_string();
+    Ok(id)
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub async fn send_file(
+    proto: tauri::State<'_, Arc<protocol::Protocol>>,
+    node_id: String,
+    file_name: String,
+    file_data: Vec<u8>,
+    metadata: Option<metadata::FileMetadata>,
+) -> DropResult<()> {
+    let node_id = parse_node_id(&node_id)?;
+    proto
+        .send_file(node_id, file_name, file_data, metadata.unwrap_or_default())
+        .await?;
+
+    Ok(())
+}
+
+/// Sends one file to all of `node_ids` at the same time.
+///
+/// Returns each recipient with its error, `None` if the file was delivered.
+#[tauri::command(rename_all = "snake_case")]
+pub async fn send_file_to_many(
+    proto: tauri::State<'_, Arc<protocol::Protocol>>,
+    node_ids: Vec<String>,
+    file_name: String,
+    file_data: Vec<u8>,
+    metadata: Option<metadata::FileMetadata>,
+) -> DropResult<Vec<(String, Option<DropError>)>> {
+    if node_ids.is_empty() {
+        return Err(DropError::InvalidArgument("no recipients".to_string()));
+    }
+    let node_ids = node_ids
+        .iter()
+        .map(|node_id| parse_node_id(node_id))
+        .collect::<DropResult<Vec<_>>>()?;
+    let results = proto
+        .send_file_to_many(node_ids, file_name, file_data, metadata.unwrap_or_default())
+        .await;
+
+    Ok(results
+        .into_iter()
+        .map(|(node_id, result)| (node_id.to_string(), result.err().map(DropError::from)))
+        .collect())
+}
+
+/// Sends the file at `path` to all members of the peer group `group`.
+///
+/// Returns each member with its error, `None` if the file was delivered.
+#[tauri::command(rename_all = "snake_case")]
+pub async fn send_to_group(
+    proto: tauri::State<'_, Arc<protocol::Protocol>>,
+    group: String,
+    path: PathBuf,
+) -> DropResult<Vec<(String, Option<DropError>)>> {
+    let results = send_path_to_group(&proto, &group, &path).await?;
+    Ok(results
+        .into_iter()
+        .map(|(node_id, error)| (node_id.to_string(), error))
+        .collect())
+}
+
+/// Sends the file at `path` to the peer group `group`, with its name and metadata.
+pub(crate) async fn send_path_to_group(
+    proto: &protocol::Protocol,
+    group: &str,
+    path: &Path,
+) -> DropResult<Vec<(NodeId, Option<DropError>)>> {
+    let file_metadata = tokio::fs::metadata(path).await?;
+    let file_data = tokio::fs::read(path).await?;
+    let results = proto
+        .send_to_group(
+            group,
+            share_target::file_name(path),
+            file_data,
+            metadata::FileMetadata::from_fs(&file_metadata),
+        )
+        .await?;
+
+    Ok(results)
+}
+
+/// The peer groups with the node ids of their members.
+#[tauri::command]
+pub async fn peer_groups(
+    proto: tauri::State<'_, Arc<protocol::Protocol>>,
+) -> DropResult<BTreeMap<String, Vec<String>>> {
+    let groups = proto.settings().get().await.peer_groups;
+    Ok(groups
+        .into_iter()
+        .map(|(name, members)| (name, members.iter().map(|id| id.to_string()).collect()))
+        .collect())
+}
+
+/// Replaces the members of the peer group `name`, no members remove the group.
+#[tauri::command(rename_all = "snake_case")]
+pub async fn set_peer_group(
+    proto: tauri::State<'_, Arc<protocol::Protocol>>,
+    name: String,
+    node_ids: Vec<String>,
+) -> DropResult<()> {
+    proto.admin_lock().check()?;
+    let members = node_ids
+        .iter()
+        .map(|node_id| parse_node_id(node_id))
+        .collect::<DropResult<_>>()?;
+    proto
+        .settings()
+        .set_peer_group(&name, members)
+        .await
+        .map_err(|e| DropError::InvalidArgument(e.to_string()))
+}
+
+/// Holds a file for the next swap with `node_id`, returning the number of held files.
+#[tauri::command(rename_all = "snake_case")]
+pub fn queue_swap_file(
+    proto: tauri::State<'_, Arc<protocol::Protocol>>,
+    node_id: String,
+    file_name: String,
+    file_data: Vec<u8>,
+    metadata: Option<metadata::FileMetadata>,
+) -> DropResult<usize> {
+    let node_id = parse_node_id(&node_id)?;
+    Ok(proto.queue_swap(node_id, file_name, file_data, metadata.unwrap_or_default()))
+}
+
+#[tauri::command]
+pub fn swap_queue(
+    proto: tauri::State<'_, Arc<protocol::Protocol>>,
+    node_id: String,
+) -> DropResult<Vec<String>> {
+    Ok(proto.swap_queue(parse_node_id(&node_id)?))
+}
+
+#[tauri::command]
+pub fn clear_swap_queue(
+    proto: tauri::State<'_, Arc<protocol::Protocol>>,
+    node_id: String,
+) -> DropResult<()> {
+    proto.clear_swap_queue(parse_node_id(&node_id)?);
+    Ok(())
+}
+
+/// Exchanges the held files with `node_id`, progress is reported as `swap-progress`.
+#[tauri::command]
+pub async fn start_swap(
+    proto: tauri::State<'_, Arc<protocol::Protocol>>,
+    node_id: String,
+) -> DropResult<()> {
+    let node_id = parse_node_id(&node_id)?;
+    proto.start_swap(node_id).await?;
+    Ok(())
+}
+
+/// Checks whether `node_id` would accept the file, before it is read and sent.
+#[tauri::command(rename_all = "snake_case")]
+pub async fn preflight(
+    proto: tauri::State<'_, Arc<protocol::Protocol>>,
+    node_id: String,
+    file_name: String,
+    size: u64,
+    metadata: Option<metadata::FileMetadata>,
+) -> DropResult<()> {
+    let node_id = parse_node_id(&node_id)?;
+    proto
+        .preflight(node_id, &file_name, size, &metadata.unwrap_or_default())
+        .await?;
+    Ok(())
+}
+
+/// Sends the content of the clipboard to `node_id`.
+#[tauri::command(rename_all = "snake_case")]
+pub async fn send_clipboard_file(
+    app: tauri::AppHandle,
+    proto: tauri::State<'_, Arc<protocol::Protocol>>,
+    node_id: String,
+) -> DropResult<()> {
+    let node_id = parse_node_id(&node_id)?;
+    let (file_name, file_data) = clipboard::read(&app)?;
+    proto
+        .send_file(node_id, file_name, file_data, Default::default())
+        .await?;
+
+    Ok(())
+}
+
+/// Files shared into the app from other apps, waiting to be sent.
+#[tauri::command]
+pub fn shared_files(
+    shared: tauri::State<'_, share_target::SharedFiles>,
+) -> Vec<share_target::SharedFile> {
+    shared.list()
+}
+
+/// Sends the file at `path`, which was shared into the app, to `node_id`.
+#[tauri::command(rename_all = "snake_case")]
+pub async fn send_shared_file(
+    app: tauri::AppHandle,
+    proto: tauri::State<'_, Arc<protocol::Protocol>>,
+    shared: tauri::State<'_, share_target::SharedFiles>,
+    node_id: String,
+    path: PathBuf,
+) -> DropResult<()> {
+    let node_id = parse_node_id(&node_id)?;
+    shared.check(&path)?;
+    send_path(&proto, node_id, &path).await?;
+    shared.remove(&app, &path);
+
+    Ok(())
+}
+
+/// Asks for files with the native file dialog and sends them to `node_id`.
+///
+/// Returns how many files were sent, `0` if the dialog was cancelled.
+#[tauri::command(rename_all = "snake_case")]
+pub async fn pick_and_send(
+    app: tauri::AppHandle,
+    proto: tauri::State<'_, Arc<protocol::Protocol>>,
+    node_id: String,
+) -> DropResult<usize> {
+    let node_id = parse_node_id(&node_id)?;
+    let (s, r) = tokio::sync::oneshot::channel();
+    app.dialog().file().pick_files(move |paths| {
+        s.send(paths).ok();
+    });
+    let Some(paths) = r.await.ok().flatten() else {
+        return Ok(0);
+    };
+    for path in &paths {
+        let path = path
+            .clone()
+            .into_path()
+            .map_err(|err| DropError::InvalidArgument(err.to_string()))?;
+        send_path(&proto, node_id, &path).await?;
+    }
+
+    Ok(paths.len())
+}
+
+/// Asks for photos and videos with the picker of the phone and sends them to `node_id`.
+///
+/// Returns how many files were sent, `0` if the picker was cancelled.
+#[tauri::command(rename_all = "snake_case")]
+pub async fn pick_media_and_send(
+    app: tauri::AppHandle,
+    proto: tauri::State<'_, Arc<protocol::Protocol>>,
+    node_id: String,
+) -> DropResult<usize> {
+    let node_id = parse_node_id(&node_id)?;
+    #[cfg(mobile)]
+    {
+        let Some(paths) = crate::media_picker::pick(&app).await else {
+            return Ok(0);
+        };
+        let count = paths.len();
+        for path in paths {
+            let media = crate::media_picker::open(&app, path).await?;
+            proto
+                .send_reader(node_id, media.name, media.reader, media.metadata)
+                .await?;
+        }
+        Ok(count)
+    }
+    #[cfg(desktop)]
+    {
+        let _ = (app, proto, node_id);
+        let message = "the photo picker is only available on mobile".to_string();
+        Err(DropError::InvalidArgument(message))
+    }
+}
+
+/// Sends the file at `path` to `node_id`, with its name and metadata.
+pub(crate) async fn send_path(
+    proto: &protocol::Protocol,
+    node_id: NodeId,
+    path: &Path,
+) -> DropResult<()> {
+    let file_metadata = tokio::fs::metadata(path).await?;
+    let file_data = tokio::fs::read(path).await?;
+    proto
+        .send_file(
+            node_id,
+            share_target::file_name(path),
+            file_data,
+            metadata::FileMetadata::from_fs(&file_metadata),
+        )
+        .await?;
+
+    Ok(())
+}
+
+/// Drops a file shared into the app without sending it.
+#[tauri::command]
+pub fn dismiss_shared_file(
+    app: tauri::AppHandle,
+    shared: tauri::State<'_, share_target::SharedFiles>,
+    path: PathBuf,
+) {
+    shared.remove(&app, &path);
+}
+
+/// Forwards a received file to another peer, without importing it again.
+#[tauri::command(rename_all = "snake_case")]
+pub async fn reshare(
+    proto: tauri::State<'_, Arc<protocol::Protocol>>,
+    hash: String,
+    node_id: String,
+    confirmed: bool,
+) -> DropResult<()> {
+    let hash = parse_hash(&hash)?;
+    let node_id = parse_node_id(&node_id)?;
+    proto.reshare(hash, node_id, confirmed).await?;
+
+    Ok(())
+}
+
+/// A one-time download link for the file at `path`, valid for `ttl_secs`.
+#[tauri::command(rename_all = "snake_case")]
+pub async fn create_share_link(
+    proto: tauri::State<'_, Arc<protocol::Protocol>>,
+    path: String,
+    ttl_secs: u64,
+) -> DropResult<String> {
+    let url = proto
+        .create_share_link(Path::new(&path), Duration::from_secs(ttl_secs))
+        .await?;
+    Ok(url)
+}
+
+/// Like [`create_share_link`], for a file of the history.
+#[tauri::command(rename_all = "snake_case")]
+pub async fn share_transfer(
+    proto: tauri::State<'_, Arc<protocol::Protocol>>,
+    hash: String,
+    ttl_secs: u64,
+    confirmed: bool,
+) -> DropResult<String> {
+    let hash = parse_hash(&hash)?;
+    let url = proto
+        .share_transfer(hash, Duration::from_secs(ttl_secs), confirmed)
+        .await?;
+    Ok(url)
+}
+
+#[tauri::command]
+pub fn storage_status(proto: tauri::State<'_, Arc<protocol::Protocol>>) -> vault::VaultStatus {
+    proto.vault().status()
+}
+
+/// Opts into encrypted storage, protected by `password`.
+#[tauri::command]
+pub async fn set_up_encrypted_storage(
+    proto: tauri::State<'_, Arc<protocol::Protocol>>,
+    password: String,
+) -> DropResult<()> {
+    proto.admin_lock().check()?;
+    Ok(proto.vault().set_up(&password)?)
+}
+
+/// Unlocks the encrypted storage, received files can only be saved while it is unlocked.
+#[tauri::command]
+pub async fn unlock_storage(
+    proto: tauri::State<'_, Arc<protocol::Protocol>>,
+    password: String,
+) -> DropResult<()> {
+    Ok(proto.vault().unlock(&password)?)
+}
+
+#[tauri::command]
+pub fn lock_storage(proto: tauri::State<'_, Arc<protocol::Protocol>>) {
+    proto.vault().lock();
+}
+
+#[tauri::command]
+pub fn admin_lock_status(
+    proto: tauri::State<'_, Arc<protocol::Protocol>>,
+) -> admin_lock::AdminLockStatus {
+    proto.admin_lock().status()
+}
+
+/// Turns on read-only mode, see [`admin_lock`].
+#[tauri::command]
+pub fn enable_admin_lock(
+    proto: tauri::State<'_, Arc<protocol::Protocol>>,
+    passphrase: String,
+) -> DropResult<()> {
+    Ok(proto.admin_lock().enable(&passphrase)?)
+}
+
+#[tauri::command]
+pub fn disable_admin_lock(
+    proto: tauri::State<'_, Arc<protocol::Protocol>>,
+    passphrase: String,
+) -> DropResult<()> {
+    Ok(proto.admin_lock().disable(&passphrase)?)
+}
+
+/// Allows changes to a read-only machine for a few minutes.
+#[tauri::command]
+pub fn unlock_admin_lock(
+    proto: tauri::State<'_, Arc<protocol::Protocol>>,
+    passphrase: String,
+) -> DropResult<()> {
+    Ok(proto.admin_lock().unlock(&passphrase)?)
+}
+
+#[tauri::command]
+pub fn lock_admin_lock(proto: tauri::State<'_, Arc<protocol::Protocol>>) {
+    proto.admin_lock().lock();
+}
+
+/// Writes the settings, paired devices and known peers to `path`, encrypted with `password`,
+/// to move them to a new machine. With `include_key` the node id moves along.
+#[tauri::command(rename_all = "snake_case")]
+pub async fn export_profile(
+    app: tauri::AppHandle,
+    proto: tauri::State<'_, Arc<protocol::Protocol>>,
+    path: String,
+    password: String,
+    include_key: bool,
+) -> DropResult<()> {
+    // The profile holds the group secret, and maybe the key of this device.
+    proto.admin_lock().check()?;
+    let path = parse_file_path(&path)?;
+    let exported = proto.export_profile(&path, &password, include_key).await;
+    Ok(permissions::check_fs(&app, exported)?)
+}
+
+/// Replaces the settings with the profile at `path`, written by `export_profile`.
+///
+/// Returns whether the app has to be restarted, to take on the node id of the profile.
+#[tauri::command]
+pub async fn import_profile(
+    app: tauri::AppHandle,
+    proto: tauri::State<'_, Arc<protocol::Protocol>>,
+    backend: tauri::State<'_, Arc<dyn persistence::Backend>>,
+    path: String,
+    password: String,
+) -> DropResult<bool> {
+    proto.admin_lock().check()?;
+    let path = parse_file_path(&path)?;
+    let secret_key = proto.import_profile(&path, &password).await?;
+    control::apply(&app, &proto.settings().get().await.control).await?;
+    let Some(secret_key) = secret_key else {
+        return Ok(false);
+    };
+    node::save_secret_key(backend.inner().as_ref(), &secret_key)?;
+    Ok(true)
+}
+
+#[tauri::command]
+pub async fn my_ticket(proto: tauri::State<'_, Arc<protocol::Protocol>>) -> DropResult<String> {
+    let ticket = proto.ticket().await?;
+    Ok(ticket.to_string())
+}
+
+#[tauri::command]
+pub async fn connect_by_ticket(
+    proto: tauri::State<'_, Arc<protocol::Protocol>>,
+    ticket: String,
+) -> DropResult<(String, String)> {
+    add_remote(&proto, &ticket).await
+}
+
+#[tauri::command]
+pub async fn my_qr_code(proto: tauri::State<'_, Arc<protocol::Protocol>>) -> DropResult<String> {
+    let ticket = proto.ticket().await?;
+    Ok(pairing::qr_svg(&ticket)?)
+}
+
+/// Issues a guest ticket valid for `minutes`, returning it and its QR code.
+#[tauri::command]
+pub async fn guest_ticket(
+    proto: tauri::State<'_, Arc<protocol::Protocol>>,
+    minutes: u64,
+) -> DropResult<(String, String)> {
+    proto.admin_lock().check()?;
+    let ticket = proto.guest_ticket(guest_duration(minutes)?).await?;
+    let qr = pairing::qr_svg(&ticket)?;
+    Ok((ticket.to_string(), qr))
+}
+
+/// Issues a ticket adding another device of the user, returning it and its QR code.
+#[tauri::command]
+pub async fn own_device_ticket(
+    proto: tauri::State<'_, Arc<protocol::Protocol>>,
+) -> DropResult<(String, String)> {
+    proto.admin_lock().check()?;
+    let ticket = proto.own_device_ticket().await?;
+    let qr = pairing::qr_svg(&ticket)?;
+    Ok((ticket.to_string(), qr))
+}
+
+#[tauri::command]
+pub async fn leave_device_group(
+    proto: tauri::State<'_, Arc<protocol::Protocol>>,
+) -> DropResult<()> {
+    proto.admin_lock().check()?;
+    proto.leave_group().await?;
+    Ok(())
+}
+
+/// The TXT record to publish for the own handle, as name and value.
+#[tauri::command]
+pub async fn handle_record(
+    proto: tauri::State<'_, Arc<protocol::Protocol>>,
+) -> DropResult<(String, String)> {
+    Ok(proto.handle_record().await?)
+}
+
+fn guest_duration(minutes: u64) -> DropResult<Duration> {
+    if minutes == 0 {
+        let message = "guest tickets must be valid for at least a minute".to_string();
+        return Err(DropError::InvalidArgument(message));
+    }
+    Ok(Duration::from_secs(minutes.saturating_mul(60)))
+}
+
+/// Starts kiosk mode, accepting files from guests for `minutes`, see [`kiosk`].
+#[tauri::command]
+pub async fn start_kiosk(
+    app: tauri::AppHandle,
+    proto: tauri::State<'_, Arc<protocol::Protocol>>,
+    kiosk: tauri::State<'_, kiosk::Kiosk>,
+    minutes: u64,
+) -> DropResult<kiosk::KioskSession> {
+    proto.admin_lock().check()?;
+    kiosk.start(&app, &proto, guest_duration(minutes)?).await
+}
+
+#[tauri::command]
+pub fn stop_kiosk(app: tauri::AppHandle, kiosk: tauri::State<'_, kiosk::Kiosk>) {
+    kiosk.stop(&app);
+}
+
+#[tauri::command]
+pub fn kiosk_session(kiosk: tauri::State<'_, kiosk::Kiosk>) -> Option<kiosk::KioskSession> {
+    kiosk.session()
+}
+
+#[tauri::command]
+pub async fn pair_from_qr(
+    proto: tauri::State<'_, Arc<protocol::Protocol>>,
+    data: String,
+) -> DropResult<(String, String)> {
+    let ticket = pairing::parse_qr(&data);
+    add_remote(&proto, ticket).await
+}
+
+/// Connects to a remote device, which adds it to the device list.
+async fn add_remote(
+    proto: &protocol::Protocol,
+    ticket: &str,
+) -> DropResult<(String, String)> {
+    // Pairing trusts the device, unlike the guest tickets of kiosk mode.
+    proto.admin_lock().check()?;
+    let (node_id, name) = proto.connect_by_ticket(ticket).await?;
+
+    Ok((name, node_id.to_string()))
+}
+
+#[tauri::command]
+pub async fn export_received(
+    app: tauri::AppHandle,
+    proto: tauri::State<'_, Arc<protocol::Protocol>>,
+    hash: String,
+    confirmed: bool,
+    target: Option<storage::ExportTarget>,
+) -> DropResult<String> {
+    let hash = parse_hash(&hash)?;
+    let dir = match target.unwrap_or_default() {
+        storage::ExportTarget::Files => {
+            let dir = storage::export_dir(&app)?;
+            proto.sorted_export_dir(hash, &dir).await?
+        }
+        storage::ExportTarget::Gallery => {
+            proto.vault().ensure_unlocked()?;
+            let head = proto.read_head(hash).await?;
+            storage::gallery_dir(&app, &head)?
+        }
+    };
+    let exported = proto.export_received(hash, &dir, confirmed).await;
+    let (path, entry) = permissions::check_fs(&app, exported)?;
+
+    let event = automation::AutomationEvent {
+        kind: automation::AutomationEventKind::Exported,
+        name: entry.name,
+        hash: hash.to_string(),
+        size: entry.size,
+        from: entry.node_id.to_string(),
+        path: Some(path.clone()),
+    };
+    automation::dispatch(&proto.settings().get().await.automation, event).await;
+
+    Ok(path.display().to_string())
+}
+
+#[tauri::command]
+pub fn permissions_status(
+    permissions: tauri::State<'_, permissions::Permissions>,
+) -> Vec<permissions::PermissionStatus> {
+    permissions.status()
+}
+
+/// Lets the frontend report permissions the webview asks for itself, like the camera.
+#[tauri::command]
+pub fn report_permission(
+    app: tauri::AppHandle,
+    permission: permissions::Permission,
+    granted: bool,
+) {
+    let state = if granted {
+        permissions::PermissionState::Granted
+    } else {
+        permissions::PermissionState::Denied
+    };
+    permissions::report(&app, permission, state);
+}
+
+/// Opens the system settings where `permission` is granted, on iOS and macOS.
+#[tauri::command]
+pub fn open_permission_settings(
+    app: tauri::AppHandle,
+    permission: permissions::Permission,
+) -> DropResult<()> {
+    permissions::open_settings(&app, permission)?;
+    Ok(())
+}
+
+/// Opens a link received as text in the browser.
+#[tauri::command]
+pub fn open_link(app: tauri::AppHandle, url: String) -> DropResult<()> {
+    let url = link::parse_url(&url)
+        .ok_or_else(|| DropError::InvalidArgument(format!("{url} is not a web address")))?;
+    app.shell()
+        .open(url.as_str(), None)
+        .map_err(|err| DropError::Internal(err.to_string()))?;
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn get_settings(
+    proto: tauri::State<'_, Arc<protocol::Protocol>>,
+) -> DropResult<settings::Settings> {
+    Ok(proto.settings().get().await)
+}
+
+#[tauri::command]
+pub async fn set_settings(
+    app: tauri::AppHandle,
+    proto: tauri::State<'_, Arc<protocol::Protocol>>,
+    settings: settings::Settings,
+) -> DropResult<()> {
+    proto.admin_lock().check()?;
+    proto
+        .settings()
+        .set(settings.clone())
+        .await
+        .map_err(|e| DropError::InvalidArgument(e.to_string()))?;
+    let name = proto.name();
+    proto.apply_settings(&settings);
+    proto.push_settings().await;
+    if proto.name() != name {
+        proto.announce_name().await;
+    }
+    control::apply(&app, &settings.control).await?;
+    // Other windows, e.g. the kiosk, apply the appearance again.
+    app.emit("settings-changed", ()).ok();
+
+    Ok(())
+}
+
+/// Whether the app starts when the user logs in.
+#[tauri::command]
+pub fn autostart_enabled(app: tauri::AppHandle) -> DropResult<bool> {
+    autostart::is_enabled(&app)
+}
+
+#[tauri::command]
+pub fn set_autostart(
+    app: tauri::AppHandle,
+    proto: tauri::State<'_, Arc<protocol::Protocol>>,
+    enabled: bool,
+) -> DropResult<()> {
+    proto.admin_lock().check()?;
+    autostart::set_enabled(&app, enabled)
+}
+
+/// A new random token for the control API, only used once it is saved in the settings.
+#[tauri::command]
+pub fn generate_control_token() -> String {
+    settings::ControlSettings::generate_token()
+}
+
+#[tauri::command]
+pub async fn set_receive_mode(
+    proto: tauri::State<'_, Arc<protocol::Protocol>>,
+    mode: settings::ReceiveMode,
+) -> DropResult<()> {
+    proto.admin_lock().check()?;
+    let mut settings = proto.settings().get().await;
+    settings.receive_mode = mode;
+    proto
+        .settings()
+        .set(settings)
+        .await
+        .map_err(|e| DropError::InvalidArgument(e.to_string()))?;
+    tracing::info!("receive mode set to {mode:?}");
+
+    Ok(())
+}
+
+/// Sets the conversions applied to files sent to `node_id`, e.g. `["downscale"]`.
+#[tauri::command(rename_all = "snake_case")]
+pub async fn set_send_transforms(
+    proto: tauri::State<'_, Arc<protocol::Protocol>>,
+    node_id: String,
+    transforms: Vec<String>,
+) -> DropResult<()> {
+    proto.admin_lock().check()?;
+    let node_id = parse_node_id(&node_id)?;
+    proto
+        .set_send_transforms(node_id, transforms)
+        .await
+        .map_err(|e| DropError::InvalidArgument(e.to_string()))?;
+
+    Ok(())
+}
+
+/// Joins a room, only devices in the same room see each other. `None` leaves the room.
+#[tauri::command]
+pub async fn set_room(
+    proto: tauri::State<'_, Arc<protocol::Protocol>>,
+    room: Option<String>,
+) -> DropResult<()> {
+    proto.admin_lock().check()?;
+    proto
+        .set_room(room)
+        .await
+        .map_err(|e| DropError::InvalidArgument(e.to_string()))?;
+
+    Ok(())
+}
+
+/// Turns invisible mode on or off, see [`protocol::Protocol::set_invisible`].
+#[tauri::command]
+pub async fn set_invisible(
+    proto: tauri::State<'_, Arc<protocol::Protocol>>,
+    invisible: bool,
+) -> DropResult<()> {
+    proto.set_invisible(invisible).await;
+    Ok(())
+}
+
+/// The network the device is on and whether it is trusted, `None` while offline.
+#[tauri::command]
+pub async fn current_network(
+    proto: tauri::State<'_, Arc<protocol::Protocol>>,
+) -> DropResult<Option<(network_trust::Network, network_trust::Trust)>> {
+    Ok(proto.current_network().await)
+}
+
+#[tauri::command]
+pub async fn set_network_trusted(
+    proto: tauri::State<'_, Arc<protocol::Protocol>>,
+    id: String,
+    trusted: bool,
+) -> DropResult<()> {
+    proto.admin_lock().check()?;
+    proto
+        .set_network_trusted(id, trusted)
+        .await
+        .map_err(|e| DropError::InvalidArgument(e.to_string()))?;
+
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn is_invisible(proto: tauri::State<'_, Arc<protocol::Protocol>>) -> DropResult<bool> {
+    Ok(proto.is_invisible())
+}
+
+/// Marks `node_id` as one of the user's own devices, which settings are synced with.
+#[tauri::command]
+pub async fn set_own_device(
+    proto: tauri::State<'_, Arc<protocol::Protocol>>,
+    node_id: String,
+    own: bool,
+) -> DropResult<()> {
+    proto.admin_lock().check()?;
+    let node_id = parse_node_id(&node_id)?;
+    proto.settings().set_own_device(node_id, own).await?;
+    if own {
+        proto.sync_settings_with(node_id).await;
+    }
+
+    Ok(())
+}
+
+/// Pins `node_id` to the top of the device list, or unpins it.
+#[tauri::command(rename_all = "snake_case")]
+pub async fn set_favorite(
+    proto: tauri::State<'_, Arc<protocol::Protocol>>,
+    node_id: String,
+    favorite: bool,
+) -> DropResult<()> {
+    proto.admin_lock().check()?;
+    let node_id = parse_node_id(&node_id)?;
+    proto.settings().set_favorite(node_id, favorite).await?;
+    Ok(())
+}
+
+/// Asks `node_id` to show the verification code of the connection, returning our code.
+#[tauri::command(rename_all = "snake_case")]
+pub async fn verify_identity(
+    proto: tauri::State<'_, Arc<protocol::Protocol>>,
+    node_id: String,
+) -> DropResult<String> {
+    let node_id = parse_node_id(&node_id)?;
+    let code = proto.verify_identity(node_id).await?;
+    Ok(code)
+}
+
+/// Pins the current name of `node_id`, after the user saw the same code on both devices.
+#[tauri::command(rename_all = "snake_case")]
+pub async fn confirm_identity(
+    proto: tauri::State<'_, Arc<protocol::Protocol>>,
+    node_id: String,
+) -> DropResult<()> {
+    proto.admin_lock().check()?;
+    let node_id = parse_node_id(&node_id)?;
+    proto.confirm_identity(node_id).await?;
+    Ok(())
+}
+
+/// Accepts the next file of `node_id`, without trusting it permanently.
+#[tauri::command]
+pub fn accept_once(
+    proto: tauri::State<'_, Arc<protocol::Protocol>>,
+    node_id: String,
+) -> DropResult<()> {
+    proto.admin_lock().check()?;
+    proto.accept_once(parse_node_id(&node_id)?);
+    Ok(())
+}
+
+/// The current connection to `node_id`, with its latency and a quality rating.
+#[tauri::command]
+pub fn peer_connection_info(
+    proto: tauri::State<'_, Arc<protocol::Protocol>>,
+    node_id: String,
+) -> DropResult<Option<diagnostics::ConnectionInfo>> {
+    Ok(proto.connection_info(parse_node_id(&node_id)?))
+}
+
+/// The configured relays, and the home relay the node is currently connected to.
+#[tauri::command]
+pub async fn relay_status(
+    proto: tauri::State<'_, Arc<protocol::Protocol>>,
+) -> DropResult<protocol::RelayStatus> {
+    Ok(proto.relay_status().await)
+}
+
+/// The latest `limit` log messages at least as severe as `level`, newest first.
+#[tauri::command]
+pub fn recent_logs(
+    logs: tauri::State<'_, logs::LogBuffer>,
+    level: String,
+    limit: usize,
+) -> DropResult<Vec<logs::LogRecord>> {
+    let level = level
+        .parse()
+        .map_err(|_| DropError::InvalidArgument(format!("unknown log level: {level}")))?;
+    Ok(logs.recent(level, limit))
+}
+
+#[tauri::command]
+pub async fn security_log(
+    proto: tauri::State<'_, Arc<protocol::Protocol>>,
+) -> DropResult<Vec<security_log::RejectedOffer>> {
+    Ok(proto.security_log().list().await)
+}
+
+/// Recent connection attempts, to `node_id` if given, newest first.
+#[tauri::command]
+pub async fn connection_audit(
+    proto: tauri::State<'_, Arc<protocol::Protocol>>,
+    node_id: Option<String>,
+) -> DropResult<Vec<diagnostics::ConnectionAttempt>> {
+    let node_id = node_id.as_deref().map(parse_node_id).transpose()?;
+    Ok(proto.diagnostics().list(node_id).await)
+}
+
+/// Steps to find out why no peers show up, with what to do about each problem.
+#[tauri::command]
+pub async fn troubleshoot(
+    proto: tauri::State<'_, Arc<protocol::Protocol>>,
+    permissions: tauri::State<'_, permissions::Permissions>,
+) -> DropResult<Vec<diagnostics::CheckResult>> {
+    use diagnostics::{Check, CheckResult, CheckState};
+
+    let mut checks = proto.troubleshoot().await;
+    // Only iOS and macOS have a permission for the local network.
+    if let Some(state) = permissions.state(permissions::Permission::LocalNetwork) {
+        let state = match state {
+            permissions::PermissionState::Granted => CheckState::Ok,
+            permissions::PermissionState::Denied => CheckState::Problem,
+            permissions::PermissionState::Unknown => CheckState::Unknown,
+        };
+        let after = checks
+            .iter()
+            .position(|check| check.check == Check::SameNetwork)
+            .map_or(0, |i| i + 1);
+        checks.insert(after, CheckResult::new(Check::Permission, state, None));
+    }
+    Ok(checks)
+}
+
+#[tauri::command]
+pub async fn history(
+    proto: tauri::State<'_, Arc<protocol::Protocol>>,
+) -> DropResult<Vec<history::HistoryEntry>> {
+    Ok(proto.history().list().await)
+}
+
+/// The received files matching `query`, for the library.
+#[tauri::command]
+pub async fn search_received(
+    proto: tauri::State<'_, Arc<protocol::Protocol>>,
+    query: library::SearchQuery,
+) -> DropResult<Vec<library::ReceivedFile>> {
+    Ok(proto.search_received(&query).await)
+}
+
+/// Sets the note of the transfer of `hash` with `node_id`, an empty note removes it.
+#[tauri::command(rename_all = "snake_case")]
+pub async fn annotate_transfer(
+    proto: tauri::State<'_, Arc<protocol::Protocol>>,
+    hash: String,
+    node_id: String,
+    note: String,
+) -> DropResult<()> {
+    proto.admin_lock().check()?;
+    let hash = parse_hash(&hash)?;
+    let node_id = parse_node_id(&node_id)?;
+    proto.annotate(node_id, hash, Some(note)).await?;
+    Ok(())
+}
+
+/// A downscaled preview of a received image, as a `data:` URL.
+#[tauri::command]
+pub async fn read_received_blob(
+    proto: tauri::State<'_, Arc<protocol::Protocol>>,
+    hash: String,
+) -> DropResult<String> {
+    let hash = parse_hash(&hash)?;
+    Ok(proto.preview(hash).await?)
+}
+
+/// Deleted transfers that can still be restored, newest first.
+#[tauri::command]
+pub async fn trash(
+    proto: tauri::State<'_, Arc<protocol::Protocol>>,
+) -> DropResult<Vec<history::HistoryEntry>> {
+    Ok(proto.history().trashed().await)
+}
+
+#[tauri::command]
+pub async fn delete_transfer(
+    proto: tauri::State<'_, Arc<protocol::Protocol>>,
+    hash: String,
+) -> DropResult<()> {
+    proto.admin_lock().check()?;
+    let hash = parse_hash(&hash)?;
+    proto.delete_transfer(hash).await?;
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn undo_delete(
+    proto: tauri::State<'_, Arc<protocol::Protocol>>,
+    hash: String,
+) -> DropResult<()> {
+    proto.admin_lock().check()?;
+    let hash = parse_hash(&hash)?;
+    proto.undo_delete(hash).await?;
+    Ok(())
+}
+
+/// Waiting and running transfers, in the order they were queued.
+#[tauri::command]
+pub async fn list_transfers(
+    proto: tauri::State<'_, Arc<protocol::Protocol>>,
+) -> DropResult<Vec<transfers::Transfer>> {
+    Ok(proto.transfers().list())
+}
+
+/// Opens a window following transfer `transfer_id`, or focuses it if already open.
+#[tauri::command(rename_all = "snake_case")]
+pub fn open_transfer_window(
+    app: tauri::AppHandle,
+    proto: tauri::State<'_, Arc<protocol::Protocol>>,
+    transfer_id: u64,
+) -> DropResult<()> {
+    let transfer = proto
+        .transfers()
+        .list()
+        .into_iter()
+        .find(|transfer| transfer.id == transfer_id)
+        .ok_or_else(|| DropError::InvalidArgument(format!("no transfer {transfer_id}")))?;
+    #[cfg(desktop)]
+    {
+        use tauri::Manager;
+
+        let label = format!("transfer-{transfer_id}");
+        if let Some(window) = app.get_webview_window(&label) {
+            window
+                .set_focus()
+                .map_err(|err| DropError::Internal(err.to_string()))?;
+            return Ok(());
+        }
+        let url = format!("index.html#transfer-{transfer_id}");
+        tauri::WebviewWindowBuilder::new(&app, label, tauri::WebviewUrl::App(url.into()))
+            .title(format!("{} - iroh-drop", transfer.name))
+            .inner_size(480., 560.)
+            .disable_drag_drop_handler()
+            .build()
+            .map_err(|err| DropError::Internal(err.to_string()))?;
+        Ok(())
+    }
+    #[cfg(mobile)]
+    {
+        let _ = (app, transfer);
+        let message = "transfer windows are only available on desktop".to_string();
+        Err(DropError::InvalidArgument(message))
+    }
+}
+
+/// The latest `limit` log messages about transfer `transfer_id`, newest first.
+#[tauri::command(rename_all = "snake_case")]
+pub fn transfer_logs(
+    proto: tauri::State<'_, Arc<protocol::Protocol>>,
+    logs: tauri::State<'_, logs::LogBuffer>,
+    transfer_id: u64,
+    limit: usize,
+) -> DropResult<Vec<logs::LogRecord>> {
+    let Some(stats) = proto
+        .transfers()
+        .stats()
+        .into_iter()
+        .find(|stats| stats.id == transfer_id)
+    else {
+        return Ok(Vec::new());
+    };
+    // Messages name the peer by its short id, or the file by its name.
+    let terms = [stats.node_id.fmt_short(), stats.name];
+    Ok(logs.search(&terms, limit))
+}
+
+/// Speed and connection path of the running and last finished transfers.
+#[tauri::command]
+pub async fn transfer_stats(
+    proto: tauri::State<'_, Arc<protocol::Protocol>>,
+) -> DropResult<Vec<transfers::TransferStats>> {
+    Ok(proto.transfers().stats())
+}
+
+/// Disk usage of the blob store, and how much of it can be removed.
+#[tauri::command]
+pub async fn storage_usage(
+    proto: tauri::State<'_, Arc<protocol::Protocol>>,
+) -> DropResult<protocol::StorageUsage> {
+    Ok(proto.storage_usage().await?)
+}
+
+/// The blobs in the store, received ones can be exported again with `export_blob`.
+#[tauri::command]
+pub async fn list_blobs(
+    proto: tauri::State<'_, Arc<protocol::Protocol>>,
+) -> DropResult<Vec<protocol::StoredBlob>> {
+    Ok(proto.list_blobs().await?)
+}
+
+/// Exports the received blob `hash` to `dest_path` from the store, without downloading it
+/// again.
+#[tauri::command(rename_all = "snake_case")]
+pub async fn export_blob(
+    app: tauri::AppHandle,
+    proto: tauri::State<'_, Arc<protocol::Protocol>>,
+    hash: String,
+    dest_path: String,
+    confirmed: Option<bool>,
+) -> DropResult<()> {
+    let hash = parse_hash(&hash)?;
+    let dest = parse_file_path(&dest_path)?;
+    let exported = proto
+        .export_blob(hash, &dest, confirmed.unwrap_or(false))
+        .await;
+    let entry = permissions::check_fs(&app, exported)?;
+
+    let event = automation::AutomationEvent {
+        kind: automation::AutomationEventKind::Exported,
+        name: entry.name,
+        hash: hash.to_string(),
+        size: entry.size,
+        from: entry.node_id.to_string(),
+        path: Some(dest),
+    };
+    automation::dispatch(&proto.settings().get().await.automation, event).await;
+
+    Ok(())
+}
+
+/// Pauses the running download `id`, keeping what was downloaded so far.
+#[tauri::command]
+pub fn pause_transfer(proto: tauri::State<'_, Arc<protocol::Protocol>>, id: u64) -> DropResult<()> {
+    proto.transfers().pause(id)?;
+    Ok(())
+}
+
+#[tauri::command]
+pub fn resume_transfer(
+    proto: tauri::State<'_, Arc<protocol::Protocol>>,
+    id: u64,
+) -> DropResult<()> {
+    proto.transfers().resume(id)?;
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn drop_stats(
+    proto: tauri::State<'_, Arc<protocol::Protocol>>,
+) -> DropResult<stats::DropStats> {
+    let entries = proto.history().list().await;
+    let peers = proto.list_peers().await;
+    Ok(stats::compute(&entries, &peers))
+}
+
+#[tauri::command]
+pub async fn list_peers(
+    proto: tauri::State<'_, Arc<protocol::Protocol>>,
+) -> DropResult<Vec<protocol::PeerInfo>> {
+    Ok(proto.list_peers().await)
+}
+
+pub(crate) fn parse_node_id(node_id: &str) -> DropResult<NodeId> {
+    node_id
+        .parse()
+        .map_err(|_| DropError::InvalidArgument(format!("invalid node id: {node_id}")))
+}
+
+fn parse_hash(hash: &str) -> DropResult<Hash> {
+    hash.parse()
+        .map_err(|_| DropError::InvalidArgument(format!("invalid hash: {hash}")))
+}
+
+fn parse_file_path(path: &str) -> DropResult<PathBuf> {
+    let path = PathBuf::from(path.trim());
+    if !path.is_absolute() || path.file_name().is_none() {
+        let message = format!("not a path to a file: {}", path.display());
+        return Err(DropError::InvalidArgument(message));
+    }
+    Ok(path)
+}
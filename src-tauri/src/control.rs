@@ -0,0 +1,277 @@
+//! Local control API, so scripts and launchers like Raycast or Alfred can send files without
+//! the window.
+//!
+//! When enabled in [`ControlSettings`], a WebSocket server listens on localhost. Clients
+//! authenticate with the token of the settings, as `Authorization: Bearer <token>` header or
+//! `?token=<token>` query. Requests mirror the commands of the UI,
+//!
+//! ```json
+//! {"id": 1, "method": "send_file_path", "params": {"node_id": "…", "path": "/tmp/a.txt"}}
+//! ```
+//!
+//! and are answered with `{"id": 1, "result": …}` or `{"id": 1, "error": {"code", "message"}}`.
+//! The transfer events of the UI are pushed as `{"event": "file-sent", "payload": …}`.
+
+use std::net::Ipv4Addr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use futures_util::{SinkExt, StreamExt};
+use iroh_drop_core::error::{DropError, DropResult};
+use iroh_drop_core::protocol::Protocol;
+use iroh_drop_core::settings::ControlSettings;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Listener, Manager};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio_tungstenite::tungstenite::handshake::server::{ErrorResponse, Request, Response};
+use tokio_tungstenite::tungstenite::http::StatusCode;
+use tokio_tungstenite::tungstenite::Message;
+
+use crate::commands;
+
+/// Events of the UI that are pushed to clients.
+const EVENTS: &[&str] = &[
+    "incoming-file",
+    "file-downloaded",
+    "text-received",
+    "file-sent",
+    "sent-file-delivered",
+    "group-sent",
+    "offer-rejected",
+    "transfer-updated",
+    "transfer-progress",
+    "upload-progress",
+    "transfer-finished",
+    "peer-online",
+    "peer-offline",
+];
+
+/// The running server, managed by the app.
+#[derive(Default)]
+pub struct ControlServer {
+    /// Port and accept loop of the running server.
+    running: Mutex<Option<(u16, tauri::async_runtime::JoinHandle<()>)>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Call {
+    id: u64,
+    #[serde(flatten)]
+    method: Method,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "method", content = "params", rename_all = "snake_case")]
+enum Method {
+    ListPeers,
+    ListTransfers,
+    /// Sends the file at the absolute `path`.
+    SendFilePath {
+        node_id: String,
+        path: PathBuf,
+    },
+    /// Sends the file at the absolute `path` to all members of a peer group.
+    SendToGroup {
+        group: String,
+        path: PathBuf,
+    },
+}
+
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+enum Reply {
+    Result {
+        id: u64,
+        result: serde_json::Value,
+    },
+    /// `id` is `None` if the request could not be parsed.
+    Error {
+        id: Option<u64>,
+        error: DropError,
+    },
+    Event {
+        event: &'static str,
+        payload: serde_json::Value,
+    },
+}
+
+/// Starts or stops the server to match `settings`.
+pub async fn apply(app: &AppHandle, settings: &ControlSettings) -> DropResult<()> {
+    let server = app.state::<ControlServer>();
+    let port = settings.enabled.then_some(settings.port);
+    let mut running = server.running.lock().unwrap().take();
+    if running.as_ref().map(|(port, _)| *port) == port {
+        *server.running.lock().unwrap() = running;
+        return Ok(());
+    }
+    if let Some((port, task)) = running.take() {
+        task.abort();
+        tracing::info!("stopped the control API on port {port}");
+    }
+    let Some(port) = port else {
+        return Ok(());
+    };
+
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port)).await?;
+    tracing::info!("serving the control API on port {port}");
+    let handle = app.clone();
+    let task = tauri::async_runtime::spawn(async move {
+        loop {
+            let stream = match listener.accept().await {
+                Ok((stream, _)) => stream,
+                Err(err) => {
+                    tracing::warn!("failed to accept a control connection: {err}");
+                    continue;
+                }
+            };
+            let handle = handle.clone();
+            tauri::async_runtime::spawn(async move {
+                if let Err(err) = serve(&handle, stream).await {
+                    tracing::debug!("control connection failed: {err:#}");
+                }
+            });
+        }
+    });
+    *server.running.lock().unwrap() = Some((port, task));
+    Ok(())
+}
+
+async fn serve(app: &AppHandle, stream: TcpStream) -> anyhow::Result<()> {
+    let proto = app.state::<Arc<Protocol>>().inner().clone();
+    let token = proto.settings().get().await.control.token;
+    let callback =
+        |request: &Request, response: Response| authorize(request, &token).map(|()| response);
+    let ws = tokio_tungstenite::accept_hdr_async(stream, callback).await?;
+    let (mut sink, mut stream) = ws.split();
+
+    let (s, mut r) = mpsc::unbounded_channel();
+    let writer = tauri::async_runtime::spawn(async move {
+        while let Some(reply) = r.recv().await {
+            let Ok(text) = serde_json::to_string(&reply) else {
+                continue;
+            };
+            if sink.send(Message::Text(text)).await.is_err() {
+                break;
+            }
+        }
+    });
+    let listeners: Vec<_> = EVENTS
+        .iter()
+        .map(|&event| {
+            let s = s.clone();
+            app.listen_any(event, move |e| {
+                let payload = serde_json::from_str(e.payload()).unwrap_or_default();
+                s.send(Reply::Event { event, payload }).ok();
+            })
+        })
+        .collect();
+
+    let result = async {
+        while let Some(message) = stream.next().await {
+            let text = match message? {
+                Message::Text(text) => text,
+                Message::Close(_) => break,
+                _ => continue,
+            };
+            // Disabling the API or changing the token also ends open connections.
+            let settings = proto.settings().get().await.control;
+            if !settings.enabled || settings.token != token {
+                break;
+            }
+            let call = match serde_json::from_str::<Call>(&text) {
+                Ok(call) => call,
+                Err(err) => {
+                    let error = DropError::InvalidArgument(err.to_string());
+                    s.send(Reply::Error { id: None, error }).ok();
+                    continue;
+                }
+            };
+            // Sends take a while, answer other requests meanwhile.
+            let proto = proto.clone();
+            let s = s.clone();
+            tauri::async_runtime::spawn(async move {
+                let reply = match run(&proto, call.method).await {
+                    Ok(result) => Reply::Result {
+                        id: call.id,
+                        result,
+                    },
+                    Err(error) => Reply::Error {
+                        id: Some(call.id),
+                        error,
+                    },
+                };
+                s.send(reply).ok();
+            });
+        }
+        anyhow::Ok(())
+    }
+    .await;
+
+    for id in listeners {
+        app.unlisten(id);
+    }
+    writer.abort();
+    result
+}
+
+async fn run(proto: &Protocol, method: Method) -> DropResult<serde_json::Value> {
+    let result = match method {
+        Method::ListPeers => serde_json::to_value(proto.list_peers().await),
+        Method::ListTransfers => serde_json::to_value(proto.transfers().list()),
+        Method::SendFilePath { node_id, path } => {
+            let node_id = commands::parse_node_id(&node_id)?;
+            check_absolute(&path)?;
+            commands::send_path(proto, node_id, &path).await?;
+            Ok(serde_json::Value::Null)
+        }
+        Method::SendToGroup { group, path } => {
+            check_absolute(&path)?;
+            let results: Vec<_> = commands::send_path_to_group(proto, &group, &path)
+                .await?
+                .into_iter()
+                .map(|(node_id, error)| (node_id.to_string(), error))
+                .collect();
+            serde_json::to_value(results)
+        }
+    };
+    result.map_err(|err| DropError::Internal(err.to_string()))
+}
+
+/// Paths are resolved by the app, not the client, so relative ones make no sense.
+fn check_absolute(path: &Path) -> DropResult<()> {
+    if !path.is_absolute() {
+        let message = format!("{} is not an absolute path", path.display());
+        return Err(DropError::InvalidArgument(message));
+    }
+    Ok(())
+}
+
+/// Accepts clients presenting `token`.
+fn authorize(request: &Request, token: &str) -> Result<(), ErrorResponse> {
+    // Any web page can connect to localhost, but browsers always send their origin.
+    if request.headers().contains_key("origin") {
+        return Err(error_response(StatusCode::FORBIDDEN));
+    }
+    let bearer = request
+        .headers()
+        .get("authorization")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    let query = request
+        .uri()
+        .query()
+        .into_iter()
+        .flat_map(|query| query.split('&'))
+        .find_map(|pair| pair.strip_prefix("token="));
+    match bearer.or(query) {
+        Some(given) if !token.is_empty() && given == token => Ok(()),
+        _ => Err(error_response(StatusCode::UNAUTHORIZED)),
+    }
+}
+
+fn error_response(status: StatusCode) -> ErrorResponse {
+    let mut response = ErrorResponse::new(None);
+    *response.status_mut() = status;
+    response
+}
@@ -0,0 +1,82 @@
+//! Session bus interface, so scripts and status bars can control drops on Linux.
+
+use std::path::Path;
+use std::sync::Arc;
+
+use iroh::net::NodeId;
+use iroh_drop_core::metadata::FileMetadata;
+use iroh_drop_core::protocol::Protocol;
+use zbus::fdo;
+
+pub const BUS_NAME: &str = "org.irohdrop.Drop";
+pub const OBJECT_PATH: &str = "/org/irohdrop/Drop";
+
+struct DropService {
+    proto: Arc<Protocol>,
+}
+
+#[zbus::interface(name = "org.irohdrop.Drop")]
+impl DropService {
+    /// Sends the file at `path` to `node_id`.
+    async fn send_file(&self, node_id: &str, path: &str) -> fdo::Result<()> {
+        let node_id: NodeId = node_id
+            .parse()
+            .map_err(|_| fdo::Error::InvalidArgs("invalid node id".into()))?;
+        let path = Path::new(path);
+        let file_name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .ok_or_else(|| fdo::Error::InvalidArgs("invalid path".into()))?;
+        let metadata = tokio::fs::metadata(path)
+            .await
+            .map_err(|e| fdo::Error::IOError(e.to_string()))?;
+        let file_data = tokio::fs::read(path)
+            .await
+            .map_err(|e| fdo::Error::IOError(e.to_string()))?;
+        self.proto
+            .send_file(node_id, file_name, file_data, FileMetadata::from_fs(&metadata))
+            .await
+            .map_err(|e| fdo::Error::Failed(e.to_string()))
+    }
+
+    /// All known peers as `(node_id, name, online)`.
+    async fn list_peers(&self) -> Vec<(String, String, bool)> {
+        self.proto
+            .list_peers()
+            .await
+            .into_iter()
+            .map(|peer| (peer.node_id.to_string(), peer.name, peer.online))
+            .collect()
+    }
+
+    /// Number of running downloads and the most recent transfers as
+    /// `(direction, name, size, timestamp)`.
+    async fn transfer_status(&self) -> (u32, Vec<(String, String, u64, u64)>) {
+        let recent = self
+            .proto
+            .history()
+            .list()
+            .await
+            .into_iter()
+            .take(20)
+            .map(|entry| {
+                (
+                    entry.direction.as_str().to_string(),
+                    entry.name,
+                    entry.size,
+                    entry.timestamp,
+                )
+            })
+            .collect();
+        (self.proto.active_downloads() as u32, recent)
+    }
+}
+
+/// Registers the interface on the session bus, the connection must be kept alive to keep serving.
+pub async fn serve(proto: Arc<Protocol>) -> zbus::Result<zbus::Connection> {
+    zbus::connection::Builder::session()?
+        .name(BUS_NAME)?
+        .serve_at(OBJECT_PATH, DropService { proto })?
+        .build()
+        .await
+}
@@ -0,0 +1,119 @@
+//! Kiosk mode for meeting-room machines collecting presentations.
+//!
+//! A full-screen window shows a guest ticket, see [`iroh_drop_core::guest`]. Guests can send
+//! files whatever the receive mode, and every file received during the session is saved to
+//! a folder of its own.
+
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use iroh::blobs::Hash;
+use iroh_drop_core::error::{DropError, DropResult};
+use iroh_drop_core::protocol::Protocol;
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::{pairing, permissions, storage};
+
+/// Label of the kiosk window.
+const WINDOW: &str = "kiosk";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct KioskSession {
+    pub ticket: String,
+    /// The guest ticket as an SVG QR code
+    pub qr: String,
+    /// Where received files are saved
+    pub folder: PathBuf,
+    /// Names of the files saved so far
+    pub files: Vec<String>,
+}
+
+/// The running kiosk session, if any.
+#[derive(Debug, Clone, Default)]
+pub struct Kiosk(Arc<Mutex<Option<KioskSession>>>);
+
+impl Kiosk {
+    pub fn session(&self) -> Option<KioskSession> {
+        self.0.lock().unwrap().clone()
+    }
+
+    /// Starts a session accepting guests for `duration`, and opens the kiosk window.
+    pub async fn start(
+        &self,
+        app: &AppHandle,
+        proto: &Protocol,
+        duration: Duration,
+    ) -> DropResult<KioskSession> {
+        if cfg!(mobile) {
+            let message = "kiosk mode is only available on desktop".to_string();
+            return Err(DropError::InvalidArgument(message));
+        }
+        let ticket = proto.guest_ticket(duration).await?;
+        let started = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        let folder = storage::export_dir(app)?
+            .join("iroh-drop-kiosk")
+            .join(format!("session-{started}"));
+        std::fs::create_dir_all(&folder)?;
+        let session = KioskSession {
+            qr: pairing::qr_svg(&ticket)?,
+            ticket: ticket.to_string(),
+            folder,
+            files: Vec::new(),
+        };
+        *self.0.lock().unwrap() = Some(session.clone());
+        tracing::info!("started a kiosk session in {}", session.folder.display());
+
+        #[cfg(desktop)]
+        if app.get_webview_window(WINDOW).is_none() {
+            tauri::WebviewWindowBuilder::new(
+                app,
+                WINDOW,
+                tauri::WebviewUrl::App("index.html#kiosk".into()),
+            )
+            .title("iroh-drop kiosk")
+            .fullscreen(true)
+            .disable_drag_drop_handler()
+            .build()
+            .map_err(|err| DropError::Internal(err.to_string()))?;
+        }
+        Ok(session)
+    }
+
+    /// Ends the session and closes the kiosk window, the guest ticket stays valid.
+    pub fn stop(&self, app: &AppHandle) {
+        self.0.lock().unwrap().take();
+        if let Some(window) = app.get_webview_window(WINDOW) {
+            window.close().ok();
+        }
+    }
+
+    /// Saves the received file `hash` to the session folder, if a session is running.
+    ///
+    /// Quarantined files are not saved, they stay in the app like outside of kiosk mode.
+    pub async fn on_downloaded(
+        &self,
+        app: &AppHandle,
+        proto: &Protocol,
+        hash: Hash,
+    ) -> DropResult<()> {
+        let Some(folder) = self.session().map(|session| session.folder) else {
+            return Ok(());
+        };
+        let exported = proto.export_received(hash, &folder, false).await;
+        let (path, _) = permissions::check_fs(app, exported)?;
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        if let Some(ref mut session) = *self.0.lock().unwrap() {
+            session.files.push(name.clone());
+        }
+        app.emit("kiosk-file", name).ok();
+        Ok(())
+    }
+}
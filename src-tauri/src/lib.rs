@@ -1,120 +1,95 @@
 use std::sync::Arc;
 
-use futures_lite::stream::StreamExt;
-use iroh::net::{discovery::local_swarm_discovery::NAME as SWARM_DISCOVERY_NAME, NodeAddr, NodeId};
-use log::info;
-use tauri::Emitter;
+use tracing::info;
+use tauri::{Emitter, Manager};
 use tauri_plugin_log::{Target, TargetKind};
-use tokio::sync::mpsc;
 
-mod protocol;
+use iroh_drop_core::{history, node, peer_cache, persistence, protocol, settings};
 
-#[tauri::command]
-async fn node_id(iroh: tauri::State<'_, iroh::node::MemNode>) -> Result<String, ()> {
-    let id = iroh.node_id().to_string();
-    Ok(id)
-}
-
-#[tauri::command(rename_all = "snake_case")]
-async fn send_file(
-    proto: tauri::State<'_, Arc<protocol::Protocol>>,
-    node_id: String,
-    file_name: String,
-    file_data: Vec<u8>,
-) -> Result<(), String> {
-    let node_id: NodeId = node_id.parse::<NodeId>().map_err(|e| e.to_string())?;
-    proto
-        .send_file(node_id, file_name, file_data)
-        .await
-        .map_err(|e| e.to_string())?;
+mod automation;
+mod autostart;
+mod background;
+mod clipboard;
+mod commands;
+mod control;
+#[cfg(target_os = "linux")]
+mod dbus;
+mod kiosk;
+mod link;
+mod logs;
+#[cfg(mobile)]
+mod media_picker;
+mod notifications;
+mod pairing;
+mod permissions;
+mod share_target;
+mod storage;
+#[cfg(all(desktop, feature = "tray"))]
+mod tray;
 
-    Ok(())
-}
-
-#[tauri::command]
-async fn discover(
-    iroh: tauri::State<'_, iroh::node::MemNode>,
-    proto: tauri::State<'_, Arc<protocol::Protocol>>,
-) -> Result<Vec<(String, String)>, ()> {
-    use iroh::net::endpoint::Source;
-
-    let limit = std::time::Duration::from_secs(60);
-
-    // for remote in iroh.endpoint().remote_info_iter() {
-    //     for (source, last_seen) in remote.sources() {
-    //         if let Source::Discovery { name } = source {
-    //             if name == SWARM_DISCOVERY_NAME && last_seen <= limit {
-    //                 let addrs = remote.addrs.iter().map(|i| i.addr).collect();
-    //                 let node_addr = NodeAddr::from_parts(
-    //                     remote.node_id,
-    //                     remote.relay_url.clone().map(Into::into),
-    //                     addrs,
-    //                 );
-    //                 match proto.send_intro(node_addr).await {
-    //                     Ok(name) => {
-    //                         log::info!("discovered {}", remote.node_id);
-    //                     }
-    //                     Err(err) => {
-    //                         log::warn!("failed to intro: {:?}", err);
-    //                     }
-    //                 }
-    //             }
-    //         }
-    //     }
-    // }
-
-    let eps: Vec<_> = proto
-        .known_nodes()
-        .await
-        .into_iter()
-        .map(|(id, name)| (name, id.to_string()))
-        .collect();
-
-    println!("found {} nodes", eps.len());
-
-    Ok(eps)
+/// Shuts down the node, giving running transfers some time to finish.
+fn shutdown(app: &tauri::AppHandle) {
+    info!("shutting down");
+    let node = app.state::<iroh::node::MemNode>().inner().clone();
+    if let Err(err) = tauri::async_runtime::block_on(node.shutdown()) {
+        tracing::warn!("failed to shut down the node: {err:?}");
+    }
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
-    let (iroh_node, proto, mut r) = tauri::async_runtime::block_on(async move {
-        info!("starting iroh");
-        let builder = iroh::node::Node::memory()
-            .node_discovery(iroh::node::DiscoveryConfig::Default)
-            .build()
-            .await
-            .expect("failed to build iroh");
-
-        let (s, r) = mpsc::channel(64);
-        let proto = protocol::Protocol::new(
-            "drop-1".to_string(),
-            builder.client().clone(),
-            builder.endpoint().clone(),
-            s,
-        );
-        let node = builder
-            .accept(protocol::ALPN.to_vec(), proto.clone())
-            .spawn()
-            .await
-            .expect("failed to spawn iroh");
-        (node, proto, r)
-    });
+    let logs = logs::LogBuffer::default();
+    {
+        use tracing_subscriber::layer::SubscriberExt;
+        let subscriber = tracing_subscriber::registry().with(logs.clone());
+        // Only fails if there is a subscriber already, which then gets the messages.
+        tracing::subscriber::set_global_default(subscriber).ok();
+    }
 
-    info!("inner run");
-    let endpoint = iroh_node.endpoint().clone();
-    let protocol = proto.clone();
-
-    tauri::Builder::default()
+    let builder = tauri::Builder::default()
+        .manage(logs)
+        .manage(control::ControlServer::default())
         .setup(|app| {
             info!("setup");
 
+            let backend = persistence::open(app.path().app_config_dir()?)?;
+            let secret_key = node::load_secret_key(backend.as_ref())?;
+            let settings = settings::SettingsStore::load(backend.clone())?;
+            let history = history::History::load(backend.clone())?;
+            let peer_cache = peer_cache::PeerCache::load(backend.clone())?;
+            // Importing a profile replaces the secret key.
+            app.manage(backend);
+            let storage_dir = storage::staging_dir(app.handle())?;
+            std::fs::create_dir_all(&storage_dir)?;
+            info!("starting iroh");
+            let node::DropNode {
+                node: iroh_node,
+                protocol: proto,
+                events: mut r,
+            } = tauri::async_runtime::block_on(node::spawn(
+                "drop-1".to_string(),
+                secret_key,
+                Arc::new(settings),
+                history,
+                peer_cache,
+                storage_dir,
+            ))
+            .expect("failed to start iroh");
+            app.manage(iroh_node);
+            app.manage(proto.clone());
+
             #[cfg(not(mobile))]
             {
+                // The tray icon brings the window back.
+                let start_minimized = cfg!(feature = "tray")
+                    && autostart::launched_at_login()
+                    && tauri::async_runtime::block_on(proto.settings().get()).start_minimized;
                 tauri::WebviewWindowBuilder::new(
                     app,
                     "main",
                     tauri::WebviewUrl::App("index.html".into()),
                 )
+                .visible(!start_minimized)
                 .inner_size(800., 600.)
                 .title("iroh-drop")
                 .disable_drag_drop_handler()
@@ -131,42 +106,192 @@ pub fn run() {
             }
 
             let handle = app.handle().clone();
+            permissions::init(&handle);
+            share_target::check_inbox(&handle);
+            // The discovery tasks run on the runtime of the app.
+            tauri::async_runtime::block_on(async {
+                proto.spawn_network_watch();
+                proto.spawn_paired_reconnect();
+                proto.spawn_gc();
+                proto.spawn_keep_alive();
+                proto.spawn_discovery()
+            })?;
+
+            #[cfg(all(desktop, feature = "tray"))]
+            tray::create(&handle)?;
+            let control = tauri::async_runtime::block_on(proto.settings().get()).control;
+            if let Err(err) = tauri::async_runtime::block_on(control::apply(&handle, &control)) {
+                tracing::warn!("failed to start the control API: {err}");
+            }
+            #[cfg(target_os = "linux")]
+            {
+                match tauri::async_runtime::block_on(dbus::serve(proto.clone())) {
+                    Ok(connection) => {
+                        app.manage(connection);
+                    }
+                    Err(err) => tracing::warn!("failed to register on the session bus: {err}"),
+                }
+            }
 
             tauri::async_runtime::spawn(async move {
-                info!("spawning discovery stream");
-                let mut stream = endpoint.discovery().unwrap().subscribe().unwrap();
-                loop {
-                    tokio::select! {
-                        Some(item) = stream.next() => {
-                            if item.provenance == SWARM_DISCOVERY_NAME {
-                                let mut node_addr = NodeAddr::new(item.node_id);
-                                node_addr.info = item.addr_info;
-                                let proto = proto.clone();
-                                let handle = handle.clone();
-                                tauri::async_runtime::spawn(async move {
-                                    // if !proto.is_known_node(&item.node_id).await {
-                                    match proto.send_intro(node_addr).await {
-                                        Ok(name) => {
-                                            handle.emit("discovery", (name, item.node_id.to_string())).ok();
-                                        }
-                                        Err(err) => {
-                                            eprintln!("failed to discover: {:?}", err);
-                                            proto.mark_protocol_missmatch(&item.node_id).await;
-                                        }
-                                    }
-                                    // }
-                                });
-                            }
-                        }
-                        Some(msg) = r.recv() => {
-                            match msg {
-                                protocol::LocalProtocolMessage::FileDownloaded { name, hash, size } => {
-                                    handle.emit("file-downloaded", (name, hash.to_string(), size)).ok();
+                while let Some(msg) = r.recv().await {
+                    match msg {
+                        protocol::LocalProtocolMessage::IncomingFile { from, sender, name, hash, size } => {
+                            handle.emit("incoming-file", (sender.clone(), from.to_string(), name.clone(), hash.to_string(), size)).ok();
+                            notifications::notify(
+                                &handle,
+                                "Incoming file",
+                                &format!("{sender} is sending {name}"),
+                                &hash.to_string(),
+                            );
+                        }
+                        protocol::LocalProtocolMessage::FileDownloaded { from, name, hash, size, already_had } => {
+                            handle.emit("file-downloaded", (name.clone(), hash.to_string(), size, already_had)).ok();
+                            notifications::notify(
+                                &handle,
+                                "File received",
+                                &format!("{name} ({size} bytes)"),
+                                &hash.to_string(),
+                            );
+                            background::received(&handle, name.clone());
+                            let event = automation::AutomationEvent {
+                                kind: automation::AutomationEventKind::Received,
+                                name,
+                                hash: hash.to_string(),
+                                size,
+                                from: from.to_string(),
+                                path: None,
+                            };
+                            let settings = proto.settings().get().await.automation;
+                            let proto = proto.clone();
+                            let handle = handle.clone();
+                            tauri::async_runtime::spawn(async move {
+                                let kiosk = handle.state::<kiosk::Kiosk>();
+                                if let Err(err) = kiosk.on_downloaded(&handle, &proto, hash).await {
+                                    tracing::warn!("failed to save {hash} for the kiosk: {err}");
                                 }
-                            }
-                        },
-                        else => {
-                            break;
+                                link::on_downloaded(&handle, &proto, &name, hash, size).await;
+                                automation::dispatch(&settings, event).await;
+                                if let Err(err) = automation::run_workflow(&proto, hash).await {
+                                    tracing::warn!("failed to run the workflow for {hash}: {err:#}");
+                                }
+                            });
+                            #[cfg(all(desktop, feature = "tray"))]
+                            tray::refresh(&handle).await;
+                        }
+                        protocol::LocalProtocolMessage::FileSent { to, name, hash, size, verified, already_had } => {
+                            handle.emit("file-sent", (to.to_string(), name, hash.to_string(), size, verified, already_had)).ok();
+                            #[cfg(all(desktop, feature = "tray"))]
+                            tray::refresh(&handle).await;
+                        }
+                        protocol::LocalProtocolMessage::SentFileDelivery { to, name, hash, status } => {
+                            handle.emit("sent-file-delivered", (to.to_string(), name, hash.to_string(), status)).ok();
+                        }
+                        protocol::LocalProtocolMessage::ContentMismatch { name, hash, message } => {
+                            handle.emit("content-mismatch", (name, hash.to_string(), message)).ok();
+                        }
+                        protocol::LocalProtocolMessage::TransferWarning { node_id, message } => {
+                            handle.emit("transfer-warning", (node_id.to_string(), message)).ok();
+                        }
+                        protocol::LocalProtocolMessage::PeerOnline { node_id, name } => {
+                            handle.emit("peer-online", (name, node_id.to_string())).ok();
+                            // Catch up on settings and notes changed while the device was away.
+                            let proto = proto.clone();
+                            tauri::async_runtime::spawn(async move {
+                                proto.sync_settings_with(node_id).await;
+                                proto.sync_annotations_with(node_id).await;
+                            });
+                        }
+                        protocol::LocalProtocolMessage::PeerOffline { node_id } => {
+                            handle.emit("peer-offline", node_id.to_string()).ok();
+                        }
+                        protocol::LocalProtocolMessage::PeerIncompatible { node_id } => {
+                            handle.emit("peer-incompatible", node_id.to_string()).ok();
+                        }
+                        protocol::LocalProtocolMessage::PeerRenamed { node_id, name } => {
+                            handle.emit("peer-renamed", (node_id.to_string(), name)).ok();
+                        }
+                        protocol::LocalProtocolMessage::IdentityWarning { node_id, name, warning } => {
+                            handle.emit("identity-warning", (node_id.to_string(), name, warning)).ok();
+                        }
+                        protocol::LocalProtocolMessage::IdentityVerification { node_id, name, code } => {
+                            handle.emit("identity-verification", (node_id.to_string(), name, code)).ok();
+                        }
+                        protocol::LocalProtocolMessage::GroupSent {
+                            group,
+                            name,
+                            results,
+                        } => {
+                            let results: Vec<_> = results
+                                .into_iter()
+                                .map(|(node_id, error)| (node_id.to_string(), error))
+                                .collect();
+                            handle.emit("group-sent", (group, name, results)).ok();
+                        }
+                        protocol::LocalProtocolMessage::PeerUnreachable { node_id, error } => {
+                            handle.emit("peer-unreachable", (node_id.to_string(), error)).ok();
+                        }
+                        protocol::LocalProtocolMessage::OfferRejected { sender, name, size, reason } => {
+                            handle.emit("offer-rejected", (sender, name, size, reason)).ok();
+                        }
+                        protocol::LocalProtocolMessage::SettingsChanged => {
+                            handle.emit("settings-changed", ()).ok();
+                        }
+                        protocol::LocalProtocolMessage::HistoryChanged => {
+                            handle.emit("history-changed", ()).ok();
+                        }
+                        protocol::LocalProtocolMessage::TransfersActive { active } => {
+                            background::update(&handle, active);
+                        }
+                        protocol::LocalProtocolMessage::TransferUpdated(transfer) => {
+                            handle.emit("transfer-updated", transfer).ok();
+                        }
+                        protocol::LocalProtocolMessage::TransferFinished { id } => {
+                            handle.emit("transfer-finished", id).ok();
+                        }
+                        protocol::LocalProtocolMessage::TransferProgress {
+                            id,
+                            offset,
+                            size,
+                            bps,
+                            paused,
+                        } => {
+                            handle
+                                .emit("transfer-progress", (id, offset, size, bps, paused))
+                                .ok();
+                        }
+                        protocol::LocalProtocolMessage::UploadProgress {
+                            id,
+                            offset,
+                            size,
+                            bps,
+                        } => {
+                            handle.emit("upload-progress", (id, offset, size, bps)).ok();
+                        }
+                        protocol::LocalProtocolMessage::IncomingPreview { hash, text } => {
+                            handle.emit("incoming-preview", (hash.to_string(), text)).ok();
+                        }
+                        protocol::LocalProtocolMessage::VisibilityChanged { invisible } => {
+                            handle.emit("visibility-changed", invisible).ok();
+                            #[cfg(all(desktop, feature = "tray"))]
+                            tray::refresh(&handle).await;
+                        }
+                        protocol::LocalProtocolMessage::UnknownNetwork { network } => {
+                            handle.emit("unknown-network", (network.id, network.label)).ok();
+                        }
+                        protocol::LocalProtocolMessage::TrashPurged { hashes } => {
+                            handle.emit("trash-purged", hashes).ok();
+                        }
+                        protocol::LocalProtocolMessage::SwapProgress {
+                            node_id,
+                            sent,
+                            received,
+                            to_send,
+                            to_receive,
+                        } => {
+                            let node_id = node_id.to_string();
+                            let progress = (node_id, sent, received, to_send, to_receive);
+                            handle.emit("swap-progress", progress).ok();
                         }
                     }
                 }
@@ -174,7 +299,40 @@ pub fn run() {
 
             Ok(())
         })
+        .on_window_event(|window, event| match event {
+            tauri::WindowEvent::Focused(true) => {
+                background::set_foreground(window.app_handle(), true);
+                notifications::on_focus(window.app_handle());
+                share_target::check_inbox(window.app_handle());
+            }
+            tauri::WindowEvent::Focused(false) => {
+                background::set_foreground(window.app_handle(), false);
+            }
+            #[cfg(all(desktop, feature = "tray"))]
+            tauri::WindowEvent::CloseRequested { api, .. } => {
+                let proto = window.state::<Arc<protocol::Protocol>>();
+                let settings = tauri::async_runtime::block_on(proto.settings().get());
+                if settings.close_to_tray {
+                    // Keep the node running in the background to receive files.
+                    api.prevent_close();
+                    window.hide().ok();
+                }
+            }
+            _ => {}
+        })
+        .manage(notifications::PendingFocus::default())
+        .manage(kiosk::Kiosk::default())
+        .manage(permissions::Permissions::default())
+        .manage(background::Background::default())
+        .manage(share_target::SharedFiles::default())
         .plugin(tauri_plugin_shell::init())
+        .plugin(tauri_plugin_clipboard_manager::init())
+        .plugin(tauri_plugin_dialog::init());
+    #[cfg(feature = "notifications")]
+    let builder = builder.plugin(tauri_plugin_notification::init());
+    #[cfg(desktop)]
+    let builder = builder.plugin(autostart::plugin());
+    builder
         .plugin(
             tauri_plugin_log::Builder::new()
                 .targets([
@@ -185,9 +343,102 @@ pub fn run() {
                 .level(log::LevelFilter::Info)
                 .build(),
         )
-        .manage(iroh_node)
-        .manage(protocol)
-        .invoke_handler(tauri::generate_handler![discover, send_file, node_id])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .invoke_handler(tauri::generate_handler![
+            commands::list_peers,
+            commands::send_file,
+            commands::send_file_to_many,
+            commands::send_to_group,
+            commands::peer_groups,
+            commands::set_peer_group,
+            commands::send_clipboard_file,
+            commands::pick_and_send,
+            commands::pick_media_and_send,
+            commands::shared_files,
+            commands::send_shared_file,
+            commands::dismiss_shared_file,
+            commands::preflight,
+            commands::queue_swap_file,
+            commands::swap_queue,
+            commands::clear_swap_queue,
+            commands::start_swap,
+            commands::reshare,
+            commands::create_share_link,
+            commands::share_transfer,
+            commands::storage_status,
+            commands::set_up_encrypted_storage,
+            commands::unlock_storage,
+            commands::lock_storage,
+            commands::admin_lock_status,
+            commands::enable_admin_lock,
+            commands::disable_admin_lock,
+            commands::unlock_admin_lock,
+            commands::lock_admin_lock,
+            commands::export_profile,
+            commands::import_profile,
+            commands::node_id,
+            commands::history,
+            commands::search_received,
+            commands::annotate_transfer,
+            commands::read_received_blob,
+            commands::trash,
+            commands::delete_transfer,
+            commands::undo_delete,
+            commands::my_ticket,
+            commands::connect_by_ticket,
+            commands::guest_ticket,
+            commands::own_device_ticket,
+            commands::leave_device_group,
+            commands::handle_record,
+            commands::start_kiosk,
+            commands::stop_kiosk,
+            commands::kiosk_session,
+            commands::export_received,
+            commands::my_qr_code,
+            commands::pair_from_qr,
+            commands::get_settings,
+            commands::set_settings,
+            commands::generate_control_token,
+            commands::autostart_enabled,
+            commands::set_autostart,
+            commands::drop_stats,
+            commands::transfer_stats,
+            commands::open_transfer_window,
+            commands::transfer_logs,
+            commands::storage_usage,
+            commands::list_blobs,
+            commands::export_blob,
+            commands::security_log,
+            commands::connection_audit,
+            commands::troubleshoot,
+            commands::set_own_device,
+            commands::set_favorite,
+            commands::verify_identity,
+            commands::confirm_identity,
+            commands::accept_once,
+            commands::recent_logs,
+            commands::permissions_status,
+            commands::report_permission,
+            commands::open_permission_settings,
+            commands::open_link,
+            commands::peer_connection_info,
+            commands::relay_status,
+            commands::set_receive_mode,
+            commands::set_send_transforms,
+            commands::set_room,
+            commands::set_invisible,
+            commands::current_network,
+            commands::set_network_trusted,
+            commands::is_invisible,
+            commands::list_transfers,
+            commands::pause_transfer,
+            commands::resume_transfer
+        ])
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|app, event| match event {
+            tauri::RunEvent::Exit => shutdown(app),
+            #[cfg(any(target_os = "macos", target_os = "ios"))]
+            tauri::RunEvent::Opened { urls } => share_target::opened(app, &urls),
+            _ => {}
+        });
 }
@@ -0,0 +1,341 @@
+//! Links received as text, e.g. a URL sent from the clipboard.
+//!
+//! A received text file that holds nothing but a web address is announced to the UI with a
+//! `text-received` event, so it can show the link with an "Open in browser" button. With
+//! `link_previews` on in the settings, the title, description and image of the page are
+//! fetched first, from its `<title>` and OpenGraph `<meta>` tags. This is off by default, as
+//! it tells the site the address of this device.
+//!
+//! The link comes from another device, so previews are only fetched from public addresses,
+//! never from this machine or the local network, and every redirect is checked the same way.
+
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+
+use iroh::blobs::Hash;
+use iroh_drop_core::protocol::Protocol;
+use reqwest::Url;
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Runtime};
+
+/// Largest text file checked for a link.
+const MAX_TEXT_LEN: u64 = 2048;
+/// How much of a page is searched for its metadata, it is in the `<head>`.
+const MAX_PAGE_LEN: usize = 512 * 1024;
+const FETCH_TIMEOUT: Duration = Duration::from_secs(5);
+/// How many redirects are followed to the page.
+const MAX_REDIRECTS: usize = 5;
+
+/// Metadata of a linked page.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct LinkPreview {
+    pub url: String,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    /// Absolute URL of the image of the page
+    pub image: Option<String>,
+    pub site_name: Option<String>,
+}
+
+/// Emits `text-received` if the downloaded file `name` is a link.
+pub async fn on_downloaded<R: Runtime>(
+    app: &AppHandle<R>,
+    proto: &Arc<Protocol>,
+    name: &str,
+    hash: Hash,
+    size: u64,
+) {
+    if size > MAX_TEXT_LEN || !name.to_lowercase().ends_with(".txt") {
+        return;
+    }
+    // Like previews, received text is only shown while the encrypted storage is unlocked.
+    if proto.vault().ensure_unlocked().is_err() {
+        return;
+    }
+    let head = match proto.read_head(hash).await {
+        Ok(head) => head,
+        Err(err) => {
+            tracing::warn!("failed to read {hash}: {err:#}");
+            return;
+        }
+    };
+    let Ok(text) = String::from_utf8(head) else {
+        return;
+    };
+    let Some(url) = parse_url(&text) else {
+        return;
+    };
+    let preview = if proto.settings().get().await.link_previews {
+        match fetch(url.clone()).await {
+            Ok(preview) => Some(preview),
+            Err(err) => {
+                tracing::warn!("failed to preview {url}: {err:#}");
+                None
+            }
+        }
+    } else {
+        None
+    };
+    app.emit("text-received", (hash.to_string(), text, preview))
+        .ok();
+}
+
+/// The web address `text` consists of, if any.
+pub fn parse_url(text: &str) -> Option<Url> {
+    let text = text.trim();
+    if text.contains(char::is_whitespace) {
+        return None;
+    }
+    let url = Url::parse(text).ok()?;
+    let web = matches!(url.scheme(), "http" | "https") && url.host().is_some();
+    web.then_some(url)
+}
+
+async fn fetch(mut url: Url) -> anyhow::Result<LinkPreview> {
+    let mut redirects = 0;
+    let mut response = loop {
+        let response = get(&url).await?;
+        if !response.status().is_redirection() {
+            break response.error_for_status()?;
+        }
+        anyhow::ensure!(redirects < MAX_REDIRECTS, "too many redirects");
+        redirects += 1;
+        let location = response
+            .headers()
+            .get(reqwest::header::LOCATION)
+            .and_then(|value| value.to_str().ok())
+            .ok_or_else(|| anyhow::anyhow!("redirect without a location"))?;
+        url = url.join(location)?;
+    };
+    let is_html = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("text/html"));
+    if !is_html {
+        return Ok(LinkPreview {
+            url: url.to_string(),
+            ..Default::default()
+        });
+    }
+    let mut page = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        page.extend_from_slice(&chunk);
+        if page.len() >= MAX_PAGE_LEN {
+            break;
+        }
+    }
+    Ok(parse_page(&url, &String::from_utf8_lossy(&page)))
+}
+
+/// Sends a GET to `url` without following redirects, if its host is a public address.
+async fn get(url: &Url) -> anyhow::Result<reqwest::Response> {
+    anyhow::ensure!(
+        matches!(url.scheme(), "http" | "https"),
+        "not a web address: {url}"
+    );
+    let host = url
+        .host_str()
+        .ok_or_else(|| anyhow::anyhow!("no host in {url}"))?;
+    let port = url
+        .port_or_known_default()
+        .ok_or_else(|| anyhow::anyhow!("no port for {url}"))?;
+    // Resolved once and pinned below, so the name can't point elsewhere for the request.
+    let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host.trim_matches(['[', ']']), port))
+        .await?
+        .collect();
+    anyhow::ensure!(!addrs.is_empty(), "{host} has no address");
+    if let Some(addr) = addrs.iter().find(|addr| !is_public(addr.ip())) {
+        anyhow::bail!("{host} resolves to the local address {}", addr.ip());
+    }
+    let client = reqwest::Client::builder()
+        .timeout(FETCH_TIMEOUT)
+        .redirect(reqwest::redirect::Policy::none())
+        .resolve_to_addrs(host, &addrs)
+        .user_agent(concat!("iroh-drop/", env!("CARGO_PKG_VERSION")))
+        .build()?;
+    Ok(client.get(url.clone()).send().await?)
+}
+
+/// Whether `ip` is reachable on the internet, rather than on this machine or in a local
+/// network.
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            !(ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                // Shared address space of carrier-grade NATs, 100.64.0.0/10.
+                || (ip.octets()[0] == 100 && ip.octets()[1] & 0xc0 == 64))
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public(IpAddr::V4(ip)),
+            None => {
+                let first = ip.segments()[0];
+                !(ip.is_loopback()
+                    || ip.is_unspecified()
+                    // Unique local, fc00::/7.
+                    || first & 0xfe00 == 0xfc00
+                    // Link-local, fe80::/10.
+                    || first & 0xffc0 == 0xfe80)
+            }
+        },
+    }
+}
+
+/// Reads the metadata from the `<title>` and `<meta>` tags of `html`.
+fn parse_page(url: &Url, html: &str) -> LinkPreview {
+    let mut preview = LinkPreview {
+        url: url.to_string(),
+        ..Default::default()
+    };
+    let mut title = None;
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        rest = &rest[start + 1..];
+        let Some(end) = rest.find('>') else {
+            break;
+        };
+        let tag = &rest[..end];
+        rest = &rest[end + 1..];
+        let tag_name = tag.split_whitespace().next().unwrap_or_default();
+        if tag_name.eq_ignore_ascii_case("title") {
+            title = rest
+                .find("</")
+                .map(|end| decode_entities(rest[..end].trim()));
+        } else if tag_name.eq_ignore_ascii_case("meta") {
+            let key = attribute(tag, "property").or_else(|| attribute(tag, "name"));
+            let (Some(key), Some(content)) = (key, attribute(tag, "content")) else {
+                continue;
+            };
+            let field = match key.to_ascii_lowercase().as_str() {
+                "og:title" => &mut preview.title,
+                "og:description" | "description" => &mut preview.description,
+                "og:image" => &mut preview.image,
+                "og:site_name" => &mut preview.site_name,
+                _ => continue,
+            };
+            // The first occurrence wins, `og:description` usually comes first.
+            field.get_or_insert(decode_entities(&content));
+        } else if tag_name.eq_ignore_ascii_case("body") {
+            break;
+        }
+    }
+    preview.title = preview.title.or(title).filter(|title| !title.is_empty());
+    preview.image = preview
+        .image
+        .and_then(|image| url.join(&image).ok())
+        .filter(|image| matches!(image.scheme(), "http" | "https"))
+        .map(|image| image.to_string());
+    preview
+}
+
+/// The value of the attribute `name` in the inside of a tag, e.g. `meta content="…"`.
+fn attribute(tag: &str, name: &str) -> Option<String> {
+    // ASCII only, so the positions in `lower` are the same in `tag`.
+    let lower = tag.to_ascii_lowercase();
+    let mut from = 0;
+    while let Some(pos) = lower[from..].find(name) {
+        let start = from + pos;
+        from = start + name.len();
+        let preceded = lower[..start].ends_with(char::is_whitespace);
+        let value = lower[from..].trim_start();
+        if !preceded || !value.starts_with('=') {
+            continue;
+        }
+        let value_start = tag.len() - value.len() + 1;
+        let value = tag[value_start..].trim_start();
+        return match value.chars().next()? {
+            quote @ ('"' | '\'') => value[1..].split(quote).next().map(str::to_string),
+            _ => value.split_whitespace().next().map(str::to_string),
+        };
+    }
+    None
+}
+
+fn decode_entities(text: &str) -> String {
+    text.replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&#x27;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn page_metadata() {
+        let url = Url::parse("https://example.com/posts/1").unwrap();
+        let html = r#"<!doctype html>
+            <html><head>
+            <title>Fallback title</title>
+            <meta property="og:title" content="Tom &amp; Jerry">
+            <meta name="description" content='Plain description'>
+            <meta property="og:description" content="First wins">
+            <META PROPERTY="og:image" CONTENT="/images/cover.png">
+            <meta property="og:site_name" content=Example>
+            </head><body><meta property="og:title" content="In the body"></body></html>"#;
+        let preview = parse_page(&url, html);
+        assert_eq!(preview.url, "https://example.com/posts/1");
+        assert_eq!(preview.title.as_deref(), Some("Tom & Jerry"));
+        assert_eq!(preview.description.as_deref(), Some("Plain description"));
+        assert_eq!(
+            preview.image.as_deref(),
+            Some("https://example.com/images/cover.png")
+        );
+        assert_eq!(preview.site_name.as_deref(), Some("Example"));
+    }
+
+    #[test]
+    fn page_without_metadata() {
+        let url = Url::parse("https://example.com/").unwrap();
+        let preview = parse_page(&url, "<title> Just a &lt;title&gt; </title>");
+        assert_eq!(preview.title.as_deref(), Some("Just a <title>"));
+        assert_eq!(preview.description, None);
+
+        let preview = parse_page(
+            &url,
+            r#"<title></title><meta property="og:image" content="javascript:alert(1)">"#,
+        );
+        assert_eq!(preview.title, None);
+        assert_eq!(preview.image, None);
+    }
+
+    #[test]
+    fn only_public_addresses() {
+        for ip in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "::1",
+            "::",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:192.168.1.1",
+        ] {
+            assert!(!is_public(ip.parse().unwrap()), "{ip}");
+        }
+        for ip in ["93.184.215.14", "1.1.1.1", "2606:4700::1111"] {
+            assert!(is_public(ip.parse().unwrap()), "{ip}");
+        }
+    }
+
+    #[test]
+    fn links() {
+        assert!(parse_url(" https://example.com/a?b=c\n").is_some());
+        assert!(parse_url("http://example.com").is_some());
+        assert!(parse_url("file:///etc/passwd").is_none());
+        assert!(parse_url("see https://example.com").is_none());
+        assert!(parse_url("not a link").is_none());
+    }
+}
@@ -0,0 +1,143 @@
+//! Keeps the latest log messages of the app in memory, for the debug log panel.
+//!
+//! Messages are still written to the targets of `tauri-plugin-log`, as `tracing` forwards
+//! them to `log` as well.
+
+use std::collections::VecDeque;
+use std::fmt::{self, Write as _};
+use std::sync::{Arc, Mutex};
+
+use serde::Serialize;
+use tracing::field::{Field, Visit};
+use tracing::{span, Event, Level, Subscriber};
+use tracing_subscriber::{layer::Context, registry::LookupSpan, Layer};
+
+/// Number of messages kept, older ones are dropped.
+const CAPACITY: usize = 1000;
+
+/// Only messages of the app are kept, not of its dependencies.
+const TARGET_PREFIX: &str = "iroh_drop";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LogRecord {
+    /// Milliseconds since the unix epoch
+    pub timestamp: u64,
+    pub level: String,
+    pub target: String,
+    /// The spans the message was logged in, e.g. `connection{remote=abcd}`
+    pub spans: String,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct LogBuffer(Arc<Mutex<VecDeque<LogRecord>>>);
+
+impl LogBuffer {
+    /// The latest `limit` messages at least as severe as `level`, newest first.
+    pub fn recent(&self, level: Level, limit: usize) -> Vec<LogRecord> {
+        self.0
+            .lock()
+            .unwrap()
+            .iter()
+            .rev()
+            .filter(|record| record.level.parse::<Level>().is_ok_and(|l| l <= level))
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+
+    /// The latest `limit` messages mentioning any of `terms`, in the message or its spans,
+    /// newest first.
+    pub fn search(&self, terms: &[String], limit: usize) -> Vec<LogRecord> {
+        self.0
+            .lock()
+            .unwrap()
+            .iter()
+            .rev()
+            .filter(|record| {
+                terms.iter().any(|term| {
+                    record.message.contains(term.as_str()) || record.spans.contains(term.as_str())
+                })
+            })
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+}
+
+impl<S> Layer<S> for LogBuffer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &span::Attributes<'_>, id: &span::Id, ctx: Context<'_, S>) {
+        let mut fields = Fields::default();
+        attrs.record(&mut fields);
+        if let Some(span) = ctx.span(id) {
+            span.extensions_mut().insert(fields);
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+        if !metadata.target().starts_with(TARGET_PREFIX) {
+            return;
+        }
+        let mut fields = Fields::default();
+        event.record(&mut fields);
+
+        let mut spans = String::new();
+        if let Some(scope) = ctx.event_scope(event) {
+            for span in scope.from_root() {
+                if !spans.is_empty() {
+                    spans.push(':');
+                }
+                spans.push_str(span.name());
+                if let Some(fields) = span.extensions().get::<Fields>() {
+                    write!(spans, "{{{}}}", fields.fields.trim_start()).ok();
+                }
+            }
+        }
+
+        let record = LogRecord {
+            timestamp: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|d| d.as_millis() as u64)
+                .unwrap_or_default(),
+            level: metadata.level().to_string(),
+            target: metadata.target().to_string(),
+            spans,
+            message: format!("{}{}", fields.message, fields.fields),
+        };
+        let mut records = self.0.lock().unwrap();
+        if records.len() == CAPACITY {
+            records.pop_front();
+        }
+        records.push_back(record);
+    }
+}
+
+/// The message and other fields of an event or span.
+#[derive(Debug, Default)]
+struct Fields {
+    message: String,
+    /// ` key=value` for each field besides the message
+    fields: String,
+}
+
+impl Visit for Fields {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            write!(self.message, "{value:?}").ok();
+        } else {
+            write!(self.fields, " {}={value:?}", field.name()).ok();
+        }
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message.push_str(value);
+        } else {
+            write!(self.fields, " {}={value}", field.name()).ok();
+        }
+    }
+}
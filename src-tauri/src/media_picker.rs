@@ -0,0 +1,73 @@
+//! The photo and video picker of the phone, which has no drag and drop.
+//!
+//! The dialog plugin shows the gallery for pickers limited to photos and videos. The picked
+//! files are streamed into the store, as videos may be larger than the webview could hold.
+//! On Android they are content URIs, opened through the fs plugin the dialog plugin registers.
+
+use std::path::Path;
+
+use iroh_drop_core::error::DropError;
+use iroh_drop_core::metadata::FileMetadata;
+use tauri::{AppHandle, Runtime};
+use tauri_plugin_dialog::{DialogExt, FilePath};
+use tauri_plugin_fs::{FsExt, OpenOptions};
+use tokio::io::{AsyncBufReadExt, BufReader};
+
+use crate::share_target;
+
+/// Extensions the picker is limited to.
+const MEDIA_EXTENSIONS: &[&str] = &[
+    "jpg", "jpeg", "png", "gif", "webp", "heic", "heif", "mp4", "mov", "m4v", "3gp", "webm",
+];
+
+/// A picked photo or video, read from where the picker left it.
+pub struct PickedMedia {
+    pub name: String,
+    pub reader: BufReader<tokio::fs::File>,
+    pub metadata: FileMetadata,
+}
+
+/// Shows the picker, `None` if it was cancelled.
+pub async fn pick<R: Runtime>(app: &AppHandle<R>) -> Option<Vec<FilePath>> {
+    let (s, r) = tokio::sync::oneshot::channel();
+    app.dialog()
+        .file()
+        .add_filter("Photos and videos", MEDIA_EXTENSIONS)
+        .pick_files(move |paths| {
+            s.send(paths).ok();
+        });
+    r.await.ok().flatten()
+}
+
+/// Opens the picked `path`, with a file extension from its content if its name has none.
+pub async fn open<R: Runtime>(
+    app: &AppHandle<R>,
+    path: FilePath,
+) -> Result<PickedMedia, DropError> {
+    let mut name = match path {
+        FilePath::Path(ref path) => share_target::file_name(path),
+        // Content URIs end in an id, e.g. `content://media/external/images/media/42`.
+        FilePath::Url(ref url) => url
+            .path_segments()
+            .and_then(|mut segments| segments.next_back())
+            .filter(|segment| !segment.is_empty())
+            .unwrap_or("media")
+            .to_string(),
+    };
+    let mut options = OpenOptions::new();
+    options.read(true);
+    let file = app.fs().open(path, options)?;
+    let mut metadata = FileMetadata::from_fs(&file.metadata()?);
+    let mut reader = BufReader::new(tokio::fs::File::from_std(file));
+    if let Some(kind) = infer::get(reader.fill_buf().await?) {
+        if Path::new(&name).extension().is_none() {
+            name = format!("{name}.{}", kind.extension());
+        }
+        metadata.mime = Some(kind.mime_type().to_string());
+    }
+    Ok(PickedMedia {
+        name,
+        reader,
+        metadata,
+    })
+}
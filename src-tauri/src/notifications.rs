@@ -0,0 +1,44 @@
+use std::sync::Mutex;
+
+use tauri::{AppHandle, Emitter, Manager, Runtime};
+#[cfg(feature = "notifications")]
+use tauri_plugin_notification::NotificationExt;
+
+/// Transfer to show once the user comes back to the window after a notification.
+#[derive(Debug, Default)]
+pub struct PendingFocus(Mutex<Option<String>>);
+
+/// Shows a native notification about the transfer of `hash`, unless the window is focused.
+///
+/// Only logged if the app is built without the `notifications` feature.
+pub fn notify<R: Runtime>(app: &AppHandle<R>, title: &str, body: &str, hash: &str) {
+    let focused = app
+        .get_webview_window("main")
+        .and_then(|window| window.is_focused().ok())
+        .unwrap_or(false);
+    if focused {
+        return;
+    }
+
+    #[cfg(feature = "notifications")]
+    {
+        if let Err(err) = app.notification().builder().title(title).body(body).show() {
+            tracing::warn!("failed to show notification: {err}");
+            crate::permissions::check_notifications(app);
+            return;
+        }
+        *app.state::<PendingFocus>().0.lock().unwrap() = Some(hash.to_string());
+    }
+    #[cfg(not(feature = "notifications"))]
+    tracing::info!("{title}: {body} ({hash})");
+}
+
+/// Called when the main window gains focus, e.g. by clicking a notification.
+///
+/// Tells the frontend to show the transfer the last notification was about.
+pub fn on_focus<R: Runtime>(app: &AppHandle<R>) {
+    let hash = app.state::<PendingFocus>().0.lock().unwrap().take();
+    if let Some(hash) = hash {
+        app.emit("focus-transfer", hash).ok();
+    }
+}
@@ -0,0 +1,25 @@
+use std::fmt::Display;
+
+use anyhow::Result;
+use qrcode::{render::svg, QrCode};
+
+/// URI scheme prefixed to tickets in QR codes, so scanners can recognize them.
+const QR_SCHEME: &str = "iroh-drop:";
+
+/// Renders `ticket` as an SVG QR code, for node and guest tickets.
+pub fn qr_svg(ticket: &impl Display) -> Result<String> {
+    let code = QrCode::new(format!("{QR_SCHEME}{ticket}"))?;
+    let image = code
+        .render()
+        .min_dimensions(256, 256)
+        .dark_color(svg::Color("#000000"))
+        .light_color(svg::Color("#ffffff"))
+        .build();
+    Ok(image)
+}
+
+/// Extracts the ticket from scanned QR contents.
+pub fn parse_qr(data: &str) -> &str {
+    let data = data.trim();
+    data.strip_prefix(QR_SCHEME).unwrap_or(data)
+}
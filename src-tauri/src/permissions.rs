@@ -0,0 +1,304 @@
+//! Permissions the OS may deny, and how to get them granted.
+//!
+//! Features that need a permission report whether it was granted here, instead of failing
+//! silently. Each change is sent to the frontend as a `permissions-status` event with all
+//! permissions, and a hint for the user where to grant the missing ones.
+//!
+//! The local network permission of iOS and macOS is special: discovery just finds nothing
+//! while it is denied. It is probed periodically, see [`watch_local_network`].
+
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager, Runtime};
+#[cfg(feature = "notifications")]
+use tauri_plugin_notification::NotificationExt;
+
+/// How often the local network permission is probed, to notice once it is granted.
+const LOCAL_NETWORK_PROBE_INTERVAL: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Permission {
+    Notifications,
+    /// Writing exported files
+    FileSystem,
+    /// Scanning pairing QR codes, requested by the webview
+    Camera,
+    /// Discovering devices on the local network, iOS and macOS only
+    LocalNetwork,
+}
+
+impl Permission {
+    /// What stops working without the permission.
+    fn feature(&self) -> &'static str {
+        match self {
+            Self::Notifications => "You are not notified about incoming files.",
+            Self::FileSystem => "Received files can not be saved.",
+            Self::Camera => "QR codes can not be scanned, paste the ticket instead.",
+            Self::LocalNetwork => "Devices nearby are not found, only paired ones.",
+        }
+    }
+
+    /// Where to grant the permission on this platform, if there is a place for it.
+    fn hint(&self) -> Option<&'static str> {
+        let hint = match self {
+            Self::Notifications if cfg!(target_os = "macos") => {
+                "System Settings → Notifications → iroh-drop"
+            }
+            Self::Notifications if cfg!(target_os = "windows") => {
+                "Settings → System → Notifications"
+            }
+            Self::Notifications if cfg!(target_os = "ios") => {
+                "Settings → iroh-drop → Notifications"
+            }
+            Self::Notifications if cfg!(target_os = "android") => {
+                "Settings → Apps → iroh-drop → Notifications"
+            }
+            Self::FileSystem if cfg!(target_os = "macos") => {
+                "System Settings → Privacy & Security → Files and Folders → iroh-drop"
+            }
+            Self::FileSystem if cfg!(target_os = "android") => {
+                "Settings → Apps → iroh-drop → Permissions → Files and media"
+            }
+            Self::Camera if cfg!(target_os = "macos") => {
+                "System Settings → Privacy & Security → Camera → iroh-drop"
+            }
+            Self::Camera if cfg!(target_os = "ios") => "Settings → iroh-drop → Camera",
+            Self::Camera if cfg!(target_os = "android") => {
+                "Settings → Apps → iroh-drop → Permissions → Camera"
+            }
+            Self::LocalNetwork if cfg!(target_os = "macos") => {
+                "System Settings → Privacy & Security → Local Network → iroh-drop"
+            }
+            Self::LocalNetwork if cfg!(target_os = "ios") => "Settings → iroh-drop → Local Network",
+            _ => return None,
+        };
+        Some(hint)
+    }
+
+    /// The settings pane to open with [`open_settings`] on macOS.
+    #[cfg(target_os = "macos")]
+    fn macos_settings_url(&self) -> &'static str {
+        match self {
+            Self::Notifications => {
+                "x-apple.systempreferences:com.apple.Notifications-Settings.extension"
+            }
+            Self::FileSystem => {
+                "x-apple.systempreferences:com.apple.preference.security?Privacy_FilesAndFolders"
+            }
+            Self::Camera => {
+                "x-apple.systempreferences:com.apple.preference.security?Privacy_Camera"
+            }
+            Self::LocalNetwork => {
+                "x-apple.systempreferences:com.apple.preference.security?Privacy_LocalNetwork"
+            }
+        }
+    }
+}
+
+/// Whether [`open_settings`] works on this platform.
+const CAN_OPEN_SETTINGS: bool = cfg!(any(target_os = "ios", target_os = "macos"));
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PermissionState {
+    Granted,
+    Denied,
+    /// Not asked for yet, or the platform does not tell
+    Unknown,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PermissionStatus {
+    pub permission: Permission,
+    pub state: PermissionState,
+    /// What stops working while denied
+    pub feature: &'static str,
+    /// Where to grant the permission
+    pub hint: Option<&'static str>,
+    /// Whether [`open_settings`] can take the user there
+    pub can_open_settings: bool,
+}
+
+/// The last known state of each permission.
+#[derive(Debug, Default)]
+pub struct Permissions(Mutex<BTreeMap<Permission, PermissionState>>);
+
+impl Permissions {
+    /// The last known state of `permission`, `None` until it was checked.
+    pub fn state(&self, permission: Permission) -> Option<PermissionState> {
+        self.0.lock().unwrap().get(&permission).copied()
+    }
+
+    pub fn status(&self) -> Vec<PermissionStatus> {
+        self.0
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(permission, state)| PermissionStatus {
+                permission: *permission,
+                state: *state,
+                feature: permission.feature(),
+                hint: permission.hint(),
+                can_open_settings: CAN_OPEN_SETTINGS,
+            })
+            .collect()
+    }
+}
+
+/// Records the state of `permission`, telling the frontend if it changed.
+pub fn report<R: Runtime>(app: &AppHandle<R>, permission: Permission, state: PermissionState) {
+    let permissions = app.state::<Permissions>();
+    let previous = permissions.0.lock().unwrap().insert(permission, state);
+    if previous == Some(state) {
+        return;
+    }
+    if state == PermissionState::Denied {
+        tracing::warn!("permission {permission:?} was denied");
+    }
+    app.emit("permissions-status", permissions.status()).ok();
+}
+
+/// Checks the permissions that can be queried up front, called once on startup.
+pub fn init<R: Runtime>(app: &AppHandle<R>) {
+    check_notifications(app);
+    watch_local_network(app.clone());
+}
+
+/// Probes the local network permission now and every [`LOCAL_NETWORK_PROBE_INTERVAL`],
+/// as there is no notification when the user changes it.
+fn watch_local_network<R: Runtime>(app: AppHandle<R>) {
+    if !cfg!(any(target_os = "ios", target_os = "macos")) {
+        return;
+    }
+    tauri::async_runtime::spawn(async move {
+        let mut interval = tokio::time::interval(LOCAL_NETWORK_PROBE_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Some(state) = probe_local_network() {
+                report(&app, Permission::LocalNetwork, state);
+            }
+        }
+    });
+}
+
+/// Opens the system settings where `permission` is granted.
+pub fn open_settings<R: Runtime>(app: &AppHandle<R>, permission: Permission) -> anyhow::Result<()> {
+    #[cfg(target_os = "macos")]
+    {
+        let _ = app;
+        let status = std::process::Command::new("open")
+            .arg(permission.macos_settings_url())
+            .status()?;
+        anyhow::ensure!(status.success(), "failed to open the settings: {status}");
+        Ok(())
+    }
+    #[cfg(target_os = "ios")]
+    {
+        // All permissions of an app are on its page in the settings app.
+        let _ = permission;
+        app.run_on_main_thread(ios::open_app_settings)?;
+        Ok(())
+    }
+    #[cfg(not(any(target_os = "ios", target_os = "macos")))]
+    {
+        let _ = app;
+        let message = format!("can not open the settings for {permission:?} on this platform");
+        Err(iroh_drop_core::error::DropError::InvalidArgument(message).into())
+    }
+}
+
+/// Asks the notification plugin whether notifications may be shown.
+pub fn check_notifications<R: Runtime>(app: &AppHandle<R>) {
+    #[cfg(feature = "notifications")]
+    {
+        let state = match app.notification().permission_state() {
+            Ok(tauri::plugin::PermissionState::Granted) => PermissionState::Granted,
+            Ok(tauri::plugin::PermissionState::Denied) => PermissionState::Denied,
+            Ok(_) => PermissionState::Unknown,
+            Err(err) => {
+                tracing::warn!("failed to check the notification permission: {err}");
+                PermissionState::Unknown
+            }
+        };
+        report(app, Permission::Notifications, state);
+    }
+    #[cfg(not(feature = "notifications"))]
+    let _ = app;
+}
+
+/// Records whether writing files worked, as far as `result` tells.
+///
+/// Only a permission error counts as a denial, e.g. not a full disk.
+pub fn check_fs<R: Runtime, T>(app: &AppHandle<R>, result: anyhow::Result<T>) -> anyhow::Result<T> {
+    match &result {
+        Ok(_) => report(app, Permission::FileSystem, PermissionState::Granted),
+        Err(err) if is_permission_denied(err) => {
+            report(app, Permission::FileSystem, PermissionState::Denied)
+        }
+        Err(_) => {}
+    }
+    result
+}
+
+fn is_permission_denied(err: &anyhow::Error) -> bool {
+    err.chain().any(|err| {
+        err.downcast_ref::<std::io::Error>()
+            .is_some_and(|err| err.kind() == std::io::ErrorKind::PermissionDenied)
+    })
+}
+
+/// Sends a packet to the mDNS group, which fails with "no route to host" if the local
+/// network permission is denied.
+///
+/// `None` on platforms without such a permission.
+fn probe_local_network() -> Option<PermissionState> {
+    if !cfg!(any(target_os = "ios", target_os = "macos")) {
+        return None;
+    }
+    /// `EHOSTUNREACH` on Apple platforms
+    const NO_ROUTE_TO_HOST: i32 = 65;
+
+    let socket = std::net::UdpSocket::bind(("0.0.0.0", 0)).ok()?;
+    let state = match socket.send_to(&[0], ("224.0.0.251", 5353)) {
+        Ok(_) => PermissionState::Granted,
+        Err(err) if err.raw_os_error() == Some(NO_ROUTE_TO_HOST) => PermissionState::Denied,
+        Err(err) => {
+            tracing::debug!("failed to probe the local network: {err}");
+            PermissionState::Unknown
+        }
+    };
+    Some(state)
+}
+
+#[cfg(target_os = "ios")]
+mod ios {
+    use objc2::runtime::AnyObject;
+    use objc2::{class, msg_send};
+
+    /// The value of `UIApplicationOpenSettingsURLString`
+    const SETTINGS_URL: &std::ffi::CStr = c"app-settings:";
+
+    /// Opens the page of the app in the settings app, must run on the main thread.
+    pub fn open_app_settings() {
+        // SAFETY: the string is nul terminated, `URLWithString:` returns nil for invalid
+        // URLs, which `openURL:options:completionHandler:` ignores. The completion handler
+        // is nullable.
+        unsafe {
+            let string: *mut AnyObject =
+                msg_send![class!(NSString), stringWithUTF8String: SETTINGS_URL.as_ptr()];
+            let url: *mut AnyObject = msg_send![class!(NSURL), URLWithString: string];
+            let options: *mut AnyObject = msg_send![class!(NSDictionary), dictionary];
+            let app: *mut AnyObject = msg_send![class!(UIApplication), sharedApplication];
+            let _: () = msg_send![
+                app,
+                openURL: url,
+                options: options,
+                completionHandler: std::ptr::null::<AnyObject>()
+            ];
+        }
+    }
+}
@@ -0,0 +1,137 @@
+//! Files shared into the app from other apps, e.g. a photo from the gallery via "Share with…".
+//!
+//! On iOS the app declares that it opens any document (`CFBundleDocumentTypes` in the
+//! Info.plist), so it is offered in the share sheet. The system then opens the app with
+//! the URL of the file, delivered as [`tauri::RunEvent::Opened`], the same as on macOS when
+//! a file is opened with the app.
+//!
+//! Content shared on Android can only be read through the content resolver, so the main
+//! activity of the generated Android project declares intent filters for `ACTION_SEND` and
+//! `ACTION_SEND_MULTIPLE` and copies the shared streams into [`inbox_dir`]. It is checked
+//! whenever the app comes to the foreground.
+//!
+//! Shared files wait until the user sent or dismissed them. The UI is told with a
+//! `files-shared` event and asks for them with the `shared_files` command, also on start,
+//! when the app was opened by a share before the UI listened.
+
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use iroh_drop_core::error::DropError;
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager, Runtime};
+
+/// A file shared into the app, waiting to be sent.
+#[derive(Debug, Clone, Serialize)]
+pub struct SharedFile {
+    pub path: PathBuf,
+    pub name: String,
+    pub size: u64,
+}
+
+/// Files shared into the app that were not sent or dismissed yet, managed by the app.
+#[derive(Debug, Default)]
+pub struct SharedFiles {
+    files: Mutex<Vec<PathBuf>>,
+}
+
+impl SharedFiles {
+    /// Adds `paths`, returns whether any of them is new.
+    fn add(&self, paths: impl IntoIterator<Item = PathBuf>) -> bool {
+        let mut files = self.files.lock().unwrap();
+        let before = files.len();
+        for path in paths {
+            if !files.contains(&path) {
+                tracing::info!("file shared into the app: {}", path.display());
+                files.push(path);
+            }
+        }
+        files.len() > before
+    }
+
+    /// The waiting files, files that vanished meanwhile are dropped.
+    pub fn list(&self) -> Vec<SharedFile> {
+        let mut files = self.files.lock().unwrap();
+        let mut shared = Vec::new();
+        files.retain(|path| match std::fs::metadata(path) {
+            Ok(metadata) if metadata.is_file() => {
+                shared.push(SharedFile {
+                    path: path.clone(),
+                    name: file_name(path),
+                    size: metadata.len(),
+                });
+                true
+            }
+            _ => false,
+        });
+        shared
+    }
+
+    /// Fails unless `path` is waiting, so the UI can not read arbitrary files through it.
+    pub fn check(&self, path: &Path) -> Result<(), DropError> {
+        if !self.files.lock().unwrap().iter().any(|p| p == path) {
+            return Err(DropError::InvalidArgument(format!(
+                "{} was not shared with the app",
+                path.display()
+            )));
+        }
+        Ok(())
+    }
+
+    /// Forgets `path` once it was sent or dismissed, deleting our copy in the inbox.
+    pub fn remove<R: Runtime>(&self, app: &AppHandle<R>, path: &Path) {
+        self.files.lock().unwrap().retain(|p| p != path);
+        let in_inbox = inbox_dir(app).is_some_and(|inbox| path.starts_with(inbox));
+        if in_inbox {
+            if let Err(err) = std::fs::remove_file(path) {
+                tracing::warn!("failed to delete the shared {}: {err}", path.display());
+            }
+        }
+    }
+}
+
+/// The name a shared file is sent with.
+pub fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "shared file".to_string())
+}
+
+/// Where files shared on Android are copied to, and iOS keeps the files it copies into the
+/// app (`Documents/Inbox`).
+fn inbox_dir<R: Runtime>(app: &AppHandle<R>) -> Option<PathBuf> {
+    #[cfg(target_os = "ios")]
+    let dir = app.path().document_dir().ok()?.join("Inbox");
+    #[cfg(not(target_os = "ios"))]
+    let dir = app.path().app_cache_dir().ok()?.join("shared");
+    Some(dir)
+}
+
+/// Called with the URLs of [`tauri::RunEvent::Opened`].
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+pub fn opened<R: Runtime>(app: &AppHandle<R>, urls: &[tauri::Url]) {
+    let paths = urls.iter().filter_map(|url| url.to_file_path().ok());
+    if app.state::<SharedFiles>().add(paths) {
+        app.emit("files-shared", ()).ok();
+    }
+}
+
+/// Picks up the files the Android activity copied into the inbox.
+pub fn check_inbox<R: Runtime>(app: &AppHandle<R>) {
+    if !cfg!(target_os = "android") {
+        return;
+    }
+    let Some(inbox) = inbox_dir(app) else {
+        return;
+    };
+    let Ok(entries) = std::fs::read_dir(&inbox) else {
+        return;
+    };
+    let paths = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file());
+    if app.state::<SharedFiles>().add(paths) {
+        app.emit("files-shared", ()).ok();
+    }
+}
@@ -0,0 +1,67 @@
+//! Where received files are stored.
+//!
+//! Received data stays in the app's own storage until the user explicitly
+//! exports it, see [`export_dir`]. The checks before accepting an offer are part
+//! of the core, see [`iroh_drop_core::storage`].
+
+use std::path::PathBuf;
+
+use iroh_drop_core::error::DropError;
+use serde::Deserialize;
+use tauri::{AppHandle, Manager};
+
+/// Where a received file is exported to.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportTarget {
+    /// The regular file location, see [`export_dir`].
+    #[default]
+    Files,
+    /// The picture or video folder of the platform, for media.
+    Gallery,
+}
+
+/// App scoped directory received data is staged in.
+pub fn staging_dir(app: &AppHandle) -> tauri::Result<PathBuf> {
+    app.path().app_data_dir()
+}
+
+/// Where received files are saved to when exported.
+///
+/// On iOS this is the app's documents directory, which is shown in the Files app.
+pub fn export_dir(app: &AppHandle) -> Result<PathBuf, DropError> {
+    #[cfg(target_os = "ios")]
+    let dir = app.path().document_dir();
+    #[cfg(not(target_os = "ios"))]
+    let dir = app.path().download_dir();
+    dir.map_err(internal)
+}
+
+/// Where received media is saved to, based on the detected content type of `head`.
+///
+/// On Android these are the shared picture and video folders, which the gallery picks up
+/// once the media scanner ran.
+pub fn gallery_dir(app: &AppHandle, head: &[u8]) -> Result<PathBuf, DropError> {
+    if cfg!(target_os = "ios") {
+        return Err(DropError::InvalidArgument(
+            "saving to the photo library is not supported on iOS yet".to_string(),
+        ));
+    }
+    let dir = if infer::is_image(head) {
+        app.path().picture_dir().map_err(internal)?
+    } else if infer::is_video(head) {
+        app.path().video_dir().map_err(internal)?
+    } else {
+        return Err(DropError::InvalidArgument(
+            "only images and videos can be saved to the gallery".to_string(),
+        ));
+    };
+    let dir = dir.join("iroh-drop");
+    std::fs::create_dir_all(&dir)?;
+    Ok(dir)
+}
+
+/// The core does not know about Tauri, so its errors are converted here.
+fn internal(err: tauri::Error) -> DropError {
+    DropError::Internal(err.to_string())
+}
@@ -0,0 +1,114 @@
+use std::sync::Arc;
+
+use iroh_drop_core::history::{Direction, HistoryEntry};
+use iroh_drop_core::protocol::Protocol;
+use tauri::{
+    menu::{CheckMenuItem, IsMenuItem, Menu, MenuItem, PredefinedMenuItem, Submenu},
+    tray::TrayIconBuilder,
+    AppHandle, Emitter, Manager,
+};
+
+/// Number of transfers listed under "Recent transfers".
+const RECENT_TRANSFERS: usize = 5;
+
+/// Adds the tray icon, shown as an AppIndicator on Linux.
+pub fn create(app: &AppHandle) -> tauri::Result<()> {
+    let menu = build_menu(app, &[], false, false)?;
+
+    let mut builder = TrayIconBuilder::with_id("main")
+        .title("iroh-drop")
+        .menu(&menu)
+        .on_menu_event(|app, event| match event.id.as_ref() {
+            "show" => show_window(app),
+            "pause" => {
+                let proto = app.state::<Arc<Protocol>>();
+                proto.set_receiving_paused(!proto.is_receiving_paused());
+                let app = app.clone();
+                tauri::async_runtime::spawn(async move { refresh(&app).await });
+            }
+            "invisible" => {
+                let proto = app.state::<Arc<Protocol>>().inner().clone();
+                // The menu is refreshed once the change is reported.
+                tauri::async_runtime::spawn(async move {
+                    proto.set_invisible(!proto.is_invisible()).await;
+                });
+            }
+            "quit" => app.exit(0),
+            id => {
+                if let Some(hash) = id.strip_prefix("recent:") {
+                    show_window(app);
+                    app.emit("focus-transfer", hash).ok();
+                }
+            }
+        });
+    if let Some(icon) = app.default_window_icon() {
+        builder = builder.icon(icon.clone());
+    }
+    builder.build(app)?;
+
+    Ok(())
+}
+
+/// Rebuilds the menu, to show the latest transfers and receive state.
+pub async fn refresh(app: &AppHandle) {
+    let proto = app.state::<Arc<Protocol>>().inner().clone();
+    let recent: Vec<_> = proto
+        .history()
+        .list()
+        .await
+        .into_iter()
+        .take(RECENT_TRANSFERS)
+        .collect();
+    let paused = proto.is_receiving_paused();
+    let res = build_menu(app, &recent, paused, proto.is_invisible()).and_then(|menu| {
+        match app.tray_by_id("main") {
+            Some(tray) => tray.set_menu(Some(menu)),
+            None => Ok(()),
+        }
+    });
+    if let Err(err) = res {
+        tracing::warn!("failed to update the tray menu: {err}");
+    }
+}
+
+fn build_menu(
+    app: &AppHandle,
+    recent: &[HistoryEntry],
+    paused: bool,
+    invisible: bool,
+) -> tauri::Result<Menu<tauri::Wry>> {
+    let show = MenuItem::with_id(app, "show", "Show window", true, None::<&str>)?;
+    let pause = CheckMenuItem::with_id(app, "pause", "Pause receiving", true, paused, None::<&str>)?;
+    let invisible = CheckMenuItem::with_id(app, "invisible", "Invisible", true, invisible, None::<&str>)?;
+
+    let mut items = Vec::new();
+    for entry in recent {
+        let arrow = match entry.direction {
+            Direction::Sent => "↑",
+            Direction::Received => "↓",
+        };
+        items.push(MenuItem::with_id(
+            app,
+            format!("recent:{}", entry.hash),
+            format!("{arrow} {}", entry.name),
+            true,
+            None::<&str>,
+        )?);
+    }
+    if items.is_empty() {
+        items.push(MenuItem::with_id(app, "no-recent", "No transfers yet", false, None::<&str>)?);
+    }
+    let items: Vec<&dyn IsMenuItem<tauri::Wry>> = items.iter().map(|item| item as _).collect();
+    let recent = Submenu::with_items(app, "Recent transfers", true, &items)?;
+
+    let separator = PredefinedMenuItem::separator(app)?;
+    let quit = MenuItem::with_id(app, "quit", "Quit", true, None::<&str>)?;
+    Menu::with_items(app, &[&show, &pause, &invisible, &recent, &separator, &quit])
+}
+
+fn show_window(app: &AppHandle) {
+    if let Some(window) = app.get_webview_window("main") {
+        window.show().ok();
+        window.set_focus().ok();
+    }
+}
@@ -1,82 +1,4441 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
 use js_sys::Uint8Array;
 use leptoaster::*;
-use leptos::html::Div;
+use leptos::html::{Div, Main};
 use leptos::leptos_dom::ev::SubmitEvent;
 use leptos::*;
 use leptos_use::{
     use_drop_zone_with_options, UseDropZoneEvent, UseDropZoneOptions, UseDropZoneReturn,
 };
-use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde::{Deserialize, Serialize};
 use wasm_bindgen::prelude::*;
 use wasm_bindgen_futures::JsFuture;
 
-#[wasm_bindgen]
-extern "C" {
-    #[wasm_bindgen(js_namespace = ["window", "__TAURI__", "core"])]
-    async fn invoke(cmd: &str, args: JsValue) -> JsValue;
-    #[wasm_bindgen(js_namespace = ["window", "__TAURI__", "core"], js_name = invoke)]
-    async fn invoke_without_args(cmd: &str) -> JsValue;
-    #[wasm_bindgen(js_namespace = ["window", "__TAURI__", "event"], js_name = "listen")]
-    async fn listen_sys(event: &str, handler: &js_sys::Function) -> js_sys::Function;
+use crate::ipc::{invoke_without_args, listen, try_invoke};
+
+/// Error returned by the backend commands.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DropError {
+    pub code: String,
+    pub message: String,
+    /// Why the receiver rejected a file, for `rejected`
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+impl DropError {
+    /// Message for the user, explaining what went wrong.
+    pub fn user_message(&self) -> String {
+        match self.code.as_str() {
+            "unknown_node" => "This device is not known yet, wait until it shows up as online".to_string(),
+            "connection_failed" => format!("Could not reach the device ({})", self.message),
+            "timeout" => "The device did not answer in time".to_string(),
+            "rejected" => match self.reason.as_deref() {
+                Some("exceeds_limit") => {
+                    "The file is larger than the device accepts, ask to accept it once".to_string()
+                }
+                Some("low_storage") => "The device does not have enough free space".to_string(),
+                Some("low_memory") => "The device does not have enough free memory".to_string(),
+                Some("timed_out") => "The device did not get to the file in time".to_string(),
+                _ => format!("The device declined the file ({})", self.message),
+            },
+            "io" => format!("Could not access the disk ({})", self.message),
+            "other_room" => "This device is in another room".to_string(),
+            "incompatible" => "This device runs an incompatible version of iroh-drop, update the app on both devices".to_string(),
+            "storage_locked" => "Unlock the encrypted storage first".to_string(),
+            "read_only" => "This machine is read-only, unlock it first".to_string(),
+            _ => self.message.clone(),
+        }
+    }
+}
+
+impl From<JsValue> for DropError {
+    fn from(value: JsValue) -> Self {
+        serde_wasm_bindgen::from_value(value.clone()).unwrap_or_else(|_| DropError {
+            code: "internal".to_string(),
+            message: value.as_string().unwrap_or_default(),
+            reason: None,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PeerInfo {
+    pub node_id: String,
+    pub name: String,
+    pub online: bool,
+    /// Seconds since the unix epoch
+    pub last_seen: u64,
+    pub device: DeviceInfo,
+    /// What the peer accepts, `None` for older versions
+    #[serde(default)]
+    pub constraints: Option<ReceiveConstraints>,
+    /// Whether this is one of the user's own devices
+    pub own_device: bool,
+    /// Paired by ticket or QR code, and not found on the local network
+    #[serde(default)]
+    pub remote: bool,
+    /// Joined with one of our guest tickets
+    #[serde(default)]
+    pub guest: bool,
+    /// Conversions applied to files sent to the peer
+    #[serde(default)]
+    pub send_transforms: Vec<String>,
+    /// Handle like `alice@example.com`, once it was verified
+    #[serde(default)]
+    pub handle: Option<String>,
+    /// Set if the name does not match the one first seen for the device
+    #[serde(default)]
+    pub identity_warning: Option<IdentityWarning>,
+    /// Whether the user compared the verification codes of the device
+    #[serde(default)]
+    pub identity_verified: bool,
+    /// Pinned to the top of the device list
+    #[serde(default)]
+    pub favorite: bool,
+    /// Protocol version of the device, as last reported
+    #[serde(default)]
+    pub capabilities: PeerCapabilities,
+    /// Runs a version files can not be sent to
+    #[serde(default)]
+    pub incompatible: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PeerCapabilities {
+    /// 0 if the device never introduced itself
+    pub version: u32,
+}
+
+impl PeerInfo {
+    /// Whether the device matches the lowercase search `query`.
+    fn matches(&self, query: &str) -> bool {
+        self.name.to_lowercase().contains(query)
+            || self.node_id.starts_with(query)
+            || self
+                .handle
+                .as_ref()
+                .is_some_and(|handle| handle.contains(query))
+    }
+
+    /// Key of the card of the device, so it is only rendered again when what it shows
+    /// changed.
+    fn render_key(&self) -> String {
+        format!(
+            "{}:{}:{}:{}:{}:{}:{:?}:{}:{}",
+            self.node_id,
+            self.name,
+            self.online,
+            self.last_seen,
+            self.own_device,
+            self.favorite,
+            self.handle,
+            self.identity_warning.is_some(),
+            self.incompatible
+        )
+    }
+}
+
+/// How the device list is ordered, favorites always come first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PeerOrder {
+    Name,
+    LastSeen,
+}
+
+/// How many devices are shown before "Show more".
+const PEER_PAGE: usize = 50;
+
+/// The devices matching `query`, in `order`.
+fn sort_peers(
+    peers: impl Iterator<Item = PeerInfo>,
+    order: PeerOrder,
+    query: &str,
+) -> Vec<PeerInfo> {
+    let query = query.trim().to_lowercase();
+    let mut peers: Vec<_> = peers.filter(|peer| peer.matches(&query)).collect();
+    peers.sort_by(|a, b| {
+        b.favorite
+            .cmp(&a.favorite)
+            .then_with(|| match order {
+                PeerOrder::Name => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
+                PeerOrder::LastSeen => b.online.cmp(&a.online).then(b.last_seen.cmp(&a.last_seen)),
+            })
+            .then_with(|| a.node_id.cmp(&b.node_id))
+    });
+    peers
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IdentityWarning {
+    /// `name_changed` or `name_taken`
+    pub kind: String,
+    /// The name first seen for the device, for `name_changed`
+    #[serde(default)]
+    pub pinned: Option<String>,
+    /// The other device known under the name, for `name_taken`
+    #[serde(default)]
+    pub node_id: Option<String>,
+}
+
+impl IdentityWarning {
+    pub fn describe(&self, name: &str) -> String {
+        match self.pinned {
+            Some(ref pinned) => format!("{} was first seen as {}", name, pinned),
+            None => format!("Another device was first seen as {}", name),
+        }
+    }
+}
+
+/// Conversions that can be enabled per peer, with their labels.
+const TRANSFORMS: &[(&str, &str)] = &[
+    ("downscale", "Downscale huge images"),
+    ("png-to-jpeg", "Send PNGs as JPEG"),
+];
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DeviceInfo {
+    /// `macos`, `windows`, `linux`, `android`, `ios` or `unknown`
+    pub platform: String,
+    /// `laptop`, `phone` or `unknown`
+    pub device_type: String,
+}
+
+impl DeviceInfo {
+    fn icon(&self) -> &'static str {
+        match self.device_type.as_str() {
+            "phone" => "📱",
+            "laptop" => "💻",
+            _ => "🖥",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub direction: String,
+    pub node_id: String,
+    pub name: String,
+    pub hash: String,
+    pub size: u64,
+    pub timestamp: u64,
+    pub content_warning: Option<String>,
+    pub quarantined: bool,
+    /// Whether the receiver confirmed the hash and size, `None` if unknown
+    pub verified: Option<bool>,
+    /// Set while the entry is in the trash
+    pub deleted_at: Option<u64>,
+    #[serde(default)]
+    pub metadata: FileMetadata,
+    /// Added by either side after the transfer
+    #[serde(default)]
+    pub note: Option<String>,
+    /// Removed from the store to free space
+    #[serde(default)]
+    pub collected: bool,
+}
+
+/// Metadata of the page a received link points to.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LinkPreview {
+    pub url: String,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub image: Option<String>,
+    pub site_name: Option<String>,
+}
+
+/// MIME type, modification time and permissions of a file.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileMetadata {
+    pub mime: Option<String>,
+    pub modified: Option<u64>,
+    pub permissions: Option<u32>,
+}
+
+/// A waiting or running transfer.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Transfer {
+    pub id: u64,
+    pub direction: String,
+    pub node_id: String,
+    pub name: String,
+    pub hash: Option<String>,
+    pub size: u64,
+    /// `queued`, `active` or `paused`
+    pub state: String,
+    /// Bytes received and current throughput, for running downloads
+    #[serde(default)]
+    pub progress: Option<(u64, u64)>,
+}
+
+async fn fetch_peers() -> HashMap<String, PeerInfo> {
+    let result = invoke_without_args("list_peers").await;
+    let peers: Vec<PeerInfo> = serde_wasm_bindgen::from_value(result).unwrap_or_else(|err| {
+        logging::warn!("invalid peers: {}", err);
+        Vec::new()
+    });
+    logging::log!("peers: {:?}", peers);
+    peers
+        .into_iter()
+        .map(|peer| (peer.node_id.clone(), peer))
+        .collect()
+}
+
+async fn fetch_history() -> Vec<HistoryEntry> {
+    let result = invoke_without_args("history").await;
+    serde_wasm_bindgen::from_value(result).unwrap()
+}
+
+async fn fetch_trash() -> Vec<HistoryEntry> {
+    let result = invoke_without_args("trash").await;
+    serde_wasm_bindgen::from_value(result).unwrap()
+}
+
+/// Deletes or restores the transfers of `hash`, then reloads the history and trash.
+async fn set_deleted(
+    hash: String,
+    deleted: bool,
+    set_history: WriteSignal<Vec<HistoryEntry>>,
+    set_trash: WriteSignal<Vec<HistoryEntry>>,
+) -> Result<(), DropError> {
+    #[derive(Debug, Serialize, Deserialize)]
+    struct HashArgs {
+        hash: String,
+    }
+
+    let args = serde_wasm_bindgen::to_value(&HashArgs { hash }).expect("failed conversion");
+    let cmd = if deleted { "delete_transfer" } else { "undo_delete" };
+    let res = try_invoke(cmd, args).await.map(|_| ()).map_err(DropError::from);
+    set_history.set(fetch_history().await);
+    set_trash.set(fetch_trash().await);
+    res
+}
+
+/// The note of a transfer, with a button to change it that is synced to the other side.
+fn note_view(entry: &HistoryEntry, set_history: WriteSignal<Vec<HistoryEntry>>) -> impl IntoView {
+    #[derive(Debug, Serialize, Deserialize)]
+    struct AnnotateTransferArgs {
+        hash: String,
+        node_id: String,
+        note: String,
+    }
+
+    let toaster = expect_toaster();
+    let hash = entry.hash.clone();
+    let node_id = entry.node_id.clone();
+    let current = entry.note.clone().unwrap_or_default();
+    let edit = move |_| {
+        let Ok(Some(note)) = window().prompt_with_message_and_default(
+            "Note for this transfer, also shown to the other side:",
+            &current,
+        ) else {
+            return;
+        };
+        let args = AnnotateTransferArgs {
+            hash: hash.clone(),
+            node_id: node_id.clone(),
+            note,
+        };
+        spawn_local(async move {
+            let args = serde_wasm_bindgen::to_value(&args).expect("failed conversion");
+            if let Err(err) = try_invoke("annotate_transfer", args).await {
+                toaster.toast(
+                    ToastBuilder::new(&format!(
+                        "Failed to save the note: {}",
+                        DropError::from(err).user_message()
+                    ))
+                    .with_level(ToastLevel::Error)
+                    .with_position(ToastPosition::TopRight),
+                );
+            }
+            set_history.set(fetch_history().await);
+        });
+    };
+
+    view! {
+        { entry.note.clone().map(|note| view! { <p class="note">{note}</p> }) }
+        <button on:click=edit>{ if entry.note.is_some() { "Edit note" } else { "Add note" } }</button>
+    }
+}
+
+/// A link received as text, with the page metadata if previews are enabled.
+fn link_view(text: String, preview: Option<LinkPreview>) -> impl IntoView {
+    #[derive(Debug, Serialize, Deserialize)]
+    struct OpenLinkArgs {
+        url: String,
+    }
+
+    let toaster = expect_toaster();
+    let url = text.trim().to_string();
+    let open = {
+        let url = url.clone();
+        move |_| {
+            let args = OpenLinkArgs { url: url.clone() };
+            let toaster = toaster.clone();
+            spawn_local(async move {
+                let args = serde_wasm_bindgen::to_value(&args).expect("failed conversion");
+                if let Err(err) = try_invoke("open_link", args).await {
+                    toaster.toast(
+                        ToastBuilder::new(&format!(
+                            "Failed to open the link: {}",
+                            DropError::from(err).user_message()
+                        ))
+                        .with_level(ToastLevel::Error)
+                        .with_position(ToastPosition::TopRight),
+                    );
+                }
+            });
+        }
+    };
+    let preview = preview.unwrap_or(LinkPreview {
+        url: url.clone(),
+        title: None,
+        description: None,
+        image: None,
+        site_name: None,
+    });
+
+    view! {
+        <div class="link-card">
+            { preview.image.map(|src| view! { <img src=src /> }) }
+            { preview.site_name.map(|site| view! { <p class="site">{site}</p> }) }
+            <p class="title">{ preview.title.unwrap_or_else(|| url.clone()) }</p>
+            { preview.description.map(|description| view! { <p>{description}</p> }) }
+            <button on:click=open>"Open in browser"</button>
+        </div>
+    }
+}
+
+#[component]
+pub fn App() -> impl IntoView {
+    let (peers, set_peers) = create_signal(HashMap::<String, PeerInfo>::new());
+    let (history, set_history) = create_signal(Vec::<HistoryEntry>::new());
+    let (trash, set_trash) = create_signal(Vec::<HistoryEntry>::new());
+    let (focused, set_focused) = create_signal(None::<String>);
+    let (transfers, set_transfers) = create_signal(BTreeMap::<u64, Transfer>::new());
+    // Start of offered text files, by hash
+    let (previews, set_previews) = create_signal(HashMap::<String, String>::new());
+    // Received links and their page metadata, by hash
+    let (links, set_links) = create_signal(HashMap::<String, (String, Option<LinkPreview>)>::new());
+    // Delivery status of sent files reported by the receiver, by node id and hash
+    let (deliveries, set_deliveries) = create_signal(HashMap::<(String, String), String>::new());
+
+    let (my_node_id, set_my_node_id) = create_signal(String::new());
+    let (my_ticket, set_my_ticket) = create_signal(String::new());
+    let (remote_ticket, set_remote_ticket) = create_signal(String::new());
+    let (my_qr_code, set_my_qr_code) = create_signal(String::new());
+
+    provide_toaster();
+
+    spawn_local(async move {
+        let result = invoke_without_args("node_id").await;
+        let my_node_id: String = serde_wasm_bindgen::from_value(result).unwrap();
+        set_my_node_id.set(my_node_id);
+    });
+
+    spawn_local(async move {
+        let result = invoke_without_args("my_ticket").await;
+        let my_ticket: String = serde_wasm_bindgen::from_value(result).unwrap();
+        set_my_ticket.set(my_ticket);
+    });
+
+    spawn_local(async move {
+        let result = invoke_without_args("my_qr_code").await;
+        let my_qr_code: String = serde_wasm_bindgen::from_value(result).unwrap();
+        set_my_qr_code.set(my_qr_code);
+    });
+
+    #[derive(Serialize, Deserialize)]
+    struct ConnectByTicketArgs {
+        ticket: String,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct PairFromQrArgs {
+        data: String,
+    }
+
+    let toaster = expect_toaster();
+    let add_remote = move |ev: SubmitEvent| {
+        ev.prevent_default();
+        let ticket = remote_ticket.get_untracked();
+        if ticket.is_empty() {
+            return;
+        }
+        spawn_local(async move {
+            // Contents of a scanned pairing QR code
+            let result = if ticket.starts_with("iroh-drop:") {
+                let args = serde_wasm_bindgen::to_value(&PairFromQrArgs { data: ticket })
+                    .expect("failed conversion");
+                try_invoke("pair_from_qr", args).await
+            } else {
+                let args = serde_wasm_bindgen::to_value(&ConnectByTicketArgs { ticket })
+                    .expect("failed conversion");
+                try_invoke("connect_by_ticket", args).await
+            };
+            match result {
+                Ok(result) => {
+                    let (name, node_id): (String, String) =
+                        serde_wasm_bindgen::from_value(result).unwrap();
+                    logging::log!("added remote device: {} ({})", name, node_id);
+                    set_remote_ticket.set(String::new());
+                }
+                Err(err) => {
+                    let err = DropError::from(err).user_message();
+                    toaster.toast(
+                        ToastBuilder::new(&format!("Failed to add device: {}", err))
+                            .with_level(ToastLevel::Error)
+                            .with_position(ToastPosition::TopRight),
+                    );
+                }
+            }
+        });
+    };
+
+    spawn_local(async move {
+        let result = invoke_without_args("list_transfers").await;
+        let list: Vec<Transfer> = serde_wasm_bindgen::from_value(result).unwrap();
+        set_transfers.set(list.into_iter().map(|t| (t.id, t)).collect());
+    });
+    spawn_local(async move {
+        let unlisten = listen::<Transfer, _>("transfer-updated", move |transfer| {
+            set_transfers.update(|val| {
+                val.insert(transfer.id, transfer);
+            });
+        })
+        .await;
+
+        on_cleanup(unlisten);
+    });
+    spawn_local(async move {
+        let unlisten = listen::<(u64, u64, u64, u64, bool), _>(
+            "transfer-progress",
+            move |(id, offset, _size, bps, paused)| {
+                set_transfers.update(|val| {
+                    if let Some(transfer) = val.get_mut(&id) {
+                        transfer.progress = Some((offset, bps));
+                        transfer.state = if paused { "paused" } else { "active" }.to_string();
+                    }
+                });
+            },
+        )
+        .await;
+
+        on_cleanup(unlisten);
+    });
+    spawn_local(async move {
+        let unlisten = listen::<(u64, u64, u64, u64), _>(
+            "upload-progress",
+            move |(id, offset, _size, bps)| {
+                set_transfers.update(|val| {
+                    if let Some(transfer) = val.get_mut(&id) {
+                        transfer.progress = Some((offset, bps));
+                    }
+                });
+            },
+        )
+        .await;
+
+        on_cleanup(unlisten);
+    });
+    spawn_local(async move {
+        let unlisten = listen::<u64, _>("transfer-finished", move |id| {
+            set_transfers.update(|val| {
+                if let Some(hash) = val.remove(&id).and_then(|t| t.hash) {
+                    set_previews.update(|val| {
+                        val.remove(&hash);
+                    });
+                }
+            });
+        })
+        .await;
+
+        on_cleanup(unlisten);
+    });
+
+    spawn_local(async move {
+        let unlisten = listen::<(String, String, String, String), _>(
+            "sent-file-delivered",
+            move |(node_id, name, hash, status)| {
+                logging::log!("recv event sent-file-delivered: {} - {} - {}", node_id, name, status);
+                set_deliveries.update(|val| {
+                    val.insert((node_id, hash), status);
+                });
+            },
+        )
+        .await;
+
+        on_cleanup(unlisten);
+    });
+
+    spawn_local(async move {
+        let unlisten = listen::<(String, String), _>("incoming-preview", move |(hash, text)| {
+            set_previews.update(|val| {
+                val.insert(hash, text);
+            });
+        })
+        .await;
+
+        on_cleanup(unlisten);
+    });
+
+    spawn_local(async move {
+        let unlisten = listen::<(String, String, Option<LinkPreview>), _>(
+            "text-received",
+            move |(hash, text, preview)| {
+                set_links.update(|val| {
+                    val.insert(hash, (text, preview));
+                });
+            },
+        )
+        .await;
+
+        on_cleanup(unlisten);
+    });
+
+    spawn_local(async move {
+        set_peers.set(fetch_peers().await);
+    });
+    spawn_local(async move {
+        let unlisten = listen::<(String, String), _>("peer-online", move |(name, node_id)| {
+            logging::log!("recv event peer-online: {}: {}", name, node_id);
+            // The peer registry merges all discovery sources of a node, show its view.
+            spawn_local(async move {
+                set_peers.set(fetch_peers().await);
+            });
+        })
+        .await;
+
+        on_cleanup(unlisten);
+    });
+    spawn_local(async move {
+        let unlisten = listen::<(String, String), _>("peer-renamed", move |(node_id, name)| {
+            logging::log!("recv event peer-renamed: {}: {}", node_id, name);
+            set_peers.update(|val| {
+                if let Some(peer) = val.get_mut(&node_id) {
+                    peer.name = name;
+                }
+            });
+        })
+        .await;
+
+        on_cleanup(unlisten);
+    });
+    spawn_local(async move {
+        let unlisten = listen::<String, _>("peer-incompatible", move |node_id| {
+            logging::log!("recv event peer-incompatible: {}", node_id);
+            spawn_local(async move {
+                set_peers.set(fetch_peers().await);
+            });
+        })
+        .await;
+
+        on_cleanup(unlisten);
+    });
+    spawn_local(async move {
+        let unlisten = listen::<String, _>("peer-offline", move |node_id| {
+            logging::log!("recv event peer-offline: {}", node_id);
+            set_peers.update(|val| {
+                if let Some(peer) = val.get_mut(&node_id) {
+                    peer.online = false;
+                }
+            });
+        })
+        .await;
+
+        on_cleanup(unlisten);
+    });
+
+    let toaster = expect_toaster();
+    spawn_local(async move {
+        let unlisten = listen::<(String, String, Vec<(String, Option<DropError>)>), _>(
+            "group-sent",
+            move |(group, name, results)| {
+                let failed = results.iter().filter(|(_, err)| err.is_some()).count();
+                let (msg, level) = if failed == 0 {
+                    let msg = format!("Sent {} to everyone in {}", name, group);
+                    (msg, ToastLevel::Success)
+                } else {
+                    let sent = results.len() - failed;
+                    let msg = format!(
+                        "Sent {} to {} of {} devices in {}",
+                        name,
+                        sent,
+                        results.len(),
+                        group
+                    );
+                    (msg, ToastLevel::Warn)
+                };
+                toaster.toast(
+                    ToastBuilder::new(&msg)
+                        .with_level(level)
+                        .with_position(ToastPosition::TopRight),
+                );
+            },
+        )
+        .await;
+
+        on_cleanup(unlisten);
+    });
+
+    let toaster = expect_toaster();
+    spawn_local(async move {
+        let unlisten = listen::<(String, String, IdentityWarning), _>(
+            "identity-warning",
+            move |(node_id, name, warning)| {
+                logging::log!("recv event identity-warning: {}: {:?}", node_id, warning);
+                spawn_local(async move {
+                    set_peers.set(fetch_peers().await);
+                });
+                toaster.toast(
+                    ToastBuilder::new(&format!(
+                        "{}. Verify the device before sending it anything.",
+                        warning.describe(&name)
+                    ))
+                    .with_level(ToastLevel::Warn)
+                    .with_expiry(None)
+                    .with_position(ToastPosition::TopRight),
+                );
+            },
+        )
+        .await;
+
+        on_cleanup(unlisten);
+    });
+
+    let toaster = expect_toaster();
+    spawn_local(async move {
+        let unlisten = listen::<(String, String, String), _>(
+            "identity-verification",
+            move |(node_id, name, code)| {
+                logging::log!("recv event identity-verification: {}", node_id);
+                toaster.toast(
+                    ToastBuilder::new(&format!(
+                        "{} is verifying this device, check that it shows {}",
+                        name, code
+                    ))
+                    .with_level(ToastLevel::Info)
+                    .with_expiry(None)
+                    .with_position(ToastPosition::TopRight),
+                );
+            },
+        )
+        .await;
+
+        on_cleanup(unlisten);
+    });
+
+    let toaster = expect_toaster();
+    spawn_local(async move {
+        let unlisten =
+            listen::<(String, String), _>("peer-unreachable", move |(node_id, error)| {
+                logging::log!("recv event peer-unreachable: {} - {}", node_id, error);
+                let name = peers
+                    .get_untracked()
+                    .get(&node_id)
+                    .map(|peer| peer.name.clone())
+                    .unwrap_or_else(|| node_id.chars().take(8).collect());
+                toaster.toast(
+                    ToastBuilder::new(&format!("Could not reach {}: {}", name, error))
+                        .with_level(ToastLevel::Error)
+                        .with_expiry(None)
+                        .with_position(ToastPosition::TopRight),
+                );
+            })
+            .await;
+
+        on_cleanup(unlisten);
+    });
+
+    let toaster = expect_toaster();
+    spawn_local(async move {
+        let unlisten = listen::<(String, String, String, u64, Option<bool>, bool), _>(
+            "file-sent",
+            move |(node_id, name, hash, size, verified, already_had)| {
+                logging::log!("recv event file-sent: {} - {} - {} - {}", node_id, name, hash, size);
+                spawn_local(async move {
+                    set_history.set(fetch_history().await);
+                });
+                // Failed verifications are reported by `send_file`.
+                if verified == Some(false) {
+                    return;
+                }
+                let status = if already_had {
+                    "Already on the receiving device"
+                } else {
+                    delivery_status(verified)
+                };
+                toaster.toast(
+                    ToastBuilder::new(&format!("{}: {}", status, name))
+                        .with_level(ToastLevel::Success)
+                        .with_position(ToastPosition::TopRight),
+                );
+            },
+        )
+        .await;
+
+        on_cleanup(unlisten);
+    });
+
+    let toaster = expect_toaster();
+    spawn_local(async move {
+        let unlisten = listen::<(String, String, u64, bool), _>(
+            "file-downloaded",
+            move |(name, hash, size, already_had)| {
+                logging::log!("recv event file-downloaed: {} - {} - {}", name, hash, size);
+                spawn_local(async move {
+                    set_history.set(fetch_history().await);
+                });
+                let message = if already_had {
+                    format!("File received: {} (already on this device)", name)
+                } else {
+                    format!("File received: {} ({}bytes)", name, size)
+                };
+                toaster.toast(
+                    ToastBuilder::new(&message)
+                        .with_level(ToastLevel::Success)
+                        .with_expiry(None)
+                        .with_position(ToastPosition::TopRight),
+                );
+            },
+        )
+        .await;
+
+        on_cleanup(unlisten);
+    });
+
+    let toaster = expect_toaster();
+    spawn_local(async move {
+        let unlisten = listen::<Vec<String>, _>("received-while-away", move |names| {
+            toaster.toast(
+                ToastBuilder::new(&format!(
+                    "Received while in the background: {}",
+                    names.join(", ")
+                ))
+                .with_level(ToastLevel::Info)
+                .with_expiry(None)
+                .with_position(ToastPosition::TopRight),
+            );
+        })
+        .await;
+
+        on_cleanup(unlisten);
+    });
+
+    let toaster = expect_toaster();
+    spawn_local(async move {
+        let unlisten =
+            listen::<(String, String), _>("transfer-warning", move |(node_id, message)| {
+                logging::log!("recv event transfer-warning: {} - {}", node_id, message);
+                toaster.toast(
+                    ToastBuilder::new(&message)
+                        .with_level(ToastLevel::Warn)
+                        .with_position(ToastPosition::TopRight),
+                );
+            })
+            .await;
+
+        on_cleanup(unlisten);
+    });
+
+    let toaster = expect_toaster();
+    spawn_local(async move {
+        let unlisten = listen::<(String, String, u64, String), _>(
+            "offer-rejected",
+            move |(sender, name, size, reason)| {
+                logging::log!("recv event offer-rejected: {} - {} - {}", sender, name, reason);
+                let message = match reason.as_str() {
+                    "low_storage" => format!(
+                        "Not enough free space to receive {} ({}) from {}",
+                        name,
+                        format_bytes(size),
+                        sender
+                    ),
+                    "low_memory" => format!(
+                        "Not enough free memory to receive {} ({}) from {}",
+                        name,
+                        format_bytes(size),
+                        sender
+                    ),
+                    "too_large" => format!(
+                        "{} from {} is too large for this device ({})",
+                        name,
+                        sender,
+                        format_bytes(size)
+                    ),
+                    "exceeds_limit" => format!(
+                        "{} from {} is larger than you accept ({}), accept it once in the security log",
+                        name,
+                        sender,
+                        format_bytes(size)
+                    ),
+                    "timed_out" => format!(
+                        "{} from {} waited too long to be received and expired",
+                        name, sender
+                    ),
+                    _ => format!("Rejected {} from {}", name, sender),
+                };
+                // An expired offer needs no action, it goes away on its own.
+                let expiry = (reason == "timed_out").then_some(10_000);
+                toaster.toast(
+                    ToastBuilder::new(&message)
+                        .with_level(ToastLevel::Warn)
+                        .with_expiry(expiry)
+                        .with_position(ToastPosition::TopRight),
+                );
+            },
+        )
+        .await;
+
+        on_cleanup(unlisten);
+    });
+
+    let toaster = expect_toaster();
+    spawn_local(async move {
+        let unlisten = listen::<(String, String, String), _>(
+            "content-mismatch",
+            move |(name, hash, message)| {
+                logging::log!("recv event content-mismatch: {} - {} - {}", name, hash, message);
+                toaster.toast(
+                    ToastBuilder::new(&message)
+                        .with_level(ToastLevel::Error)
+                        .with_expiry(None)
+                        .with_position(ToastPosition::TopRight),
+                );
+            },
+        )
+        .await;
+
+        on_cleanup(unlisten);
+    });
+
+    spawn_local(async move {
+        set_trash.set(fetch_trash().await);
+    });
+    spawn_local(async move {
+        let unlisten = listen::<(), _>("history-changed", move |()| {
+            spawn_local(async move {
+                set_history.set(fetch_history().await);
+            });
+        })
+        .await;
+
+        on_cleanup(unlisten);
+    });
+    spawn_local(async move {
+        let unlisten = listen::<Vec<String>, _>("trash-purged", move |_hashes| {
+            spawn_local(async move {
+                set_trash.set(fetch_trash().await);
+            });
+        })
+        .await;
+
+        on_cleanup(unlisten);
+    });
+
+    let (invisible, set_invisible) = create_signal(false);
+    spawn_local(async move {
+        let result = invoke_without_args("is_invisible").await;
+        set_invisible.set(serde_wasm_bindgen::from_value(result).unwrap_or_default());
+    });
+    spawn_local(async move {
+        let unlisten = listen::<bool, _>("visibility-changed", move |value| {
+            set_invisible.set(value);
+        })
+        .await;
+
+        on_cleanup(unlisten);
+    });
+
+    #[derive(Serialize, Deserialize)]
+    struct SetInvisibleArgs {
+        invisible: bool,
+    }
+
+    let toaster = expect_toaster();
+    let toggle_invisible = move || {
+        let args = SetInvisibleArgs {
+            invisible: !invisible.get_untracked(),
+        };
+        spawn_local(async move {
+            let args = serde_wasm_bindgen::to_value(&args).expect("failed conversion");
+            if let Err(err) = try_invoke("set_invisible", args).await {
+                toaster.toast(
+                    ToastBuilder::new(&DropError::from(err).user_message())
+                        .with_level(ToastLevel::Error)
+                        .with_position(ToastPosition::TopRight),
+                );
+            }
+        });
+    };
+    // Ctrl+Shift+H hides the device right away, e.g. when joining a public network.
+    let shortcut = window_event_listener(ev::keydown, move |ev| {
+        if ev.ctrl_key() && ev.shift_key() && ev.key().eq_ignore_ascii_case("h") {
+            ev.prevent_default();
+            toggle_invisible();
+        }
+    });
+    on_cleanup(move || shortcut.remove());
+
+    spawn_local(async move {
+        let unlisten = listen::<String, _>("focus-transfer", move |hash| {
+            logging::log!("recv event focus-transfer: {}", hash);
+            spawn_local(async move {
+                set_history.set(fetch_history().await);
+                set_focused.set(Some(hash));
+            });
+        })
+        .await;
+
+        on_cleanup(unlisten);
+    });
+
+    let main_el = create_node_ref::<Main>();
+
+    view! {
+        <Toaster stacked={true} />
+        <RecipientPicker target=main_el peers=peers />
+        <SharedFilesView peers=peers />
+
+        <main class="container" node_ref=main_el>
+            <Show when=move || invisible.get()>
+                <p class="invisible">
+                    "Invisible: this device is hidden from others and transfers are paused."
+                </p>
+            </Show>
+            <div class="row">
+                <button on:click=move |_| toggle_invisible() title="Ctrl+Shift+H">
+                    { move || if invisible.get() { "Become visible" } else { "Go invisible" } }
+                </button>
+            </div>
+            <p>"Local iroh nodes are discovered automatically."</p>
+            <p>"Drop files on a device, or anywhere else to pick several devices."</p>
+            <p>"Use the arrow keys to select a device, and Enter to pick files to send to it."</p>
+            <p>"My Node: " { move || my_node_id.get() }</p>
+            <p class="ticket">"My Ticket: " { move || my_ticket.get() }</p>
+            <div class="row qr" inner_html=my_qr_code></div>
+
+            <form class="row" on:submit=add_remote>
+                <input
+                    placeholder="Ticket, node id or handle"
+                    on:input=move |ev| set_remote_ticket.set(event_target_value(&ev))
+                    prop:value=remote_ticket
+                />
+                <button type="submit">"Add remote device"</button>
+            </form>
+
+            <PeerList peers=peers set_peers=set_peers />
+            <Show when=move || peers.get().is_empty()>
+                <TroubleshootView />
+            </Show>
+
+            <NetworkTrustPrompt />
+            <PermissionsView />
+
+            <RoomView />
+            <GuestView />
+            <OwnDeviceView />
+            <EncryptedStorageView />
+            <AdminLockView />
+            <ProfileView />
+
+            <StatsView />
+            <TransferStatsView />
+            <StorageView />
+
+            <SecurityLogView />
+            <ConnectionAuditView />
+            <LogView />
+
+            <AdvancedSettingsView />
+            <WorkflowsView />
+
+            <Show when=move || !transfers.get().is_empty()>
+                <h3>"Transfers"</h3>
+                <ul class="transfers">
+                    { move || transfers.get().into_values()
+                        .map(|transfer| view! {
+                            <li class:queued=transfer.state == "queued" class:paused=transfer.state == "paused">
+                                {format!(
+                                    "{} {} ({}) - {}",
+                                    if transfer.direction == "sent" { "↑" } else { "↓" },
+                                    transfer.name,
+                                    format_bytes(transfer.size),
+                                    match transfer.progress {
+                                        Some((offset, _)) if transfer.state == "paused" => format!(
+                                            "paused at {}",
+                                            format_bytes(offset),
+                                        ),
+                                        Some((offset, bps)) => format!(
+                                            "{} at {}/s",
+                                            format_bytes(offset),
+                                            format_bytes(bps),
+                                        ),
+                                        None => transfer.state.clone(),
+                                    },
+                                )}
+                                { (transfer.direction == "received" && transfer.state != "queued")
+                                    .then(|| pause_button(transfer.id, transfer.state == "paused")) }
+                                { details_button(transfer.id) }
+                                { transfer.hash.as_ref()
+                                    .and_then(|hash| previews.get().get(hash).cloned())
+                                    .map(|text| view! { <pre class="preview">{text}</pre> }) }
+                            </li>
+                        })
+                        .collect_view() }
+                </ul>
+            </Show>
+
+            <LibraryView
+                focused=focused
+                peers=peers
+                links=links
+                set_history=set_history
+                set_trash=set_trash
+            />
+
+            <h3>"Received"</h3>
+            <ul class="received">
+                { move || history.get().into_iter()
+                    .filter(|entry| entry.direction == "received")
+                    .map(move |entry| received_view(entry, focused, peers, links, set_history, set_trash))
+                    .collect_view() }
+            </ul>
+
+            <h3>"Sent"</h3>
+            <ul class="sent">
+                { move || history.get().into_iter()
+                    .filter(|entry| entry.direction == "sent")
+                    .map(move |entry| {
+                        let hash = entry.hash.clone();
+                        let downloading = deliveries
+                            .get()
+                            .get(&(entry.node_id.clone(), entry.hash.clone()))
+                            .is_some_and(|status| status == "downloading");
+                        view! {
+                            <li class:warning=entry.verified == Some(false)>
+                                {format!("{} ({}bytes)", entry.name, entry.size)}
+                                <p>{ if downloading {
+                                    "Downloading on the receiving device"
+                                } else {
+                                    delivery_status(entry.verified)
+                                } }</p>
+                                { note_view(&entry, set_history) }
+                                <button on:click=move |_| {
+                                    let hash = hash.clone();
+                                    spawn_local(async move {
+                                        set_deleted(hash, true, set_history, set_trash).await.ok();
+                                    });
+                                }>"Delete"</button>
+                            </li>
+                        }
+                    })
+                    .collect_view() }
+            </ul>
+
+            <Show when=move || !trash.get().is_empty()>
+                <h3>"Recently deleted"</h3>
+                <ul class="trash">
+                    { move || trash.get().into_iter()
+                        .map(|entry| {
+                            let hash = entry.hash.clone();
+                            view! {
+                                <li>
+                                    {format!("{} ({}bytes)", entry.name, entry.size)}
+                                    <button on:click=move |_| {
+                                        let hash = hash.clone();
+                                        spawn_local(async move {
+                                            set_deleted(hash, false, set_history, set_trash).await.ok();
+                                        });
+                                    }>"Undo"</button>
+                                </li>
+                            }
+                        })
+                        .collect_view() }
+                </ul>
+            </Show>
+        </main>
+    }
+}
+
+/// A received file found in the library.
+#[derive(Debug, Clone, Deserialize)]
+pub struct LibraryFile {
+    #[serde(flatten)]
+    pub entry: HistoryEntry,
+    pub sender: Option<String>,
+    /// `image`, `video`, `audio`, `document`, `archive`, `text` or `other`
+    pub kind: String,
+}
+
+/// Filters of `search_received`, dates in seconds since the unix epoch.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SearchQuery {
+    pub text: String,
+    pub node_id: Option<String>,
+    pub from: Option<u64>,
+    pub until: Option<u64>,
+    pub kind: Option<String>,
+}
+
+/// Seconds since the unix epoch of the start of the day `date` from a date input, plus
+/// `days`.
+fn parse_date(date: &str, days: u64) -> Option<u64> {
+    let ms = js_sys::Date::parse(date);
+    (!ms.is_nan()).then(|| (ms / 1000.) as u64 + days * 86_400)
+}
+
+/// The received files, searchable by name, sender, date and type.
+#[component]
+fn LibraryView(
+    focused: ReadSignal<Option<String>>,
+    peers: ReadSignal<HashMap<String, PeerInfo>>,
+    links: ReadSignal<HashMap<String, (String, Option<LinkPreview>)>>,
+    set_history: WriteSignal<Vec<HistoryEntry>>,
+    set_trash: WriteSignal<Vec<HistoryEntry>>,
+) -> impl IntoView {
+    #[derive(Serialize)]
+    struct SearchArgs {
+        query: SearchQuery,
+    }
+
+    let (query, set_query) = create_signal(SearchQuery::default());
+    let (results, set_results) = create_signal(None::<Vec<LibraryFile>>);
+    let search = move |ev: SubmitEvent| {
+        ev.prevent_default();
+        spawn_local(async move {
+            let args = serde_wasm_bindgen::to_value(&SearchArgs {
+                query: query.get_untracked(),
+            })
+            .expect("failed conversion");
+            match try_invoke("search_received", args).await {
+                Ok(result) => set_results.set(serde_wasm_bindgen::from_value(result).ok()),
+                Err(err) => logging::warn!("search failed: {:?}", DropError::from(err)),
+            }
+        });
+    };
+    let optional = |value: String| (!value.is_empty()).then_some(value);
+
+    view! {
+        <details class="settings">
+            <summary>"Library"</summary>
+            <form class="library-filters" on:submit=search>
+                <input
+                    type="search"
+                    placeholder="Name, sender or note"
+                    on:input=move |ev| set_query.update(|q| q.text = event_target_value(&ev))
+                    prop:value=move || query.get().text
+                />
+                <select on:change=move |ev| {
+                    set_query.update(|q| q.node_id = optional(event_target_value(&ev)));
+                }>
+                    <option value="">"From anyone"</option>
+                    { move || peers.get().into_values()
+                        .map(|peer| view! { <option value=peer.node_id>{peer.name}</option> })
+                        .collect_view() }
+                </select>
+                <select on:change=move |ev| {
+                    set_query.update(|q| q.kind = optional(event_target_value(&ev)));
+                }>
+                    <option value="">"Any type"</option>
+                    <option value="image">"Images"</option>
+                    <option value="video">"Videos"</option>
+                    <option value="audio">"Audio"</option>
+                    <option value="document">"Documents"</option>
+                    <option value="archive">"Archives"</option>
+                    <option value="text">"Text"</option>
+                    <option value="other">"Other"</option>
+                </select>
+                <label>
+                    "From"
+                    <input
+                        type="date"
+                        on:change=move |ev| {
+                            set_query.update(|q| q.from = parse_date(&event_target_value(&ev), 0));
+                        }
+                    />
+                </label>
+                <label>
+                    "Until"
+                    <input
+                        type="date"
+                        on:change=move |ev| {
+                            // Including the whole last day.
+                            set_query.update(|q| q.until = parse_date(&event_target_value(&ev), 1));
+                        }
+                    />
+                </label>
+                <button type="submit">"Search"</button>
+            </form>
+            { move || results.get().map(|files| if files.is_empty() {
+                view! { <p>"No received files match."</p> }.into_view()
+            } else {
+                files.into_iter()
+                    .map(|file| {
+                        let date = js_sys::Date::new(&JsValue::from_f64(file.entry.timestamp as f64 * 1000.));
+                        let date: String = date.to_iso_string().into();
+                        let sender = file.sender.unwrap_or_else(|| file.entry.node_id[..8].to_string());
+                        view! {
+                            <p class="library-sender">{format!("From {} on {}", sender, &date[..10])}</p>
+                            <ul class="received">
+                                { received_view(file.entry, focused, peers, links, set_history, set_trash) }
+                            </ul>
+                        }
+                    })
+                    .collect_view()
+            }) }
+        </details>
+    }
+}
+
+fn delivery_status(verified: Option<bool>) -> &'static str {
+    match verified {
+        Some(true) => "Delivered and verified",
+        Some(false) => "Verification failed",
+        None => "Delivered",
+    }
+}
+
+/// Moves the focus from the device card of `ev` by `step` cards, wrapping around.
+fn focus_card(ev: &ev::KeyboardEvent, step: i32) {
+    let Ok(cards) = document().query_selector_all(".dropzone") else {
+        return;
+    };
+    let cards: Vec<web_sys::HtmlElement> = (0..cards.length())
+        .filter_map(|i| cards.item(i))
+        .filter_map(|card| card.dyn_into().ok())
+        .collect();
+    let Some(current) = ev.current_target().map(JsValue::from) else {
+        return;
+    };
+    let Some(index) = cards.iter().position(|card| JsValue::from(card) == current) else {
+        return;
+    };
+    let next = (index as i32 + step).rem_euclid(cards.len() as i32) as usize;
+    cards[next].focus().ok();
+}
+
+/// Formats a unix timestamp relative to now, e.g. "5 min ago".
+fn format_ago(timestamp: u64) -> String {
+    let now = (js_sys::Date::now() / 1000.) as u64;
+    let secs = now.saturating_sub(timestamp);
+    match secs {
+        0..=59 => "just now".to_string(),
+        60..=3599 => format!("{} min ago", secs / 60),
+        3600..=86399 => format!("{} h ago", secs / 3600),
+        _ => format!("{} days ago", secs / 86400),
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConnectionInfo {
+    pub path: String,
+    pub latency_ms: Option<u64>,
+    pub relay_url: Option<String>,
+    pub addrs: Vec<String>,
+    pub quality: String,
+}
+
+impl ConnectionInfo {
+    /// Details for the tooltip of the quality indicator.
+    fn describe(&self) -> String {
+        let mut lines = vec![format!("Connection: {}", self.path)];
+        if let Some(latency) = self.latency_ms {
+            lines.push(format!("Latency: {}ms", latency));
+        }
+        if let Some(ref relay) = self.relay_url {
+            lines.push(format!("Relay: {}", relay));
+        }
+        lines.extend(self.addrs.iter().cloned());
+        lines.join("\n")
+    }
+}
+
+/// Extensions of executables and scripts, matching the backend.
+const EXECUTABLE_EXTENSIONS: &[&str] = &[
+    "exe", "msi", "bat", "cmd", "com", "scr", "ps1", "vbs", "js", "jar", "app", "dmg", "pkg",
+    "sh", "bash", "zsh", "command", "py", "pl", "rb", "apk", "deb", "rpm", "appimage", "run",
+    "bin", "elf", "dylib", "so", "dll",
+];
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ReceiveConstraints {
+    pub max_file_size: Option<u64>,
+    pub accepts_folders: bool,
+    pub accepts_executables: bool,
+}
+
+impl ReceiveConstraints {
+    /// Why the peer would reject the file `name`, if it would.
+    fn problem(&self, name: &str, size: u64) -> Option<String> {
+        if let Some(max) = self.max_file_size.filter(|max| size > *max) {
+            return Some(format!("the device only has room for {}", format_bytes(max)));
+        }
+        let executable = name
+            .rsplit_once('.')
+            .is_some_and(|(_, ext)| EXECUTABLE_EXTENSIONS.contains(&ext.to_lowercase().as_str()));
+        if executable && !self.accepts_executables {
+            return Some("the device does not accept executables".to_string());
+        }
+        None
+    }
+
+    /// Summary for the device card.
+    fn describe(&self) -> String {
+        let mut limits = Vec::new();
+        if let Some(max) = self.max_file_size {
+            limits.push(format!("up to {}", format_bytes(max)));
+        }
+        if !self.accepts_executables {
+            limits.push("no executables".to_string());
+        }
+        if !self.accepts_folders {
+            limits.push("no folders".to_string());
+        }
+        limits.join(", ")
+    }
+}
+
+/// How long share links of the history are valid.
+const SHARE_LINK_TTL_SECS: u64 = 60 * 60;
+
+/// Files at least this large are checked with the receiver before they are read, matching
+/// the backend.
+const PREFLIGHT_MIN_SIZE: u64 = 64 * 1024 * 1024;
+
+/// The device cards, own devices first, with search and sorting for large networks.
+#[component]
+fn PeerList(
+    peers: ReadSignal<HashMap<String, PeerInfo>>,
+    set_peers: WriteSignal<HashMap<String, PeerInfo>>,
+) -> impl IntoView {
+    let (order, set_order) = create_signal(PeerOrder::Name);
+    let (query, set_query) = create_signal(String::new());
+    let (limit, set_limit) = create_signal(PEER_PAGE);
+
+    let sorted = create_memo(move |_| {
+        query.with(|query| sort_peers(peers.get().into_values(), order.get(), query))
+    });
+    let own = move || {
+        sorted
+            .get()
+            .into_iter()
+            .filter(|peer| peer.own_device)
+            .collect::<Vec<_>>()
+    };
+    let others = create_memo(move |_| {
+        sorted
+            .get()
+            .into_iter()
+            .filter(|peer| !peer.own_device)
+            .collect::<Vec<_>>()
+    });
+    let hidden = move || others.with(|others| others.len().saturating_sub(limit.get()));
+
+    view! {
+        <Show when=move || { peers.with(|peers| peers.len() > 1) }>
+            <div class="row peer-controls">
+                <input
+                    type="search"
+                    placeholder="Search devices"
+                    prop:value=query
+                    on:input=move |ev| {
+                        set_query.set(event_target_value(&ev));
+                        set_limit.set(PEER_PAGE);
+                    }
+                />
+                <select on:change=move |ev| {
+                    let order = match event_target_value(&ev).as_str() {
+                        "last-seen" => PeerOrder::LastSeen,
+                        _ => PeerOrder::Name,
+                    };
+                    set_order.set(order);
+                }>
+                    <option value="name">"By name"</option>
+                    <option value="last-seen">"Recently seen"</option>
+                </select>
+            </div>
+        </Show>
+        <Show when=move || sorted.with(|peers| peers.iter().any(|peer| peer.own_device))>
+            <h3>"My devices"</h3>
+            <p><b>
+                <For each=own key=PeerInfo::render_key children=move |peer| node_view(peer, set_peers) />
+            </b></p>
+            <h3>"Other devices"</h3>
+        </Show>
+        <p><b>
+            <For
+                each=move || others.get().into_iter().take(limit.get())
+                key=PeerInfo::render_key
+                children=move |peer| node_view(peer, set_peers)
+            />
+        </b></p>
+        <Show when=move || { hidden() > 0 }>
+            <button on:click=move |_| set_limit.update(|limit| *limit += PEER_PAGE)>
+                {move || format!("Show more ({} hidden)", hidden())}
+            </button>
+        </Show>
+    }
+}
+
+fn node_view(peer: PeerInfo, set_peers: WriteSignal<HashMap<String, PeerInfo>>) -> impl IntoView {
+    let PeerInfo {
+        node_id,
+        name,
+        online,
+        last_seen,
+        device,
+        constraints,
+        own_device,
+        remote,
+        guest,
+        send_transforms,
+        handle,
+        identity_warning,
+        identity_verified,
+        favorite,
+        capabilities,
+        incompatible,
+    } = peer;
+    let (dropped, set_dropped) = create_signal(false);
+    let (own, set_own) = create_signal(own_device);
+    let (transforms, set_transforms) = create_signal(send_transforms);
+    // While set, dropped files are held for a swap instead of being sent.
+    let (hold, set_hold) = create_signal(false);
+    let (held, set_held) = create_signal(0usize);
+    // Files sent, received, to send and to receive of the running swap.
+    let (swap, set_swap) = create_signal(None::<(u32, u32, u32, u32)>);
+
+    let drop_zone_el = create_node_ref::<Div>();
+
+    #[derive(Debug, Serialize, Deserialize)]
+    struct SendFileArgs {
+        node_id: String,
+        file_name: String,
+        file_data: Vec<u8>,
+        metadata: FileMetadata,
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    struct PreflightArgs {
+        node_id: String,
+        file_name: String,
+        size: u64,
+        metadata: FileMetadata,
+    }
+
+    let toaster = expect_toaster();
+    let node = node_id.clone();
+    let limits = constraints.clone();
+    let on_drop = move |event: UseDropZoneEvent| {
+        let node_id = node.clone();
+        let limits = limits.clone();
+        set_dropped.set(true);
+        spawn_local(async move {
+            let file = &event.files[0];
+            logging::log!("reading: {:?}", file);
+            let metadata = FileMetadata {
+                mime: Some(file.type_()).filter(|mime| !mime.is_empty()),
+                modified: Some((file.last_modified() / 1000.0) as u64),
+                permissions: None,
+            };
+            let command = if hold.get_untracked() {
+                "queue_swap_file"
+            } else {
+                "send_file"
+            };
+            let size = file.size() as u64;
+            if let Some(problem) = limits.and_then(|limits| limits.problem(&file.name(), size)) {
+                toaster.toast(
+                    ToastBuilder::new(&format!("Not sending {}: {}", file.name(), problem))
+                        .with_level(ToastLevel::Warn)
+                        .with_position(ToastPosition::TopRight),
+                );
+                return;
+            }
+            // Large files are only read once the receiver agreed to take them.
+            if command == "send_file" && size >= PREFLIGHT_MIN_SIZE {
+                let args = serde_wasm_bindgen::to_value(&PreflightArgs {
+                    node_id: node_id.clone(),
+                    file_name: file.name(),
+                    size,
+                    metadata: metadata.clone(),
+                })
+                .expect("failed conversion");
+                if let Err(err) = try_invoke("preflight", args).await {
+                    toaster.toast(
+                        ToastBuilder::new(&format!(
+                            "Not sending {}: {}",
+                            file.name(),
+                            DropError::from(err).user_message()
+                        ))
+                        .with_level(ToastLevel::Error)
+                        .with_position(ToastPosition::TopRight),
+                    );
+                    return;
+                }
+            }
+            let buffer = JsFuture::from(file.array_buffer())
+                .await
+                .expect("failed future");
+            let array = Uint8Array::new(&buffer);
+            let file_data: Vec<u8> = array.to_vec();
+            logging::log!("{} to {}", command, node_id);
+            let args = serde_wasm_bindgen::to_value(&SendFileArgs {
+                node_id,
+                file_name: file.name(),
+                file_data,
+                metadata,
+            })
+                .expect("failed conversion");
+            match try_invoke(command, args).await {
+                Ok(result) if command == "queue_swap_file" => {
+                    set_held.set(serde_wasm_bindgen::from_value(result).unwrap_or_default());
+                }
+                Ok(_) => logging::log!("sent file"),
+                Err(err) => {
+                    let err = DropError::from(err);
+                    logging::error!("failed to send file: {:?}", err);
+                    toaster.toast(
+                        ToastBuilder::new(&format!("Failed to send: {}", err.user_message()))
+                            .with_level(ToastLevel::Error)
+                            .with_position(ToastPosition::TopRight),
+                    );
+                }
+            }
+        })
+    };
+
+    #[derive(Debug, Serialize, Deserialize)]
+    struct SetOwnDeviceArgs {
+        node_id: String,
+        own: bool,
+    }
+
+    let toaster = expect_toaster();
+    let node = node_id.clone();
+    let toggle_own = move |ev| {
+        let own = event_target_checked(&ev);
+        let node_id = node.clone();
+        spawn_local(async move {
+            let args = serde_wasm_bindgen::to_value(&SetOwnDeviceArgs { node_id, own })
+                .expect("failed conversion");
+            match try_invoke("set_own_device", args).await {
+                Ok(_) => set_own.set(own),
+                Err(err) => {
+                    toaster.toast(
+                        ToastBuilder::new(&format!(
+                            "Failed to update device: {}",
+                            DropError::from(err).user_message()
+                        ))
+                        .with_level(ToastLevel::Error)
+                        .with_position(ToastPosition::TopRight),
+                    );
+                }
+            }
+        });
+    };
+
+    let UseDropZoneReturn {
+        is_over_drop_zone,
+        files,
+    } = use_drop_zone_with_options(
+        drop_zone_el,
+        UseDropZoneOptions::default()
+            .on_drop(on_drop)
+            .on_enter(move |_| set_dropped.set(false)),
+    );
+
+    let class = move || {
+        let mut base = "row dropzone".to_string();
+        if !online || incompatible {
+            base += " offline";
+        }
+        if is_over_drop_zone.get() {
+            base += " dropping";
+        }
+        base
+    };
+    #[derive(Debug, Serialize, Deserialize)]
+    struct SetSendTransformsArgs {
+        node_id: String,
+        transforms: Vec<String>,
+    }
+
+    let toaster = expect_toaster();
+    let node = node_id.clone();
+    let toggle_transform = move |transform: &'static str, enabled: bool| {
+        let mut list = transforms.get_untracked();
+        list.retain(|t| t != transform);
+        if enabled {
+            list.push(transform.to_string());
+        }
+        let node_id = node.clone();
+        spawn_local(async move {
+            let args = serde_wasm_bindgen::to_value(&SetSendTransformsArgs {
+                node_id,
+                transforms: list.clone(),
+            })
+            .expect("failed conversion");
+            match try_invoke("set_send_transforms", args).await {
+                Ok(_) => set_transforms.set(list),
+                Err(err) => {
+                    toaster.toast(
+                        ToastBuilder::new(&format!(
+                            "Failed to update conversions: {}",
+                            DropError::from(err).user_message()
+                        ))
+                        .with_level(ToastLevel::Error)
+                        .with_position(ToastPosition::TopRight),
+                    );
+                }
+            }
+        });
+    };
+
+    #[derive(Debug, Serialize, Deserialize)]
+    struct SendClipboardFileArgs {
+        node_id: String,
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    struct PickAndSendArgs {
+        node_id: String,
+    }
+
+    let toaster = expect_toaster();
+    let node = node_id.clone();
+    let pick_and_send = move || {
+        let node_id = node.clone();
+        let toaster = toaster.clone();
+        spawn_local(async move {
+            let args = serde_wasm_bindgen::to_value(&PickAndSendArgs { node_id })
+                .expect("failed conversion");
+            if let Err(err) = try_invoke("pick_and_send", args).await {
+                toaster.toast(
+                    ToastBuilder::new(&format!(
+                        "Failed to send: {}",
+                        DropError::from(err).user_message()
+                    ))
+                    .with_level(ToastLevel::Error)
+                    .with_position(ToastPosition::TopRight),
+                );
+            }
+        });
+    };
+
+    let toaster = expect_toaster();
+    let node = node_id.clone();
+    let pick_media = move || {
+        let node_id = node.clone();
+        let toaster = toaster.clone();
+        spawn_local(async move {
+            let args = serde_wasm_bindgen::to_value(&PickAndSendArgs { node_id })
+                .expect("failed conversion");
+            if let Err(err) = try_invoke("pick_media_and_send", args).await {
+                toaster.toast(
+                    ToastBuilder::new(&format!(
+                        "Failed to send: {}",
+                        DropError::from(err).user_message()
+                    ))
+                    .with_level(ToastLevel::Error)
+                    .with_position(ToastPosition::TopRight),
+                );
+            }
+        });
+    };
+
+    // Arrow keys move between the cards, Enter picks files to send, and Ctrl+V (Cmd+V on
+    // macOS) on a focused card sends the clipboard.
+    let toaster = expect_toaster();
+    let node = node_id.clone();
+    let on_keydown = move |ev: ev::KeyboardEvent| {
+        // Keys typed into the controls of the card are left to them.
+        let on_card = ev.target() == ev.current_target();
+        if on_card && !(ev.ctrl_key() || ev.meta_key() || ev.alt_key()) {
+            let step = match ev.key().as_str() {
+                "ArrowDown" | "ArrowRight" => Some(1),
+                "ArrowUp" | "ArrowLeft" => Some(-1),
+                "Enter" => {
+                    ev.prevent_default();
+                    pick_and_send();
+                    return;
+                }
+                _ => None,
+            };
+            if let Some(step) = step {
+                ev.prevent_default();
+                focus_card(&ev, step);
+                return;
+            }
+        }
+        if !(ev.ctrl_key() || ev.meta_key()) || !ev.key().eq_ignore_ascii_case("v") {
+            return;
+        }
+        ev.prevent_default();
+        let node_id = node.clone();
+        spawn_local(async move {
+            let args = serde_wasm_bindgen::to_value(&SendClipboardFileArgs { node_id })
+                .expect("failed conversion");
+            match try_invoke("send_clipboard_file", args).await {
+                Ok(_) => logging::log!("sent clipboard"),
+                Err(err) => {
+                    toaster.toast(
+                        ToastBuilder::new(&format!(
+                            "Failed to send the clipboard: {}",
+                            DropError::from(err).user_message()
+                        ))
+                        .with_level(ToastLevel::Error)
+                        .with_position(ToastPosition::TopRight),
+                    );
+                }
+            }
+        });
+    };
+
+    #[derive(Debug, Serialize, Deserialize)]
+    struct SwapArgs {
+        node_id: String,
+    }
+
+    let node = node_id.clone();
+    spawn_local(async move {
+        let unlisten = listen::<(String, u32, u32, u32, u32), _>(
+            "swap-progress",
+            move |(node_id, sent, received, to_send, to_receive)| {
+                if node_id == node {
+                    // The held files are part of the swap now.
+                    set_held.set(0);
+                    set_swap.set(Some((sent, received, to_send, to_receive)));
+                }
+            },
+        )
+        .await;
+
+        on_cleanup(unlisten);
+    });
+
+    let toaster = expect_toaster();
+    let node = node_id.clone();
+    let start_swap = move |_| {
+        let node_id = node.clone();
+        spawn_local(async move {
+            let args =
+                serde_wasm_bindgen::to_value(&SwapArgs { node_id }).expect("failed conversion");
+            match try_invoke("start_swap", args).await {
+                Ok(_) => set_held.set(0),
+                Err(err) => {
+                    toaster.toast(
+                        ToastBuilder::new(&format!(
+                            "Failed to swap: {}",
+                            DropError::from(err).user_message()
+                        ))
+                        .with_level(ToastLevel::Error)
+                        .with_position(ToastPosition::TopRight),
+                    );
+                }
+            }
+        });
+    };
+
+    let node = node_id.clone();
+    let clear_swap = move |_| {
+        let node_id = node.clone();
+        spawn_local(async move {
+            let args =
+                serde_wasm_bindgen::to_value(&SwapArgs { node_id }).expect("failed conversion");
+            if try_invoke("clear_swap_queue", args).await.is_ok() {
+                set_held.set(0);
+            }
+        });
+    };
+
+    let swap_status = move || {
+        swap.get().map(|(sent, received, to_send, to_receive)| {
+            if sent >= to_send && received >= to_receive {
+                format!("Swap done: sent {}, received {}", sent, received)
+            } else {
+                format!(
+                    "Swapping: sent {}/{}, received {}/{}",
+                    sent, to_send, received, to_receive
+                )
+            }
+        })
+    };
+
+    #[derive(Debug, Serialize, Deserialize)]
+    struct PeerConnectionInfoArgs {
+        node_id: String,
+    }
+
+    let (connection, set_connection) = create_signal(None::<ConnectionInfo>);
+    let node = node_id.clone();
+    spawn_local(async move {
+        let args = serde_wasm_bindgen::to_value(&PeerConnectionInfoArgs { node_id: node })
+            .expect("failed conversion");
+        if let Ok(result) = try_invoke("peer_connection_info", args).await {
+            set_connection.set(serde_wasm_bindgen::from_value(result).unwrap_or_default());
+        }
+    });
+    let quality = move || {
+        connection.get().map(|info| {
+            view! { <span class=format!("quality {}", info.quality) title=info.describe()></span> }
+        })
+    };
+
+    #[derive(Debug, Serialize, Deserialize)]
+    struct SetFavoriteArgs {
+        node_id: String,
+        favorite: bool,
+    }
+
+    let node = node_id.clone();
+    let toggle_favorite = move |_| {
+        let node_id = node.clone();
+        let favorite = !favorite;
+        spawn_local(async move {
+            let args = serde_wasm_bindgen::to_value(&SetFavoriteArgs {
+                node_id: node_id.clone(),
+                favorite,
+            })
+            .expect("failed conversion");
+            if try_invoke("set_favorite", args).await.is_ok() {
+                set_peers.update(|peers| {
+                    if let Some(peer) = peers.get_mut(&node_id) {
+                        peer.favorite = favorite;
+                    }
+                });
+            }
+        });
+    };
+
+    #[derive(Debug, Serialize, Deserialize)]
+    struct IdentityArgs {
+        node_id: String,
+    }
+
+    let (warning, set_warning) = create_signal(identity_warning);
+    let (verified, set_verified) = create_signal(identity_verified);
+    // The code to compare with the one the peer shows, while verifying.
+    let (code, set_code) = create_signal(None::<String>);
+    let toaster = expect_toaster();
+    let node = node_id.clone();
+    let verify_identity = move |_| {
+        let node_id = node.clone();
+        let toaster = toaster.clone();
+        spawn_local(async move {
+            let args =
+                serde_wasm_bindgen::to_value(&IdentityArgs { node_id }).expect("failed conversion");
+            match try_invoke("verify_identity", args).await {
+                Ok(result) => set_code.set(serde_wasm_bindgen::from_value(result).ok()),
+                Err(err) => {
+                    toaster.toast(
+                        ToastBuilder::new(&format!(
+                            "Failed to verify: {}",
+                            DropError::from(err).user_message()
+                        ))
+                        .with_level(ToastLevel::Error)
+                        .with_position(ToastPosition::TopRight),
+                    );
+                }
+            }
+        });
+    };
+    let node = node_id.clone();
+    let confirm_identity = move |_| {
+        let node_id = node.clone();
+        spawn_local(async move {
+            let args =
+                serde_wasm_bindgen::to_value(&IdentityArgs { node_id }).expect("failed conversion");
+            if try_invoke("confirm_identity", args).await.is_ok() {
+                set_warning.set(None);
+                set_verified.set(true);
+            }
+            set_code.set(None);
+        });
+    };
+    let warning_name = name.clone();
+    let identity_status = move || {
+        warning.get().map(
+            |warning| view! { <p class="identity-warning">{warning.describe(&warning_name)}</p> },
+        )
+    };
+
+    logging::log!("showing {}: {}", name, node_id);
+
+    view! {
+        <div node_ref=drop_zone_el class={ class } tabindex="0" on:keydown=on_keydown>
+          <p title=device.platform.clone()>
+            <button
+                class="favorite"
+                title=if favorite { "Unpin from the top" } else { "Pin to the top" }
+                on:click=toggle_favorite
+            >
+                { if favorite { "★" } else { "☆" } }
+            </button>
+            {quality}
+            {format!("{} {} ({})", device.icon(), name, node_id)}
+            { remote.then(|| view! { <span class="badge" title="Found through DNS">"remote"</span> }) }
+            { guest.then(|| view! { <span class="badge" title="Can send files until the guest ticket expires">"guest"</span> }) }
+            { handle.map(|handle| view! { <span class="badge" title="Verified through DNS">{handle}</span> }) }
+            { move || verified.get().then(|| view! { <span class="badge" title="Verification codes compared">"verified"</span> }) }
+          </p>
+          {identity_status}
+          { incompatible.then(|| {
+              let version = if capabilities.version > 0 {
+                  format!(" (protocol {})", capabilities.version)
+              } else {
+                  String::new()
+              };
+              view! {
+                  <p class="incompatible">
+                      {format!("Needs update: runs an incompatible version of iroh-drop{}. ", version)}
+                      "Update the app on both devices to send files."
+                  </p>
+              }
+          }) }
+          { move || match code.get() {
+              Some(code) => view! {
+                  <p class="verification">
+                    "Check that the device shows " <code>{code}</code>
+                    <button on:click=confirm_identity.clone()>"Codes match"</button>
+                    <button on:click=move |_| set_code.set(None)>"Cancel"</button>
+                  </p>
+              }.into_view(),
+              None => (online && !verified.get()).then(|| view! {
+                  <button on:click=verify_identity.clone()>"Verify"</button>
+              }).into_view(),
+          } }
+          // Phones have no drag and drop, photos and videos are picked instead.
+          { (online && is_mobile()).then(|| view! {
+              <button on:click=move |_| pick_media()>"Send photos and videos"</button>
+          }) }
+          { (!online).then(|| {
+              // Paired devices are listed before they were seen.
+              let seen = if last_seen == 0 {
+                  "not seen yet".to_string()
+              } else {
+                  format!("last seen {}", format_ago(last_seen))
+              };
+              view! { <p>{seen}</p> }
+          }) }
+          { constraints.map(|limits| view! { <p class="constraints">{limits.describe()}</p> }) }
+          <label>
+            <input type="checkbox" prop:checked=move || own.get() on:change=toggle_own />
+            "My device"
+          </label>
+          <details>
+            <summary>"Conversions"</summary>
+            { TRANSFORMS.iter().map(|&(transform, label)| {
+                let toggle_transform = toggle_transform.clone();
+                view! {
+                    <label>
+                        <input
+                            type="checkbox"
+                            prop:checked=move || transforms.get().iter().any(|t| t == transform)
+                            on:change=move |ev| toggle_transform(transform, event_target_checked(&ev))
+                        />
+                        {label}
+                    </label>
+                }
+            }).collect_view() }
+          </details>
+          <label>
+            <input
+                type="checkbox"
+                prop:checked=move || hold.get()
+                on:change=move |ev| set_hold.set(event_target_checked(&ev))
+            />
+            "Hold files for a swap"
+          </label>
+          <Show when=move || hold.get() || held.get() > 0>
+            <p>
+              <button on:click=start_swap.clone()>{move || format!("Swap ({} held)", held.get())}</button>
+              <button on:click=clear_swap.clone() disabled=move || held.get() == 0>"Clear"</button>
+            </p>
+          </Show>
+          { move || swap_status().map(|status| view! { <p class="swap-status">{status}</p> }) }
+        </div>
+    }
+}
+
+/// Whether the app runs on a phone or tablet.
+fn is_mobile() -> bool {
+    let agent = window().navigator().user_agent().unwrap_or_default();
+    ["Android", "iPhone", "iPad"]
+        .iter()
+        .any(|os| agent.contains(os))
+}
+
+fn received_view(
+    entry: HistoryEntry,
+    focused: ReadSignal<Option<String>>,
+    peers: ReadSignal<HashMap<String, PeerInfo>>,
+    links: ReadSignal<HashMap<String, (String, Option<LinkPreview>)>>,
+    set_history: WriteSignal<Vec<HistoryEntry>>,
+    set_trash: WriteSignal<Vec<HistoryEntry>>,
+) -> impl IntoView {
+    #[derive(Debug, Serialize, Deserialize)]
+    struct ExportReceivedArgs {
+        hash: String,
+        confirmed: bool,
+        target: &'static str,
+    }
+
+    let toaster = expect_toaster();
+    let hash = entry.hash.clone();
+    let hash_focus = entry.hash.clone();
+    let hash_delete = entry.hash.clone();
+    let hash_link = entry.hash.clone();
+
+    #[derive(Debug, Serialize, Deserialize)]
+    struct ReadReceivedBlobArgs {
+        hash: String,
+    }
+
+    let (preview, set_preview) = create_signal(None::<String>);
+    if has_preview(&entry.name) {
+        let hash = entry.hash.clone();
+        spawn_local(async move {
+            let args = serde_wasm_bindgen::to_value(&ReadReceivedBlobArgs { hash })
+                .expect("failed conversion");
+            match try_invoke("read_received_blob", args).await {
+                Ok(result) => set_preview.set(serde_wasm_bindgen::from_value(result).ok()),
+                Err(err) => logging::warn!("no preview: {:?}", DropError::from(err)),
+            }
+        });
+    }
+    let name = entry.name.clone();
+    let quarantined = entry.quarantined;
+    let is_media = is_media(&entry.name);
+    let export = move |target: &'static str| {
+        let confirmed = quarantined
+            && window()
+                .confirm_with_message(&format!(
+                    "\"{}\" is an executable or script. Only save it if you trust the sender.",
+                    name
+                ))
+                .unwrap_or(false);
+        if quarantined && !confirmed {
+            return;
+        }
+        let hash = hash.clone();
+        spawn_local(async move {
+            let args = serde_wasm_bindgen::to_value(&ExportReceivedArgs {
+                hash,
+                confirmed,
+                target,
+            })
+            .expect("failed conversion");
+            let (msg, level) = match try_invoke("export_received", args).await {
+                Ok(result) => {
+                    let path: String = serde_wasm_bindgen::from_value(result).unwrap();
+                    (format!("Saved to {}", path), ToastLevel::Success)
+                }
+                Err(err) => (
+                    format!("Failed to save: {}", DropError::from(err).user_message()),
+                    ToastLevel::Error,
+                ),
+            };
+            toaster.toast(
+                ToastBuilder::new(&msg)
+                    .with_level(level)
+                    .with_position(ToastPosition::TopRight),
+            );
+        });
+    };
+    let export_gallery = export.clone();
+
+    #[derive(Debug, Serialize, Deserialize)]
+    struct ReshareArgs {
+        hash: String,
+        node_id: String,
+        confirmed: bool,
+    }
+
+    let toaster = expect_toaster();
+    let hash_forward = entry.hash.clone();
+    let name_forward = entry.name.clone();
+    let forward = move |ev| {
+        let node_id = event_target_value(&ev);
+        if node_id.is_empty() {
+            return;
+        }
+        let confirmed = quarantined
+            && window()
+                .confirm_with_message(&format!(
+                    "\"{}\" is an executable or script. Only forward it if you trust the sender.",
+                    name_forward
+                ))
+                .unwrap_or(false);
+        if quarantined && !confirmed {
+            return;
+        }
+        let hash = hash_forward.clone();
+        spawn_local(async move {
+            let args = serde_wasm_bindgen::to_value(&ReshareArgs {
+                hash,
+                node_id,
+                confirmed,
+            })
+            .expect("failed conversion");
+            if let Err(err) = try_invoke("reshare", args).await {
+                toaster.toast(
+                    ToastBuilder::new(&format!(
+                        "Failed to forward: {}",
+                        DropError::from(err).user_message()
+                    ))
+                    .with_level(ToastLevel::Error)
+                    .with_position(ToastPosition::TopRight),
+                );
+            }
+        });
+    };
+
+    #[derive(Debug, Serialize, Deserialize)]
+    struct ShareTransferArgs {
+        hash: String,
+        ttl_secs: u64,
+        confirmed: bool,
+    }
+
+    let toaster = expect_toaster();
+    let hash_share = entry.hash.clone();
+    let name_share = entry.name.clone();
+    let share = move |_| {
+        let confirmed = quarantined
+            && window()
+                .confirm_with_message(&format!(
+                    "\"{}\" is an executable or script. Only share it if you trust the sender.",
+                    name_share
+                ))
+                .unwrap_or(false);
+        if quarantined && !confirmed {
+            return;
+        }
+        let hash = hash_share.clone();
+        spawn_local(async move {
+            let args = serde_wasm_bindgen::to_value(&ShareTransferArgs {
+                hash,
+                ttl_secs: SHARE_LINK_TTL_SECS,
+                confirmed,
+            })
+            .expect("failed conversion");
+            let (message, level) = match try_invoke("share_transfer", args).await {
+                Ok(url) => (
+                    format!(
+                        "Download once within an hour: {}",
+                        url.as_string().unwrap_or_default()
+                    ),
+                    ToastLevel::Success,
+                ),
+                Err(err) => (
+                    format!("Failed to share: {}", DropError::from(err).user_message()),
+                    ToastLevel::Error,
+                ),
+            };
+            toaster.toast(
+                ToastBuilder::new(&message)
+                    .with_level(level)
+                    .with_expiry(None)
+                    .with_position(ToastPosition::TopRight),
+            );
+        });
+    };
+
+    view! {
+        <li
+            class:warning=entry.content_warning.is_some() || entry.quarantined
+            class:focused=move || focused.get().as_deref() == Some(hash_focus.as_str())
+        >
+            { move || preview.get().map(|src| view! { <img class="preview" src=src /> }) }
+            {format!("{} ({}bytes)", entry.name, entry.size)}
+            { move || links.get().get(&hash_link).cloned().map(|(text, preview)| link_view(text, preview)) }
+            { entry.content_warning.clone().map(|warning| view! { <p class="warning">{warning}</p> }) }
+            { entry.collected.then(|| view! { <p>"Removed from storage after it was saved"</p> }) }
+            { note_view(&entry, set_history) }
+            <button on:click=move |_| export("files")>
+                { if quarantined { "Save executable" } else { "Save" } }
+            </button>
+            { is_media.then(|| view! {
+                <button on:click=move |_| export_gallery("gallery")>"Save to gallery"</button>
+            }) }
+            <button on:click=share title="A link for browsers on the local network">"Share link"</button>
+            <select on:change=forward prop:value="">
+                <option value="">"Forward to…"</option>
+                { move || peers.get().into_values()
+                    .filter(|peer| peer.online)
+                    .map(|peer| view! { <option value=peer.node_id>{peer.name}</option> })
+                    .collect_view() }
+            </select>
+            <button on:click=move |_| {
+                let hash = hash_delete.clone();
+                spawn_local(async move {
+                    set_deleted(hash, true, set_history, set_trash).await.ok();
+                });
+            }>"Delete"</button>
+        </li>
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Settings {
+    pub name: Option<String>,
+    pub close_to_tray: bool,
+    #[serde(default)]
+    pub start_minimized: bool,
+    pub receive_in_background: bool,
+    pub receive_mode: String,
+    pub reject_executables: bool,
+    #[serde(default)]
+    pub link_previews: bool,
+    pub room: Option<String>,
+    #[serde(default)]
+    pub handle: Option<String>,
+    pub advanced: AdvancedSettings,
+    pub automation: AutomationSettings,
+    #[serde(default)]
+    pub control: ControlSettings,
+    pub sync: SyncSettings,
+    pub network: NetworkSettings,
+    #[serde(default)]
+    pub storage: StorageSettings,
+    #[serde(default)]
+    pub export: ExportSettings,
+    #[serde(default)]
+    pub appearance: AppearanceSettings,
+    pub send_transforms: HashMap<String, Vec<String>>,
+    pub workflows: Vec<Workflow>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Workflow {
+    pub name: String,
+    pub enabled: bool,
+    pub from: Vec<String>,
+    pub types: Vec<String>,
+    pub auto_accept: bool,
+    pub destination: Option<String>,
+    pub command: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct NetworkSettings {
+    pub trusted: Vec<String>,
+    pub untrusted: Vec<String>,
+    pub untrusted_policy: String,
+    pub relay: String,
+    pub relay_url: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StorageSettings {
+    pub retention_days: u32,
+    pub max_store_size: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ExportSettings {
+    pub by_sender: bool,
+    pub by_date: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AppearanceSettings {
+    pub theme: String,
+    pub accent_color: Option<String>,
+    pub compact: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RelayStatus {
+    pub mode: String,
+    pub url: Option<String>,
+    pub home_relay: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SyncSettings {
+    pub own_devices: Vec<String>,
+    pub sections: Vec<String>,
+    pub updated_at: HashMap<String, u64>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AutomationSettings {
+    pub enabled: bool,
+    pub command: Option<String>,
+    pub shortcut: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ControlSettings {
+    pub enabled: bool,
+    pub port: u16,
+    pub token: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AdvancedSettings {
+    pub offer_timeout_secs: u64,
+    pub dial_timeout_secs: u64,
+    pub max_frame_size: usize,
+    pub max_concurrent_downloads: usize,
+    pub max_concurrent_uploads: usize,
+    pub peer_timeout_secs: u64,
+    pub prefer_local_transport: bool,
+    pub max_up_bps: u64,
+    pub max_down_bps: u64,
+    pub request_max_dimension: u64,
+    pub compress: bool,
+    pub compress_min_size: u64,
+    #[serde(default)]
+    pub max_receive_size: u64,
+    #[serde(default)]
+    pub idle_connection_timeout_secs: u64,
+    #[serde(default)]
+    pub offer_ttl_secs: u64,
+}
+
+async fn fetch_settings() -> Settings {
+    let result = invoke_without_args("get_settings").await;
+    serde_wasm_bindgen::from_value(result).unwrap()
+}
+
+/// Applies the appearance from the settings to the window, and again whenever they change.
+pub fn watch_appearance() {
+    spawn_local(async move {
+        apply_appearance(&fetch_settings().await.appearance);
+        let unlisten = listen::<(), _>("settings-changed", move |()| {
+            spawn_local(async move {
+                apply_appearance(&fetch_settings().await.appearance);
+            });
+        })
+        .await;
+
+        on_cleanup(unlisten);
+    });
+}
+
+/// Sets the attributes `styles.css` themes the window by on the root element.
+fn apply_appearance(appearance: &AppearanceSettings) {
+    let Some(root) = document().document_element() else {
+        return;
+    };
+    root.set_attribute("data-theme", &appearance.theme).ok();
+    root.set_attribute("data-compact", &appearance.compact.to_string())
+        .ok();
+    match appearance.accent_color {
+        Some(ref color) => root.set_attribute("style", &format!("--accent: {color}")),
+        None => root.remove_attribute("style"),
+    }
+    .ok();
+}
+
+/// Stores `settings`, returning the validation error if they are rejected.
+async fn save_settings(settings: Settings) -> Result<(), String> {
+    #[derive(Serialize)]
+    struct SetSettingsArgs {
+        settings: Settings,
+    }
+
+    let args = serde_wasm_bindgen::to_value(&SetSettingsArgs { settings }).expect("failed conversion");
+    try_invoke("set_settings", args)
+        .await
+        .map(|_| ())
+        .map_err(|err| DropError::from(err).user_message())
+}
+
+#[component]
+fn AdvancedSettingsView() -> impl IntoView {
+    let (settings, set_settings) = create_signal(Settings::default());
+    spawn_local(async move {
+        set_settings.set(fetch_settings().await);
+    });
+    let (relay_status, set_relay_status) = create_signal(None::<RelayStatus>);
+    spawn_local(async move {
+        let result = invoke_without_args("relay_status").await;
+        set_relay_status.set(serde_wasm_bindgen::from_value(result).ok());
+    });
+    spawn_local(async move {
+        let unlisten = listen::<(), _>("settings-changed", move |()| {
+            spawn_local(async move {
+                set_settings.set(fetch_settings().await);
+            });
+        })
+        .await;
+
+        on_cleanup(unlisten);
+    });
+    // TXT record to publish for the handle, `None` while no handle is set.
+    let (handle_record, set_handle_record) = create_signal(None::<(String, String)>);
+    let refresh_handle_record = move || {
+        spawn_local(async move {
+            let record = try_invoke("handle_record", JsValue::UNDEFINED).await.ok();
+            set_handle_record.set(record.and_then(|r| serde_wasm_bindgen::from_value(r).ok()));
+        });
+    };
+    refresh_handle_record();
+
+    let toaster = expect_toaster();
+    let save = move |ev: SubmitEvent| {
+        ev.prevent_default();
+        let settings = settings.get_untracked();
+        spawn_local(async move {
+            let (msg, level) = match save_settings(settings).await {
+                Ok(()) => {
+                    refresh_handle_record();
+                    ("Settings saved".to_string(), ToastLevel::Success)
+                }
+                Err(err) => (format!("Invalid settings: {}", err), ToastLevel::Error),
+            };
+            toaster.toast(
+                ToastBuilder::new(&msg)
+                    .with_level(level)
+                    .with_position(ToastPosition::TopRight),
+            );
+        });
+    };
+
+    let number_input = move |label: &'static str,
+                             get: fn(&AdvancedSettings) -> u64,
+                             set: fn(&mut AdvancedSettings, u64)| {
+        view! {
+            <label>
+                {label}
+                <input
+                    type="number"
+                    min="0"
+                    prop:value=move || get(&settings.get().advanced)
+                    on:change=move |ev| {
+                        if let Ok(value) = event_target_value(&ev).parse() {
+                            set_settings.update(|s| set(&mut s.advanced, value));
+                        }
+                    }
+                />
+            </label>
+        }
+    };
+
+    let text_input = move |label: &'static str,
+                           get: fn(&AutomationSettings) -> &Option<String>,
+                           set: fn(&mut AutomationSettings, Option<String>)| {
+        view! {
+            <label>
+                {label}
+                <input
+                    type="text"
+                    prop:value=move || get(&settings.get().automation).clone().unwrap_or_default()
+                    on:change=move |ev| {
+                        let value = event_target_value(&ev);
+                        let value = (!value.is_empty()).then_some(value);
+                        set_settings.update(|s| set(&mut s.automation, value));
+                    }
+                />
+            </label>
+        }
+    };
+
+    // Kept by the OS, so it is changed right away instead of with the other settings.
+    let (autostart, set_autostart) = create_signal(false);
+    spawn_local(async move {
+        if let Ok(result) = try_invoke("autostart_enabled", JsValue::UNDEFINED).await {
+            set_autostart.set(serde_wasm_bindgen::from_value(result).unwrap_or_default());
+        }
+    });
+    let autostart_toaster = expect_toaster();
+    let toggle_autostart = move |ev| {
+        #[derive(Serialize)]
+        struct SetAutostartArgs {
+            enabled: bool,
+        }
+
+        let enabled = event_target_checked(&ev);
+        let toaster = autostart_toaster.clone();
+        spawn_local(async move {
+            let args = serde_wasm_bindgen::to_value(&SetAutostartArgs { enabled })
+                .expect("failed conversion");
+            match try_invoke("set_autostart", args).await {
+                Ok(_) => set_autostart.set(enabled),
+                Err(err) => {
+                    set_autostart.set(!enabled);
+                    toaster.toast(
+                        ToastBuilder::new(&format!(
+                            "Failed to change starting at login: {}",
+                            DropError::from(err).user_message()
+                        ))
+                        .with_level(ToastLevel::Error)
+                        .with_position(ToastPosition::TopRight),
+                    );
+                }
+            }
+        });
+    };
+
+    // Only saved with the other settings, so a token can be thrown away.
+    let new_control_token = move || {
+        spawn_local(async move {
+            let result = invoke_without_args("generate_control_token").await;
+            if let Ok(token) = serde_wasm_bindgen::from_value::<String>(result) {
+                set_settings.update(|s| s.control.token = token);
+            }
+        });
+    };
+
+    let toaster = expect_toaster();
+    let set_receive_mode = move |ev| {
+        #[derive(Serialize)]
+        struct SetReceiveModeArgs {
+            mode: String,
+        }
+
+        let mode = event_target_value(&ev);
+        set_settings.update(|s| s.receive_mode = mode.clone());
+        spawn_local(async move {
+            let args = serde_wasm_bindgen::to_value(&SetReceiveModeArgs { mode })
+                .expect("failed conversion");
+            if let Err(err) = try_invoke("set_receive_mode", args).await {
+                toaster.toast(
+                    ToastBuilder::new(&format!(
+                        "Failed to change receive mode: {}",
+                        DropError::from(err).user_message()
+                    ))
+                    .with_level(ToastLevel::Error)
+                    .with_position(ToastPosition::TopRight),
+                );
+            }
+        });
+    };
+
+    let sync_toggle = move |label: &'static str, section: &'static str| {
+        view! {
+            <label>
+                {label}
+                <input
+                    type="checkbox"
+                    prop:checked=move || settings.get().sync.sections.iter().any(|s| s == section)
+                    on:change=move |ev| {
+                        let enabled = event_target_checked(&ev);
+                        set_settings.update(|s| {
+                            s.sync.sections.retain(|s| s != section);
+                            if enabled {
+                                s.sync.sections.push(section.to_string());
+                            }
+                        });
+                    }
+                />
+            </label>
+        }
+    };
+
+    view! {
+        <details class="settings">
+            <summary>"Settings"</summary>
+            <form on:submit=save>
+                <label>
+                    "Device name"
+                    <input
+                        maxlength="64"
+                        placeholder="Shown to other devices"
+                        prop:value=move || settings.get().name.unwrap_or_default()
+                        on:change=move |ev| {
+                            let name = event_target_value(&ev).trim().to_string();
+                            let name = (!name.is_empty()).then_some(name);
+                            set_settings.update(|s| s.name = name);
+                        }
+                    />
+                </label>
+                <label>
+                    "Handle"
+                    <input
+                        placeholder="alice@example.com"
+                        prop:value=move || settings.get().handle.unwrap_or_default()
+                        on:change=move |ev| {
+                            let handle = event_target_value(&ev).trim().to_string();
+                            let handle = (!handle.is_empty()).then_some(handle);
+                            set_settings.update(|s| s.handle = handle);
+                        }
+                    />
+                </label>
+                { move || handle_record.get().map(|(name, value)| view! {
+                    <p class="handle-record">
+                        "Publish this TXT record so others can add you by your handle: "
+                        <code>{name}</code> " " <code>{value}</code>
+                    </p>
+                }) }
+                <label>
+                    "Receive files from"
+                    <select
+                        prop:value=move || settings.get().receive_mode
+                        on:change=set_receive_mode
+                    >
+                        <option value="everyone">"Everyone"</option>
+                        <option value="trusted-only">"My devices only"</option>
+                        <option value="off">"Nobody (do not disturb)"</option>
+                    </select>
+                </label>
+                <label>
+                    "On untrusted networks"
+                    <select
+                        prop:value=move || settings.get().network.untrusted_policy
+                        on:change=move |ev| {
+                            let policy = event_target_value(&ev);
+                            set_settings.update(|s| s.network.untrusted_policy = policy);
+                        }
+                    >
+                        <option value="invisible">"Go invisible"</option>
+                        <option value="trusted-only">"Only accept files from my devices"</option>
+                    </select>
+                </label>
+                <label>
+                    "Relays (applied after a restart)"
+                    <select
+                        prop:value=move || settings.get().network.relay
+                        on:change=move |ev| {
+                            let relay = event_target_value(&ev);
+                            set_settings.update(|s| s.network.relay = relay);
+                        }
+                    >
+                        <option value="default">"Default relays"</option>
+                        <option value="custom">"Own relay"</option>
+                        <option value="disabled">"No relays (local network only)"</option>
+                    </select>
+                </label>
+                <Show when=move || settings.get().network.relay == "custom">
+                    <label>
+                        "Relay URL"
+                        <input
+                            type="url"
+                            placeholder="https://relay.example.com"
+                            prop:value=move || settings.get().network.relay_url.unwrap_or_default()
+                            on:change=move |ev| {
+                                let url = event_target_value(&ev);
+                                let url = (!url.is_empty()).then_some(url);
+                                set_settings.update(|s| s.network.relay_url = url);
+                            }
+                        />
+                    </label>
+                </Show>
+                <p class="relay-status">{move || relay_status.get().map(|status| {
+                    match status.home_relay {
+                        Some(relay) => format!("Connected through {}", relay),
+                        None => "Not connected to a relay".to_string(),
+                    }
+                })}</p>
+                <label>
+                    "Reject executables and scripts"
+                    <input
+                        type="checkbox"
+                        prop:checked=move || settings.get().reject_executables
+                        on:change=move |ev| {
+                            let enabled = event_target_checked(&ev);
+                            set_settings.update(|s| s.reject_executables = enabled);
+                        }
+                    />
+                </label>
+                <label>
+                    "Show previews of received links (the linked site sees this device's address)"
+                    <input
+                        type="checkbox"
+                        prop:checked=move || settings.get().link_previews
+                        on:change=move |ev| {
+                            let enabled = event_target_checked(&ev);
+                            set_settings.update(|s| s.link_previews = enabled);
+                        }
+                    />
+                </label>
+                <label>
+                    "Keep receiving in the background (mobile)"
+                    <input
+                        type="checkbox"
+                        prop:checked=move || settings.get().receive_in_background
+                        on:change=move |ev| {
+                            let enabled = event_target_checked(&ev);
+                            set_settings.update(|s| s.receive_in_background = enabled);
+                        }
+                    />
+                </label>
+                <label>
+                    "Keep running in the tray when the window is closed"
+                    <input
+                        type="checkbox"
+                        prop:checked=move || settings.get().close_to_tray
+                        on:change=move |ev| {
+                            let enabled = event_target_checked(&ev);
+                            set_settings.update(|s| s.close_to_tray = enabled);
+                        }
+                    />
+                </label>
+                <label>
+                    "Start when I log in"
+                    <input
+                        type="checkbox"
+                        prop:checked=move || autostart.get()
+                        on:change=toggle_autostart
+                    />
+                </label>
+                <label>
+                    "Start in the tray, without opening the window"
+                    <input
+                        type="checkbox"
+                        prop:checked=move || settings.get().start_minimized
+                        on:change=move |ev| {
+                            let enabled = event_target_checked(&ev);
+                            set_settings.update(|s| s.start_minimized = enabled);
+                        }
+                    />
+                </label>
+                <label>
+                    "Theme"
+                    <select
+                        prop:value=move || settings.get().appearance.theme
+                        on:change=move |ev| {
+                            let theme = event_target_value(&ev);
+                            set_settings.update(|s| s.appearance.theme = theme);
+                        }
+                    >
+                        <option value="system">"Like the system"</option>
+                        <option value="light">"Light"</option>
+                        <option value="dark">"Dark"</option>
+                    </select>
+                </label>
+                <label>
+                    "Accent color"
+                    <input
+                        type="color"
+                        prop:value=move || {
+                            settings.get().appearance.accent_color.unwrap_or("#396cd8".to_string())
+                        }
+                        on:change=move |ev| {
+                            let color = event_target_value(&ev);
+                            set_settings.update(|s| s.appearance.accent_color = Some(color));
+                        }
+                    />
+                    <button type="button" on:click=move |_| {
+                        set_settings.update(|s| s.appearance.accent_color = None);
+                    }>"Default"</button>
+                </label>
+                <label>
+                    "Compact layout"
+                    <input
+                        type="checkbox"
+                        prop:checked=move || settings.get().appearance.compact
+                        on:change=move |ev| {
+                            let enabled = event_target_checked(&ev);
+                            set_settings.update(|s| s.appearance.compact = enabled);
+                        }
+                    />
+                </label>
+                <label>
+                    "Sort saved files into a folder per device"
+                    <input
+                        type="checkbox"
+                        prop:checked=move || settings.get().export.by_sender
+                        on:change=move |ev| {
+                            let enabled = event_target_checked(&ev);
+                            set_settings.update(|s| s.export.by_sender = enabled);
+                        }
+                    />
+                </label>
+                <label>
+                    "Sort saved files into a folder per day"
+                    <input
+                        type="checkbox"
+                        prop:checked=move || settings.get().export.by_date
+                        on:change=move |ev| {
+                            let enabled = event_target_checked(&ev);
+                            set_settings.update(|s| s.export.by_date = enabled);
+                        }
+                    />
+                </label>
+                {number_input("Offer timeout (s)", |a| a.offer_timeout_secs, |a, v| a.offer_timeout_secs = v)}
+                {number_input("Offers expire after (s)", |a| a.offer_ttl_secs, |a, v| a.offer_ttl_secs = v)}
+                {number_input("Dial timeout (s)", |a| a.dial_timeout_secs, |a, v| a.dial_timeout_secs = v)}
+                {number_input("Max frame size (bytes)", |a| a.max_frame_size as u64, |a, v| a.max_frame_size = v as usize)}
+                {number_input("Max concurrent downloads", |a| a.max_concurrent_downloads as u64, |a, v| a.max_concurrent_downloads = v as usize)}
+                {number_input("Max concurrent uploads", |a| a.max_concurrent_uploads as u64, |a, v| a.max_concurrent_uploads = v as usize)}
+                {number_input("Peer offline after (s)", |a| a.peer_timeout_secs, |a, v| a.peer_timeout_secs = v)}
+                {number_input("Keep idle connections open for (s, 0 = close right away)", |a| a.idle_connection_timeout_secs, |a, v| a.idle_connection_timeout_secs = v)}
+                {number_input("Upload limit (bytes/s, 0 = unlimited)", |a| a.max_up_bps, |a, v| a.max_up_bps = v)}
+                {number_input("Download limit (bytes/s, 0 = unlimited)", |a| a.max_down_bps, |a, v| a.max_down_bps = v)}
+                {number_input("Ask to downscale large images to (px, 0 = original)", |a| a.request_max_dimension, |a, v| a.request_max_dimension = v)}
+                <label>
+                    "Prefer local network transports (Wi-Fi Direct, link-local)"
+                    <input
+                        type="checkbox"
+                        prop:checked=move || settings.get().advanced.prefer_local_transport
+                        on:change=move |ev| {
+                            let enabled = event_target_checked(&ev);
+                            set_settings.update(|s| s.advanced.prefer_local_transport = enabled);
+                        }
+                    />
+                </label>
+                <label>
+                    "Compress text files, logs and CSVs before sending"
+                    <input
+                        type="checkbox"
+                        prop:checked=move || settings.get().advanced.compress
+                        on:change=move |ev| {
+                            let enabled = event_target_checked(&ev);
+                            set_settings.update(|s| s.advanced.compress = enabled);
+                        }
+                    />
+                </label>
+                {number_input("Compress files from (bytes)", |a| a.compress_min_size, |a, v| a.compress_min_size = v)}
+                {number_input("Reject larger files unless accepted once (bytes, 0 = no limit)", |a| a.max_receive_size, |a, v| a.max_receive_size = v)}
+                <h4>"Automation"</h4>
+                <label>
+                    "Run hooks when files arrive"
+                    <input
+                        type="checkbox"
+                        prop:checked=move || settings.get().automation.enabled
+                        on:change=move |ev| {
+                            let enabled = event_target_checked(&ev);
+                            set_settings.update(|s| s.automation.enabled = enabled);
+                        }
+                    />
+                </label>
+                {text_input("Command", |a| &a.command, |a, v| a.command = v)}
+                {text_input("macOS Shortcut", |a| &a.shortcut, |a, v| a.shortcut = v)}
+                <h4>"Control API"</h4>
+                <p>
+                    "Scripts on this computer can list devices and send files through a WebSocket on localhost, authenticating with the token."
+                </p>
+                <label>
+                    "Enable the control API"
+                    <input
+                        type="checkbox"
+                        prop:checked=move || settings.get().control.enabled
+                        on:change=move |ev| {
+                            let enabled = event_target_checked(&ev);
+                            set_settings.update(|s| s.control.enabled = enabled);
+                            if enabled && settings.get_untracked().control.token.is_empty() {
+                                new_control_token();
+                            }
+                        }
+                    />
+                </label>
+                <label>
+                    "Port"
+                    <input
+                        type="number"
+                        min="1"
+                        max="65535"
+                        prop:value=move || settings.get().control.port
+                        on:change=move |ev| {
+                            if let Ok(port) = event_target_value(&ev).parse() {
+                                set_settings.update(|s| s.control.port = port);
+                            }
+                        }
+                    />
+                </label>
+                <label>
+                    "Token"
+                    <input type="text" readonly prop:value=move || settings.get().control.token />
+                </label>
+                <button type="button" on:click=move |_| new_control_token()>
+                    "New token"
+                </button>
+                <h4>"Sync with my devices"</h4>
+                {sync_toggle("Advanced settings", "advanced")}
+                {sync_toggle("Appearance", "appearance")}
+                <button type="submit">"Save"</button>
+            </form>
+        </details>
+    }
+}
+
+/// Editor for the workflows, e.g. saving PDFs from the phone to a folder and running a script.
+#[component]
+fn WorkflowsView() -> impl IntoView {
+    let (workflows, set_workflows) = create_signal(Vec::<Workflow>::new());
+    let (peers, set_peers) = create_signal(Vec::<PeerInfo>::new());
+    let refresh = move |_| {
+        spawn_local(async move {
+            set_workflows.set(fetch_settings().await.workflows);
+            set_peers.set(fetch_peers().await.into_values().collect());
+        });
+    };
+
+    let toaster = expect_toaster();
+    let save = move |ev: SubmitEvent| {
+        ev.prevent_default();
+        let workflows = workflows.get_untracked();
+        spawn_local(async move {
+            let mut settings = fetch_settings().await;
+            settings.workflows = workflows;
+            let (msg, level) = match save_settings(settings).await {
+                Ok(()) => ("Workflows saved".to_string(), ToastLevel::Success),
+                Err(err) => (format!("Invalid workflow: {}", err), ToastLevel::Error),
+            };
+            toaster.toast(
+                ToastBuilder::new(&msg)
+                    .with_level(level)
+                    .with_position(ToastPosition::TopRight),
+            );
+        });
+    };
+
+    let add = move |_| {
+        set_workflows.update(|workflows| {
+            workflows.push(Workflow {
+                name: format!("Workflow {}", workflows.len() + 1),
+                enabled: true,
+                ..Default::default()
+            })
+        });
+    };
+
+    let optional = |value: String| (!value.trim().is_empty()).then(|| value.trim().to_string());
+    let workflow_view = move |index: usize| {
+        let update = move |f: Box<dyn FnOnce(&mut Workflow)>| {
+            set_workflows.update(|workflows| {
+                if let Some(workflow) = workflows.get_mut(index) {
+                    f(workflow);
+                }
+            });
+        };
+        let get = move || workflows.get().get(index).cloned().unwrap_or_default();
+        view! {
+            <fieldset class="workflow">
+                <label>
+                    "Name"
+                    <input
+                        type="text"
+                        prop:value=move || get().name
+                        on:change=move |ev| {
+                            let name = event_target_value(&ev);
+                            update(Box::new(move |w| w.name = name));
+                        }
+                    />
+                </label>
+                <label>
+                    "Enabled"
+                    <input
+                        type="checkbox"
+                        prop:checked=move || get().enabled
+                        on:change=move |ev| {
+                            let enabled = event_target_checked(&ev);
+                            update(Box::new(move |w| w.enabled = enabled));
+                        }
+                    />
+                </label>
+                <p>"From"</p>
+                { move || peers.get().into_iter().map(|peer| {
+                    let node_id = peer.node_id.clone();
+                    let checked = node_id.clone();
+                    view! {
+                        <label>
+                            <input
+                                type="checkbox"
+                                prop:checked=move || get().from.contains(&checked)
+                                on:change=move |ev| {
+                                    let enabled = event_target_checked(&ev);
+                                    let node_id = node_id.clone();
+                                    update(Box::new(move |w| {
+                                        w.from.retain(|n| *n != node_id);
+                                        if enabled {
+                                            w.from.push(node_id);
+                                        }
+                                    }));
+                                }
+                            />
+                            {peer.name}
+                        </label>
+                    }
+                }).collect_view() }
+                <label>
+                    "File types (e.g. pdf, image/*)"
+                    <input
+                        type="text"
+                        prop:value=move || get().types.join(", ")
+                        on:change=move |ev| {
+                            let types = event_target_value(&ev)
+                                .split(',')
+                                .map(|ty| ty.trim().to_string())
+                                .filter(|ty| !ty.is_empty())
+                                .collect();
+                            update(Box::new(move |w| w.types = types));
+                        }
+                    />
+                </label>
+                <label>
+                    "Accept automatically"
+                    <input
+                        type="checkbox"
+                        prop:checked=move || get().auto_accept
+                        on:change=move |ev| {
+                            let enabled = event_target_checked(&ev);
+                            update(Box::new(move |w| w.auto_accept = enabled));
+                        }
+                    />
+                </label>
+                <label>
+                    "Save to folder"
+                    <input
+                        type="text"
+                        prop:value=move || get().destination.unwrap_or_default()
+                        on:change=move |ev| {
+                            let destination = optional(event_target_value(&ev));
+                            update(Box::new(move |w| w.destination = destination));
+                        }
+                    />
+                </label>
+                <label>
+                    "Then run"
+                    <input
+                        type="text"
+                        prop:value=move || get().command.unwrap_or_default()
+                        on:change=move |ev| {
+                            let command = optional(event_target_value(&ev));
+                            update(Box::new(move |w| w.command = command));
+                        }
+                    />
+                </label>
+                <button
+                    type="button"
+                    on:click=move |_| set_workflows.update(|workflows| {
+                        if index < workflows.len() {
+                            workflows.remove(index);
+                        }
+                    })
+                >
+                    "Remove"
+                </button>
+            </fieldset>
+        }
+    };
+
+    view! {
+        <details class="settings" on:toggle=refresh>
+            <summary>"Workflows"</summary>
+            <form on:submit=save>
+                { move || (0..workflows.with(Vec::len)).map(workflow_view).collect_view() }
+                <button type="button" on:click=add>"Add workflow"</button>
+                <button type="submit">"Save"</button>
+            </form>
+        </details>
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DropStats {
+    pub files_sent: u64,
+    pub files_received: u64,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    pub best_throughput: Option<u64>,
+    pub direct: u64,
+    pub local: u64,
+    pub relay: u64,
+    pub per_peer: Vec<PeerStats>,
+    pub per_day: Vec<DayStats>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PeerStats {
+    pub node_id: String,
+    pub name: Option<String>,
+    pub files: u64,
+    pub bytes: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DayStats {
+    pub day: u64,
+    pub files: u64,
+    pub bytes: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransferStats {
+    pub id: u64,
+    pub direction: String,
+    pub node_id: String,
+    pub name: String,
+    pub size: u64,
+    pub started_at: u64,
+    pub duration_ms: u64,
+    /// Bytes in each second, empty for uploads
+    pub samples: Vec<u64>,
+    pub direct_bytes: u64,
+    pub relay_bytes: u64,
+    pub finished: bool,
+}
+
+impl TransferStats {
+    /// Average speed in bytes per second.
+    fn throughput(&self) -> Option<u64> {
+        let bytes = self.direct_bytes + self.relay_bytes;
+        (bytes > 0 && self.duration_ms > 0).then(|| bytes * 1000 / self.duration_ms)
+    }
+}
+
+/// Whether `name` looks like an image or video, the backend checks the actual content.
+fn is_media(name: &str) -> bool {
+    const MEDIA: &[&str] = &[
+        "jpg", "jpeg", "png", "gif", "webp", "heic", "heif", "avif", "mp4", "mov", "m4v", "webm",
+        "mkv", "avi",
+    ];
+    has_extension(name, MEDIA)
+}
+
+/// Whether the backend can generate a preview for `name`.
+fn has_preview(name: &str) -> bool {
+    has_extension(name, &["jpg", "jpeg", "png", "gif", "webp", "bmp"])
+}
+
+fn has_extension(name: &str, extensions: &[&str]) -> bool {
+    name.rsplit_once('.')
+        .map(|(_, ext)| extensions.contains(&ext.to_lowercase().as_str()))
+        .unwrap_or(false)
+}
+
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024. && unit < UNITS.len() - 1 {
+        value /= 1024.;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
+/// Horizontal bar chart, each row is `(label, value, value label)`.
+fn bar_chart(rows: Vec<(String, u64, String)>) -> impl IntoView {
+    let max = rows.iter().map(|(_, value, _)| *value).max().unwrap_or(0).max(1);
+    rows.into_iter()
+        .map(|(label, value, value_label)| {
+            let width = format!("width: {}%", value * 100 / max);
+            view! {
+                <div class="bar-row">
+                    <span class="bar-label">{label}</span>
+                    <span class="bar" style=width></span>
+                    <span>{value_label}</span>
+                </div>
+            }
+        })
+        .collect_view()
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PermissionStatus {
+    /// `notifications`, `file-system`, `camera` or `local-network`
+    pub permission: String,
+    /// `granted`, `denied` or `unknown`
+    pub state: String,
+    /// What stops working while denied
+    pub feature: String,
+    /// Where to grant the permission
+    pub hint: Option<String>,
+    /// Whether `open_permission_settings` can take the user there
+    #[serde(default)]
+    pub can_open_settings: bool,
+}
+
+/// Opens the system settings where `permission` is granted.
+async fn open_permission_settings(permission: String) -> Result<(), DropError> {
+    #[derive(Serialize)]
+    struct Args {
+        permission: String,
+    }
+    let args = serde_wasm_bindgen::to_value(&Args { permission }).expect("failed conversion");
+    try_invoke("open_permission_settings", args).await?;
+    Ok(())
+}
+
+/// Button opening the settings for `permission`, if the platform allows it.
+fn open_settings_button(permission: &PermissionStatus) -> impl IntoView {
+    let name = permission.permission.clone();
+    let toaster = expect_toaster();
+    permission.can_open_settings.then(move || {
+        let on_click = move |_| {
+            let name = name.clone();
+            let toaster = toaster.clone();
+            spawn_local(async move {
+                if let Err(err) = open_permission_settings(name).await {
+                    toaster.toast(
+                        ToastBuilder::new(&err.user_message())
+                            .with_level(ToastLevel::Error)
+                            .with_position(ToastPosition::TopRight),
+                    );
+                }
+            });
+        };
+        view! { <button on:click=on_click>"Open settings"</button> }
+    })
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CheckResult {
+    /// `visibility`, `same_network`, `permission`, `firewall` or `relay`
+    pub check: String,
+    /// `ok`, `problem` or `unknown`
+    pub state: String,
+    pub detail: Option<String>,
+    pub advice: Option<String>,
+}
+
+impl CheckResult {
+    fn title(&self) -> &'static str {
+        match self.check.as_str() {
+            "visibility" => "This device is visible",
+            "same_network" => "Both devices are on the same network",
+            "permission" => "The OS allows local network access",
+            "firewall" => "The firewall lets connections through",
+            "relay" => "A relay is reachable",
+            _ => "Unknown check",
+        }
+    }
+}
+
+/// Walks through why no device was found, shown instead of an empty device list.
+#[component]
+fn TroubleshootView() -> impl IntoView {
+    let (checks, set_checks) = create_signal(Vec::<CheckResult>::new());
+    let check = move || {
+        spawn_local(async move {
+            let result = invoke_without_args("troubleshoot").await;
+            set_checks.set(serde_wasm_bindgen::from_value(result).unwrap_or_default());
+        });
+    };
+    check();
+    // The first step that did not pass is where the user should start.
+    let current = move || checks.get().iter().position(|check| check.state != "ok");
+
+    view! {
+        <div class="troubleshoot">
+            <p>"No devices found yet. Start iroh-drop on the other device, then go through these steps:"</p>
+            <ol>
+                { move || checks.get().into_iter().enumerate().map(|(i, check)| {
+                    let class = format!("{}{}", check.state, if current() == Some(i) { " current" } else { "" });
+                    let mark = match check.state.as_str() {
+                        "ok" => "✓",
+                        "problem" => "✗",
+                        _ => "?",
+                    };
+                    let open_settings = (check.check == "permission" && check.state == "problem").then(|| {
+                        let toaster = expect_toaster();
+                        view! {
+                            <button on:click=move |_| {
+                                let toaster = toaster.clone();
+                                spawn_local(async move {
+                                    if let Err(err) = open_permission_settings("local-network".to_string()).await {
+                                        toaster.toast(
+                                            ToastBuilder::new(&err.user_message())
+                                                .with_level(ToastLevel::Error)
+                                                .with_position(ToastPosition::TopRight),
+                                        );
+                                    }
+                                });
+                            }>"Open settings"</button>
+                        }
+                    });
+                    view! {
+                        <li class=class>
+                            <span class="mark">{mark}</span>
+                            " "{check.title()}
+                            { check.detail.map(|detail| view! { <span class="detail">{format!(" ({})", detail)}</span> }) }
+                            { check.advice.map(|advice| view! { <p class="hint">{advice}</p> }) }
+                            {open_settings}
+                        </li>
+                    }
+                }).collect_view() }
+            </ol>
+            <button on:click=move |_| check()>"Check again"</button>
+        </div>
+    }
+}
+
+/// Accepts files dropped anywhere in `target` except on a device card, and asks whom to send
+/// them to.
+#[component]
+fn RecipientPicker(
+    target: NodeRef<Main>,
+    peers: ReadSignal<HashMap<String, PeerInfo>>,
+) -> impl IntoView {
+    // Files dropped outside of a card, until recipients were picked
+    let (pending, set_pending) = create_signal(Vec::<web_sys::File>::new());
+    let (selected, set_selected) = create_signal(Vec::<String>::new());
+
+    let on_drop = move |event: UseDropZoneEvent| {
+        // Cards have drop zones of their own.
+        let on_card = event
+            .event
+            .target()
+            .and_then(|target| target.dyn_into::<web_sys::Element>().ok())
+            .and_then(|element| element.closest(".dropzone").ok().flatten())
+            .is_some();
+        if !on_card && !event.files.is_empty() {
+            set_selected.set(Vec::new());
+            set_pending.set(event.files);
+        }
+    };
+    use_drop_zone_with_options(target, UseDropZoneOptions::default().on_drop(on_drop));
+
+    let recipients = move || {
+        let mut peers: Vec<_> = peers
+            .get()
+            .into_values()
+            .filter(|peer| peer.online)
+            .collect();
+        peers.sort_by(|a, b| a.name.cmp(&b.name));
+        peers
+    };
+    // Named groups of peers with their members, selected with one click.
+    let (groups, set_groups) = create_signal(BTreeMap::<String, Vec<String>>::new());
+    let refresh_groups = move || {
+        spawn_local(async move {
+            let result = invoke_without_args("peer_groups").await;
+            set_groups.set(serde_wasm_bindgen::from_value(result).unwrap_or_default());
+        });
+    };
+    refresh_groups();
+    let select_group = move |mut members: Vec<String>| {
+        let online: Vec<_> = recipients().into_iter().map(|peer| peer.node_id).collect();
+        members.retain(|id| online.contains(id));
+        set_selected.set(members);
+    };
+    let group_toaster = expect_toaster();
+    let save_group = move |_| {
+        #[derive(Serialize)]
+        struct SetPeerGroupArgs {
+            name: String,
+            node_ids: Vec<String>,
+        }
+
+        let Ok(Some(name)) = window().prompt_with_message("Name of the group, e.g. Team:") else {
+            return;
+        };
+        let node_ids = selected.get_untracked();
+        let toaster = group_toaster.clone();
+        spawn_local(async move {
+            let args = serde_wasm_bindgen::to_value(&SetPeerGroupArgs { name, node_ids })
+                .expect("failed conversion");
+            match try_invoke("set_peer_group", args).await {
+                Ok(_) => refresh_groups(),
+                Err(err) => toaster.toast(
+                    ToastBuilder::new(&format!(
+                        "Failed to save the group: {}",
+                        DropError::from(err).user_message()
+                    ))
+                    .with_level(ToastLevel::Error)
+                    .with_position(ToastPosition::TopRight),
+                ),
+            }
+        });
+    };
+
+    let toggle = move |node_id: String, checked: bool| {
+        set_selected.update(|selected| {
+            selected.retain(|id| id != &node_id);
+            if checked {
+                selected.push(node_id);
+            }
+        })
+    };
+
+    #[derive(Debug, Serialize, Deserialize)]
+    struct SendFileToManyArgs {
+        node_ids: Vec<String>,
+        file_name: String,
+        file_data: Vec<u8>,
+        metadata: FileMetadata,
+    }
+
+    let toaster = expect_toaster();
+    let send = move |_| {
+        let files = pending.get_untracked();
+        let node_ids = selected.get_untracked();
+        let peers = peers.get_untracked();
+        set_pending.set(Vec::new());
+        let toaster = toaster.clone();
+        spawn_local(async move {
+            for file in files {
+                let size = file.size() as u64;
+                let name_of = |node_id: &str| {
+                    peers
+                        .get(node_id)
+                        .map(|peer| peer.name.clone())
+                        .unwrap_or_default()
+                };
+                // Recipients whose limits rule the file out are skipped.
+                let node_ids: Vec<_> = node_ids
+                    .iter()
+                    .filter(|node_id| {
+                        let problem = peers
+                            .get(*node_id)
+                            .and_then(|peer| peer.constraints.as_ref())
+                            .and_then(|limits| limits.problem(&file.name(), size));
+                        if let Some(problem) = &problem {
+                            toaster.toast(
+                                ToastBuilder::new(&format!(
+                                    "Not sending {} to {}: {}",
+                                    file.name(),
+                                    name_of(node_id),
+                                    problem
+                                ))
+                                .with_level(ToastLevel::Warn)
+                                .with_position(ToastPosition::TopRight),
+                            );
+                        }
+                        problem.is_none()
+                    })
+                    .cloned()
+                    .collect();
+                if node_ids.is_empty() {
+                    continue;
+                }
+
+                let buffer = JsFuture::from(file.array_buffer())
+                    .await
+                    .expect("failed future");
+                let args = serde_wasm_bindgen::to_value(&SendFileToManyArgs {
+                    node_ids,
+                    file_name: file.name(),
+                    file_data: Uint8Array::new(&buffer).to_vec(),
+                    metadata: FileMetadata {
+                        mime: Some(file.type_()).filter(|mime| !mime.is_empty()),
+                        modified: Some((file.last_modified() / 1000.0) as u64),
+                        permissions: None,
+                    },
+                })
+                .expect("failed conversion");
+                let results: Vec<(String, Option<DropError>)> =
+                    match try_invoke("send_file_to_many", args).await {
+                        Ok(result) => serde_wasm_bindgen::from_value(result).unwrap_or_default(),
+                        Err(err) => {
+                            toaster.toast(
+                                ToastBuilder::new(&format!(
+                                    "Failed to send {}: {}",
+                                    file.name(),
+                                    DropError::from(err).user_message()
+                                ))
+                                .with_level(ToastLevel::Error)
+                                .with_position(ToastPosition::TopRight),
+                            );
+                            continue;
+                        }
+                    };
+                let mut sent = 0;
+                for (node_id, err) in results {
+                    match err {
+                        None => sent += 1,
+                        Some(err) => toaster.toast(
+                            ToastBuilder::new(&format!(
+                                "Failed to send {} to {}: {}",
+                                file.name(),
+                                name_of(&node_id),
+                                err.user_message()
+                            ))
+                            .with_level(ToastLevel::Error)
+                            .with_position(ToastPosition::TopRight),
+                        ),
+                    }
+                }
+                if sent > 0 {
+                    toaster.toast(
+                        ToastBuilder::new(&format!("Sent {} to {} devices", file.name(), sent))
+                            .with_level(ToastLevel::Success)
+                            .with_position(ToastPosition::TopRight),
+                    );
+                }
+            }
+        });
+    };
+
+    view! {
+        <Show when=move || !pending.get().is_empty()>
+            <div class="recipient-picker">
+                <p>
+                    { move || match &pending.get()[..] {
+                        [file] => format!("Send {} to:", file.name()),
+                        files => format!("Send {} files to:", files.len()),
+                    } }
+                </p>
+                <Show when=move || recipients().is_empty()>
+                    <p>"No device is online."</p>
+                </Show>
+                <ul>
+                    { move || recipients().into_iter().map(|peer| {
+                        let node_id = peer.node_id.clone();
+                        let checked = {
+                            let node_id = node_id.clone();
+                            move || selected.get().contains(&node_id)
+                        };
+                        view! {
+                            <li>
+                                <label>
+                                    <input
+                                        type="checkbox"
+                                        prop:checked=checked
+                                        on:change=move |ev| toggle(node_id.clone(), event_target_checked(&ev))
+                                    />
+                                    {peer.name}
+                                </label>
+                            </li>
+                        }
+                    }).collect_view() }
+                </ul>
+                <Show when=move || !groups.get().is_empty()>
+                    <p class="groups">
+                        "Select a group: "
+                        { move || groups.get().into_iter().map(|(name, members)| view! {
+                            <button on:click=move |_| select_group(members.clone())>{name}</button>
+                        }).collect_view() }
+                    </p>
+                </Show>
+                <button on:click=send.clone() disabled=move || selected.get().is_empty()>"Send"</button>
+                <button on:click=save_group disabled=move || selected.get().is_empty()>"Save as group…"</button>
+                <button on:click=move |_| set_pending.set(Vec::new())>"Cancel"</button>
+            </div>
+        </Show>
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SharedFile {
+    pub path: String,
+    pub name: String,
+    pub size: u64,
+}
+
+/// Files shared into the app from other apps, e.g. from the gallery on mobile, each with a
+/// device to send it to.
+#[component]
+fn SharedFilesView(peers: ReadSignal<HashMap<String, PeerInfo>>) -> impl IntoView {
+    #[derive(Serialize)]
+    struct SharedFileArgs {
+        node_id: Option<String>,
+        path: String,
+    }
+
+    let (files, set_files) = create_signal(Vec::<SharedFile>::new());
+    let refresh = move || {
+        spawn_local(async move {
+            let result = invoke_without_args("shared_files").await;
+            set_files.set(serde_wasm_bindgen::from_value(result).unwrap_or_default());
+        });
+    };
+    // The app may have been opened by the share, before listening.
+    refresh();
+    spawn_local(async move {
+        let unlisten = listen::<(), _>("files-shared", move |()| refresh()).await;
+
+        on_cleanup(unlisten);
+    });
+
+    let toaster = expect_toaster();
+    let send = move |file: SharedFile, node_id: String| {
+        let toaster = toaster.clone();
+        spawn_local(async move {
+            let args = serde_wasm_bindgen::to_value(&SharedFileArgs {
+                node_id: Some(node_id),
+                path: file.path,
+            })
+            .expect("failed conversion");
+            let (msg, level) = match try_invoke("send_shared_file", args).await {
+                Ok(_) => (format!("Sent {}", file.name), ToastLevel::Success),
+                Err(err) => (
+                    format!(
+                        "Failed to send {}: {}",
+                        file.name,
+                        DropError::from(err).user_message()
+                    ),
+                    ToastLevel::Error,
+                ),
+            };
+            toaster.toast(
+                ToastBuilder::new(&msg)
+                    .with_level(level)
+                    .with_position(ToastPosition::TopRight),
+            );
+            refresh();
+        });
+    };
+    let dismiss = move |path: String| {
+        spawn_local(async move {
+            let args = serde_wasm_bindgen::to_value(&SharedFileArgs {
+                node_id: None,
+                path,
+            })
+            .expect("failed conversion");
+            try_invoke("dismiss_shared_file", args).await.ok();
+            refresh();
+        });
+    };
+
+    view! {
+        <Show when=move || !files.get().is_empty()>
+            <div class="shared-files">
+                <h3>"Shared with iroh-drop"</h3>
+                <ul>
+                    { move || files.get().into_iter().map(|file| {
+                        let send = send.clone();
+                        let (device, set_device) = create_signal(String::new());
+                        let path = file.path.clone();
+                        let label = format!("{} ({})", file.name, format_bytes(file.size));
+                        view! {
+                            <li>
+                                {label}
+                                <select on:change=move |ev| set_device.set(event_target_value(&ev))>
+                                    <option value="">"Send to…"</option>
+                                    { move || peers.get().into_values()
+                                        .filter(|peer| peer.online)
+                                        .map(|peer| view! { <option value=peer.node_id>{peer.name}</option> })
+                                        .collect_view() }
+                                </select>
+                                <button
+                                    disabled=move || device.get().is_empty()
+                                    on:click=move |_| send(file.clone(), device.get_untracked())
+                                >"Send"</button>
+                                <button on:click=move |_| dismiss(path.clone())>"Dismiss"</button>
+                            </li>
+                        }
+                    }).collect_view() }
+                </ul>
+            </div>
+        </Show>
+    }
+}
+
+/// Pauses the download `id`, or resumes it if it is `paused`.
+/// Opens the transfer in its own window, on desktop.
+fn details_button(id: u64) -> impl IntoView {
+    #[derive(Serialize)]
+    struct OpenTransferWindowArgs {
+        transfer_id: u64,
+    }
+
+    let toaster = expect_toaster();
+    let on_click = move |_| {
+        let toaster = toaster.clone();
+        spawn_local(async move {
+            let args = serde_wasm_bindgen::to_value(&OpenTransferWindowArgs { transfer_id: id })
+                .expect("failed conversion");
+            if let Err(err) = try_invoke("open_transfer_window", args).await {
+                toaster.toast(
+                    ToastBuilder::new(&DropError::from(err).user_message())
+                        .with_level(ToastLevel::Error)
+                        .with_position(ToastPosition::TopRight),
+                );
+            }
+        });
+    };
+    view! {
+        <button on:click=on_click title="Follow this transfer in its own window">"Details"</button>
+    }
+}
+
+fn pause_button(id: u64, paused: bool) -> impl IntoView {
+    #[derive(Serialize)]
+    struct TransferArgs {
+        id: u64,
+    }
+
+    let toaster = expect_toaster();
+    let on_click = move |_| {
+        let toaster = toaster.clone();
+        spawn_local(async move {
+            let command = if paused {
+                "resume_transfer"
+            } else {
+                "pause_transfer"
+            };
+            let args =
+                serde_wasm_bindgen::to_value(&TransferArgs { id }).expect("failed conversion");
+            if let Err(err) = try_invoke(command, args).await {
+                toaster.toast(
+                    ToastBuilder::new(&DropError::from(err).user_message())
+                        .with_level(ToastLevel::Error)
+                        .with_position(ToastPosition::TopRight),
+                );
+            }
+        });
+    };
+    view! {
+        <button on:click=on_click>{ if paused { "Resume" } else { "Pause" } }</button>
+    }
+}
+
+/// Lists the permissions the OS denied, with where to grant them.
+#[component]
+fn PermissionsView() -> impl IntoView {
+    let (permissions, set_permissions) = create_signal(Vec::<PermissionStatus>::new());
+    spawn_local(async move {
+        let result = invoke_without_args("permissions_status").await;
+        set_permissions.set(serde_wasm_bindgen::from_value(result).unwrap_or_default());
+    });
+    spawn_local(async move {
+        let unlisten = listen::<Vec<PermissionStatus>, _>("permissions-status", move |status| {
+            set_permissions.set(status);
+        })
+        .await;
+
+        on_cleanup(unlisten);
+    });
+    // Discovery finds nothing without it, so it gets a banner of its own.
+    let local_network = move || {
+        permissions.get().into_iter().find(|permission| {
+            permission.permission == "local-network" && permission.state == "denied"
+        })
+    };
+    let denied = move || {
+        permissions
+            .get()
+            .into_iter()
+            .filter(|permission| {
+                permission.state == "denied" && permission.permission != "local-network"
+            })
+            .collect::<Vec<_>>()
+    };
+
+    view! {
+        { move || local_network().map(|permission| view! {
+            <div class="permissions local-network">
+                <p><strong>"Local discovery is blocked by an OS permission."</strong></p>
+                <p>{permission.feature.clone()}
+                { permission.hint.clone().map(|hint| view! { <span class="hint">{format!(" Allow it in {}.", hint)}</span> }) }
+                </p>
+                {open_settings_button(&permission)}
+            </div>
+        }) }
+        <Show when=move || !denied().is_empty()>
+            <div class="permissions">
+                <p>"Some permissions are missing:"</p>
+                <ul>
+                    { move || denied().into_iter().map(|permission| view! {
+                        <li>
+                            {permission.feature}
+                            { permission.hint.clone().map(|hint| view! { <span class="hint">{format!(" Allow it in {}.", hint)}</span> }) }
+                            {open_settings_button(&permission)}
+                        </li>
+                    }).collect_view() }
+                </ul>
+            </div>
+        </Show>
+    }
+}
+
+/// Asks whether a newly joined network is trusted.
+#[component]
+fn NetworkTrustPrompt() -> impl IntoView {
+    // Id and label of the network to ask about
+    let (network, set_network) = create_signal(None::<(String, String)>);
+    #[derive(Deserialize)]
+    struct Network {
+        id: String,
+        label: String,
+    }
+
+    spawn_local(async move {
+        let result = invoke_without_args("current_network").await;
+        let current: Option<(Network, String)> =
+            serde_wasm_bindgen::from_value(result).unwrap_or_default();
+        if let Some((network, trust)) = current {
+            if trust == "unknown" {
+                set_network.set(Some((network.id, network.label)));
+            }
+        }
+    });
+    spawn_local(async move {
+        let unlisten = listen::<(String, String), _>("unknown-network", move |network| {
+            set_network.set(Some(network));
+        })
+        .await;
+
+        on_cleanup(unlisten);
+    });
+
+    #[derive(Serialize)]
+    struct SetNetworkTrustedArgs {
+        id: String,
+        trusted: bool,
+    }
+
+    let toaster = expect_toaster();
+    let answer = move |trusted: bool| {
+        let Some((id, _)) = network.get_untracked() else {
+            return;
+        };
+        set_network.set(None);
+        spawn_local(async move {
+            let args = serde_wasm_bindgen::to_value(&SetNetworkTrustedArgs { id, trusted })
+                .expect("failed conversion");
+            if let Err(err) = try_invoke("set_network_trusted", args).await {
+                toaster.toast(
+                    ToastBuilder::new(&DropError::from(err).user_message())
+                        .with_level(ToastLevel::Error)
+                        .with_position(ToastPosition::TopRight),
+                );
+            }
+        });
+    };
+
+    view! {
+        { move || network.get().map(|(_, label)| view! {
+            <div class="network-prompt">
+                <p>{format!("New network ({}). Do you trust this network?", label)}</p>
+                <button on:click=move |_| answer(true)>"Trust"</button>
+                <button on:click=move |_| answer(false)>"Don't trust"</button>
+            </div>
+        }) }
+    }
+}
+
+/// The active room, only devices in the same room are shown.
+#[component]
+fn RoomView() -> impl IntoView {
+    let (room, set_room) = create_signal(None::<String>);
+    let (input, set_input) = create_signal(String::new());
+    spawn_local(async move {
+        set_room.set(fetch_settings().await.room);
+    });
+    spawn_local(async move {
+        let unlisten = listen::<(), _>("settings-changed", move |()| {
+            spawn_local(async move {
+                set_room.set(fetch_settings().await.room);
+            });
+        })
+        .await;
+
+        on_cleanup(unlisten);
+    });
+
+    #[derive(Serialize)]
+    struct SetRoomArgs {
+        room: Option<String>,
+    }
+
+    let toaster = expect_toaster();
+    let change_room = move |room: Option<String>| {
+        spawn_local(async move {
+            let args = serde_wasm_bindgen::to_value(&SetRoomArgs { room })
+                .expect("failed conversion");
+            match try_invoke("set_room", args).await {
+                Ok(_) => set_input.set(String::new()),
+                Err(err) => toaster.toast(
+                    ToastBuilder::new(&format!(
+                        "Failed to change the room: {}",
+                        DropError::from(err).user_message()
+                    ))
+                    .with_level(ToastLevel::Error)
+                    .with_position(ToastPosition::TopRight),
+                ),
+            }
+        });
+    };
+
+    view! {
+        <div class="room">
+            { move || match room.get() {
+                Some(name) => view! {
+                    <p>
+                        "Room: " <b>{name}</b> " "
+                        <button on:click=move |_| change_room(None)>"Leave"</button>
+                    </p>
+                }.into_view(),
+                None => view! { <p>"Not in a room, all devices are shown."</p> }.into_view(),
+            } }
+            <form class="row" on:submit=move |ev: SubmitEvent| {
+                ev.prevent_default();
+                let name = input.get_untracked();
+                if !name.trim().is_empty() {
+                    change_room(Some(name));
+                }
+            }>
+                <input
+                    placeholder="Room name"
+                    on:input=move |ev| set_input.set(event_target_value(&ev))
+                    prop:value=input
+                />
+                <button type="submit">"Join room"</button>
+            </form>
+        </div>
+    }
+}
+
+#[component]
+fn GuestView() -> impl IntoView {
+    #[derive(Serialize)]
+    struct GuestTicketArgs {
+        minutes: u64,
+    }
+
+    let (minutes, set_minutes) = create_signal(60u64);
+    // The ticket, its QR code and until when it is valid.
+    let (ticket, set_ticket) = create_signal(None::<(String, String, String)>);
+
+    let toaster = expect_toaster();
+    let create = move |ev: SubmitEvent| {
+        ev.prevent_default();
+        let minutes = minutes.get_untracked();
+        spawn_local(async move {
+            let args = serde_wasm_bindgen::to_value(&GuestTicketArgs { minutes })
+                .expect("failed conversion");
+            match try_invoke("guest_ticket", args).await {
+                Ok(result) => {
+                    let (ticket, qr): (String, String) =
+                        serde_wasm_bindgen::from_value(result).unwrap();
+                    let until = js_sys::Date::now() + minutes as f64 * 60_000.0;
+                    let until = js_sys::Date::new(&JsValue::from_f64(until));
+                    let until: String = until.to_locale_time_string("default").into();
+                    set_ticket.set(Some((ticket, qr, until)));
+                }
+                Err(err) => toaster.toast(
+                    ToastBuilder::new(&format!(
+                        "Failed to create a guest ticket: {}",
+                        DropError::from(err).user_message()
+                    ))
+                    .with_level(ToastLevel::Error)
+                    .with_position(ToastPosition::TopRight),
+                ),
+            }
+        });
+    };
+
+    #[derive(Serialize)]
+    struct StartKioskArgs {
+        minutes: u64,
+    }
+
+    let toaster = expect_toaster();
+    let start_kiosk = move |_| {
+        let minutes = minutes.get_untracked();
+        spawn_local(async move {
+            let args = serde_wasm_bindgen::to_value(&StartKioskArgs { minutes })
+                .expect("failed conversion");
+            if let Err(err) = try_invoke("start_kiosk", args).await {
+                toaster.toast(
+                    ToastBuilder::new(&format!(
+                        "Failed to start kiosk mode: {}",
+                        DropError::from(err).user_message()
+                    ))
+                    .with_level(ToastLevel::Error)
+                    .with_position(ToastPosition::TopRight),
+                );
+            }
+        });
+    };
+
+    view! {
+        <details class="guest">
+            <summary>"Guest access"</summary>
+            <p>"Visitors scanning a guest ticket can send files to this device until it expires."</p>
+            <form class="row" on:submit=create>
+                <label>
+                    "Valid for (minutes)"
+                    <input
+                        type="number"
+                        min="1"
+                        prop:value=move || minutes.get()
+                        on:change=move |ev| {
+                            if let Ok(value) = event_target_value(&ev).parse() {
+                                set_minutes.set(value);
+                            }
+                        }
+                    />
+                </label>
+                <button type="submit">"Create guest ticket"</button>
+                <button type="button" on:click=start_kiosk>"Start kiosk mode"</button>
+            </form>
+            { move || ticket.get().map(|(ticket, qr, until)| view! {
+                <p>{format!("Valid until {}", until)}</p>
+                <p class="ticket">{ticket}</p>
+                <div class="row qr" inner_html=qr></div>
+            }) }
+        </details>
+    }
+}
+
+/// Ticket adding another device of the user, which then accepts files from this one without
+/// asking, and the other way around.
+#[component]
+fn OwnDeviceView() -> impl IntoView {
+    // The ticket and its QR code.
+    let (ticket, set_ticket) = create_signal(None::<(String, String)>);
+
+    let toaster = expect_toaster();
+    let create = move |_| {
+        spawn_local(async move {
+            match try_invoke("own_device_ticket", JsValue::UNDEFINED).await {
+                Ok(result) => set_ticket.set(serde_wasm_bindgen::from_value(result).ok()),
+                Err(err) => toaster.toast(
+                    ToastBuilder::new(&format!(
+                        "Failed to create a ticket: {}",
+                        DropError::from(err).user_message()
+                    ))
+                    .with_level(ToastLevel::Error)
+                    .with_position(ToastPosition::TopRight),
+                ),
+            }
+        });
+    };
+    let leave = move |_| {
+        spawn_local(async move {
+            match try_invoke("leave_device_group", JsValue::UNDEFINED).await {
+                Ok(_) => set_ticket.set(None),
+                Err(err) => toaster.toast(
+                    ToastBuilder::new(&format!(
+                        "Failed to leave the group: {}",
+                        DropError::from(err).user_message()
+                    ))
+                    .with_level(ToastLevel::Error)
+                    .with_position(ToastPosition::TopRight),
+                ),
+            }
+        });
+    };
+
+    view! {
+        <details class="guest">
+            <summary>"Add one of my devices"</summary>
+            <p>
+                "Add this ticket on your other device. Your devices accept files from each "
+                "other without asking, and find each other when they are not on the same network."
+            </p>
+            <div class="row">
+                <button on:click=create>"Create ticket"</button>
+                <button on:click=leave>"Leave group"</button>
+            </div>
+            { move || ticket.get().map(|(ticket, qr)| view! {
+                <p class="ticket">{ticket}</p>
+                <div class="row qr" inner_html=qr></div>
+            }) }
+        </details>
+    }
 }
 
-#[derive(Serialize, Deserialize)]
-pub struct Event<T> {
-    pub event: String,
-    pub payload: T,
-    pub id: f64,
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct StorageStatus {
+    /// Whether received files are kept encrypted
+    pub set_up: bool,
+    pub unlocked: bool,
 }
 
-async fn listen<T: DeserializeOwned, F: Fn(T) + 'static>(event: &str, handler: F) -> impl FnOnce() {
-    logging::log!("listenting to event: {}", event);
-    let closure = Closure::<dyn FnMut(_)>::new(move |s: JsValue| {
-        let event: Event<T> = serde_wasm_bindgen::from_value(s).unwrap();
-        handler(event.payload);
+async fn fetch_storage_status() -> StorageStatus {
+    let result = invoke_without_args("storage_status").await;
+    serde_wasm_bindgen::from_value(result).unwrap_or_default()
+}
+
+/// Opting into encrypted copies of received files, and unlocking them to save files.
+#[component]
+fn EncryptedStorageView() -> impl IntoView {
+    #[derive(Serialize)]
+    struct PasswordArgs {
+        password: String,
+    }
+
+    let (status, set_status) = create_signal(StorageStatus::default());
+    let (password, set_password) = create_signal(String::new());
+    spawn_local(async move {
+        set_status.set(fetch_storage_status().await);
     });
 
-    let unlisten = listen_sys(event, closure.as_ref().unchecked_ref()).await;
-    closure.forget();
+    let toaster = expect_toaster();
+    let submit = move |ev: SubmitEvent| {
+        ev.prevent_default();
+        let cmd = if status.get_untracked().set_up {
+            "unlock_storage"
+        } else {
+            "set_up_encrypted_storage"
+        };
+        let password = password.get_untracked();
+        set_password.set(String::new());
+        spawn_local(async move {
+            let args = serde_wasm_bindgen::to_value(&PasswordArgs { password })
+                .expect("failed conversion");
+            if let Err(err) = try_invoke(cmd, args).await {
+                toaster.toast(
+                    ToastBuilder::new(&DropError::from(err).user_message())
+                        .with_level(ToastLevel::Error)
+                        .with_position(ToastPosition::TopRight),
+                );
+            }
+            set_status.set(fetch_storage_status().await);
+        });
+    };
+    let lock = move |_| {
+        spawn_local(async move {
+            invoke_without_args("lock_storage").await;
+            set_status.set(fetch_storage_status().await);
+        });
+    };
 
-    move || {
-        logging::log!("unlistening");
-        unlisten.call0(&JsValue::NULL).expect("failed to unlisten");
+    view! {
+        <details class="settings">
+            <summary>"Encrypted copies"</summary>
+            <p>{move || match status.get() {
+                StorageStatus { set_up: false, .. } => {
+                    "Received files are only kept in memory. Once enabled, an encrypted copy is kept on disk and saving them needs the password."
+                }
+                StorageStatus { unlocked: false, .. } => "Locked, received files can not be saved.",
+                StorageStatus { .. } => "Unlocked, received files can be saved.",
+            }}</p>
+            <Show
+                when=move || !status.get().unlocked
+                fallback=move || view! { <button on:click=lock>"Lock"</button> }
+            >
+                <form class="row" on:submit=submit>
+                    <input
+                        type="password"
+                        placeholder="Password"
+                        on:input=move |ev| set_password.set(event_target_value(&ev))
+                        prop:value=password
+                    />
+                    <button type="submit">
+                        { move || if status.get().set_up { "Unlock" } else { "Encrypt received files" } }
+                    </button>
+                </form>
+            </Show>
+        </details>
     }
 }
 
-#[component]
-pub fn App() -> impl IntoView {
-    let (discover_msg, set_discover_msg) = create_signal(HashMap::new());
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct AdminLockStatus {
+    /// Whether the machine is in read-only mode
+    pub enabled: bool,
+    /// Whether changes are currently allowed
+    pub unlocked: bool,
+}
 
-    let (my_node_id, set_my_node_id) = create_signal(String::new());
+async fn fetch_admin_lock_status() -> AdminLockStatus {
+    let result = invoke_without_args("admin_lock_status").await;
+    serde_wasm_bindgen::from_value(result).unwrap_or_default()
+}
 
-    provide_toaster();
+/// Read-only mode for shared machines, locking the settings behind an admin passphrase.
+#[component]
+fn AdminLockView() -> impl IntoView {
+    #[derive(Serialize)]
+    struct PassphraseArgs {
+        passphrase: String,
+    }
 
+    let (status, set_status) = create_signal(AdminLockStatus::default());
+    let (passphrase, set_passphrase) = create_signal(String::new());
     spawn_local(async move {
-        let result = invoke_without_args("node_id").await;
-        let my_node_id: String = serde_wasm_bindgen::from_value(result).unwrap();
-        set_my_node_id.set(my_node_id);
+        set_status.set(fetch_admin_lock_status().await);
     });
 
-    let discover = move |ev: SubmitEvent| {
+    let toaster = expect_toaster();
+    let run = move |cmd: &'static str| {
+        let passphrase = passphrase.get_untracked();
+        set_passphrase.set(String::new());
+        spawn_local(async move {
+            let args = serde_wasm_bindgen::to_value(&PassphraseArgs { passphrase })
+                .expect("failed conversion");
+            if let Err(err) = try_invoke(cmd, args).await {
+                toaster.toast(
+                    ToastBuilder::new(&DropError::from(err).user_message())
+                        .with_level(ToastLevel::Error)
+                        .with_position(ToastPosition::TopRight),
+                );
+            }
+            set_status.set(fetch_admin_lock_status().await);
+        });
+    };
+    let submit = move |ev: SubmitEvent| {
         ev.prevent_default();
+        if status.get_untracked().enabled {
+            run("unlock_admin_lock");
+        } else {
+            run("enable_admin_lock");
+        }
+    };
+    let lock = move |_| {
         spawn_local(async move {
-            let result = invoke_without_args("discover").await;
-            let discover: Vec<(String, String)> = serde_wasm_bindgen::from_value(result).unwrap();
-            logging::log!("discovered: {:?}", discover);
-            set_discover_msg.update(|val| {
-                for (name, node_id) in discover {
-                    val.insert(node_id, name);
+            invoke_without_args("lock_admin_lock").await;
+            set_status.set(fetch_admin_lock_status().await);
+        });
+    };
+
+    view! {
+        <details class="settings">
+            <summary>"Read-only mode"</summary>
+            <p>{move || match status.get() {
+                AdminLockStatus { enabled: false, .. } => {
+                    "Anyone using this machine can change its settings and delete its history."
                 }
-            });
+                AdminLockStatus { unlocked: false, .. } => {
+                    "Read-only: settings, trusted devices and history need the admin passphrase."
+                }
+                AdminLockStatus { .. } => "Unlocked for a few minutes.",
+            }}</p>
+            <Show
+                when=move || !status.get().enabled || !status.get().unlocked
+                fallback=move || view! { <button on:click=lock>"Lock"</button> }
+            >
+                <form class="row" on:submit=submit>
+                    <input
+                        type="password"
+                        placeholder="Admin passphrase"
+                        on:input=move |ev| set_passphrase.set(event_target_value(&ev))
+                        prop:value=passphrase
+                    />
+                    <button type="submit">
+                        { move || if status.get().enabled { "Unlock" } else { "Make read-only" } }
+                    </button>
+                    <Show when=move || status.get().enabled>
+                        <button type="button" on:click=move |_| run("disable_admin_lock")>
+                            "Turn off"
+                        </button>
+                    </Show>
+                </form>
+            </Show>
+        </details>
+    }
+}
+
+/// Export and import of the settings and paired devices, to move to a new machine.
+#[component]
+fn ProfileView() -> impl IntoView {
+    #[derive(Serialize)]
+    struct ExportProfileArgs {
+        path: String,
+        password: String,
+        include_key: bool,
+    }
+
+    #[derive(Serialize)]
+    struct ImportProfileArgs {
+        path: String,
+        password: String,
+    }
+
+    let (password, set_password) = create_signal(String::new());
+    let (include_key, set_include_key) = create_signal(false);
+
+    let toaster = expect_toaster();
+    let export = move |_| {
+        let Ok(Some(path)) = window()
+            .prompt_with_message_and_default("Export to (full path)", "iroh-drop-profile.age")
+        else {
+            return;
+        };
+        let toaster = toaster.clone();
+        spawn_local(async move {
+            let args = ExportProfileArgs {
+                path,
+                password: password.get_untracked(),
+                include_key: include_key.get_untracked(),
+            };
+            let (msg, level) = match try_invoke(
+                "export_profile",
+                serde_wasm_bindgen::to_value(&args).expect("failed conversion"),
+            )
+            .await
+            {
+                Ok(_) => (format!("Exported to {}", args.path), ToastLevel::Success),
+                Err(err) => (
+                    format!("Failed to export: {}", DropError::from(err).user_message()),
+                    ToastLevel::Error,
+                ),
+            };
+            toaster.toast(
+                ToastBuilder::new(&msg)
+                    .with_level(level)
+                    .with_position(ToastPosition::TopRight),
+            );
+        });
+    };
+
+    let toaster = expect_toaster();
+    let import = move |_| {
+        let Ok(Some(path)) = window().prompt_with_message("Import from (full path)") else {
+            return;
+        };
+        if !window()
+            .confirm_with_message("Replace all settings with the imported ones?")
+            .unwrap_or(false)
+        {
+            return;
+        }
+        let toaster = toaster.clone();
+        spawn_local(async move {
+            let args = ImportProfileArgs {
+                path,
+                password: password.get_untracked(),
+            };
+            let result = try_invoke(
+                "import_profile",
+                serde_wasm_bindgen::to_value(&args).expect("failed conversion"),
+            )
+            .await;
+            let (msg, level) = match result {
+                Ok(restart) if restart.as_bool().unwrap_or(false) => (
+                    "Imported, restart the app to take on the identity of the old device"
+                        .to_string(),
+                    ToastLevel::Success,
+                ),
+                Ok(_) => ("Imported the settings".to_string(), ToastLevel::Success),
+                Err(err) => (
+                    format!("Failed to import: {}", DropError::from(err).user_message()),
+                    ToastLevel::Error,
+                ),
+            };
+            toaster.toast(
+                ToastBuilder::new(&msg)
+                    .with_level(level)
+                    .with_position(ToastPosition::TopRight),
+            );
         });
     };
+
+    view! {
+        <details class="settings">
+            <summary>"Move to a new machine"</summary>
+            <p>
+                "Export the settings and paired devices to an encrypted file, and import it on the new machine."
+            </p>
+            <input
+                type="password"
+                placeholder="Password of the file"
+                on:input=move |ev| set_password.set(event_target_value(&ev))
+                prop:value=password
+            />
+            <label>
+                <input
+                    type="checkbox"
+                    prop:checked=include_key
+                    on:change=move |ev| set_include_key.set(event_target_checked(&ev))
+                />
+                "Include the identity of this device, so others know the new machine without pairing again"
+            </label>
+            <div class="row">
+                <button on:click=export>"Export"</button>
+                <button on:click=import>"Import"</button>
+            </div>
+        </details>
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct KioskSession {
+    pub ticket: String,
+    pub qr: String,
+    pub folder: String,
+    pub files: Vec<String>,
+}
+
+/// Full-screen view of the kiosk window, with the guest ticket and the files received.
+#[component]
+pub fn KioskView() -> impl IntoView {
+    let (session, set_session) = create_signal(None::<KioskSession>);
+    spawn_local(async move {
+        let result = invoke_without_args("kiosk_session").await;
+        set_session.set(serde_wasm_bindgen::from_value(result).unwrap_or_default());
+    });
     spawn_local(async move {
-        let unlisten = listen::<(String, String), _>("discovery", move |(name, node_id)| {
-            logging::log!("recv event: {}: {}", name, node_id);
-            set_discover_msg.update(|val| {
-                val.insert(node_id, name);
+        let unlisten = listen::<String, _>("kiosk-file", move |name| {
+            set_session.update(|session| {
+                if let Some(session) = session {
+                    session.files.push(name);
+                }
             });
         })
         .await;
@@ -84,101 +4443,648 @@ pub fn App() -> impl IntoView {
         on_cleanup(unlisten);
     });
 
-    let toaster = expect_toaster();
-    spawn_local(async move {
-        let unlisten =
-            listen::<(String, String, u64), _>("file-downloaded", move |(name, hash, size)| {
-                logging::log!("recv event file-downloaed: {} - {} - {}", name, hash, size);
-                toaster.toast(
-                    ToastBuilder::new(&format!("File received: {} ({}bytes)", name, size))
-                        .with_level(ToastLevel::Success)
-                        .with_expiry(None)
-                        .with_position(ToastPosition::TopRight),
-                );
+    view! {
+        <main class="kiosk">
+            { move || match session.get() {
+                Some(session) => view! {
+                    <h1>"Send your files to this screen"</h1>
+                    <p>"Scan the code with iroh-drop, or add the ticket as a remote device."</p>
+                    <div class="qr" inner_html=session.qr></div>
+                    <p class="ticket">{session.ticket}</p>
+                    <h2>{format!("Received ({})", session.files.len())}</h2>
+                    <ul>
+                        { session.files.into_iter().rev().map(|name| view! { <li>{name}</li> }).collect_view() }
+                    </ul>
+                    <p>{format!("Saved to {}", session.folder)}</p>
+                }.into_view(),
+                None => view! { <p>"No kiosk session is running."</p> }.into_view(),
+            } }
+            <button on:click=move |_| {
+                spawn_local(async move {
+                    invoke_without_args("stop_kiosk").await;
+                });
+            }>"End session"</button>
+        </main>
+    }
+}
+
+/// Number of log messages shown in a transfer window.
+const TRANSFER_LOG_LIMIT: usize = 100;
+
+/// Window following a single transfer, with its live progress, connection path and logs.
+#[component]
+pub fn TransferWindow(id: u64) -> impl IntoView {
+    #[derive(Serialize)]
+    struct TransferLogsArgs {
+        transfer_id: u64,
+        limit: usize,
+    }
+    #[derive(Serialize)]
+    struct PeerConnectionInfoArgs {
+        node_id: String,
+    }
+
+    let (transfer, set_transfer) = create_signal(None::<Transfer>);
+    let (stats, set_stats) = create_signal(None::<TransferStats>);
+    let (connection, set_connection) = create_signal(None::<ConnectionInfo>);
+    let (records, set_records) = create_signal(Vec::<LogRecord>::new());
+    let (progress, set_progress) = create_signal(None::<(u64, u64)>);
+
+    let refresh = move || {
+        spawn_local(async move {
+            let result = invoke_without_args("list_transfers").await;
+            let transfers: Vec<Transfer> =
+                serde_wasm_bindgen::from_value(result).unwrap_or_default();
+            set_transfer.set(transfers.into_iter().find(|transfer| transfer.id == id));
+
+            let result = invoke_without_args("transfer_stats").await;
+            let all: Vec<TransferStats> =
+                serde_wasm_bindgen::from_value(result).unwrap_or_default();
+            let current = all.into_iter().find(|stats| stats.id == id);
+            if let Some(ref current) = current {
+                let args = serde_wasm_bindgen::to_value(&PeerConnectionInfoArgs {
+                    node_id: current.node_id.clone(),
+                })
+                .expect("failed conversion");
+                if let Ok(result) = try_invoke("peer_connection_info", args).await {
+                    set_connection.set(serde_wasm_bindgen::from_value(result).unwrap_or_default());
+                }
+            }
+            set_stats.set(current);
+
+            let args = serde_wasm_bindgen::to_value(&TransferLogsArgs {
+                transfer_id: id,
+                limit: TRANSFER_LOG_LIMIT,
             })
-            .await;
+            .expect("failed conversion");
+            match try_invoke("transfer_logs", args).await {
+                Ok(result) => {
+                    set_records.set(serde_wasm_bindgen::from_value(result).unwrap_or_default())
+                }
+                Err(err) => logging::warn!("no logs: {:?}", DropError::from(err)),
+            }
+        });
+    };
+    refresh();
+    if let Ok(handle) = set_interval_with_handle(refresh, std::time::Duration::from_secs(1)) {
+        on_cleanup(move || handle.clear());
+    }
+
+    spawn_local(async move {
+        let unlisten = listen::<(u64, u64, u64, u64, bool), _>(
+            "transfer-progress",
+            move |(transfer_id, offset, _size, bps, _paused)| {
+                if transfer_id == id {
+                    set_progress.set(Some((offset, bps)));
+                }
+            },
+        )
+        .await;
+
+        on_cleanup(unlisten);
+    });
+    spawn_local(async move {
+        let unlisten = listen::<(u64, u64, u64, u64), _>(
+            "upload-progress",
+            move |(transfer_id, offset, _size, bps)| {
+                if transfer_id == id {
+                    set_progress.set(Some((offset, bps)));
+                }
+            },
+        )
+        .await;
 
         on_cleanup(unlisten);
     });
 
+    let status = move || {
+        let finished = stats.get().is_some_and(|stats| stats.finished);
+        match (transfer.get(), progress.get()) {
+            _ if finished => "Finished".to_string(),
+            (Some(transfer), _) if transfer.state == "queued" => "Waiting in the queue".to_string(),
+            (Some(transfer), Some((offset, _))) if transfer.state == "paused" => {
+                format!("Paused at {}", format_bytes(offset))
+            }
+            (_, Some((offset, bps))) => {
+                format!("{} at {}/s", format_bytes(offset), format_bytes(bps))
+            }
+            (Some(transfer), None) => transfer.state,
+            (None, None) => "Not running".to_string(),
+        }
+    };
+
     view! {
-        <Toaster stacked={true} />
+        <main class="transfer-window">
+            { move || stats.get().map(|stats| {
+                let percent = progress.get()
+                    .map(|(offset, _)| offset * 100 / stats.size.max(1))
+                    .unwrap_or(if stats.finished { 100 } else { 0 });
+                view! {
+                    <h1>{ if stats.direction == "sent" { "↑ " } else { "↓ " } }{stats.name.clone()}</h1>
+                    <p>{format!("{} with {}", format_bytes(stats.size), &stats.node_id[..8])}</p>
+                    <progress max="100" value=percent></progress>
+                    <p>{format!(
+                        "{} over a direct connection, {} through a relay, average {}/s",
+                        format_bytes(stats.direct_bytes),
+                        format_bytes(stats.relay_bytes),
+                        format_bytes(stats.throughput().unwrap_or(0)),
+                    )}</p>
+                }
+            }) }
+            <p class="status">{status}</p>
+            { move || connection.get().map(|info| view! {
+                <pre class="connection">{info.describe()}</pre>
+            }) }
+            <h2>"Log"</h2>
+            <table>
+                { move || records.get().into_iter().map(|record| view! {
+                    <tr class:warning=record.level == "WARN" || record.level == "ERROR">
+                        <td>{format_ago(record.timestamp / 1000)}</td>
+                        <td>{record.level}</td>
+                        <td>{record.message}</td>
+                    </tr>
+                }).collect_view() }
+            </table>
+        </main>
+    }
+}
 
-        <main class="container">
-            <p>"Discover local iroh nodes."</p>
-            <p>"My Node: " { move || my_node_id.get() }</p>
+#[component]
+fn StatsView() -> impl IntoView {
+    let (stats, set_stats) = create_signal(DropStats::default());
+    let refresh = move |_| {
+        spawn_local(async move {
+            let result = invoke_without_args("drop_stats").await;
+            set_stats.set(serde_wasm_bindgen::from_value(result).unwrap());
+        });
+    };
 
-            <form class="row" on:submit=discover>
-                <button type="submit">"Discover"</button>
-            </form>
+    view! {
+        <details class="stats" on:toggle=refresh>
+            <summary>"Statistics"</summary>
+            {move || {
+                let stats = stats.get();
+                let total = (stats.direct + stats.relay).max(1);
+                let direct = format!("width: {}%", stats.direct * 100 / total);
+                let per_day = stats.per_day.iter().map(|day| {
+                    let date = js_sys::Date::new(&JsValue::from_f64((day.day * 86_400_000) as f64));
+                    let date: String = date.to_iso_string().into();
+                    (date[..10].to_string(), day.bytes, format_bytes(day.bytes))
+                }).collect();
+                let per_peer = stats.per_peer.iter().map(|peer| {
+                    let name = peer.name.clone().unwrap_or_else(|| peer.node_id[..8].to_string());
+                    (name, peer.bytes, format!("{} files, {}", peer.files, format_bytes(peer.bytes)))
+                }).collect();
 
-        <p><b>{ move || discover_msg.get().into_iter().map(|(node_id, name)| {
-            node_view(name, node_id)
-            }).collect_view() }</b></p>
-        </main>
+                view! {
+                    <p>
+                        {format!("Sent {} files ({}), received {} files ({})",
+                            stats.files_sent, format_bytes(stats.bytes_sent),
+                            stats.files_received, format_bytes(stats.bytes_received))}
+                    </p>
+                    <p>
+                        "Best throughput: "
+                        {stats.best_throughput.map(|t| format!("{}/s", format_bytes(t))).unwrap_or_else(|| "-".into())}
+                    </p>
+                    <p>{format!("Direct: {} (local network: {}) / Relay: {}", stats.direct, stats.local, stats.relay)}</p>
+                    <div class="ratio"><span class="bar" style=direct></span></div>
+                    <h4>"Per day"</h4>
+                    {bar_chart(per_day)}
+                    <h4>"Per peer"</h4>
+                    {bar_chart(per_peer)}
+                }
+            }}
+        </details>
     }
 }
 
-fn node_view(name: String, node_id: String) -> impl IntoView {
-    let (dropped, set_dropped) = create_signal(false);
+/// Speed and connection path of the running and last finished transfers.
+#[component]
+fn TransferStatsView() -> impl IntoView {
+    let (transfers, set_transfers) = create_signal(Vec::<TransferStats>::new());
+    let refresh = move |_| {
+        spawn_local(async move {
+            let result = invoke_without_args("transfer_stats").await;
+            set_transfers.set(serde_wasm_bindgen::from_value(result).unwrap_or_default());
+        });
+    };
 
-    let drop_zone_el = create_node_ref::<Div>();
+    view! {
+        <details class="transfer-stats" on:toggle=refresh>
+            <summary>"Transfer speed"</summary>
+            <Show when=move || transfers.get().is_empty()>
+                <p>"No transfers yet."</p>
+            </Show>
+            <ul>
+                { move || transfers.get().into_iter().rev().map(|transfer| {
+                    let arrow = if transfer.direction == "sent" { "↑" } else { "↓" };
+                    let speed = transfer.throughput()
+                        .map(|t| format!("{}/s", format_bytes(t)))
+                        .unwrap_or_else(|| "-".into());
+                    let total = (transfer.direct_bytes + transfer.relay_bytes).max(1);
+                    let direct = format!("width: {}%", transfer.direct_bytes * 100 / total);
+                    let max = transfer.samples.iter().copied().max().unwrap_or(0).max(1);
+                    let samples = transfer.samples.iter().map(|bytes| {
+                        let height = format!("height: {}%", bytes * 100 / max);
+                        view! { <span style=height title=format!("{}/s", format_bytes(*bytes))></span> }
+                    }).collect_view();
+
+                    view! {
+                        <li>
+                            <p>
+                                {format!("{} {} ({}) with {}", arrow, transfer.name, format_bytes(transfer.size), &transfer.node_id[..8])}
+                                {(!transfer.finished).then_some(" - running")}
+                            </p>
+                            <p>
+                                {format!("{:.1} s, average {}, direct {} / relay {}",
+                                    transfer.duration_ms as f64 / 1000., speed,
+                                    format_bytes(transfer.direct_bytes), format_bytes(transfer.relay_bytes))}
+                            </p>
+                            <div class="ratio"><span class="bar" style=direct></span></div>
+                            <div class="sparkline">{samples}</div>
+                        </li>
+                    }
+                }).collect_view() }
+            </ul>
+        </details>
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StorageUsage {
+    pub size: u64,
+    pub blobs: usize,
+    pub collectable: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoredBlob {
+    pub hash: String,
+    pub size: u64,
+    pub name: Option<String>,
+    pub received: bool,
+}
+
+/// Disk usage of received and sent files, and when they are cleaned up.
+#[component]
+fn StorageView() -> impl IntoView {
+    let (usage, set_usage) = create_signal(StorageUsage::default());
+    let (settings, set_settings) = create_signal(Settings::default());
+    let (blobs, set_blobs) = create_signal(Vec::<StoredBlob>::new());
+    let refresh = move |_| {
+        spawn_local(async move {
+            let result = invoke_without_args("storage_usage").await;
+            set_usage.set(serde_wasm_bindgen::from_value(result).unwrap_or_default());
+            set_settings.set(fetch_settings().await);
+            let result = invoke_without_args("list_blobs").await;
+            set_blobs.set(serde_wasm_bindgen::from_value(result).unwrap_or_default());
+        });
+    };
 
     #[derive(Debug, Serialize, Deserialize)]
-    struct SendFileArgs {
+    struct ExportBlobArgs {
+        hash: String,
+        dest_path: String,
+        confirmed: bool,
+    }
+
+    // Received files can be saved again, e.g. after the saved copy was deleted.
+    let toaster = expect_toaster();
+    let export = move |hash: String, name: String| {
+        let Ok(Some(dest_path)) = window()
+            .prompt_with_message_and_default(&format!("Save \"{}\" to (full path)", name), &name)
+        else {
+            return;
+        };
+        let toaster = toaster.clone();
+        spawn_local(async move {
+            let mut args = ExportBlobArgs {
+                hash,
+                dest_path,
+                confirmed: false,
+            };
+            let mut result = try_invoke(
+                "export_blob",
+                serde_wasm_bindgen::to_value(&args).expect("failed conversion"),
+            )
+            .await
+            .map_err(DropError::from);
+            if let Err(ref err) = result {
+                if err.code == "needs_confirmation"
+                    && window().confirm_with_message(&err.message).unwrap_or(false)
+                {
+                    args.confirmed = true;
+                    result = try_invoke(
+                        "export_blob",
+                        serde_wasm_bindgen::to_value(&args).expect("failed conversion"),
+                    )
+                    .await
+                    .map_err(DropError::from);
+                }
+            }
+            let (msg, level) = match result {
+                Ok(_) => (format!("Saved to {}", args.dest_path), ToastLevel::Success),
+                Err(err) => (
+                    format!("Failed to save: {}", err.user_message()),
+                    ToastLevel::Error,
+                ),
+            };
+            toaster.toast(
+                ToastBuilder::new(&msg)
+                    .with_level(level)
+                    .with_position(ToastPosition::TopRight),
+            );
+        });
+    };
+
+    let toaster = expect_toaster();
+    let save = move |ev: SubmitEvent| {
+        ev.prevent_default();
+        spawn_local(async move {
+            // Only the storage settings are edited here, keep the rest as it is now.
+            let mut current = fetch_settings().await;
+            current.storage = settings.get_untracked().storage;
+            let (msg, level) = match save_settings(current).await {
+                Ok(()) => ("Storage settings saved".to_string(), ToastLevel::Success),
+                Err(err) => (format!("Invalid settings: {}", err), ToastLevel::Error),
+            };
+            toaster.toast(
+                ToastBuilder::new(&msg)
+                    .with_level(level)
+                    .with_position(ToastPosition::TopRight),
+            );
+        });
+    };
+
+    view! {
+        <details class="storage" on:toggle=refresh>
+            <summary>"Storage"</summary>
+            <p>
+                {move || {
+                    let usage = usage.get();
+                    format!("{} in {} files, {} of them can be cleaned up",
+                        format_bytes(usage.size), usage.blobs, format_bytes(usage.collectable))
+                }}
+            </p>
+            <p>"Files are only cleaned up once they were sent, or saved after receiving them."</p>
+            <form on:submit=save>
+                <label>
+                    "Keep files for (days, 0 to keep them)"
+                    <input
+                        type="number"
+                        min="0"
+                        prop:value=move || settings.get().storage.retention_days
+                        on:change=move |ev| {
+                            if let Ok(days) = event_target_value(&ev).parse() {
+                                set_settings.update(|s| s.storage.retention_days = days);
+                            }
+                        }
+                    />
+                </label>
+                <label>
+                    "Maximum size (MiB, 0 for no limit)"
+                    <input
+                        type="number"
+                        min="0"
+                        prop:value=move || settings.get().storage.max_store_size / (1024 * 1024)
+                        on:change=move |ev| {
+                            if let Ok(mib) = event_target_value(&ev).parse::<u64>() {
+                                set_settings.update(|s| s.storage.max_store_size = mib * 1024 * 1024);
+                            }
+                        }
+                    />
+                </label>
+                <button type="submit">"Save"</button>
+            </form>
+            <ul class="blobs">
+                { move || blobs.get().into_iter().filter(|blob| blob.received).map(|blob| {
+                    let name = blob.name.unwrap_or_default();
+                    let label = format!("{} ({})", name, format_bytes(blob.size));
+                    let export = export.clone();
+                    view! {
+                        <li>
+                            {label}
+                            <button on:click=move |_| export(blob.hash.clone(), name.clone())>
+                                "Save again…"
+                            </button>
+                        </li>
+                    }
+                }).collect_view() }
+            </ul>
+        </details>
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RejectedOffer {
+    pub timestamp: u64,
+    pub node_id: String,
+    pub sender: Option<String>,
+    pub name: String,
+    pub hash: String,
+    pub size: u64,
+    pub reason: String,
+}
+
+#[component]
+fn SecurityLogView() -> impl IntoView {
+    #[derive(Debug, Serialize, Deserialize)]
+    struct AcceptOnceArgs {
         node_id: String,
-        file_name: String,
-        file_data: Vec<u8>,
     }
 
-    let node = node_id.clone();
-    let on_drop = move |event: UseDropZoneEvent| {
-        let node_id = node.clone();
-        set_dropped.set(true);
+    let (entries, set_entries) = create_signal(Vec::<RejectedOffer>::new());
+    let refresh = move |_| {
         spawn_local(async move {
-            let file = &event.files[0];
-            logging::log!("reading: {:?}", file);
-            let buffer = JsFuture::from(file.array_buffer())
-                .await
-                .expect("failed future");
-            let array = Uint8Array::new(&buffer);
-            let file_data: Vec<u8> = array.to_vec();
-            logging::log!("sending file to {}", node_id);
-            let args = serde_wasm_bindgen::to_value(&SendFileArgs {
-                node_id,
-                file_name: file.name(),
-                file_data,
+            let result = invoke_without_args("security_log").await;
+            set_entries.set(serde_wasm_bindgen::from_value(result).unwrap());
+        });
+    };
+
+    view! {
+        <details class="security-log" on:toggle=refresh>
+            <summary>"Security log"</summary>
+            <table>
+                <tr>
+                    <th>"When"</th>
+                    <th>"Who"</th>
+                    <th>"What"</th>
+                    <th>"Why"</th>
+                    <th></th>
+                </tr>
+                { move || entries.get().into_iter().map(|entry| {
+                    let who = entry.sender.unwrap_or_else(|| entry.node_id[..8].to_string());
+                    // Only offers rejected for who sent them or the size limit can be let
+                    // through once.
+                    let can_accept = matches!(
+                        entry.reason.as_str(),
+                        "unknown_peer" | "not_trusted" | "untrusted_network" | "exceeds_limit"
+                    );
+                    let toaster = expect_toaster();
+                    let node_id = entry.node_id.clone();
+                    let sender = who.clone();
+                    let accept_once = move |_| {
+                        let node_id = node_id.clone();
+                        let sender = sender.clone();
+                        spawn_local(async move {
+                            let args = serde_wasm_bindgen::to_value(&AcceptOnceArgs { node_id })
+                                .expect("failed conversion");
+                            let (message, level) = match try_invoke("accept_once", args).await {
+                                Ok(_) => (
+                                    format!("The next file from {} will be accepted", sender),
+                                    ToastLevel::Success,
+                                ),
+                                Err(err) => (
+                                    format!(
+                                        "Failed to accept: {}",
+                                        DropError::from(err).user_message()
+                                    ),
+                                    ToastLevel::Error,
+                                ),
+                            };
+                            toaster.toast(
+                                ToastBuilder::new(&message)
+                                    .with_level(level)
+                                    .with_position(ToastPosition::TopRight),
+                            );
+                        });
+                    };
+                    view! {
+                        <tr>
+                            <td>{format_ago(entry.timestamp)}</td>
+                            <td>{who}</td>
+                            <td>{format!("{} ({})", entry.name, format_bytes(entry.size))}</td>
+                            <td>{entry.reason.replace('_', " ")}</td>
+                            <td>
+                                <Show when=move || can_accept>
+                                    <button
+                                        title="Accept one file, without trusting the sender"
+                                        on:click=accept_once.clone()
+                                    >
+                                        "Accept once"
+                                    </button>
+                                </Show>
+                            </td>
+                        </tr>
+                    }
+                }).collect_view() }
+            </table>
+        </details>
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConnectionAttempt {
+    pub timestamp: u64,
+    pub node_id: String,
+    pub alpn: String,
+    pub path: String,
+    pub handshake_ms: u64,
+    pub failure: Option<String>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogRecord {
+    /// Milliseconds since the unix epoch
+    pub timestamp: u64,
+    pub level: String,
+    pub target: String,
+    pub spans: String,
+    pub message: String,
+}
+
+/// Number of log messages shown in the debug log.
+const LOG_LIMIT: usize = 200;
+
+#[component]
+fn LogView() -> impl IntoView {
+    #[derive(Debug, Serialize, Deserialize)]
+    struct RecentLogsArgs {
+        level: String,
+        limit: usize,
+    }
+
+    let (records, set_records) = create_signal(Vec::<LogRecord>::new());
+    let (level, set_level) = create_signal("info".to_string());
+    let fetch = move || {
+        spawn_local(async move {
+            let args = serde_wasm_bindgen::to_value(&RecentLogsArgs {
+                level: level.get_untracked(),
+                limit: LOG_LIMIT,
             })
-                .expect("failed conversion");
-            let result = invoke("send_file", args).await;
-            logging::log!("sent file {:?}", result);
-        })
+            .expect("failed conversion");
+            match try_invoke("recent_logs", args).await {
+                Ok(result) => set_records.set(serde_wasm_bindgen::from_value(result).unwrap()),
+                Err(err) => logging::warn!("no logs: {:?}", DropError::from(err)),
+            }
+        });
     };
 
-    let UseDropZoneReturn {
-        is_over_drop_zone,
-        files,
-    } = use_drop_zone_with_options(
-        drop_zone_el,
-        UseDropZoneOptions::default()
-            .on_drop(on_drop)
-            .on_enter(move |_| set_dropped.set(false)),
-    );
+    view! {
+        <details class="debug-log" on:toggle=move |_| fetch()>
+            <summary>"Debug log"</summary>
+            <select
+                prop:value=move || level.get()
+                on:change=move |ev| {
+                    set_level.set(event_target_value(&ev));
+                    fetch();
+                }
+            >
+                <option value="error">"Errors"</option>
+                <option value="warn">"Warnings"</option>
+                <option value="info">"Info"</option>
+                <option value="debug">"Debug"</option>
+            </select>
+            <button on:click=move |_| fetch()>"Refresh"</button>
+            <table>
+                { move || records.get().into_iter().map(|record| view! {
+                    <tr class:warning=record.level == "WARN" || record.level == "ERROR">
+                        <td>{format_ago(record.timestamp / 1000)}</td>
+                        <td>{record.level}</td>
+                        <td title=record.target>{record.spans}</td>
+                        <td>{record.message}</td>
+                    </tr>
+                }).collect_view() }
+            </table>
+        </details>
+    }
+}
 
-    let class = move || {
-        let mut base = "row dropzone".to_string();
-        if is_over_drop_zone.get() {
-            base += " dropping";
-        }
-        base
+#[component]
+fn ConnectionAuditView() -> impl IntoView {
+    let (entries, set_entries) = create_signal(Vec::<ConnectionAttempt>::new());
+    let refresh = move |_| {
+        spawn_local(async move {
+            let result = invoke_without_args("connection_audit").await;
+            set_entries.set(serde_wasm_bindgen::from_value(result).unwrap());
+        });
     };
-    logging::log!("showing {}: {}", name, node_id);
 
     view! {
-        <div node_ref=drop_zone_el class={ class }>
-          <p>
-            {format!("{} ({})", name, node_id)}
-          </p>
-        </div>
+        <details class="connection-audit" on:toggle=refresh>
+            <summary>"Connection attempts"</summary>
+            <table>
+                <tr>
+                    <th>"When"</th>
+                    <th>"Who"</th>
+                    <th>"Path"</th>
+                    <th>"Handshake"</th>
+                    <th>"Result"</th>
+                </tr>
+                { move || entries.get().into_iter().map(|entry| view! {
+                    <tr class:warning=entry.failure.is_some()>
+                        <td>{format_ago(entry.timestamp)}</td>
+                        <td>{entry.node_id[..8].to_string()}</td>
+                        <td>{entry.path}</td>
+                        <td>{format!("{}ms", entry.handshake_ms)}</td>
+                        <td>{entry.error.unwrap_or_else(|| "ok".to_string())}</td>
+                    </tr>
+                }).collect_view() }
+            </table>
+        </details>
     }
 }
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,201 @@
+//! Component tests against a mocked backend, run with `wasm-pack test --headless --firefox`.
+
+use wasm_bindgen_test::*;
+use web_sys::{DragEvent, Element, HtmlElement};
+
+use super::*;
+use crate::ipc::mock;
+
+wasm_bindgen_test_configure!(run_in_browser);
+
+const NODE_ID: &str = "ae58ff8833241ac82d6ff7611046ed67b5072d142c588d0063e942d9a75502b6";
+
+fn peer(online: bool) -> PeerInfo {
+    PeerInfo {
+        node_id: NODE_ID.to_string(),
+        name: "laptop".to_string(),
+        online,
+        last_seen: 0,
+        device: DeviceInfo {
+            platform: "linux".to_string(),
+            device_type: "laptop".to_string(),
+        },
+        constraints: None,
+        own_device: false,
+        remote: false,
+        guest: false,
+        send_transforms: Vec::new(),
+        handle: None,
+    }
+}
+
+/// Mounts `f` into a fresh element of the page, with a toaster as in the app.
+fn mount<V: IntoView + 'static>(f: impl FnOnce() -> V + 'static) -> HtmlElement {
+    mock::reset();
+    let container: HtmlElement = document().create_element("div").unwrap().unchecked_into();
+    document().body().unwrap().append_child(&container).unwrap();
+    mount_to(container.clone(), move || {
+        provide_toaster();
+        f()
+    });
+    container
+}
+
+fn find(container: &HtmlElement, selector: &str) -> Element {
+    container
+        .query_selector(selector)
+        .unwrap()
+        .unwrap_or_else(|| panic!("no element matches {selector}"))
+}
+
+/// Lets spawned tasks and reactive effects run.
+async fn settle() {
+    let promise = js_sys::Promise::new(&mut |resolve, _| {
+        window()
+            .set_timeout_with_callback(&resolve)
+            .expect("failed to set a timeout");
+    });
+    JsFuture::from(promise).await.unwrap();
+}
+
+#[wasm_bindgen_test]
+async fn peer_card_shows_the_peer() {
+    let container = mount(|| node_view(peer(true)));
+    settle().await;
+
+    let text = container.text_content().unwrap_or_default();
+    assert!(text.contains("laptop"), "{text}");
+    assert!(text.contains(NODE_ID), "{text}");
+    assert!(!text.contains("last seen"), "{text}");
+    assert!(!find(&container, ".dropzone")
+        .class_list()
+        .contains("offline"));
+}
+
+#[wasm_bindgen_test]
+async fn offline_paired_peer_was_not_seen_yet() {
+    let container = mount(|| {
+        node_view(PeerInfo {
+            remote: true,
+            ..peer(false)
+        })
+    });
+    settle().await;
+
+    let text = container.text_content().unwrap_or_default();
+    assert!(text.contains("not seen yet"), "{text}");
+    assert!(text.contains("remote"), "{text}");
+    assert!(find(&container, ".dropzone")
+        .class_list()
+        .contains("offline"));
+}
+
+#[wasm_bindgen_test]
+async fn drop_zone_highlights_while_dragging() {
+    let container = mount(|| node_view(peer(true)));
+    settle().await;
+    let zone = find(&container, ".dropzone");
+    assert!(!zone.class_list().contains("dropping"));
+
+    zone.dispatch_event(&DragEvent::new("dragenter").unwrap())
+        .unwrap();
+    settle().await;
+    assert!(zone.class_list().contains("dropping"));
+
+    zone.dispatch_event(&DragEvent::new("dragleave").unwrap())
+        .unwrap();
+    settle().await;
+    assert!(!zone.class_list().contains("dropping"));
+}
+
+#[wasm_bindgen_test]
+async fn swap_progress_is_shown_on_its_peer_only() {
+    let container = mount(|| node_view(peer(true)));
+    settle().await;
+
+    mock::emit("swap-progress", ("someone-else", 1, 0, 1, 0));
+    settle().await;
+    assert!(container.query_selector(".swap-status").unwrap().is_none());
+
+    mock::emit("swap-progress", (NODE_ID, 1, 0, 2, 3));
+    settle().await;
+    assert_eq!(
+        find(&container, ".swap-status")
+            .text_content()
+            .unwrap_or_default(),
+        "Swapping: sent 1/2, received 0/3"
+    );
+}
+
+#[wasm_bindgen_test]
+async fn malformed_events_are_dropped() {
+    let container = mount(|| node_view(peer(true)));
+    settle().await;
+
+    mock::emit("swap-progress", "not a tuple");
+    mock::emit_raw("swap-progress", &JsValue::from_str("not an event"));
+    settle().await;
+    assert!(container.query_selector(".swap-status").unwrap().is_none());
+}
+
+#[wasm_bindgen_test]
+async fn peers_are_keyed_by_node_id() {
+    mock::reset();
+    mock::on_invoke_ok("list_peers", vec![peer(true)]);
+    let peers = fetch_peers().await;
+    assert_eq!(peers.len(), 1);
+    assert_eq!(peers[NODE_ID].name, "laptop");
+
+    mock::on_invoke_ok("list_peers", "not a list");
+    assert!(fetch_peers().await.is_empty());
+}
+
+#[wasm_bindgen_test]
+async fn deleting_reloads_history_and_trash() {
+    mock::reset();
+    mock::on_invoke_ok("delete_transfer", ());
+    mock::on_invoke_ok("history", Vec::<HistoryEntry>::new());
+    mock::on_invoke_ok("trash", Vec::<HistoryEntry>::new());
+    let (_, set_history) = create_signal(Vec::<HistoryEntry>::new());
+    let (_, set_trash) = create_signal(Vec::<HistoryEntry>::new());
+
+    set_deleted("hash".to_string(), true, set_history, set_trash)
+        .await
+        .unwrap();
+    let calls: Vec<_> = mock::calls().into_iter().map(|(cmd, _)| cmd).collect();
+    assert_eq!(calls, ["delete_transfer", "history", "trash"]);
+}
+
+#[wasm_bindgen_test]
+async fn backend_errors_are_explained() {
+    mock::reset();
+    mock::on_invoke("send_file", |_| {
+        Err(serde_wasm_bindgen::to_value(&DropError {
+            code: "rejected".to_string(),
+            message: "too large".to_string(),
+            reason: None,
+        })
+        .unwrap())
+    });
+
+    let err = try_invoke("send_file", JsValue::NULL).await.unwrap_err();
+    assert_eq!(
+        DropError::from(err).user_message(),
+        "The device declined the file (too large)"
+    );
+    mock::on_invoke("send_file", |_| {
+        Err(serde_wasm_bindgen::to_value(&DropError {
+            code: "rejected".to_string(),
+            message: "file is too large".to_string(),
+            reason: Some("exceeds_limit".to_string()),
+        })
+        .unwrap())
+    });
+    let err = try_invoke("send_file", JsValue::NULL).await.unwrap_err();
+    assert_eq!(
+        DropError::from(err).user_message(),
+        "The file is larger than the device accepts, ask to accept it once"
+    );
+    let err = try_invoke("unknown", JsValue::NULL).await.unwrap_err();
+    assert_eq!(DropError::from(err).code, "internal");
+}
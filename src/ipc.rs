@@ -0,0 +1,164 @@
+//! Calls into the Tauri backend, commands through `invoke` and events through `listen`.
+//!
+//! Tests replace the backend with [`mock`], which answers commands with registered handlers
+//! and delivers events emitted from the test.
+
+use leptos::logging;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+
+#[cfg(not(test))]
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(js_namespace = ["window", "__TAURI__", "core"], js_name = invoke)]
+    pub async fn invoke_without_args(cmd: &str) -> JsValue;
+    #[wasm_bindgen(js_namespace = ["window", "__TAURI__", "core"], js_name = invoke, catch)]
+    pub async fn try_invoke(cmd: &str, args: JsValue) -> Result<JsValue, JsValue>;
+    #[wasm_bindgen(js_namespace = ["window", "__TAURI__", "event"], js_name = "listen")]
+    async fn listen_sys(event: &str, handler: &js_sys::Function) -> js_sys::Function;
+}
+
+#[cfg(test)]
+use self::mock::listen_sys;
+#[cfg(test)]
+pub use self::mock::{invoke_without_args, try_invoke};
+
+#[derive(Serialize, Deserialize)]
+pub struct Event<T> {
+    pub event: String,
+    pub payload: T,
+    pub id: f64,
+}
+
+/// Calls `handler` with the payload of each `event`, returns a function to stop listening.
+///
+/// Events whose payload does not match `T` are logged and dropped.
+pub async fn listen<T: DeserializeOwned, F: Fn(T) + 'static>(
+    event: &str,
+    handler: F,
+) -> impl FnOnce() {
+    logging::log!("listenting to event: {}", event);
+    let name = event.to_string();
+    let closure = Closure::<dyn FnMut(_)>::new(move |s: JsValue| {
+        match serde_wasm_bindgen::from_value::<Event<T>>(s) {
+            Ok(event) => handler(event.payload),
+            Err(err) => logging::warn!("invalid payload of {}: {}", name, err),
+        }
+    });
+
+    let unlisten = listen_sys(event, closure.as_ref().unchecked_ref()).await;
+    closure.forget();
+
+    move || {
+        logging::log!("unlistening");
+        unlisten.call0(&JsValue::NULL).expect("failed to unlisten");
+    }
+}
+
+/// A backend for tests, see the module docs.
+#[cfg(test)]
+pub mod mock {
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+
+    use serde::Serialize;
+    use wasm_bindgen::prelude::*;
+
+    use super::Event;
+
+    type Handler = Box<dyn Fn(JsValue) -> Result<JsValue, JsValue>>;
+
+    thread_local! {
+        static COMMANDS: RefCell<HashMap<String, Handler>> = RefCell::default();
+        static CALLS: RefCell<Vec<(String, JsValue)>> = RefCell::default();
+        static LISTENERS: RefCell<Vec<(String, js_sys::Function)>> = RefCell::default();
+    }
+
+    /// Answers `cmd` with `handler`, replacing an earlier one.
+    pub fn on_invoke(cmd: &str, handler: impl Fn(JsValue) -> Result<JsValue, JsValue> + 'static) {
+        COMMANDS.with(|commands| {
+            commands
+                .borrow_mut()
+                .insert(cmd.to_string(), Box::new(handler))
+        });
+    }
+
+    /// Answers `cmd` with `value`.
+    pub fn on_invoke_ok<T: Serialize>(cmd: &str, value: T) {
+        let value = serde_wasm_bindgen::to_value(&value).expect("failed conversion");
+        on_invoke(cmd, move |_| Ok(value.clone()));
+    }
+
+    /// Commands invoked so far with their arguments, in order.
+    pub fn calls() -> Vec<(String, JsValue)> {
+        CALLS.with(|calls| calls.borrow().clone())
+    }
+
+    /// Forgets all handlers, calls and listeners, to start a test from scratch.
+    pub fn reset() {
+        COMMANDS.with(|commands| commands.borrow_mut().clear());
+        CALLS.with(|calls| calls.borrow_mut().clear());
+        LISTENERS.with(|listeners| listeners.borrow_mut().clear());
+    }
+
+    /// Delivers `event` with `payload` to everyone listening to it.
+    pub fn emit<T: Serialize>(event: &str, payload: T) {
+        let event = Event {
+            event: event.to_string(),
+            payload,
+            id: 0.,
+        };
+        let value = serde_wasm_bindgen::to_value(&event).expect("failed conversion");
+        emit_raw(&event.event, &value);
+    }
+
+    /// Delivers `value` as is, e.g. to check malformed events.
+    pub fn emit_raw(event: &str, value: &JsValue) {
+        let listeners: Vec<_> = LISTENERS.with(|listeners| {
+            listeners
+                .borrow()
+                .iter()
+                .filter(|(name, _)| name == event)
+                .map(|(_, handler)| handler.clone())
+                .collect()
+        });
+        for handler in listeners {
+            handler
+                .call1(&JsValue::NULL, value)
+                .expect("handler failed");
+        }
+    }
+
+    /// Unknown commands fail, like commands the backend does not have.
+    pub async fn try_invoke(cmd: &str, args: JsValue) -> Result<JsValue, JsValue> {
+        CALLS.with(|calls| calls.borrow_mut().push((cmd.to_string(), args.clone())));
+        COMMANDS.with(|commands| match commands.borrow().get(cmd) {
+            Some(handler) => handler(args),
+            None => Err(JsValue::from_str(&format!("{cmd} is not mocked"))),
+        })
+    }
+
+    pub async fn invoke_without_args(cmd: &str) -> JsValue {
+        try_invoke(cmd, JsValue::UNDEFINED)
+            .await
+            .unwrap_or(JsValue::UNDEFINED)
+    }
+
+    pub(super) async fn listen_sys(event: &str, handler: &js_sys::Function) -> js_sys::Function {
+        let event = event.to_string();
+        LISTENERS.with(|listeners| {
+            listeners
+                .borrow_mut()
+                .push((event.clone(), handler.clone()))
+        });
+        let handler = handler.clone();
+        let unlisten = Closure::once_into_js(move || {
+            LISTENERS.with(|listeners| {
+                listeners
+                    .borrow_mut()
+                    .retain(|(name, h)| name != &event || h != &handler)
+            });
+        });
+        unlisten.unchecked_into()
+    }
+}
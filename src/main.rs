@@ -1,13 +1,26 @@
 mod app;
+mod ipc;
 
 use app::*;
 use leptos::*;
 
 fn main() {
     console_error_panic_hook::set_once();
-    mount_to_body(|| {
-        view! {
-            <App/>
+    // The kiosk window only shows the kiosk, see `KioskView`.
+    let hash = window().location().hash().unwrap_or_default();
+    let kiosk = hash == "#kiosk";
+    // Transfer windows show one transfer, see `TransferWindow`.
+    let transfer = hash
+        .strip_prefix("#transfer-")
+        .and_then(|id| id.parse::<u64>().ok());
+    mount_to_body(move || {
+        watch_appearance();
+        if kiosk {
+            view! { <KioskView/> }.into_view()
+        } else if let Some(id) = transfer {
+            view! { <TransferWindow id=id/> }.into_view()
+        } else {
+            view! { <App/> }.into_view()
         }
     })
 }